@@ -1,74 +1,117 @@
-
-use std::process::Command;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+// те же правила имен читает hot reload в рантайме
+include!("src/vulkan_wr/shader_list.rs");
 
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
-    fs::create_dir_all(&dst)?;
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
-        } else {
-            let dst_path = dst.as_ref().join(entry.file_name());
-            println!("Copying model file: {:?} -> {:?}", entry.path(), dst_path);
-            fs::copy(entry.path(), dst_path)?;
-            println!("cargo:rerun-if-changed={}", entry.path().display());
-        }
+
+/// #include "x": сначала рядом с тем, кто включает, потом shaders/include
+#[cfg(feature = "shaderc")]
+fn resolve_include(include_dir: &Path, requesting: &str, requested: &str) -> Result<PathBuf, String> {
+    let base = Path::new(requesting).parent().map(Path::to_path_buf).unwrap_or_default();
+    [base.join(requested), include_dir.join(requested)]
+        .into_iter()
+        .find(|p| p.is_file())
+        .ok_or_else(|| format!("{}: include \"{}\" not found", requesting, requested))
+}
+
+/// Встроенный компилятор, glslc не нужен
+#[cfg(feature = "shaderc")]
+fn compile(include_dir: &Path, src: &Path, dst: &Path) -> Result<(), String> {
+    let kind = match src.extension().and_then(|e| e.to_str()) {
+        Some("vert") => shaderc::ShaderKind::Vertex,
+        Some("frag") => shaderc::ShaderKind::Fragment,
+        Some("comp") => shaderc::ShaderKind::Compute,
+        Some("geom") => shaderc::ShaderKind::Geometry,
+        Some("tesc") => shaderc::ShaderKind::TessControl,
+        Some("tese") => shaderc::ShaderKind::TessEvaluation,
+        _ => return Err(format!("{}: unknown shader stage", src.display())),
+    };
+    let compiler = shaderc::Compiler::new().expect("Failed to create shaderc compiler");
+    let mut options = shaderc::CompileOptions::new().expect("Failed to create shaderc options");
+    let include_dir = include_dir.to_path_buf();
+    options.set_include_callback(move |requested, _include_type, requesting, _depth| {
+        let path = resolve_include(&include_dir, requesting, requested)?;
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: path.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+
+    let source = fs::read_to_string(src).map_err(|e| format!("{}: {}", src.display(), e))?;
+    let artifact = compiler
+        .compile_into_spirv(&source, kind, &src.to_string_lossy(), "main", Some(&options))
+        .map_err(|e| e.to_string())?;
+    fs::write(dst, artifact.as_binary_u8()).map_err(|e| format!("{}: {}", dst.display(), e))
+}
+
+/// Без фичи shaderc - glslc из PATH, как раньше
+#[cfg(not(feature = "shaderc"))]
+fn compile(include_dir: &Path, src: &Path, dst: &Path) -> Result<(), String> {
+    let output = std::process::Command::new("glslc")
+        .arg("-I")
+        .arg(include_dir)
+        .arg(src)
+        .arg("-o")
+        .arg(dst)
+        .output()
+        .map_err(|e| format!("glslc is not available ({}), enable the shaderc feature or install the Vulkan SDK", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
     }
     Ok(())
 }
 
 fn main() {
     let shader_dir = Path::new("shaders");
-    let profile = std::env::var("PROFILE").unwrap(); // debug или release
-    let shdr = format!("target/{}/shaders", profile); // путь для шейдеров
-    let bin_dir = Path::new(&shdr); 
+    let include_dir = shader_dir.join(SHADER_INCLUDE_DIR);
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let spv_dir = out_dir.join("shaders");
+    let embed = std::env::var_os("CARGO_FEATURE_EMBED_SHADERS").is_some();
 
-    fs::create_dir_all(&bin_dir).unwrap();
+    fs::create_dir_all(&spv_dir).unwrap();
+    // каталог целиком: новые файлы и include тоже перезапускают сборку
+    println!("cargo:rerun-if-changed={}", shader_dir.display());
+    println!("cargo:rerun-if-changed=src/vulkan_wr/shader_list.rs");
 
-    let shaders = [
-        ("sphere.vert", "vert_sphere.spv"),
-        ("sphere.frag", "frag_sphere.spv"),
-        ("light.vert", "vert_light.spv"),
-        ("light.frag", "frag_light.spv"),
-        ("imgui.vert", "imgui_vert.spv"),
-        ("imgui.frag", "imgui_frag.spv"),
-        ("shadows.vert", "vert_shadows.spv"),
-        ("shadows.frag", "frag_shadows.spv"),
-        ("light_shadows.frag", "frag_light_shadows.spv"),
-        ("light_shadows.vert", "vert_light_shadows.spv"),
-    ];
+    let sources = discover_shaders(shader_dir).expect("Failed to read shaders directory");
+    let mut errors = vec![];
+    let mut embedded = String::from("pub const EMBEDDED_SHADERS: &[(&str, &[u8])] = &[\n");
+    for src_name in sources {
+        let dst_name = spv_name(&src_name).unwrap();
+        let src = shader_dir.join(&src_name);
+        let dst = spv_dir.join(&dst_name);
 
-    for (src_name, dst_name) in shaders {
-        let src = shader_dir.join(src_name);
-        let dst = bin_dir.join(dst_name);
-
-        let status = Command::new("glslc")
-            .args([src.to_str().unwrap(), "-o", dst.to_str().unwrap()])
-            .status()
-            .expect("Failed to run glslc");
-
-        if !status.success() {
-            panic!("Shader compilation failed: {}", src.display());
+        // все ошибки разом, а не по одной за сборку
+        if let Err(log) = compile(&include_dir, &src, &dst) {
+            errors.push(format!("{}:\n{}", src_name, log));
+            continue;
+        }
+        if embed {
+            embedded.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", dst_name, dst.to_string_lossy()));
         }
-
-        println!("cargo:rerun-if-changed={}", src.display());
     }
-
-    let out_dir = format!("target/{}/", profile);
-    let model_src_dir = Path::new("obj_3d");
-    let model_dst_dir = Path::new(&out_dir).join("obj_3d");
-
-    if model_src_dir.exists() {
-        copy_dir_all(model_src_dir, &model_dst_dir)
-            .expect("Failed to copy 3D models recursively");
-    } else {
-        eprintln!("Warning: 3D model source directory not found: {:?}", model_src_dir);
+    if !errors.is_empty() {
+        panic!("Shader compilation failed:\n{}", errors.join("\n"));
     }
+    embedded.push_str("];\n");
+    fs::write(out_dir.join("embedded_shaders.rs"), embedded).unwrap();
 
-
-    // println!("cargo:rustc-env=SHADER_PATH=target/debug/shaders");
+    // описания пайплайнов всегда внутри бинарника, файл с диска только перекрывает их (hot reload)
+    let pipeline_dir = Path::new("pipelines");
+    println!("cargo:rerun-if-changed={}", pipeline_dir.display());
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut pipelines = fs::read_dir(pipeline_dir)
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    pipelines.retain(|name| name.ends_with(".toml"));
+    pipelines.sort();
+    let mut embedded = String::from("pub const EMBEDDED_PIPELINES: &[(&str, &str)] = &[\n");
+    for name in pipelines {
+        let path = manifest_dir.join(pipeline_dir).join(&name);
+        embedded.push_str(&format!("    ({:?}, include_str!({:?})),\n", name, path.to_string_lossy()));
+    }
+    embedded.push_str("];\n");
+    fs::write(out_dir.join("embedded_pipelines.rs"), embedded).unwrap();
 }
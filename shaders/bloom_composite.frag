@@ -0,0 +1,36 @@
+// bloom_composite.frag
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D sceneTex;
+layout(set = 0, binding = 1) uniform sampler2D bloomTex;
+
+layout(push_constant) uniform BloomPush {
+    vec2 texel;
+    float threshold;
+    float knee;
+    float radius;
+    float intensity;
+    uint prefilter;
+    uint enabled;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+// ACES fitted (Narkowicz)
+vec3 tonemap_aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec3 hdr = texture(sceneTex, vUV).rgb;
+    if (pc.enabled != 0) {
+        hdr += texture(bloomTex, vUV).rgb * pc.intensity;
+    }
+    outColor = vec4(tonemap_aces(hdr), 1.0);
+}
@@ -0,0 +1,55 @@
+// bloom_down.frag
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D srcTex;
+
+layout(push_constant) uniform BloomPush {
+    vec2 texel;
+    float threshold;
+    float knee;
+    float radius;
+    float intensity;
+    uint prefilter;
+    uint enabled;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+// мягкий порог: quadratic кривая в [threshold - knee, threshold + knee]
+vec3 prefilter_color(vec3 c) {
+    float br = max(c.r, max(c.g, c.b));
+    float rq = clamp(br - pc.threshold + pc.knee, 0.0, 2.0 * pc.knee);
+    rq = (rq * rq) / (4.0 * pc.knee + 0.00001);
+    float contrib = max(rq, br - pc.threshold) / max(br, 0.00001);
+    return c * contrib;
+}
+
+void main() {
+    vec2 t = pc.texel;
+    // 13 tap downsample (CoD: Advanced Warfare)
+    vec3 a = texture(srcTex, vUV + t * vec2(-2.0, -2.0)).rgb;
+    vec3 b = texture(srcTex, vUV + t * vec2( 0.0, -2.0)).rgb;
+    vec3 c = texture(srcTex, vUV + t * vec2( 2.0, -2.0)).rgb;
+    vec3 d = texture(srcTex, vUV + t * vec2(-2.0,  0.0)).rgb;
+    vec3 e = texture(srcTex, vUV).rgb;
+    vec3 f = texture(srcTex, vUV + t * vec2( 2.0,  0.0)).rgb;
+    vec3 g = texture(srcTex, vUV + t * vec2(-2.0,  2.0)).rgb;
+    vec3 h = texture(srcTex, vUV + t * vec2( 0.0,  2.0)).rgb;
+    vec3 i = texture(srcTex, vUV + t * vec2( 2.0,  2.0)).rgb;
+    vec3 j = texture(srcTex, vUV + t * vec2(-1.0, -1.0)).rgb;
+    vec3 k = texture(srcTex, vUV + t * vec2( 1.0, -1.0)).rgb;
+    vec3 l = texture(srcTex, vUV + t * vec2(-1.0,  1.0)).rgb;
+    vec3 m = texture(srcTex, vUV + t * vec2( 1.0,  1.0)).rgb;
+
+    vec3 color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+
+    if (pc.prefilter != 0) {
+        color = prefilter_color(color);
+    }
+    // 16 бит float не любят inf
+    outColor = vec4(min(color, vec3(65000.0)), 1.0);
+}
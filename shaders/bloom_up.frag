@@ -0,0 +1,34 @@
+// bloom_up.frag
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D srcTex;
+
+layout(push_constant) uniform BloomPush {
+    vec2 texel;
+    float threshold;
+    float knee;
+    float radius;
+    float intensity;
+    uint prefilter;
+    uint enabled;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // 3x3 tent, radius растягивает фильтр
+    vec2 t = pc.texel * pc.radius;
+    vec3 color = texture(srcTex, vUV).rgb * 4.0;
+    color += texture(srcTex, vUV + vec2(-t.x, 0.0)).rgb * 2.0;
+    color += texture(srcTex, vUV + vec2( t.x, 0.0)).rgb * 2.0;
+    color += texture(srcTex, vUV + vec2(0.0, -t.y)).rgb * 2.0;
+    color += texture(srcTex, vUV + vec2(0.0,  t.y)).rgb * 2.0;
+    color += texture(srcTex, vUV + vec2(-t.x, -t.y)).rgb;
+    color += texture(srcTex, vUV + vec2( t.x, -t.y)).rgb;
+    color += texture(srcTex, vUV + vec2(-t.x,  t.y)).rgb;
+    color += texture(srcTex, vUV + vec2( t.x,  t.y)).rgb;
+
+    // смешивание ONE + ONE складывает с mip ниже
+    outColor = vec4(color / 16.0, 1.0);
+}
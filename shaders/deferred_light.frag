@@ -0,0 +1,187 @@
+// deferred_light.frag
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// проход света deferred пути: fullscreen, позиция из глубины, материал из G-buffer
+// свет и тени те же что в light_shadows.frag: тот же LightsSSBO и та же карта теней
+
+#include "shadow_config.glsl"
+#include "features.glsl"
+
+#define LIGHTS_WITH_SHADOWS
+#include "lights.glsl"
+
+layout(set = 1, binding = 0) uniform sampler2D gAlbedo;
+layout(set = 1, binding = 1) uniform sampler2D gNormal;  // xyz - нормаль, w - shininess
+layout(set = 1, binding = 2) uniform sampler2D gSpecular;
+layout(set = 1, binding = 3) uniform sampler2D gAmbient;
+layout(set = 1, binding = 4) uniform sampler2D gDepth;
+
+layout(set = 2, binding = 0) uniform sampler2DArrayShadow shadowMap;
+
+layout(set = 3, binding = 0) uniform sampler2D aoTex;
+
+layout(push_constant) uniform DeferredLightPush {
+    mat4 inv_view_proj;
+    vec4 cam_pos;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+
+float calculateShadow(int lightIndex, vec4 PosLightSpace, mat4 light_mtx, vec3 normal, vec3 lightDir, bool flag) {
+    // вариант без теней: весь PCF выкидывается при специализации
+    if (!USE_SHADOWS) {
+        return 1.0;
+    }
+    vec2 gMapSize = vec2(float(SHADOW_MAP_SIZE));
+
+    PosLightSpace = (light_mtx * PosLightSpace);
+    vec3 projCoords = PosLightSpace.xyz / PosLightSpace.w;
+    projCoords = projCoords * 0.5 + 0.5;
+    
+    if (projCoords.z > 1.0 || projCoords.x < 0.0 || projCoords.x > 1.0 || 
+        projCoords.y < 0.0 || projCoords.y > 1.0) {
+        return 1.0;
+    }
+
+    float minBias = 0.2;
+    float maxBias = 0.27;
+
+    if (flag) {
+        maxBias = 0.0148;
+    } else {
+        maxBias = 0.27;
+    }
+    float bias = clamp(6 * minBias * max(dot(normal, lightDir), 1.0 - dot(normal, lightDir)), minBias, maxBias);
+    // bias = 0.0148;
+    float currentDepth = projCoords.z;
+
+    float compareDepth = projCoords.z - bias;
+
+
+    float shadow = 0.0;
+
+    vec2 texelSize = 1.0 / gMapSize;
+    for (int x = -PCF_RADIUS; x <= PCF_RADIUS; x++) {
+        for (int y = -PCF_RADIUS; y <= PCF_RADIUS; y++) {
+            vec2 offset = vec2(x, y) * texelSize;
+
+            vec4 coord = vec4(
+                projCoords.xy + offset,
+                lightIndex,
+                compareDepth
+            );
+
+            shadow += texture(shadowMap, coord);
+        }
+    }
+
+    shadow /= float((2 * PCF_RADIUS + 1) * (2 * PCF_RADIUS + 1));
+
+    return shadow;
+
+
+    vec4 coord = vec4(projCoords.x, projCoords.y, lightIndex, currentDepth - bias);
+    shadow = texture(shadowMap, coord);
+    return shadow;
+    
+}
+
+vec3 calc_Blinn_Phong(vec3 N, vec3 L, vec3 V, vec3 light_color, float intensity, float shininess, vec3 specular_color, vec3 albedo) {
+    float diff = max(dot(N, L), 0.0);
+
+    vec3 H = normalize(L + V);
+    float spec = pow(max(dot(N, H), 0.0), shininess);
+
+    return light_color * intensity * (diff * albedo + spec * specular_color);
+}
+
+void main() {
+    float depth = texture(gDepth, vUV).r;
+    if (depth >= 1.0) {
+        discard;  // фон остается цветом очистки
+    }
+    vec4 world = pc.inv_view_proj * vec4(vUV * 2.0 - 1.0, depth, 1.0);
+    vec3 fragPos = world.xyz / world.w;
+    // в forward это тоже мировая позиция, light_mtx применяется внутри calculateShadow
+    vec4 fragPosLightSpace = vec4(fragPos, 1.0);
+
+    vec4 normal_full = texture(gNormal, vUV);
+    vec3 N = normalize(normal_full.xyz);
+    vec3 V = normalize(pc.cam_pos.xyz - fragPos);
+
+    vec3 albedo = texture(gAlbedo, vUV).rgb;
+    vec3 ambient_m = texture(gAmbient, vUV).rgb;
+    vec3 specular_m = texture(gSpecular, vUV).rgb;
+    float shininess = normal_full.w;
+
+    float ao = texture(aoTex, vUV).r;
+    vec3 result = ambient_m * albedo * ao;
+
+    // -----------------------------------
+    // Directional lights
+    // -----------------------------------
+    for (uint i = 0; i < light_count_directional; ++i) {
+        vec3 L = normalize(-directional_lights[i].direction.xyz);
+        vec3 col = directional_lights[i].color.rgb;
+        float light_intensity = directional_lights[i].color.w;
+
+        float shadow = calculateShadow(int(i), fragPosLightSpace, directional_lights[i].light_mtx,  N, L, false);
+
+        result += calc_Blinn_Phong(N, L, V, col, light_intensity, shininess, specular_m, albedo) * (shadow);
+    }
+
+    // -----------------------------------
+    // Point lights
+    // -----------------------------------
+    for (uint i = 0; i < light_count_point; ++i) {
+        vec3 lightPos = point_lights[i].position.xyz;
+        float c_const  = point_lights[i].coefficient.x;
+        float c_lin  = point_lights[i].coefficient.y;
+        float c_quad  = point_lights[i].coefficient.z;
+
+        vec3 L = (lightPos - fragPos);
+        float dist = length(L);
+        L = normalize(L);
+        // 1 / (a + d*b + d^2*c)
+        float attenuation = 1.0 / (c_const + c_lin * dist + c_quad * dist * dist); 
+
+        float light_intensity = point_lights[i].color.w ;
+        vec3 col = point_lights[i].color.rgb;
+
+        // float shadow = calculateShadow(int(i + MAX_LIGHTS), fragPosLightSpace, point_lights[i].light_mtx);
+        
+        result += calc_Blinn_Phong(N, L, V, col, attenuation * light_intensity, shininess, specular_m, albedo);
+        //  * (1.0 - shadow);
+    }
+
+    // -----------------------------------
+    // Spotlights
+    // -----------------------------------
+    for (uint i = 0; i < light_count_spotlight; ++i) {
+        vec3 lightPos = spotlights[i].position.xyz;
+        vec3 L = normalize(lightPos - fragPos);
+        vec3 dir = normalize(spotlights[i].direction.xyz);
+        
+        float cutoff = spotlights[i].direction.w;
+        float outer_cos = spotlights[i].cut_off.x;
+        // https://registry.khronos.org/OpenGL-Refpages/gl4/html/smoothstep.xhtml
+        // Results are undefined if edge0 ≥ edge1.
+        float inner_cos = max(spotlights[i].cut_off.y, outer_cos + 0.0001);
+        
+        float spot_angle = dot(L, -dir);
+        float spot_intensity = smoothstep(outer_cos, inner_cos, spot_angle);
+
+        float light_intensity = spotlights[i].color.w;
+
+        vec3 col = spotlights[i].color.rgb;
+
+        // float shadow = calculateShadow(int(i + MAX_LIGHTS * 2), fragPosLightSpace, spotlights[i].light_mtx);
+        float shadow = calculateShadow(int(i + MAX_LIGHTS * 2), fragPosLightSpace, spotlights[i].light_mtx,  N, L, true);
+        
+        result += calc_Blinn_Phong(N, L, V, col, light_intensity * spot_intensity, shininess, specular_m, albedo) * (shadow);
+    }
+    outColor = vec4(result, 1.0);
+}
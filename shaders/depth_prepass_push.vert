@@ -0,0 +1,16 @@
+// depth_prepass_push.vert
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// только позиция, глубина для SSAO. view_proj как у light.vert / light_shadows.vert, model из DrawPush
+layout(location = 0) in vec3 inPos;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 view_proj;
+} ubo;
+
+#include "draw.glsl"
+
+void main() {
+    gl_Position = ubo.view_proj * draw.model * vec4(inPos, 1.0);
+}
@@ -0,0 +1,58 @@
+// fxaa.frag
+#version 450
+
+// упрощенный FXAA (console-версия): ищем направление края по яркости и размываем вдоль него
+layout(set = 0, binding = 0) uniform sampler2D sceneTex;
+
+layout(push_constant) uniform FxaaPush {
+    vec2 texel;
+    float span_max;
+    float edge_threshold;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+
+// сцена в HDR, яркость сжимаем чтобы пересветы не ломали поиск края
+float luma(vec3 c) {
+    float l = dot(c, vec3(0.299, 0.587, 0.114));
+    return l / (1.0 + l);
+}
+
+void main() {
+    vec3 rgbM = texture(sceneTex, vUV).rgb;
+    float lumaNW = luma(texture(sceneTex, vUV + vec2(-1.0, -1.0) * pc.texel).rgb);
+    float lumaNE = luma(texture(sceneTex, vUV + vec2( 1.0, -1.0) * pc.texel).rgb);
+    float lumaSW = luma(texture(sceneTex, vUV + vec2(-1.0,  1.0) * pc.texel).rgb);
+    float lumaSE = luma(texture(sceneTex, vUV + vec2( 1.0,  1.0) * pc.texel).rgb);
+    float lumaM = luma(rgbM);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+    if (lumaMax - lumaMin < max(pc.edge_threshold * lumaMax, 1.0 / 32.0)) {
+        outColor = vec4(rgbM, 1.0);
+        return;
+    }
+
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+         ((lumaNW + lumaSW) - (lumaNE + lumaSE))
+    );
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-pc.span_max), vec2(pc.span_max)) * pc.texel;
+
+    vec3 rgbA = 0.5 * (
+        texture(sceneTex, vUV + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(sceneTex, vUV + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(sceneTex, vUV + dir * -0.5).rgb +
+        texture(sceneTex, vUV + dir *  0.5).rgb);
+
+    // если дальние выборки вылезли за диапазон соседей - значит перешли через край, берем ближние
+    float lumaB = luma(rgbB);
+    outColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, 1.0);
+}
@@ -0,0 +1,57 @@
+// gbuffer.frag
+#version 450
+#extension GL_GOOGLE_include_directive : require
+#extension GL_EXT_nonuniform_qualifier : require
+
+// геометрический проход deferred пути: только материал, свет считает deferred_light.frag
+// входы и сеты как у light_shadows.frag, вершинник тот же
+layout(location = 0) in vec3 fragPos;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec2 fragTexCoord;
+layout(location = 3) in vec3 camPos;
+layout(location = 4) in vec4 fragPosLightSpace;
+
+layout(std430, set = 0, binding = 1) buffer LightsSSBO {
+    uint light_count_directional;
+    uint light_count_point;
+    uint light_count_spotlight;
+    float time;
+};
+
+#include "bindless.glsl"
+#include "features.glsl"
+
+layout(location = 0) out vec4 outAlbedo;
+layout(location = 1) out vec4 outNormal;  // xyz - нормаль, w - shininess
+layout(location = 2) out vec4 outSpecular;
+layout(location = 3) out vec4 outAmbient;
+
+void main() {
+    load_material();
+    // то же закручивание uv что и в forward, чтобы картинки совпадали
+    vec2 uv = fragTexCoord;
+    vec2 center = vec2(0.5, 0.5);
+    vec2 dir = uv - center;
+    float dist = length(dir);
+
+    float angle = dist * 5.0 * sin(time);
+    float s = sin(angle);
+    float c = cos(angle);
+    mat2 rot = mat2(c, -s, s, c);
+    uv = center + rot * dir;
+
+    vec4 texture_full = material_texture(uv);
+    if (USE_ALPHA_TEST) {
+        // вырезанное отбрасываем, остальное целиком из текстуры
+        if (texture_full.a < ALPHA_CUTOFF) {
+            discard;
+        }
+        texture_full.a = 1.0;
+    }
+    vec3 albedo = texture_full.rgb * texture_full.a + material.diffuse.rgb * (1.0 - texture_full.a);
+
+    outAlbedo = vec4(albedo, 1.0);
+    outNormal = vec4(normalize(fragNormal), material.extra.x);
+    outSpecular = vec4(material.specular.rgb, 1.0);
+    outAmbient = vec4(material.ambient.rgb, 1.0);
+}
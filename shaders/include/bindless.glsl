@@ -0,0 +1,37 @@
+// bindless.glsl
+// Все текстуры и материалы сцены в одном сете (set 1), см. descriptor::bindless в Rust.
+// Материал выбирает draw.material_index, текстуру - материал. Material должен совпадать с BindlessMaterial.
+// Шейдеру нужен #extension GL_EXT_nonuniform_qualifier в начале, до любого кода
+
+#ifndef BINDLESS_GLSL
+#define BINDLESS_GLSL
+
+#include "draw.glsl"
+
+struct Material {
+    vec4 ambient;
+    vec4 diffuse;
+    vec4 specular;
+    vec4 extra; // extra.x = shininess
+    uint texture;  // индекс в textures
+};
+
+layout(set = 1, binding = 0) uniform sampler2D textures[];
+
+layout(std430, set = 1, binding = 1) readonly buffer MaterialTable {
+    Material materials[];
+};
+
+// материал текущего draw, заполняется в начале main
+Material material;
+
+void load_material() {
+    material = materials[draw.material_index];
+}
+
+// id одинаковый на весь draw, nonuniformEXT на случай instanced/indirect отрисовки
+vec4 material_texture(vec2 uv) {
+    return texture(textures[nonuniformEXT(material.texture)], uv);
+}
+
+#endif
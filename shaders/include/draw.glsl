@@ -0,0 +1,14 @@
+// draw.glsl
+// Данные одного draw через push constants, сеты на draw не перебиндиваются. Должно совпадать с DrawPush в Rust
+
+#ifndef DRAW_GLSL
+#define DRAW_GLSL
+
+layout(push_constant) uniform DrawPush {
+    mat4 model;
+    uint material_index;  // id материала в bindless таблице (set 1)
+    uint object_id;  // номер меша в сцене, для отладки/picking, шейдеры пока не читают
+    uint oit;  // light.frag: 0 - обычный цвет с альфой, 1 - weighted blended OIT
+} draw;
+
+#endif
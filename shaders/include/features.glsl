@@ -0,0 +1,14 @@
+// features.glsl
+// Флаги фич как specialization constants: один исходник, варианты пайплайна собирает
+// PermutationCache (pipeline/permutation.rs). Бит i FeatureFlags -> constant_id 100 + i
+
+#ifndef FEATURES_GLSL
+#define FEATURES_GLSL
+
+layout(constant_id = 100) const bool USE_SHADOWS = true;
+layout(constant_id = 101) const bool USE_ALPHA_TEST = false;
+
+// alpha test: альфа diffuse текстуры - маска вырезания
+const float ALPHA_CUTOFF = 0.5;
+
+#endif
@@ -0,0 +1,50 @@
+// lights.glsl
+// Источники света и LightsSSBO (set 0, binding 1). Rust сторона - gpu_struct! в scenes/*/uniform.rs,
+// расхождение offset'ов ловит check_block при создании пайплайна.
+// LIGHTS_WITH_SHADOWS до #include - у каждого источника матрица для карты теней
+// MAX_LIGHTS задает Rust через spec constant, но раскладка блока считается по значению
+// по умолчанию - оно должно совпадать с MAX_LIGHTS_IN_CAT (scenes/lighting/uniform.rs)
+
+#ifndef LIGHTS_GLSL
+#define LIGHTS_GLSL
+
+layout(constant_id = 0) const uint MAX_LIGHTS = 5;  // источников в категории
+
+#ifdef LIGHTS_WITH_SHADOWS
+#define LIGHT_MTX mat4 light_mtx;
+#else
+#define LIGHT_MTX
+#endif
+
+struct DirectionalLight {
+    vec4 direction; // xyz = dir, w unused
+    vec4 color;     // rgb + intensity in w
+    LIGHT_MTX
+};
+
+struct PointLight {
+    vec4 position;
+    vec4 color;  // rgb + intensity
+    vec4 coefficient;
+    LIGHT_MTX
+};
+
+struct Spotlight {
+    vec4 position;
+    vec4 direction; // xyz + cutoff radians in w
+    vec4 color;     // rgb + intensity
+    vec4 cut_off;
+    LIGHT_MTX
+};
+
+layout(std430, set = 0, binding = 1) buffer LightsSSBO {
+    uint light_count_directional;
+    uint light_count_point;
+    uint light_count_spotlight;
+    float time;
+    DirectionalLight directional_lights[MAX_LIGHTS];
+    PointLight point_lights[MAX_LIGHTS];
+    Spotlight spotlights[MAX_LIGHTS];
+};
+
+#endif
@@ -0,0 +1,12 @@
+// shadow_config.glsl
+// Параметры карт теней как specialization constants, тут только значения по умолчанию.
+// SHADOW_MAP_SIZE задает Rust (scenes/shadows/objects.rs), PCF_RADIUS - [constants] в pipelines/*.toml.
+// MAX_LIGHTS (constant_id 0) живет в lights.glsl, слои spotlight с MAX_LIGHTS * 2
+
+#ifndef SHADOW_CONFIG_GLSL
+#define SHADOW_CONFIG_GLSL
+
+layout(constant_id = 1) const uint SHADOW_MAP_SIZE = 1024;
+layout(constant_id = 2) const int PCF_RADIUS = 2;  // ядро (2r + 1)^2
+
+#endif
@@ -1,4 +1,6 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+#extension GL_EXT_nonuniform_qualifier : require
 
 layout(location = 0) in vec3 fragPos;
 layout(location = 1) in vec3 fragNormal;
@@ -12,52 +14,20 @@ layout(location = 3) in vec3 camPos;
 //     float time;
 // } ubo;
 
-layout(set = 3, binding = 0) uniform MBO {
-    mat4 model;
-} model;
-
-struct DirectionalLight {
-    vec4 direction; // xyz = dir, w unused
-    vec4 color;     // rgb + intensity in w
-};
-
-struct PointLight {
-    vec4 position;
-    vec4 color;  // rgb + intensity
-    vec4 coefficient;
-    vec4 _pad;
-    // vec4 _pad1;
-};
-
-struct Spotlight {
-    vec4 position;
-    vec4 direction; // xyz + cutoff radians in w
-    vec4 color;     // rgb + intensity
-    vec4 cut_off;
-    // vec3 _pad;
-};
-
-layout(std430, set = 0, binding = 1) buffer LightsSSBO {
-    uint light_count_directional;
-    uint light_count_point;
-    uint light_count_spotlight;
-    float time;
-    // uint _pad_ssbo;
-    DirectionalLight directional_lights[5];
-    PointLight       point_lights[5];
-    Spotlight        spotlights[5];
-};
-
-layout(set = 1, binding = 0) uniform sampler2D textureSampler;
-
-layout(set = 2, binding = 0) uniform MaterialUBO {
-    vec4 ambient;
-    vec4 diffuse;
-    vec4 specular;
-    vec4 extra; // extra.x = shininess
-} material;
+#include "lights.glsl"
 
+// текстуры и материалы сцены (set 1), материал draw по draw.material_index
+#include "bindless.glsl"
+
+// SSAO, уже заблюренный, по экранным координатам
+layout(set = 3, binding = 0) uniform sampler2D aoTex;
+
+// draw.oit: 0 - обычный цвет с альфой, 1 - weighted blended OIT
+#include "draw.glsl"
+
+// в OIT режиме 0 - accum, 1 - revealage, иначе location 1 без attachment и отбрасывается
 layout(location = 0) out vec4 outColor;
+layout(location = 1) out float outReveal;
 
 vec3 calc_Blinn_Phong(vec3 N, vec3 L, vec3 V, vec3 light_color, float intensity, float shininess, vec3 specular_color, vec3 albedo) {
     float diff = max(dot(N, L), 0.0);
@@ -69,6 +39,7 @@ vec3 calc_Blinn_Phong(vec3 N, vec3 L, vec3 V, vec3 light_color, float intensity,
 }
 
 void main() {
+    load_material();
     const float PI = 3.1415;
     vec3 N = normalize(fragNormal);
     vec3 V = normalize(camPos - fragPos);
@@ -86,7 +57,7 @@ void main() {
 
     // uv = fragTexCoord;
 
-    vec4 texture_full = texture(textureSampler, uv);
+    vec4 texture_full = material_texture(uv);
 
     // Ka
     vec3 ambient_m  = material.ambient.rgb;
@@ -101,7 +72,8 @@ void main() {
     // оно позволит фон делать прозрачный
     vec3 albedo = texture_full.rgb * texture_full.a + diffuse_m * (1.0 - texture_full.a);
 
-    vec3 result = ambient_m * albedo;
+    float ao = texture(aoTex, gl_FragCoord.xy / vec2(textureSize(aoTex, 0))).r;
+    vec3 result = ambient_m * albedo * ao;
 
     // -----------------------------------
     // Directional lights
@@ -160,5 +132,15 @@ void main() {
         result += calc_Blinn_Phong(N, L, V, col, light_intensity * spot_intensity, shininess, specular_m, albedo);
     }
 
-    outColor = vec4(result, 1.0);
+    // непрозрачность материала (d из mtl), у непрозрачных 1
+    float alpha = material.diffuse.w;
+    if (draw.oit == 1u) {
+        // вес по глубине, McGuire & Bavoil 2013
+        float w = clamp(alpha * max(1e-2, 3e3 * pow(1.0 - gl_FragCoord.z, 3.0)), 1e-2, 3e3);
+        outColor = vec4(result * alpha, alpha) * w;
+        outReveal = alpha;
+    } else {
+        outColor = vec4(result, alpha);
+        outReveal = 0.0;
+    }
 }
@@ -1,4 +1,5 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
 layout(location = 0) in vec3 inPos;
 layout(location = 1) in vec3 inColor;
@@ -11,10 +12,8 @@ layout(set = 0, binding = 0) uniform UBO {
     float time;
 } ubo;
 
-layout(set = 3, binding = 0) uniform MBO {
-    mat4 model;
-    mat4 normal;
-} model;
+// model и режим OIT на draw
+#include "draw.glsl"
 
 layout(location = 0) out vec3 fragPos;
 layout(location = 1) out vec3 fragNormal;
@@ -23,11 +22,12 @@ layout(location = 3) out vec3 camPos;
 
 void main() {
     camPos = vec3(ubo.cam_pos.xyz);
-    fragPos = (model.model * vec4(inPos, 1.0)).xyz;
+    fragPos = (draw.model * vec4(inPos, 1.0)).xyz;
     fragTexCoord = inTexCoord;
 
-    mat3 normal_matrix = mat3(model.normal); //transpose(inverse(mat3(model.model)));
+    // в push constants место только под model, нормальную матрицу считаем тут
+    mat3 normal_matrix = transpose(inverse(mat3(draw.model)));
     fragNormal = normalize(normal_matrix * inNormal);
 
-    gl_Position =   ubo.view_proj *  model.model * vec4(inPos, 1.0);
+    gl_Position =   ubo.view_proj *  draw.model * vec4(inPos, 1.0);
 }
\ No newline at end of file
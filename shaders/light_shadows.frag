@@ -3,6 +3,8 @@
 /// Это работает плохо
 
 #version 450
+#extension GL_GOOGLE_include_directive : require
+#extension GL_EXT_nonuniform_qualifier : require
 
 layout(location = 0) in vec3 fragPos;
 layout(location = 1) in vec3 fragNormal;
@@ -11,64 +13,29 @@ layout(location = 3) in vec3 camPos;
 layout(location = 4) in vec4 fragPosLightSpace;
 
 
-const uint MAX_LIGHTS = 5;
-
-layout(set = 3, binding = 0) uniform MBO {
-    mat4 model;
-} model;
-
-struct DirectionalLight {
-    vec4 direction; // xyz = dir, w unused
-    vec4 color;     // rgb + intensity in w
-    mat4 light_mtx;
-};
-
-struct PointLight {
-    vec4 position;
-    vec4 color;  // rgb + intensity
-    vec4 coefficient;
-    vec4 _pad;
-    // vec4 _pad1;
-    mat4 light_mtx;
-};
-
-struct Spotlight {
-    vec4 position;
-    vec4 direction; // xyz + cutoff radians in w
-    vec4 color;     // rgb + intensity
-    vec4 cut_off;
-    // vec3 _pad;
-    mat4 light_mtx;
-};
-
-layout(std430, set = 0, binding = 1) buffer LightsSSBO {
-    uint light_count_directional;
-    uint light_count_point;
-    uint light_count_spotlight;
-    float time;
-    // uint _pad_ssbo;
-    DirectionalLight directional_lights[5];
-    PointLight point_lights[5];
-    Spotlight spotlights[5];
-};
-
-layout(set = 1, binding = 0) uniform sampler2D textureSampler;
-
-layout(set = 2, binding = 0) uniform MaterialUBO {
-    vec4 ambient;
-    vec4 diffuse;
-    vec4 specular;
-    vec4 extra; // extra.x = shininess
-} material;
-
-layout(set = 4, binding = 0) uniform sampler2DArrayShadow shadowMap;
+#include "shadow_config.glsl"
+#include "features.glsl"
+
+#define LIGHTS_WITH_SHADOWS
+#include "lights.glsl"
+
+#include "bindless.glsl"
+
+layout(set = 2, binding = 0) uniform sampler2DArrayShadow shadowMap;
+
+// SSAO, уже заблюренный, по экранным координатам
+layout(set = 3, binding = 0) uniform sampler2D aoTex;
 
 
 layout(location = 0) out vec4 outColor;
 
 
 float calculateShadow(int lightIndex, vec4 PosLightSpace, mat4 light_mtx, vec3 normal, vec3 lightDir, bool flag) {
-    const vec2 gMapSize = vec2(1024, 1024);
+    // вариант без теней: весь PCF выкидывается при специализации
+    if (!USE_SHADOWS) {
+        return 1.0;
+    }
+    vec2 gMapSize = vec2(float(SHADOW_MAP_SIZE));
 
     PosLightSpace = (light_mtx * PosLightSpace);
     vec3 projCoords = PosLightSpace.xyz / PosLightSpace.w;
@@ -97,8 +64,8 @@ float calculateShadow(int lightIndex, vec4 PosLightSpace, mat4 light_mtx, vec3 n
     float shadow = 0.0;
 
     vec2 texelSize = 1.0 / gMapSize;
-    for (int x = -2; x <= 2; x++) {
-        for (int y = -2; y <= 2; y++) {
+    for (int x = -PCF_RADIUS; x <= PCF_RADIUS; x++) {
+        for (int y = -PCF_RADIUS; y <= PCF_RADIUS; y++) {
             vec2 offset = vec2(x, y) * texelSize;
 
             vec4 coord = vec4(
@@ -111,7 +78,7 @@ float calculateShadow(int lightIndex, vec4 PosLightSpace, mat4 light_mtx, vec3 n
         }
     }
 
-    shadow /= 25.0;
+    shadow /= float((2 * PCF_RADIUS + 1) * (2 * PCF_RADIUS + 1));
 
     return shadow;
 
@@ -132,6 +99,7 @@ vec3 calc_Blinn_Phong(vec3 N, vec3 L, vec3 V, vec3 light_color, float intensity,
 }
 
 void main() {
+    load_material();
     const float PI = 3.1415;
     vec3 N = normalize(fragNormal);
     vec3 V = normalize(camPos - fragPos);
@@ -149,7 +117,14 @@ void main() {
 
     // uv = fragTexCoord;
 
-    vec4 texture_full = texture(textureSampler, uv);
+    vec4 texture_full = material_texture(uv);
+    if (USE_ALPHA_TEST) {
+        // вырезанное отбрасываем, остальное целиком из текстуры
+        if (texture_full.a < ALPHA_CUTOFF) {
+            discard;
+        }
+        texture_full.a = 1.0;
+    }
 
     // Ka
     vec3 ambient_m  = material.ambient.rgb;
@@ -164,7 +139,8 @@ void main() {
     // оно позволит фон делать прозрачный
     vec3 albedo = texture_full.rgb * texture_full.a + diffuse_m * (1.0 - texture_full.a);
 
-    vec3 result = ambient_m * albedo;
+    float ao = texture(aoTex, gl_FragCoord.xy / vec2(textureSize(aoTex, 0))).r;
+    vec3 result = ambient_m * albedo * ao;
 
     // -----------------------------------
     // Directional lights
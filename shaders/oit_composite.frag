@@ -0,0 +1,20 @@
+// oit_composite.frag
+#version 450
+
+// weighted blended OIT, McGuire & Bavoil 2013
+layout(set = 0, binding = 0) uniform sampler2D accumTex;
+layout(set = 0, binding = 1) uniform sampler2D revealTex;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    float reveal = texture(revealTex, vUV).r;
+    // прозрачного тут ничего не рисовалось
+    if (reveal >= 0.9999) {
+        discard;
+    }
+    vec4 accum = texture(accumTex, vUV);
+    vec3 color = accum.rgb / max(accum.a, 1e-5);
+    outColor = vec4(color, 1.0 - reveal);
+}
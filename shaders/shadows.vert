@@ -1,5 +1,6 @@
 // shadows.vert
 #version 450
+#extension GL_GOOGLE_include_directive : require
 // #extension GL_ARB_shader_viewport_layer_array: enable
 // #extension VK_NV_viewport_array2: enable
 
@@ -8,15 +9,11 @@ layout(location = 0) in vec3 inPosition;
 layout(set = 0, binding = 0) uniform ShadowUniform {
     mat4 lightSpaceMatrix;
     uint indx;
-    vec3 _pad;
 } ubo;
 
-layout(set = 1, binding = 0) uniform MBO {
-    mat4 model;
-    mat4 normal;
-} model;
+#include "draw.glsl"
 
 void main() {
-    gl_Position = ubo.lightSpaceMatrix * model.model * vec4(inPosition, 1.0);
+    gl_Position = ubo.lightSpaceMatrix * draw.model * vec4(inPosition, 1.0);
     // gl_Layer = int(ubo.indx);
 }
@@ -0,0 +1,71 @@
+// ssao.frag
+#version 450
+
+// SSAO по глубине: позиция и нормаль восстанавливаются во view space, ядро - полусфера вокруг нормали
+layout(set = 0, binding = 0) uniform SsaoUBO {
+    mat4 proj;
+    mat4 inv_proj;
+    vec4 params;  // radius, bias, intensity, sample_count
+    vec4 noise_scale;
+    vec4 kernel[64];
+} ubo;
+
+layout(set = 1, binding = 0) uniform sampler2D depthTex;
+layout(set = 1, binding = 1) uniform sampler2D noiseTex;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out float outAO;
+
+vec3 view_pos(vec2 uv) {
+    float depth = texture(depthTex, uv).r;
+    vec4 p = ubo.inv_proj * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+// из двух соседей берем тот что ближе по глубине, так край объекта не ломает нормаль
+vec3 pick(vec3 p, vec3 a, vec3 b) {
+    return abs(a.z - p.z) < abs(b.z - p.z) ? a - p : p - b;
+}
+
+void main() {
+    float depth = texture(depthTex, vUV).r;
+    if (depth >= 1.0) {
+        outAO = 1.0;  // небо
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(depthTex, 0));
+    vec3 P = view_pos(vUV);
+    vec3 dx = pick(P, view_pos(vUV + vec2(texel.x, 0.0)), view_pos(vUV - vec2(texel.x, 0.0)));
+    vec3 dy = pick(P, view_pos(vUV + vec2(0.0, texel.y)), view_pos(vUV - vec2(0.0, texel.y)));
+    vec3 N = normalize(cross(dx, dy));
+    if (dot(N, P) > 0.0) {
+        N = -N;  // нормаль к камере
+    }
+
+    vec3 rnd = vec3(texture(noiseTex, vUV * ubo.noise_scale.xy).xy * 2.0 - 1.0, 0.0);
+    vec3 T = normalize(rnd - N * dot(rnd, N));
+    vec3 B = cross(N, T);
+    mat3 TBN = mat3(T, B, N);
+
+    float radius = ubo.params.x;
+    float bias = ubo.params.y;
+    int count = int(ubo.params.w);
+    float occlusion = 0.0;
+    for (int i = 0; i < count; ++i) {
+        vec3 S = P + TBN * ubo.kernel[i].xyz * radius;
+
+        vec4 offset = ubo.proj * vec4(S, 1.0);
+        vec2 uv = offset.xy / offset.w * 0.5 + 0.5;
+        if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+            continue;
+        }
+
+        float sceneZ = view_pos(uv).z;
+        float range = smoothstep(0.0, 1.0, radius / max(abs(P.z - sceneZ), 1e-4));
+        occlusion += (sceneZ >= S.z + bias ? 1.0 : 0.0) * range;
+    }
+
+    float ao = 1.0 - occlusion / float(max(count, 1));
+    outAO = pow(ao, ubo.params.z);
+}
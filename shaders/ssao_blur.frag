@@ -0,0 +1,27 @@
+// ssao_blur.frag
+#version 450
+
+// бокс 4x4 под размер текстуры шума, убирает ее узор
+layout(set = 0, binding = 0) uniform sampler2D aoTex;
+
+layout(push_constant) uniform BlurPush {
+    vec2 texel;
+    uint enabled;
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out float outAO;
+
+void main() {
+    if (pc.enabled == 0u) {
+        outAO = 1.0;
+        return;
+    }
+    float sum = 0.0;
+    for (int x = -2; x < 2; ++x) {
+        for (int y = -2; y < 2; ++y) {
+            sum += texture(aoTex, vUV + vec2(float(x), float(y)) * pc.texel).r;
+        }
+    }
+    outAO = sum / 16.0;
+}
@@ -0,0 +1,53 @@
+// taa.frag
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D currentTex;
+layout(set = 0, binding = 1) uniform sampler2D depthTex;
+layout(set = 0, binding = 2) uniform sampler2D historyTex;
+
+layout(push_constant) uniform TaaPush {
+    mat4 reproject;  // clip текущего кадра -> clip прошлого
+    vec4 texel_jitter;  // xy - текстель, zw - jitter в uv
+    vec4 params;  // x - feedback, y - сброс истории
+} pc;
+
+layout(location = 0) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+// клемп и смешивание в сжатом диапазоне, иначе яркие пиксели тянут за собой шлейф
+vec3 compress(vec3 c) { return c / (1.0 + max(c.r, max(c.g, c.b))); }
+vec3 expand(vec3 c) { return c / max(1.0 - max(c.r, max(c.g, c.b)), 1e-4); }
+
+void main() {
+    vec2 texel = pc.texel_jitter.xy;
+    // снимаем jitter: точка uv без сдвига в этом кадре лежит в uv + jitter
+    vec2 uv = vUV + pc.texel_jitter.zw;
+
+    vec3 current = compress(texture(currentTex, uv).rgb);
+    vec3 nMin = current;
+    vec3 nMax = current;
+    float closest = texture(depthTex, uv).r;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            if (x == 0 && y == 0) continue;
+            vec2 o = vec2(x, y) * texel;
+            vec3 c = compress(texture(currentTex, uv + o).rgb);
+            nMin = min(nMin, c);
+            nMax = max(nMax, c);
+            // ближайшая глубина, чтобы край объекта не тащил вектор фона
+            closest = min(closest, texture(depthTex, uv + o).r);
+        }
+    }
+
+    // motion vector из текущего и прошлого view_proj
+    vec4 prevClip = pc.reproject * vec4(vUV * 2.0 - 1.0, closest, 1.0);
+    vec2 prevUV = prevClip.xy / prevClip.w * 0.5 + 0.5;
+
+    if (pc.params.y > 0.5 || any(lessThan(prevUV, vec2(0.0))) || any(greaterThan(prevUV, vec2(1.0)))) {
+        outColor = vec4(expand(current), 1.0);
+        return;
+    }
+
+    vec3 history = clamp(compress(texture(historyTex, prevUV).rgb), nMin, nMax);
+    outColor = vec4(expand(mix(current, history, pc.params.x)), 1.0);
+}
@@ -92,7 +92,8 @@ fn main() {
             std::thread::sleep(std::time::Duration::from_millis(100));
             continue;
         }
-        std::thread::sleep(std::time::Duration::from_millis(5));
+        // FPS limit из UI, 0 - не ждем вовсе
+        app.frame_limiter.wait();
     }
 
     app.device_wait_idle().unwrap();
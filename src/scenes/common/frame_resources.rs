@@ -4,20 +4,23 @@ use std::f32::EPSILON;
 use ash::vk;
 use super::super::super::vulkan_wr::{
     app::{VulkanApp, SceneResources},
+    swapchain::VulkanSwapchain,
     render_pass::pass::VulkanRenderPass,
     framebuffer::VulkanFramebuffer,
     image::{image_view::{VulkanImageView, VulkanImageViewBuilder}, image::{VulkanImage, VulkanImageBuilder}},
     command_pb::command_buffer::VulkanCommandBuffer,
     sync::{
         semaphore::VulkanSemaphore,
-        fence::VulkanFence,
+        frame_scheduler::FrameScheduler,
     },
     ImGui_wr::{ImguiResources},
     types::{vector::VulkanVector, matrix::Matrix},
+    post::{bloom::BloomPass, fullscreen::HDR_COLOR_FORMAT, settings::RenderSettings, msaa::MsaaSettings, antialias::{AntiAliasPass, AaMode}, ssao::SsaoPass, oit::{OitPass, TransparencySettings}},
 };
 
 // use super::objects::{SphereObject, InitSphereObject};
 use super::renderable_object::{RenderObjectEnum, GetFrameObj};
+use super::init::create_main_render_pass;
 
 #[derive(Clone, Copy)]
 pub struct Camera {
@@ -83,15 +86,41 @@ impl Camera {
 }
 
 pub struct FrameResources<R: ImguiResources + Default> {
-    pub vec_fence: Vec<VulkanFence>,  // CPU + GPU
-    pub vec_sem: Vec<VulkanSemaphore>, // [image_available, render_finished, ...]
-    pub vec_cmd_primary: Vec<VulkanCommandBuffer>,
+    pub frames: FrameScheduler,  // CPU + GPU, кадры в полете на timeline семафоре
+    pub image_available: Vec<VulkanSemaphore>, // per-frame, индекс frame_index
+    pub render_finished: Vec<VulkanSemaphore>, // per-image, индекс image_index, число следует за свапчейном
+    pub vec_cmd_primary: Vec<VulkanCommandBuffer>, // per-frame
 
     pub image_view: Vec<VulkanImageView>,
     pub framebuffers: Vec<VulkanFramebuffer>, // one per swapchain image
 
     pub render_pass: Option<VulkanRenderPass>,
 
+    // сцена рисуется в HDR, потом bloom + тонмап в swapchain, потом поверх ImGui
+    pub hdr_images: Vec<VulkanImage>,
+    pub hdr_image_views: Vec<VulkanImageView>,
+    pub ui_render_pass: Option<VulkanRenderPass>,
+    pub ui_framebuffers: Vec<VulkanFramebuffer>,
+    pub bloom: Option<BloomPass>,
+    pub render_settings: RenderSettings,
+
+    // MSAA: сцена рисуется в msaa_images и резолвится в hdr_images, при TYPE_1 они пустые
+    pub msaa_samples: vk::SampleCountFlags,
+    pub msaa_images: Vec<VulkanImage>,
+    pub msaa_image_views: Vec<VulkanImageView>,
+
+    // FXAA/TAA между сценой и bloom. post_from_aa - bloom сейчас читает выходы AA, а не HDR
+    pub antialias: Option<AntiAliasPass>,
+    pub post_from_aa: bool,
+    pub view_proj: Matrix<4, 4>,  // без jitter, из сцены, нужен TAA для репроекции
+
+    // SSAO до основного прохода, его AO читает ambient. None если сцене он не нужен
+    pub ssao: Option<SsaoPass>,
+    pub proj: Matrix<4, 4>,  // с jitter, из сцены
+
+    // weighted blended OIT после основного прохода, None если в сцене нет прозрачной очереди
+    pub oit: Option<OitPass>,
+
     pub depth_images: Vec<VulkanImage>,
     pub depth_image_views: Vec<VulkanImageView>,
 
@@ -109,20 +138,19 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
         image_count: u32,
     ) -> Result<FrameResources<R>, &'static str>{
         
-        let cmd_count_primary: u32 = image_count;
-        let cmd_count_secondary: u32 = image_count; // основной рендер + imgui
-        let cmd_count_secondary_imgui: u32 = image_count; // основной рендер + imgui
-        let sem_count: u32 = image_count * 2;  // 2 семафора на картнику 
-        let fence_count: u32 = image_count * 2; 
+        // cmd buf и image_available живут по кадру в полете, render_finished - по картинке
+        let frames = app.frames_in_flight;
+        let cmd_count_primary: u32 = frames;
+        let cmd_count_secondary: u32 = frames; // основной рендер + imgui
+        let cmd_count_secondary_imgui: u32 = frames; // основной рендер + imgui
 
-        let mut vec_sem = vec![];
-        for _ in 0..sem_count {
-            vec_sem.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
+        let mut image_available = vec![];
+        for _ in 0..frames {
+            image_available.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
         }
-
-        let mut vec_fence = vec![];
-        for _ in 0..fence_count {
-            vec_fence.push(VulkanFence::try_new(&app.core._logical_device, vk::FenceCreateFlags::SIGNALED)?);
+        let mut render_finished = vec![];
+        for _ in 0..image_count {
+            render_finished.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
         }
 
         let mut vec_cmd_primary = vec![];
@@ -140,13 +168,33 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
             vec_cmd_secondary_imgui.append(&mut tmp);
         }
         Ok(FrameResources {
-            vec_sem: vec_sem,
+            image_available: image_available,
+            render_finished: render_finished,
             vec_cmd_primary: vec_cmd_primary,
             // vec_cmd_secondary: vec_cmd_secondary,
-            vec_fence: vec_fence,
+            frames: FrameScheduler::try_new(&app.core._logical_device, frames)?,
             image_view: vec![],
             framebuffers: vec![], // one per swapchain image
             render_pass: None,
+            hdr_images: vec![],
+            hdr_image_views: vec![],
+            ui_render_pass: None,
+            ui_framebuffers: vec![],
+            bloom: None,
+            render_settings: RenderSettings {
+                msaa: MsaaSettings { supported: app.core.supported_msaa_samples, ..Default::default() },
+                transparency: TransparencySettings { supported: cfg!(feature = "scene2"), ..Default::default() },
+                ..Default::default()
+            },
+            msaa_samples: vk::SampleCountFlags::TYPE_1,
+            msaa_images: vec![],
+            msaa_image_views: vec![],
+            antialias: None,
+            post_from_aa: false,
+            view_proj: Matrix::identity(),
+            ssao: None,
+            proj: Matrix::identity(),
+            oit: None,
             depth_image_views: vec![],
             depth_images: vec![],
             
@@ -156,18 +204,44 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
         })
     }
 
+    fn on_swapchain_recreated(&mut self, app: &VulkanApp, old_swapchain: VulkanSwapchain) -> Result<(), &'static str> {
+        // пост проходы пересоздают свои цели сразу, поэтому ждем только отправленные кадры,
+        // обычно это один последний кадр, а не весь device
+        self.frames.wait_idle()?;
+        self.init_framebuffer(app)?;
+        drop(old_swapchain);
+        Ok(())
+    }
+
     fn init_framebuffer(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
-        self.framebuffers = vec![];
-        self.depth_image_views = vec![];
-        self.depth_images = vec![];
-        self.image_view = vec![];
+        // старые цели еще может читать кадр в полете, дропнем когда GPU его пройдет
+        // в кортеже поля дропаются по порядку: фреймбуферы, потом views, потом картинки
+        let old_targets = (
+            std::mem::take(&mut self.framebuffers),
+            std::mem::take(&mut self.ui_framebuffers),
+            std::mem::take(&mut self.hdr_image_views),
+            std::mem::take(&mut self.msaa_image_views),
+            std::mem::take(&mut self.depth_image_views),
+            std::mem::take(&mut self.image_view),
+            std::mem::take(&mut self.hdr_images),
+            std::mem::take(&mut self.msaa_images),
+            std::mem::take(&mut self.depth_images),
+        );
+        self.frames.defer_drop(old_targets);
+        self.resize_render_finished(app)?;
 
         for (i, image) in app.swapchain.images.iter().enumerate() {
-            // Создание depth изображения
+            // Создание depth изображения, без MSAA его еще читает TAA
+            let depth_usage = if self.msaa_samples == vk::SampleCountFlags::TYPE_1 {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+            } else {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            };
             let depth_image = VulkanImageBuilder::new(&app.core)
             .format(app.swapchain.depth_format)
             .extent(app.swapchain.extent.width, app.swapchain.extent.height, 1)
-            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .usage(depth_usage)
+            .samples(self.msaa_samples)
             .build()?;
 
             let depth_view = VulkanImageViewBuilder::new(&app.core._logical_device, depth_image.image)
@@ -184,7 +258,37 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
                 .build()?
             );
 
-            let att = vec![self.image_view[i].view, self.depth_image_views[i].view];
+            // HDR цель основного прохода
+            let hdr_image = VulkanImageBuilder::new(&app.core)
+            .format(HDR_COLOR_FORMAT)
+            .extent(app.swapchain.extent.width, app.swapchain.extent.height, 1)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .build()?;
+            self.hdr_image_views.push(
+                VulkanImageViewBuilder::new(&app.core._logical_device, hdr_image.image)
+                .format(HDR_COLOR_FORMAT)
+                .build()?
+            );
+            self.hdr_images.push(hdr_image);
+
+            // порядок как в create_main_render_pass
+            let att = if self.msaa_samples != vk::SampleCountFlags::TYPE_1 {
+                let msaa_image = VulkanImageBuilder::new(&app.core)
+                .format(HDR_COLOR_FORMAT)
+                .extent(app.swapchain.extent.width, app.swapchain.extent.height, 1)
+                .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT)
+                .samples(self.msaa_samples)
+                .build()?;
+                self.msaa_image_views.push(
+                    VulkanImageViewBuilder::new(&app.core._logical_device, msaa_image.image)
+                    .format(HDR_COLOR_FORMAT)
+                    .build()?
+                );
+                self.msaa_images.push(msaa_image);
+                vec![self.msaa_image_views[i].view, self.depth_image_views[i].view, self.hdr_image_views[i].view]
+            } else {
+                vec![self.hdr_image_views[i].view, self.depth_image_views[i].view]
+            };
             self.framebuffers.push(VulkanFramebuffer::try_new(
                 &app.core._logical_device,
                 self.render_pass.as_ref().unwrap().render_pass.clone(),
@@ -192,10 +296,140 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
                 app.swapchain.extent,
                 1
             )?);
+
+            self.ui_framebuffers.push(VulkanFramebuffer::try_new(
+                &app.core._logical_device,
+                self.ui_render_pass.as_ref().unwrap().render_pass.clone(),
+                vec![self.image_view[i].view],
+                app.swapchain.extent,
+                1
+            )?);
+        }
+
+        let hdr_views: Vec<vk::ImageView> = self.hdr_image_views.iter().map(|v| v.view).collect();
+        if let Some(aa) = self.antialias.as_mut() {
+            let depth_views: Vec<vk::ImageView> = self.depth_image_views.iter().map(|v| v.view).collect();
+            let sampled_depth = self.msaa_samples == vk::SampleCountFlags::TYPE_1;
+            aa.resize(app, &hdr_views, if sampled_depth { Some(&depth_views) } else { None })?;
+        }
+        if let Some(ssao) = self.ssao.as_mut() {
+            ssao.resize(app)?;
+        }
+        if let Some(oit) = self.oit.as_mut() {
+            oit.resize(app)?;
+        }
+        self.bind_post_source(app)
+    }
+
+}
+
+impl<R: ImguiResources + Default> FrameResources<R> {
+    /// render_finished по одному на картинку свапчейна, после пересоздания картинок может стать больше или меньше.
+    /// Лишние могут еще ждать present, поэтому уходят в отложенное удаление
+    fn resize_render_finished(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        let image_count = app.swapchain.images.len();
+        if self.render_finished.len() > image_count {
+            let extra = self.render_finished.split_off(image_count);
+            self.frames.defer_drop(extra);
+        }
+        while self.render_finished.len() < image_count {
+            self.render_finished.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
+        }
+        Ok(())
+    }
+
+    /// Применяет то, что накрутили в ImGui и что требует пересоздания ресурсов. Вызывается до записи кадра
+    pub fn apply_render_settings(&mut self, app: &mut VulkanApp) -> Result<(), &'static str> {
+        // VSync меняется пересозданием свапчейна, app сделает его на ближайшем acquire
+        app.set_present_mode(self.render_settings.present.present_mode());
+        app.frame_limiter.set_target_fps(self.render_settings.present.fps_limit);
+        let samples = self.render_settings.msaa.sample_count();
+        if samples != self.msaa_samples {
+            self.set_msaa_samples(app, samples)?;
+        }
+        let from_aa = self.render_settings.aa_mode() != AaMode::None;
+        if from_aa != self.post_from_aa {
+            // сеты bloom могут быть еще в полете
+            app.device_wait_idle()?;
+            self.bind_post_source(app)?;
+        }
+        self.apply_shader_reload(app)?;
+        Ok(())
+    }
+
+    /// Пересоздает пайплайны, чьи шейдеры пересобрал hot reload. Не собрался новый -
+    /// остается старый, ошибка уходит в UI
+    fn apply_shader_reload(&mut self, app: &mut VulkanApp) -> Result<(), &'static str> {
+        let changed = app.shader_reload.take_changed();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        // старые пайплайны могут быть в кадрах в полете
+        app.device_wait_idle()?;
+        let render_pass = self.render_pass.as_ref().ok_or("Render pass is not initialized")?.render_pass;
+        for obj in self.vec_objects.iter_mut() {
+            let result = match obj {
+                #[cfg(feature = "scene1")]
+                RenderObjectEnum::Sphere(o) => o.reload_shaders(app, &changed, render_pass, self.msaa_samples),
+                #[cfg(feature = "scene2")]
+                RenderObjectEnum::Light(o) => o.reload_shaders(app, &changed, render_pass, self.msaa_samples),
+                RenderObjectEnum::ImGui(_) => Ok(()),
+            };
+            if let Err(err) = result {
+                app.shader_reload.report(&changed, err);
+            }
+        }
+        if let Some(bloom) = self.bloom.as_mut() {
+            if let Err(err) = bloom.reload_shaders(app, &changed) {
+                app.shader_reload.report(&changed, err);
+            }
         }
         Ok(())
     }
 
+    /// Jitter проекции для этого кадра, [0, 0] если TAA выключен
+    pub fn taa_jitter(&self) -> [f32; 2] {
+        match (self.render_settings.aa_mode(), self.antialias.as_ref()) {
+            (AaMode::Taa, Some(aa)) => aa.jitter(),
+            _ => [0.0, 0.0],
+        }
+    }
+
+    /// Переключает вход bloom между HDR и выходами AA
+    fn bind_post_source(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        let aa_views = match self.antialias.as_ref() {
+            Some(aa) if self.render_settings.aa_mode() != AaMode::None => Some(aa.output_views()),
+            _ => None,
+        };
+        self.post_from_aa = aa_views.is_some();
+        let views = aa_views.unwrap_or_else(|| self.hdr_image_views.iter().map(|v| v.view).collect());
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.resize(app, &views)?;
+        }
+        Ok(())
+    }
+
+    /// Меняет MSAA на лету: render pass, таргеты и пайплайны объектов пересоздаются
+    pub fn set_msaa_samples(&mut self, app: &VulkanApp, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        app.device_wait_idle()?;
+        self.msaa_samples = samples;
+        // framebuffers держат старый render pass, поэтому сначала они
+        self.framebuffers = vec![];
+        self.render_pass = Some(create_main_render_pass(app, samples)?);
+        self.init_framebuffer(app)?;
+
+        let render_pass = self.render_pass.as_ref().ok_or("Render pass is not initialized")?.render_pass;
+        for obj in self.vec_objects.iter_mut() {
+            match obj {
+                #[cfg(feature = "scene1")]
+                RenderObjectEnum::Sphere(o) => o.rebuild_pipeline(app, render_pass, samples)?,
+                #[cfg(feature = "scene2")]
+                RenderObjectEnum::Light(o) => o.rebuild_pipeline(app, render_pass, samples)?,
+                RenderObjectEnum::ImGui(_) => {},  // свой UI проход, MSAA не касается
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<R: ImguiResources + Default> GetFrameObj<R> for FrameResources<R> {
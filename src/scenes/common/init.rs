@@ -1,6 +1,8 @@
 
 #[cfg(feature = "scene2")]
 use crate::scenes::lighting::objects::LightObject;
+#[cfg(feature = "scene2")]
+use crate::vulkan_wr::post::{ssao::SsaoPass, oit::OitPass};
 use crate::vulkan_wr::{app::SceneResources};
 
 use super::{
@@ -14,6 +16,7 @@ use super::super::super::vulkan_wr::{
     render_pass::{subpass::SubpassConfigBuilder, pass::VulkanRenderPass},
     renderable_traits::InitObject,
     renderable_traits::InitFrameResources,
+    post::{bloom::BloomPass, fullscreen::HDR_COLOR_FORMAT, antialias::AntiAliasPass},
 };
 use ash::vk;
 // use imgui::internal::RawWrapper;
@@ -24,6 +27,101 @@ use crate::scenes::sphere::objects::SphereObject;
 pub fn init_app<R: ImguiResources + Default>(app: &mut VulkanApp, resources: &mut FrameResources<R>) -> Result<(), &'static str> {
 
     // 1. Render pass
+    resources.msaa_samples = resources.render_settings.msaa.sample_count();
+    resources.render_pass = Some(create_main_render_pass(app, resources.msaa_samples)?);
+
+    // UI проход поверх уже тонмапнутой картинки в swapchain
+    let ui_color_attachment = vk::AttachmentDescription {
+        format: app.swapchain.color_format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::LOAD,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        ..Default::default()
+    };
+    let ui_subpass = vec![SubpassConfigBuilder::new()
+        .bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .add_color_attachment(vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        })
+        .add_attachment(ui_color_attachment)
+        .build()];
+    let ui_render_pass = VulkanRenderPass::try_new(
+        ui_subpass,
+        vec![
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dependency_flags: vk::DependencyFlags::empty(),
+            }
+        ],
+        &app.core._logical_device
+    )?;
+    resources.ui_render_pass = Some(ui_render_pass);
+
+    resources.bloom = Some(BloomPass::try_new(app, app.swapchain.color_format)?);
+    resources.antialias = Some(AntiAliasPass::try_new(app)?);
+    // AO нужен только освещению, сфере он ни к чему
+    #[cfg(feature = "scene2")]
+    {
+        resources.ssao = Some(SsaoPass::try_new(app)?);
+        resources.oit = Some(OitPass::try_new(app)?);
+    }
+
+    #[cfg(feature = "scene1")]
+    resources.vec_objects.push(RenderObjectEnum::Sphere(SphereObject::init(
+            app,
+            &mut InitFrameResources {
+                render_pass: Some(resources.render_pass.as_ref().unwrap()),
+                samples: resources.msaa_samples,
+                ..Default::default()
+            }
+        )?)
+    );
+    #[cfg(feature = "scene2")]
+    resources.vec_objects.push(RenderObjectEnum::Light(LightObject::init(
+            app,
+            &mut InitFrameResources {
+                render_pass: Some(resources.render_pass.as_ref().unwrap()),
+                samples: resources.msaa_samples,
+                ao_layout: resources.ssao.as_ref().map(|s| s.ao_set_layout()),
+                ..Default::default()
+            }
+        )?)
+    );
+    // ----- IMGUI ------  СНОВА СНОВА ПОСЛЕДНИЙ 
+    resources.vec_objects.push(RenderObjectEnum::ImGui(VulkanImgui::<R>::init(
+            app,
+            &mut InitFrameResources {
+                render_pass: Some(resources.ui_render_pass.as_ref().unwrap()),
+                ..Default::default()
+            }
+        )?)
+    );
+
+    // 2. Framebuffers (по одному на image)]
+    resources.init_framebuffer(app)?;
+
+    resources.start_time = std::time::Instant::now();
+
+    Ok(())
+
+}
+
+/// Основной проход сцены. При MSAA рисует в multisample color/depth и резолвит в HDR
+/// # Args
+/// * `samples` - TYPE_1 значит без MSAA, тогда HDR сразу color attachment
+/// Порядок attachments совпадает с framebuffer: [color, depth] или [msaa color, depth, resolve HDR]
+pub fn create_main_render_pass(app: &VulkanApp, samples: vk::SampleCountFlags) -> Result<VulkanRenderPass, &'static str> {
+    let msaa = samples != vk::SampleCountFlags::TYPE_1;
     // vk::AttachmentDescription метаинфа одного вложения в рендерпасе
     // * `format` - формат пикселей вложения (должен соответствовать формату изображения)
     // * `samples` - количество сэмплов для мультисэмплинга (обычно TYPE_1 для отсутствия мультисэмплинга)
@@ -33,28 +131,31 @@ pub fn init_app<R: ImguiResources + Default>(app: &mut VulkanApp, resources: &mu
     // * `stencil_store_op` - операция сохранения для трафаретного буфера
     // * `initial_layout` - начальный layout изображения перед рендер-пассом
     // * `final_layout` - конечный layout изображения после рендер-пасса
+    // сцена рисуется в HDR, после прохода его читает bloom
+    // с MSAA сами сэмплы после резолва не нужны, читается только resolve
     let color_attachment = vk::AttachmentDescription {
-        format: app.swapchain.color_format,
-        samples: vk::SampleCountFlags::TYPE_1,
+        format: HDR_COLOR_FORMAT,
+        samples: samples,
         load_op: vk::AttachmentLoadOp::CLEAR,  // операция при начале рендер-пасса (CLEAR, LOAD, DONT_CARE)
-        store_op: vk::AttachmentStoreOp::STORE, // в конце (STORE, DONT_CARE)
+        store_op: if msaa { vk::AttachmentStoreOp::DONT_CARE } else { vk::AttachmentStoreOp::STORE }, // в конце (STORE, DONT_CARE)
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
         initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        final_layout: if msaa { vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL } else { vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL },
         ..Default::default()
     };
 
     // Depth attachment
+    // без MSAA глубину после прохода читает TAA, поэтому сохраняем
     let depth_attachment = vk::AttachmentDescription {
         format: app.swapchain.depth_format,
-        samples: vk::SampleCountFlags::TYPE_1,
+        samples: samples,
         load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::DONT_CARE,
+        store_op: if msaa { vk::AttachmentStoreOp::DONT_CARE } else { vk::AttachmentStoreOp::STORE },
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
         initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        final_layout: if msaa { vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL } else { vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL },
         ..Default::default()
     };
 
@@ -72,59 +173,46 @@ pub fn init_app<R: ImguiResources + Default>(app: &mut VulkanApp, resources: &mu
         }
     ];
 
-
-    let subpass = vec![SubpassConfigBuilder::new()
+    let mut subpass = SubpassConfigBuilder::new()
         .bind_point(vk::PipelineBindPoint::GRAPHICS)
         .add_color_attachment(att_arr[0])
         .add_attachment(color_attachment)
         .add_depth_stencil(att_arr[1])
-        .add_attachment(depth_attachment)
-        .build()];
-
-    let render_pass = VulkanRenderPass::try_new(
-        subpass,
-        vec![],  // вектор SubpassDependency между файлами
-        &app.core._logical_device
-    )?;
-
-    resources.render_pass = Some(render_pass);
-
-    #[cfg(feature = "scene1")]
-    resources.vec_objects.push(RenderObjectEnum::Sphere(SphereObject::init(
-            app,
-            &mut InitFrameResources {
-                render_pass: Some(resources.render_pass.as_ref().unwrap()),
-                upload_cmd: Some(&resources.vec_cmd_primary[0]),
-                fence: Some(&resources.vec_fence[0]),
+        .add_attachment(depth_attachment);
+    if msaa {
+        // односэмпловый HDR, в него резолвится в конце сабпасса
+        subpass = subpass
+            .add_resolve_attachment(vk::AttachmentReference {
+                attachment: 2,
+                layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            })
+            .add_attachment(vk::AttachmentDescription {
+                format: HDR_COLOR_FORMAT,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::DONT_CARE,
+                store_op: vk::AttachmentStoreOp::STORE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ..Default::default()
+            });
+    }
+
+    VulkanRenderPass::try_new(
+        vec![subpass.build()],
+        vec![
+            // bloom/AA читают HDR (и depth) только после того как сцена дорисовалась (резолв тоже COLOR_ATTACHMENT_OUTPUT)
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                dependency_flags: vk::DependencyFlags::empty(),
             }
-        )?)
-    );
-    #[cfg(feature = "scene2")]
-    resources.vec_objects.push(RenderObjectEnum::Light(LightObject::init(
-            app,
-            &mut InitFrameResources {
-                render_pass: Some(resources.render_pass.as_ref().unwrap()),
-                upload_cmd: Some(&resources.vec_cmd_primary[0]),
-                fence: Some(&resources.vec_fence[0]),
-            }
-        )?)
-    );
-    // ----- IMGUI ------  СНОВА СНОВА ПОСЛЕДНИЙ 
-    resources.vec_objects.push(RenderObjectEnum::ImGui(VulkanImgui::<R>::init(
-            app,
-            &mut InitFrameResources {
-                render_pass: Some(resources.render_pass.as_ref().unwrap()),
-                upload_cmd: Some(&resources.vec_cmd_primary[0]),
-                fence: Some(&resources.vec_fence[0]),
-            }
-        )?)
-    );
-
-    // 2. Framebuffers (по одному на image)]
-    resources.init_framebuffer(app)?;
-
-    resources.start_time = std::time::Instant::now();
-
-    Ok(())
-
+        ],
+        &app.core._logical_device
+    )
 }
@@ -8,51 +8,54 @@ use super::super::super::vulkan_wr::{
     app::VulkanApp,
     renderable_traits::{RenderObject},
     ImGui_wr::ImguiResources,
+    post::{fullscreen::color_barrier, antialias::AaMode},
 };
 
 
 pub fn render_frame_app<R: ImguiResources + Default>(app: & mut VulkanApp, resources: &mut FrameResources<R>) -> Result<(), &'static str> {
-    // fecne связывает CPU GPU, индексируется фреймом, image_available принадлежит также фрейму.
-    // Тк когда queue_submit закончит работу, этот фрейм снова освободится через fence
-    // cmd buf и render_finished индексируются картинкой, тк тесно с ней связаны.
+    // CPU и GPU связывает timeline семафор планировщика, image_available принадлежит слоту кадра.
+    // Кадр сигналит свое значение на timeline, слот освобождается когда GPU до него дошел
+    // cmd buf тоже per-frame, а render_finished индексируется картинкой, его ждет present этой картинки.
     if (0, 0) == app.window.get_width_height() {
-        app.frame_index = (app.frame_index + 1) % app.image_count;
-        return Ok(());
-    }
-    let swap_exten = app.swapchain.extent;
-    if swap_exten.height <= 1 || swap_exten.width <= 1 {
-        app.frame_index = (app.frame_index + 1) % app.image_count;
         return Ok(());
     }
+    // MSAA/AA поменяли в UI - пересобираем все что от них зависит
+    resources.apply_render_settings(app)?;
+
+    // ждем пока GPU отпустит слот кадра, слот и есть frame_index для всех per-frame ресурсов
+    // пропущенные кадры слот не тратят, поэтому индекс берем у планировщика
+    app.frame_index = resources.frames.begin_frame()? as u32;
     let current_frame: usize = app.frame_index as usize;
     
-    let frame_sync = resources.vec_fence[current_frame].fence;
-    unsafe {
-        app.core._logical_device.wait_for_fences(&[frame_sync], true, u64::MAX).unwrap();
-        app.core._logical_device.reset_fences(&[frame_sync]).unwrap();
-    }
+    let image_available = resources.image_available[current_frame].semaphore;
     
-    let sem_offset = (current_frame * 2) as usize;
-    let image_available = resources.vec_sem[sem_offset].semaphore.clone();
-    
-    let (image_index, suboptimal) = app.swapchain.acquire_next_image(Some(image_available), None)?;
-    if suboptimal {
-            // Swapchain полностью устарел, нужно пересоздать
-            app.device_wait_idle()?;
-            app.recreate_swapchain()?;
-            resources.init_framebuffer(&app)?;
-            app.frame_index = (app.frame_index + 1) % app.image_count;
-            app.device_wait_idle()?;
-            return Ok(());
-    }
+    // OutOfDate и ресайз разбирает app, тут только "есть картинка или нет"
+    let Some(image_index) = app.acquire_image(resources, image_available)? else {
+        return Ok(());
+    };
+    // размер берем после acquire, свапчейн мог только что пересоздаться
+    let swap_exten = app.swapchain.extent;
 
+    // с OIT прозрачные уходят из основного прохода в отдельный после него
+    let oit = resources.render_settings.oit_active() && resources.oit.is_some();
     let resss = RenderFrameResources{
             render_pass: Some(resources.render_pass.as_ref().unwrap()),
             framebuffer: Some(&resources.framebuffers[image_index as usize]),
+            ao_set: resources.ssao.as_ref().and_then(|s| s.ao_set(image_index as usize)),
+            oit: oit,
+            ..Default::default()
+    };
+    // ImGui рисуется отдельным проходом прямо в swapchain, после тонмапа
+    let resss_ui = RenderFrameResources{
+            render_pass: Some(resources.ui_render_pass.as_ref().unwrap()),
+            framebuffer: Some(&resources.ui_framebuffers[image_index as usize]),
             ..Default::default()
     };
     for obj in &mut resources.vec_objects {
-        obj.render(app, &resss)?;
+        match obj {
+            RenderObjectEnum::ImGui(_) => obj.render(app, &resss_ui)?,
+            _ => obj.render(app, &resss)?,
+        }
     }
 
     let cmd_primary = &resources.vec_cmd_primary[current_frame as usize];
@@ -76,76 +79,142 @@ pub fn render_frame_app<R: ImguiResources + Default>(app: & mut VulkanApp, resou
             p_clear_values: clear_values.as_ptr(),
             ..Default::default()
         };
+        let ui_begin_info = vk::RenderPassBeginInfo {
+            render_pass: resources.ui_render_pass.as_ref().unwrap().render_pass,
+            framebuffer: resources.ui_framebuffers[image_index as usize].framebuffer,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: swap_exten
+            },
+            ..Default::default()
+        };
+        // SSAO: глубина префассом, потом AO + блюр, основной проход уже читает готовый AO
+        if let Some(ssao) = resources.ssao.as_ref() {
+            let settings = &resources.render_settings.ssao;
+            if settings.enabled {
+                ssao.begin_prepass(cmd_primary, image_index as usize)?;
+                for obj in resources.vec_objects.iter() {
+                    match obj {
+                        #[cfg(feature = "scene2")]
+                        RenderObjectEnum::Light(objj) => objj.record_depth_prepass(app, cmd_primary)?,
+                        _ => {},
+                    }
+                }
+                cmd_primary.end_dynamic_rendering()?;
+            }
+            ssao.record(cmd_primary, image_index as usize, current_frame, settings, &resources.proj)?;
+        }
+
         unsafe {
             cmd_primary.begin_render_pass(
                 &begin_info,
                 vk::SubpassContents::SECONDARY_COMMAND_BUFFERS  // specifying how the commands in the first subpass will be provided.
             );
 
-            let buff_vec: Vec<vk::CommandBuffer> = resources.vec_objects.iter().map(|obj|
+            let mut buff_vec: Vec<vk::CommandBuffer> = vec![];
+            let mut ui_buff_vec: Vec<vk::CommandBuffer> = vec![];
+            for obj in resources.vec_objects.iter() {
                 match obj {
-                    RenderObjectEnum::ImGui(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
+                    RenderObjectEnum::ImGui(objj) => {ui_buff_vec.push(objj.cmd_vec[current_frame as usize]._buffer)}
                     #[cfg(feature = "scene1")]
-                    RenderObjectEnum::Sphere(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
+                    RenderObjectEnum::Sphere(objj) => {buff_vec.push(objj.cmd_vec[current_frame as usize]._buffer)}
                     #[cfg(feature = "scene2")]
-                    RenderObjectEnum::Light(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
+                    RenderObjectEnum::Light(objj) => {buff_vec.push(objj.cmd_vec[current_frame as usize]._buffer)}
                 }
-            ).collect();
+            }
 
             // основной цикл
             cmd_primary.execute_commands(
                 buff_vec.as_slice()
             );
             cmd_primary.end_render_pass();
+
+            // прозрачные с depth test по глубине основного прохода, потом смешиваются в HDR
+            if let Some(oit_pass) = resources.oit.as_ref().filter(|_| oit) {
+                oit_pass.begin(cmd_primary, image_index as usize, resources.depth_image_views[image_index as usize].view)?;
+                for obj in resources.vec_objects.iter() {
+                    match obj {
+                        #[cfg(feature = "scene2")]
+                        RenderObjectEnum::Light(objj) => objj.record_transparent_oit(app, cmd_primary, resss.ao_set.ok_or("SSAO is not initialized")?)?,
+                        _ => {},
+                    }
+                }
+                cmd_primary.end_dynamic_rendering()?;
+                oit_pass.composite(
+                    cmd_primary,
+                    image_index as usize,
+                    resources.hdr_images[image_index as usize].image,
+                    resources.hdr_image_views[image_index as usize].view,
+                )?;
+            }
+
+            // AA читает HDR (TAA еще и depth) и пишет свой таргет, его дальше берет bloom
+            let aa_mode = resources.render_settings.aa_mode();
+            if aa_mode != AaMode::None {
+                resources.antialias.as_mut().ok_or("AA is not initialized")?.record(
+                    cmd_primary,
+                    image_index as usize,
+                    &resources.render_settings.aa,
+                    aa_mode,
+                    &resources.view_proj,
+                )?;
+            }
+
+            // swapchain картинку перезаписывает композит, старое содержимое не нужно
+            // src стадия совпадает с wait стадией image_available, иначе цепочка не строится
+            cmd_primary.pipeline_barrier(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::DependencyFlags::empty(), &[], &[],
+                &[color_barrier(
+                    app.swapchain.images[image_index as usize],
+                    vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    vk::AccessFlags::empty(), vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )]
+            );
+            resources.bloom.as_ref().ok_or("Bloom is not initialized")?.record(
+                cmd_primary,
+                image_index as usize,
+                resources.image_view[image_index as usize].view,
+                swap_exten,
+                &resources.render_settings.bloom,
+            )?;
+
+            cmd_primary.begin_render_pass(&ui_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+            cmd_primary.execute_commands(ui_buff_vec.as_slice());
+            cmd_primary.end_render_pass();
         }
         cmd_primary.end()?;
     }
 
-    let render_finished = &resources.vec_sem[(image_index * 2 + 1) as usize];
+    let render_finished = resources.render_finished[image_index as usize].semaphore;
 
     // Submit
-    let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-
-    let submit_info = vk::SubmitInfo {
-        wait_semaphore_count: 1,
-        p_wait_semaphores: &image_available,
-        p_wait_dst_stage_mask: wait_stages.as_ptr(),
-        command_buffer_count: 1,
-        p_command_buffers: &cmd_primary._buffer,
-        signal_semaphore_count: 1,
-        p_signal_semaphores: &render_finished.semaphore,
-        ..Default::default()
-    };
-
-    app.core.queue_submit(&[submit_info], frame_sync)?;
-
-    // Present
-    let present_info = vk::PresentInfoKHR {
-        wait_semaphore_count: 1,
-        p_wait_semaphores: &render_finished.semaphore,
-        swapchain_count: 1,
-        p_swapchains: &app.swapchain.swapchain,
-        p_image_indices: &image_index,
-        ..Default::default()
-    };
-
-    // app.swapchain.queue_present(app.core._graphics_queue, &present_info)?;
-    match app.swapchain.queue_present(app.core._graphics_queue, &present_info) {
-        Ok(true) => {
-            // Все в порядке
-        },
-        Ok(false) => {
-            // Swapchain полностью устарел, нужно пересоздать
-            app.device_wait_idle()?;
-            app.recreate_swapchain()?;
-            resources.init_framebuffer(&app)?;
-            app.frame_index = (app.frame_index + 1) % app.image_count;
-            app.device_wait_idle()?;
-        },
-        Err(e) => {
-            return Err("Failed to present swapchain image");
-        }
-    };
+    let wait_infos = [
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(image_available)
+            .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT),
+    ];
+    // render_finished для present, timeline для CPU и отложенного удаления
+    let signal_infos = [
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(render_finished)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS),
+        resources.frames.signal_info(),
+    ];
+    let cmd_infos = [
+        vk::CommandBufferSubmitInfo::default().command_buffer(cmd_primary._buffer),
+    ];
+    let submit_info = vk::SubmitInfo2::default()
+        .wait_semaphore_infos(&wait_infos)
+        .command_buffer_infos(&cmd_infos)
+        .signal_semaphore_infos(&signal_infos);
+
+    app.core.queue_submit2(&[submit_info], vk::Fence::null())?;
+    resources.frames.end_frame();
+
+    // Present, пересоздание свапчейна если надо - там же
+    app.present_image(resources, image_index, render_finished)?;
 
     Ok(())
 
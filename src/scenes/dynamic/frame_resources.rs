@@ -4,14 +4,17 @@ use std::f32::EPSILON;
 use ash::vk;
 use super::super::super::vulkan_wr::{
     app::{VulkanApp, SceneResources},
+    swapchain::VulkanSwapchain,
     image::{image_view::{VulkanImageView, VulkanImageViewBuilder}, image::{VulkanImage, VulkanImageBuilder}},
-    command_pb::command_buffer::VulkanCommandBuffer,
+    command_pb::{command_buffer::VulkanCommandBuffer, parallel::ParallelRecorder, async_compute::AsyncCompute},
     sync::{
         semaphore::VulkanSemaphore,
-        fence::VulkanFence,
+        frame_scheduler::FrameScheduler,
     },
     ImGui_wr::{ImguiResources},
+    render_graph::transient::TransientPool,
     types::{vector::VulkanVector, matrix::Matrix},
+    post::{bloom::BloomPass, fullscreen::HDR_COLOR_FORMAT, settings::RenderSettings, msaa::MsaaSettings, antialias::{AntiAliasPass, AaMode}, ssao::SsaoPass, gbuffer::{GBuffer, ShadingSettings, DeferredLightPush}, histogram::{HistogramPass, HistogramSettings}},
 };
 
 // use super::objects::{SphereObject, InitSphereObject};
@@ -77,15 +80,46 @@ impl Camera {
 }
 
 pub struct FrameResources<R: ImguiResources + Default> {
-    pub vec_fence: Vec<VulkanFence>,  // CPU + GPU
-    pub vec_sem: Vec<VulkanSemaphore>, // [image_available, render_finished, ...]
-    pub vec_cmd_primary: Vec<VulkanCommandBuffer>,
+    pub frames: FrameScheduler,  // CPU + GPU, кадры в полете на timeline семафоре
+    // compute очередь (гистограмма). Сразу за frames: при дропе ждет свой timeline раньше, чем уйдут картинки, которые она читает
+    pub async_compute: Option<AsyncCompute>,
+    pub compute_wait: Option<u64>,  // значение compute, которое следующий кадр графики ждет до записи в ее вход
+    pub compute_owned: Option<vk::Image>,  // картинку compute отдал обратно, графика делает acquire
+    pub image_available: Vec<VulkanSemaphore>, // per-frame, индекс frame_index
+    pub render_finished: Vec<VulkanSemaphore>, // per-image, индекс image_index, число следует за свапчейном
+    pub vec_cmd_primary: Vec<VulkanCommandBuffer>, // per-frame
+    pub recorder: ParallelRecorder,  // secondary буферы объектов, пишутся в рабочих потоках
 
     pub image_view: Vec<VulkanImageView>,
 
     pub depth_images: Vec<VulkanImage>,
     pub depth_image_views: Vec<VulkanImageView>,
 
+    // основной проход рисует в HDR, bloom композит пишет в swapchain
+    pub hdr_images: Vec<VulkanImage>,
+    pub hdr_image_views: Vec<VulkanImageView>,
+    pub bloom: Option<BloomPass>,
+    pub render_settings: RenderSettings,
+
+    // MSAA: сцена рисуется в transient картинку графа и резолвится в hdr_images
+    pub msaa_samples: vk::SampleCountFlags,
+    pub transient_pools: Vec<TransientPool>,  // transient картинки render graph, по кадру в полете
+
+    // FXAA/TAA между сценой и bloom. post_from_aa - bloom сейчас читает выходы AA, а не HDR
+    pub antialias: Option<AntiAliasPass>,
+    pub post_from_aa: bool,
+    pub view_proj: Matrix<4, 4>,  // без jitter, из сцены, нужен TAA для репроекции
+
+    // SSAO до основного прохода, его AO читает ambient. None если сцене он не нужен
+    pub ssao: Option<SsaoPass>,
+    pub proj: Matrix<4, 4>,  // с jitter, из сцены
+
+    // deferred путь, пустой при MSAA
+    pub gbuffer: Option<GBuffer>,
+
+    // compute гистограмма яркости того же входа, что у bloom
+    pub histogram: Option<HistogramPass>,
+
     pub start_time: std::time::Instant,
 
     pub vec_objects: Vec<RenderObjectEnum<R>>,
@@ -96,7 +130,6 @@ pub struct FrameResources<R: ImguiResources + Default> {
     // по идее их лучше использовать и отвязать swapchain в init, но оно больше нигде не нужно и используется при пересоздании свапчейна и первой инициализации, и будто бы нет в них никакого смысла, но мало ли, надо когда=то это организовать нормально, наверное, но переделывать все это желания около 0
     pub color_attachment_format: vk::Format,
     pub depth_attachment_format: vk::Format,
-    pub shadow_finished_sem: Vec<VulkanSemaphore>,
 
 }
 
@@ -107,28 +140,22 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
         image_count: u32,
     ) -> Result<FrameResources<R>, &'static str>{
         
-        let cmd_count_primary: u32 = image_count;
-        let cmd_count_secondary: u32 = image_count; // основной рендер + imgui
-        let cmd_count_secondary_imgui: u32 = image_count; // основной рендер + imgui
-        let sem_count: u32 = image_count * 2;  // 2 семафора на картнику 
-        let fence_count: u32 = image_count * 2; 
+        // cmd buf и image_available живут по кадру в полете, render_finished - по картинке
+        let frames = app.frames_in_flight;
+        let cmd_count_primary: u32 = frames;
+        let cmd_count_secondary: u32 = frames; // основной рендер + imgui
+        let cmd_count_secondary_imgui: u32 = frames; // основной рендер + imgui
 
-        let mut vec_sem = vec![];
-        for _ in 0..sem_count {
-            vec_sem.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
+        let mut image_available = vec![];
+        for _ in 0..frames {
+            image_available.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
         }
-
-        let mut vec_sem_shadow = vec![];
+        let mut render_finished = vec![];
         for _ in 0..image_count {
-            vec_sem_shadow.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
+            render_finished.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
         }
 
 
-        let mut vec_fence = vec![];
-        for _ in 0..fence_count {
-            vec_fence.push(VulkanFence::try_new(&app.core._logical_device, vk::FenceCreateFlags::SIGNALED)?);
-        }
-
         let mut vec_cmd_primary = vec![];
         if cmd_count_primary != 0 {
             vec_cmd_primary = app.command_pool.allocate_command_buffers(cmd_count_primary, vk::CommandBufferLevel::PRIMARY)?;
@@ -144,37 +171,84 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
             vec_cmd_secondary_imgui.append(&mut tmp);
         }
         Ok(FrameResources {
-            vec_sem: vec_sem,
+            image_available: image_available,
+            render_finished: render_finished,
             vec_cmd_primary: vec_cmd_primary,
-            vec_fence: vec_fence,
+            recorder: ParallelRecorder::try_new(app, None)?,
+            frames: FrameScheduler::try_new(&app.core._logical_device, frames)?,
+            async_compute: None,
+            compute_wait: None,
+            compute_owned: None,
             image_view: vec![],
             depth_image_views: vec![],
             depth_images: vec![],
+            hdr_images: vec![],
+            hdr_image_views: vec![],
+            bloom: None,
+            render_settings: RenderSettings {
+                msaa: MsaaSettings { supported: app.core.supported_msaa_samples, ..Default::default() },
+                shading: ShadingSettings { supported: true, ..Default::default() },
+                histogram: HistogramSettings { supported: true, ..Default::default() },
+                ..Default::default()
+            },
+            msaa_samples: vk::SampleCountFlags::TYPE_1,
+            transient_pools: (0..frames).map(|_| TransientPool::new(&app.core._logical_device)).collect(),
+            antialias: None,
+            post_from_aa: false,
+            view_proj: Matrix::identity(),
+            ssao: None,
+            proj: Matrix::identity(),
+            gbuffer: None,
+            histogram: None,
             
             start_time: std::time::Instant::now(),
             vec_objects: vec![],
             camera: Camera {..Default::default()},
 
-            color_attachment_format: app.swapchain.color_format,
+            color_attachment_format: HDR_COLOR_FORMAT,
             depth_attachment_format: app.swapchain.depth_format,
-            shadow_finished_sem: vec_sem_shadow,
         })
     }
 
+    fn on_swapchain_recreated(&mut self, app: &VulkanApp, old_swapchain: VulkanSwapchain) -> Result<(), &'static str> {
+        // пост проходы пересоздают свои цели сразу, поэтому ждем только отправленные кадры,
+        // обычно это один последний кадр, а не весь device
+        self.frames.wait_idle()?;
+        self.init_framebuffer(app)?;
+        drop(old_swapchain);
+        Ok(())
+    }
+
     fn init_framebuffer(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
         // self.framebuffers = vec![];
-        self.depth_image_views = vec![];
-        self.depth_images = vec![];
-        self.image_view = vec![];
-        self.color_attachment_format = app.swapchain.color_format;
+        // старые цели еще может читать кадр в полете, дропнем когда GPU его пройдет
+        // в кортеже поля дропаются по порядку: сначала views, потом картинки
+        let old_targets = (
+            std::mem::take(&mut self.depth_image_views),
+            std::mem::take(&mut self.hdr_image_views),
+            std::mem::take(&mut self.image_view),
+            std::mem::take(&mut self.depth_images),
+            std::mem::take(&mut self.hdr_images),
+        );
+        self.frames.defer_drop(old_targets);
+        // старые картинки забирать назад уже не нужно, ждать compute все равно надо
+        self.compute_owned = None;
+        self.resize_render_finished(app)?;
+        self.color_attachment_format = HDR_COLOR_FORMAT;
         self.depth_attachment_format =  app.swapchain.depth_format;
 
         for (i, image) in app.swapchain.images.iter().enumerate() {
-            // Создание depth изображения
+            // Создание depth изображения, без MSAA его еще читает TAA
+            let depth_usage = if self.msaa_samples == vk::SampleCountFlags::TYPE_1 {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+            } else {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            };
             let depth_image = VulkanImageBuilder::new(&app.core)
             .format(app.swapchain.depth_format)
             .extent(app.swapchain.extent.width, app.swapchain.extent.height, 1)
-            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .usage(depth_usage)
+            .samples(self.msaa_samples)
             .build()?;
 
             let depth_view = VulkanImageViewBuilder::new(&app.core._logical_device, depth_image.image)
@@ -190,10 +264,160 @@ impl<R: ImguiResources + Default> SceneResources for FrameResources<R> {
                 .format(app.swapchain.color_format)
                 .build()?
             );
+
+            let hdr_image = VulkanImageBuilder::new(&app.core)
+            .format(HDR_COLOR_FORMAT)
+            .extent(app.swapchain.extent.width, app.swapchain.extent.height, 1)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .build()?;
+            self.hdr_image_views.push(
+                VulkanImageViewBuilder::new(&app.core._logical_device, hdr_image.image)
+                .format(HDR_COLOR_FORMAT)
+                .build()?
+            );
+            self.hdr_images.push(hdr_image);
+        }
+
+        let hdr_views: Vec<vk::ImageView> = self.hdr_image_views.iter().map(|v| v.view).collect();
+        if let Some(aa) = self.antialias.as_mut() {
+            let depth_views: Vec<vk::ImageView> = self.depth_image_views.iter().map(|v| v.view).collect();
+            let sampled_depth = self.msaa_samples == vk::SampleCountFlags::TYPE_1;
+            aa.resize(app, &hdr_views, if sampled_depth { Some(&depth_views) } else { None })?;
+        }
+        if let Some(ssao) = self.ssao.as_mut() {
+            ssao.resize(app)?;
+        }
+        if let Some(gbuffer) = self.gbuffer.as_mut() {
+            let depth_views: Vec<vk::ImageView> = self.depth_image_views.iter().map(|v| v.view).collect();
+            let sampled_depth = self.msaa_samples == vk::SampleCountFlags::TYPE_1;
+            gbuffer.resize(app, if sampled_depth { Some(&depth_views) } else { None })?;
+        }
+        self.bind_post_source(app)
+    }
+
+}
+
+impl<R: ImguiResources + Default> FrameResources<R> {
+    /// render_finished по одному на картинку свапчейна, после пересоздания картинок может стать больше или меньше.
+    /// Лишние могут еще ждать present, поэтому уходят в отложенное удаление
+    fn resize_render_finished(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        let image_count = app.swapchain.images.len();
+        if self.render_finished.len() > image_count {
+            let extra = self.render_finished.split_off(image_count);
+            self.frames.defer_drop(extra);
+        }
+        while self.render_finished.len() < image_count {
+            self.render_finished.push(VulkanSemaphore::try_new(&app.core._logical_device)?);
+        }
+        Ok(())
+    }
+
+    /// Применяет то, что накрутили в ImGui и что требует пересоздания ресурсов. Вызывается до записи кадра
+    pub fn apply_render_settings(&mut self, app: &mut VulkanApp) -> Result<(), &'static str> {
+        // VSync меняется пересозданием свапчейна, app сделает его на ближайшем acquire
+        app.set_present_mode(self.render_settings.present.present_mode());
+        app.frame_limiter.set_target_fps(self.render_settings.present.fps_limit);
+        let samples = self.render_settings.msaa.sample_count();
+        if samples != self.msaa_samples {
+            self.set_msaa_samples(app, samples)?;
+        }
+        let from_aa = self.render_settings.aa_mode() != AaMode::None;
+        if from_aa != self.post_from_aa {
+            // сеты bloom могут быть еще в полете
+            app.device_wait_idle()?;
+            self.bind_post_source(app)?;
+        }
+        self.apply_shader_reload(app)?;
+        Ok(())
+    }
+
+    /// Пересоздает пайплайны, чьи шейдеры пересобрал hot reload. Не собрался новый -
+    /// остается старый, ошибка уходит в UI
+    fn apply_shader_reload(&mut self, app: &mut VulkanApp) -> Result<(), &'static str> {
+        let changed = app.shader_reload.take_changed();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        // старые пайплайны могут быть в кадрах в полете
+        app.device_wait_idle()?;
+        for obj in self.vec_objects.iter_mut() {
+            let result = match obj {
+                #[cfg(feature = "scene3")]
+                RenderObjectEnum::Shadows(o) => o.reload_shaders(app, &changed),
+                RenderObjectEnum::ImGui(_) => Ok(()),
+            };
+            if let Err(err) = result {
+                app.shader_reload.report(&changed, err);
+            }
+        }
+        if let Some(bloom) = self.bloom.as_mut() {
+            if let Err(err) = bloom.reload_shaders(app, &changed) {
+                app.shader_reload.report(&changed, err);
+            }
+        }
+        if let Some(histogram) = self.histogram.as_mut() {
+            if let Err(err) = histogram.reload_shaders(app, &changed) {
+                app.shader_reload.report(&changed, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Jitter проекции для этого кадра, [0, 0] если TAA выключен
+    pub fn taa_jitter(&self) -> [f32; 2] {
+        match (self.render_settings.aa_mode(), self.antialias.as_ref()) {
+            (AaMode::Taa, Some(aa)) => aa.jitter(),
+            _ => [0.0, 0.0],
+        }
+    }
+
+    /// Deferred в этом кадре: включен в UI и G-buffer под картинку есть
+    pub fn deferred_active(&self, image_index: usize) -> bool {
+        self.render_settings.deferred_active() && self.gbuffer.as_ref().and_then(|g| g.set(image_index)).is_some()
+    }
+
+    /// Камера для прохода света: позиция восстанавливается той же матрицей, что рисовала геометрию
+    pub fn deferred_light_push(&self) -> Result<DeferredLightPush, &'static str> {
+        let inv_view_proj = (self.proj * self.camera.view_matrix).inverse()?;
+        let pos = self.camera.pos.data;
+        Ok(DeferredLightPush {
+            inv_view_proj: inv_view_proj.transpose().data,
+            cam_pos: [pos[0], pos[1], pos[2], 1.0],
+        })
+    }
+
+    /// Переключает вход bloom (и гистограммы) между HDR и выходами AA
+    fn bind_post_source(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        let aa_views = match self.antialias.as_ref() {
+            Some(aa) if self.render_settings.aa_mode() != AaMode::None => Some(aa.output_views()),
+            _ => None,
+        };
+        self.post_from_aa = aa_views.is_some();
+        let views = aa_views.unwrap_or_else(|| self.hdr_image_views.iter().map(|v| v.view).collect());
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.resize(app, &views)?;
+        }
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.resize(app, &views)?;
         }
         Ok(())
     }
 
+    /// Меняет MSAA на лету: таргеты и пайплайны объектов пересоздаются
+    pub fn set_msaa_samples(&mut self, app: &VulkanApp, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        app.device_wait_idle()?;
+        self.msaa_samples = samples;
+        self.init_framebuffer(app)?;
+
+        for obj in self.vec_objects.iter_mut() {
+            match obj {
+                #[cfg(feature = "scene3")]
+                RenderObjectEnum::Shadows(o) => o.rebuild_pipeline(app, samples)?,
+                RenderObjectEnum::ImGui(_) => {},  // свой UI проход, MSAA не касается
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<R: ImguiResources + Default> GetFrameObj<R> for FrameResources<R> {
@@ -7,18 +7,25 @@ use super::{
 use super::super::super::vulkan_wr::{
     ImGui_wr::{VulkanImgui, ImguiResources},
     app::VulkanApp,
+    command_pb::async_compute::AsyncCompute,
     renderable_traits::InitObject,
     renderable_traits::InitFrameResources,
+    post::{bloom::BloomPass, antialias::AntiAliasPass, ssao::SsaoPass, gbuffer::GBuffer, histogram::HistogramPass},
 };
 
 
 pub fn init_app<R: ImguiResources + Default>(app: &mut VulkanApp, resources: &mut FrameResources<R>) -> Result<(), &'static str> {    
 
+    resources.msaa_samples = resources.render_settings.msaa.sample_count();
+    // layout AO и G-buffer нужны объекту еще при создании пайплайнов
+    resources.ssao = Some(SsaoPass::try_new(app)?);
+    resources.gbuffer = Some(GBuffer::try_new(app)?);
     resources.vec_objects.push(RenderObjectEnum::Shadows(ShadowsObject::init(
             app,
             &mut InitFrameResources {
-                upload_cmd: Some(&resources.vec_cmd_primary[0]),
-                fence: Some(&resources.vec_fence[0]),
+                samples: resources.msaa_samples,
+                ao_layout: resources.ssao.as_ref().map(|s| s.ao_set_layout()),
+                gbuffer_layout: resources.gbuffer.as_ref().map(|g| g.set_layout()),
                 ..Default::default()
             }
         )?)
@@ -28,15 +35,16 @@ pub fn init_app<R: ImguiResources + Default>(app: &mut VulkanApp, resources: &mu
     resources.vec_objects.push(RenderObjectEnum::ImGui(VulkanImgui::<R>::init(
             app,
             &mut InitFrameResources {
-                upload_cmd: Some(&resources.vec_cmd_primary[0]),
-                fence: Some(&resources.vec_fence[0]),
                 ..Default::default()
             }
         )?)
     );
 
 
-    
+    resources.bloom = Some(BloomPass::try_new(app, app.swapchain.color_format)?);
+    resources.antialias = Some(AntiAliasPass::try_new(app)?);
+    resources.histogram = Some(HistogramPass::try_new(app)?);
+    resources.async_compute = Some(AsyncCompute::try_new(app)?);
     resources.init_framebuffer(app)?;
 
     resources.start_time = std::time::Instant::now();
@@ -4,267 +4,483 @@ use crate::vulkan_wr::app::SceneResources;
 use crate::vulkan_wr::renderable_traits::RenderFrameResources;
 
 use super::frame_resources::FrameResources;
+use super::super::shadows::objects::{ShadowsObject, ShadowJob};
 use super::super::super::vulkan_wr::{
     app::VulkanApp,
+    command_pb::parallel::RecordContext,
     renderable_traits::{RenderObject},
     ImGui_wr::ImguiResources,
+    post::{fullscreen::HDR_COLOR_FORMAT, antialias::AaMode},
+    render_graph::{graph::{RenderGraph, PassDesc}, resource::{ImageUsage, ResourceState}, transient::TransientImageDesc},
+    sync::barrier::SyncScope,
 };
 
+/// Где графика трогает вход гистограммы (HDR или выход AA): там ждет compute и забирает картинку назад
+const POST_SOURCE_STAGES: vk::PipelineStageFlags2 = vk::PipelineStageFlags2::from_raw(
+    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT.as_raw()
+    | vk::PipelineStageFlags2::FRAGMENT_SHADER.as_raw()
+    | vk::PipelineStageFlags2::COMPUTE_SHADER.as_raw()
+);
+
+
+/// Кусок записи для рабочего потока, каждый в свой secondary буфер
+enum RecordJob<'o> {
+    Main(&'o ShadowsObject),
+    ShadowLight(&'o ShadowsObject, ShadowJob),
+}
 
 pub fn render_frame_app<R: ImguiResources + Default>(app: & mut VulkanApp, resources: &mut FrameResources<R>) -> Result<(), &'static str> {
-    // fecne связывает CPU GPU, индексируется фреймом, image_available принадлежит также фрейму.
-    // Тк когда queue_submit закончит работу, этот фрейм снова освободится через fence
-    // cmd buf и render_finished индексируются картинкой, тк тесно с ней связаны.
+    // CPU и GPU связывает timeline семафор планировщика, image_available принадлежит слоту кадра.
+    // Кадр сигналит свое значение на timeline, слот освобождается когда GPU до него дошел
+    // cmd buf тоже per-frame, а render_finished индексируется картинкой, его ждет present этой картинки.
     if (0, 0) == app.window.get_width_height() {
-        app.frame_index = (app.frame_index + 1) % app.image_count;
-        return Ok(());
-    }
-    let swap_extent = app.swapchain.extent;
-    if swap_extent.height <= 1 || swap_extent.width <= 1 {
-        app.frame_index = (app.frame_index + 1) % app.image_count;
         return Ok(());
     }
+    // MSAA/AA поменяли в UI - пересобираем все что от них зависит
+    resources.apply_render_settings(app)?;
+
+    // ждем пока GPU отпустит слот кадра, слот и есть frame_index для всех per-frame ресурсов
+    // пропущенные кадры слот не тратят, поэтому индекс берем у планировщика
+    app.frame_index = resources.frames.begin_frame()? as u32;
     let current_frame: usize = app.frame_index as usize;
-    
-    let frame_sync = resources.vec_fence[current_frame].fence;
-    unsafe {
-        app.core._logical_device.wait_for_fences(&[frame_sync], true, u64::MAX).unwrap();
-        app.core._logical_device.reset_fences(&[frame_sync]).unwrap();
+    // GPU слот прошел, гистограмму, которую он считал в прошлый раз, уже можно читать.
+    // Считает ее compute очередь, ее слот ждем отдельно
+    if let Some(compute) = resources.async_compute.as_ref() {
+        compute.wait_frame(current_frame)?;
+    }
+    if let Some(histogram) = resources.histogram.as_mut() {
+        histogram.collect(current_frame)?;
     }
     
-    let sem_offset = (current_frame * 2) as usize;
-    let image_available = resources.vec_sem[sem_offset].semaphore.clone();
-    let shadow_finished = &resources.shadow_finished_sem[current_frame].semaphore;
+    let image_available = resources.image_available[current_frame].semaphore;
 
-    let (image_index, suboptimal) = app.swapchain.acquire_next_image(Some(image_available), None)?;
-    if suboptimal {
-            // Swapchain полностью устарел, нужно пересоздать
-            app.device_wait_idle()?;
-            app.recreate_swapchain()?;
-            resources.init_framebuffer(&app)?;
-            app.frame_index = (app.frame_index + 1) % app.image_count;
-            app.device_wait_idle()?;
-            return Ok(());
-    }
+    // OutOfDate и ресайз разбирает app, тут только "есть картинка или нет"
+    let Some(image_index) = app.acquire_image(resources, image_available)? else {
+        return Ok(());
+    };
+    // размер берем после acquire, свапчейн мог только что пересоздаться
+    let swap_extent = app.swapchain.extent;
 
-    // Рендерим shadow pass перед основным рендерингом
-    for obj in &mut resources.vec_objects {
-        if let RenderObjectEnum::Shadows(shadow_obj) = obj {
-            shadow_obj.render_shadow_pass(app)?;
-        }
-    }
-    // Получаем командные буферы для теней
-    let mut shadow_command_buffers = Vec::new();
-    for obj in &resources.vec_objects {
-        if let RenderObjectEnum::Shadows(shadow_obj) = obj {
-            shadow_command_buffers.push(shadow_obj.shadow_cmd_vec[current_frame as usize]._buffer);
-        }
-    }
-    
+    // deferred: геометрия в G-buffer, свет отдельным fullscreen проходом в HDR
+    let deferred = resources.deferred_active(image_index as usize);
     let resss = RenderFrameResources{
             // render_pass: Some(resources.render_pass.as_ref().unwrap()),
             // framebuffer: Some(&resources.framebuffers[image_index as usize]),
+            ao_set: resources.ssao.as_ref().and_then(|s| s.ao_set(image_index as usize)),
+            deferred: deferred,
+            features: resources.render_settings.features(),
             ..Default::default()
     };
-    for obj in &mut resources.vec_objects {
-        obj.render(app, &resss)?;
+    // ImGui трогает окно и свой контекст, пишется на главном потоке
+    let mut ui_buff_vec: Vec<vk::CommandBuffer> = vec![];
+    for obj in resources.vec_objects.iter_mut() {
+        if let RenderObjectEnum::ImGui(objj) = obj {
+            objj.render(app, &resss)?;
+            ui_buff_vec.push(objj.cmd_vec[current_frame]._buffer);
+        }
     }
-    let cmd_primary = &resources.vec_cmd_primary[current_frame as usize];
-    
-    // Основной буфер команд, который включает в себя secondary
-    {
-        cmd_primary.reset(None)?;
-        cmd_primary.begin(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE, None)?;
 
-        let color_attachment = vk::RenderingAttachmentInfo {
-            image_view: resources.image_view[image_index as usize].view,
-            image_layout: vk::ImageLayout::ATTACHMENT_OPTIMAL,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            clear_value: vk::ClearValue { 
-                color: vk::ClearColorValue { float32: [10.0/255.0, 10.0/255.0, 50.0/255.0, 1.0] } 
+    // Остальные объекты независимы. UBO теней пишем тут (map_memory из потоков нельзя),
+    // а запись раздаем рабочим потокам, каждый со своим пулом
+    let record_ctx = RecordContext::from_app(app);
+    let mut jobs: Vec<RecordJob> = vec![];
+    for obj in resources.vec_objects.iter() {
+        match obj {
+            #[cfg(feature = "scene3")]
+            RenderObjectEnum::Shadows(objj) => {
+                jobs.push(RecordJob::Main(objj));
+                for light in objj.prepare_shadow_pass(&record_ctx)? {
+                    jobs.push(RecordJob::ShadowLight(objj, light));
+                }
             },
-            ..Default::default()
-        };
-        
-        let depth_attachment = vk::RenderingAttachmentInfo {
-            image_view: resources.depth_image_views[image_index as usize].view,
-            image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::DONT_CARE,
-            clear_value: vk::ClearValue { 
-                depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } 
+            _ => {},
+        }
+    }
+    resources.recorder.begin_frame(current_frame)?;
+    let recorded = resources.recorder.record(current_frame, &jobs, |job, cmd| match job {
+        RecordJob::Main(objj) => objj.record_main(&record_ctx, &resss, cmd),
+        RecordJob::ShadowLight(objj, light) => objj.record_shadow_light(&record_ctx, light, cmd),
+    })?;
+
+    // буферы пришли в порядке jobs: основной проход объекта, за ним его источники света
+    let mut buff_vec: Vec<vk::CommandBuffer> = vec![];
+    let mut shadow_work: Vec<(&ShadowsObject, Vec<ShadowJob>, Vec<vk::CommandBuffer>)> = vec![];
+    for (job, buffer) in jobs.iter().zip(recorded) {
+        match job {
+            RecordJob::Main(objj) => {
+                buff_vec.push(buffer);
+                shadow_work.push((*objj, vec![], vec![]));
             },
-            ..Default::default()
-        };
-        
-        let rendering_info = vk::RenderingInfo {
-            render_area: vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: swap_extent,
+            RecordJob::ShadowLight(_, light) => {
+                let (_, lights, buffers) = shadow_work.last_mut().ok_or("Shadow job without its object")?;
+                lights.push(*light);
+                buffers.push(buffer);
             },
-            layer_count: 1,
-            color_attachment_count: 1,
-            p_color_attachments: &color_attachment,
-            p_depth_attachment: &depth_attachment,
-            flags: vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS,
-            ..Default::default()
+        }
+    }
+    let light_push = if deferred { Some(resources.deferred_light_push()?) } else { None };
+
+    // вход гистограммы прошлого кадра: compute его отдал, забираем до первой записи
+    let compute_wait = resources.compute_wait.take();
+    let compute_acquire = match (resources.compute_owned.take(), resources.async_compute.as_ref()) {
+        (Some(image), Some(compute)) => compute.ownership_barrier(
+            image, color_subresource_range(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, false,
+            SyncScope::new(POST_SOURCE_STAGES, vk::AccessFlags2::NONE),
+            SyncScope::new(POST_SOURCE_STAGES, vk::AccessFlags2::SHADER_SAMPLED_READ),
+        ),
+        _ => None,
+    };
+    // что после сабмита графики уйдет на compute: картинка-источник гистограммы
+    let mut histogram_source = None;
+
+    // Основной буфер команд, который включает в себя secondary
+    let cmd_primary = &resources.vec_cmd_primary[current_frame as usize];
+    {
+        let app: &VulkanApp = app;
+        let idx = image_index as usize;
+        let msaa = resources.msaa_samples != vk::SampleCountFlags::TYPE_1;
+        let aa_mode = resources.render_settings.aa_mode();
+        let taa_depth = aa_mode == AaMode::Taa && !msaa;
+        let objects = &resources.vec_objects;
+        let settings = &resources.render_settings;
+        let swapchain_view = resources.image_view[idx].view;
+
+        // Кадр собирается графом: пассы говорят что читают и пишут, порядок и барьеры на нем
+        let mut graph = RenderGraph::new();
+        let color_range = color_subresource_range();
+        // ждем стадию семафора image_available, прошлый кадр с этими картинками мог их еще читать
+        let swapchain = graph.import_image(
+            app.swapchain.images[idx], swapchain_view, color_range,
+            ResourceState::undefined_after(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT),
+        );
+        // HDR и depth сами помнят, кто их трогал в прошлый раз
+        let hdr = graph.import_tracked(&resources.hdr_images[idx], resources.hdr_image_views[idx].view);
+        let depth = graph.import_tracked(&resources.depth_images[idx], resources.depth_image_views[idx].view);
+        let msaa_color = if msaa {
+            Some(graph.create_image(TransientImageDesc {
+                format: HDR_COLOR_FORMAT,
+                extent: swap_extent,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                samples: resources.msaa_samples,
+            }))
+        } else {
+            None
         };
+        let gbuffer_targets: Vec<_> = match resources.gbuffer.as_ref() {
+            Some(gbuffer) if deferred => gbuffer.images(idx).into_iter().map(|(image, view)| graph.import_image(
+                image, view, color_range,
+                ResourceState::undefined_after(vk::PipelineStageFlags2::FRAGMENT_SHADER),
+            )).collect(),
+            _ => vec![],
+        };
+        graph.export_image(swapchain, ImageUsage::Present);
 
-        unsafe {
+        // ---- тени: одна карта на все кадры, layout с прошлого кадра она помнит сама
+        let mut shadow_map = None;
+        for (objj, lights, buffers) in shadow_work.iter() {
+            let objj: &ShadowsObject = objj;
+            let handle = graph.import_tracked(&objj.shadow_map, objj.shadow_map_view_vec[objj.shadow_map_view_vec.len() - 1].view);
+            graph.add_pass(PassDesc::new("shadow").image(handle, ImageUsage::DepthAttachment), move |cmd, _| {
+                objj.execute_shadow_pass(cmd, lights, buffers)
+            });
+            shadow_map = Some(handle);
+        }
 
-            let color_image_barrier = vk::ImageMemoryBarrier {
-                src_access_mask: vk::AccessFlags::empty(), // нет предыдущих операций
-                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                old_layout: vk::ImageLayout::UNDEFINED,
-                new_layout: vk::ImageLayout::ATTACHMENT_OPTIMAL,
-                image: app.swapchain.images[image_index as usize],
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
+        // ---- SSAO: глубина префассом, потом AO + блюр. Свои таргеты барьерит сам
+        let mut ao = None;
+        if let Some((ssao, (ao_image, ao_view))) = resources.ssao.as_ref().and_then(|s| s.ao_image(idx).map(|t| (s, t))) {
+            let handle = graph.import_image(
+                ao_image, ao_view, color_range,
+                ResourceState::undefined_after(vk::PipelineStageFlags2::FRAGMENT_SHADER),
+            );
+            let proj = &resources.proj;
+            graph.add_pass(PassDesc::new("ssao").managed(handle, ImageUsage::SampledFragment), move |cmd, _| {
+                if settings.ssao.enabled {
+                    ssao.begin_prepass(cmd, idx)?;
+                    for obj in objects.iter() {
+                        match obj {
+                            #[cfg(feature = "scene3")]
+                            RenderObjectEnum::Shadows(objj) => objj.record_depth_prepass(app, cmd)?,
+                            _ => {},
+                        }
+                    }
+                    cmd.end_dynamic_rendering()?;
+                }
+                ssao.record(cmd, idx, current_frame, &settings.ssao, proj)
+            });
+            ao = Some(handle);
+        }
+
+        // ---- основной проход: сцена в HDR (с MSAA через резолв) или в G-buffer
+        let mut main_pass = PassDesc::new(if deferred { "gbuffer" } else { "main" })
+            .image(depth, ImageUsage::DepthAttachment);
+        if deferred {
+            for &target in gbuffer_targets.iter() {
+                main_pass = main_pass.image(target, ImageUsage::ColorAttachment);
+            }
+        } else {
+            main_pass = main_pass.image(hdr, ImageUsage::ColorAttachment);
+            if let Some(msaa_color) = msaa_color {
+                main_pass = main_pass.image(msaa_color, ImageUsage::ColorAttachment);
+            }
+            for &read in shadow_map.iter().chain(ao.iter()) {
+                main_pass = main_pass.image(read, ImageUsage::SampledFragment);
+            }
+        }
+        let gbuffer_attachments = match resources.gbuffer.as_ref() {
+            Some(gbuffer) if deferred => gbuffer.attachments(idx),
+            _ => vec![],
+        };
+        let clear_color = vk::ClearValue {
+            color: vk::ClearColorValue { float32: [10.0/255.0, 10.0/255.0, 50.0/255.0, 1.0] }
+        };
+        graph.add_pass(main_pass, move |cmd, ctx| {
+            let mut color_attachment = vk::RenderingAttachmentInfo {
+                image_view: ctx.view(hdr),
+                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                clear_value: clear_color,
+                ..Default::default()
+            };
+            if let Some(msaa_color) = msaa_color {
+                color_attachment.image_view = ctx.view(msaa_color);
+                color_attachment.store_op = vk::AttachmentStoreOp::DONT_CARE;
+                color_attachment.resolve_mode = vk::ResolveModeFlags::AVERAGE;
+                color_attachment.resolve_image_view = ctx.view(hdr);
+                color_attachment.resolve_image_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+            }
+            // без MSAA глубину после прохода читают TAA и свет deferred
+            let depth_attachment = vk::RenderingAttachmentInfo {
+                image_view: ctx.view(depth),
+                image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: if msaa { vk::AttachmentStoreOp::DONT_CARE } else { vk::AttachmentStoreOp::STORE },
+                clear_value: vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 }
                 },
                 ..Default::default()
             };
-            let depth_image_barrier = vk::ImageMemoryBarrier {
-                src_access_mask: vk::AccessFlags::empty(),
-                dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                old_layout: vk::ImageLayout::UNDEFINED,
-                new_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-                image: resources.depth_images[image_index as usize].image,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::DEPTH,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
+            let rendering_info = vk::RenderingInfo {
+                render_area: vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: swap_extent,
                 },
+                layer_count: 1,
+                color_attachment_count: if deferred { gbuffer_attachments.len() as u32 } else { 1 },
+                p_color_attachments: if deferred { gbuffer_attachments.as_ptr() } else { &color_attachment },
+                p_depth_attachment: &depth_attachment,
+                flags: vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS,
                 ..Default::default()
             };
+            cmd.begin_dynamic_rendering(&rendering_info)?;
+            unsafe { cmd.execute_commands(buff_vec.as_slice()); }
+            cmd.end_dynamic_rendering()
+        });
 
-            cmd_primary.pipeline_barrier(
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,  // dst_stage_mask
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &[color_image_barrier, depth_image_barrier],
-            );
-
-
-            cmd_primary.begin_dynamic_rendering(&rendering_info)?;
-
-
-            let buff_vec: Vec<vk::CommandBuffer> = resources.vec_objects.iter().map(|obj|
-                match obj {
-                    RenderObjectEnum::ImGui(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
-                    #[cfg(feature = "scene1")]
-                    RenderObjectEnum::Sphere(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
-                    #[cfg(feature = "scene2")]
-                    RenderObjectEnum::Light(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
-                    #[cfg(feature = "scene3")]
-                    RenderObjectEnum::Shadows(objj) => {objj.cmd_vec[current_frame as usize]._buffer}
+        // ---- deferred свет: G-buffer + depth -> HDR, фон остается цветом очистки
+        if let (true, Some(gbuffer), Some(light_push)) = (deferred, resources.gbuffer.as_ref(), light_push) {
+            let mut light_pass = PassDesc::new("deferred_light")
+                .image(hdr, ImageUsage::ColorAttachment)
+                .image(depth, ImageUsage::DepthSampledFragment);
+            for &read in gbuffer_targets.iter() {
+                light_pass = light_pass.image(read, ImageUsage::SampledFragment);
+            }
+            for &read in shadow_map.iter().chain(ao.iter()) {
+                light_pass = light_pass.image(read, ImageUsage::SampledFragment);
+            }
+            let gbuffer_set = gbuffer.set(idx).ok_or("G-buffer is not initialized")?;
+            let ao_set = resss.ao_set.ok_or("SSAO is not initialized")?;
+            let features = resss.features;
+            graph.add_pass(light_pass, move |cmd, ctx| {
+                let color_attachment = vk::RenderingAttachmentInfo {
+                    image_view: ctx.view(hdr),
+                    image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    load_op: vk::AttachmentLoadOp::CLEAR,
+                    store_op: vk::AttachmentStoreOp::STORE,
+                    clear_value: clear_color,
+                    ..Default::default()
+                };
+                let light_rendering_info = vk::RenderingInfo {
+                    render_area: vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent: swap_extent },
+                    layer_count: 1,
+                    color_attachment_count: 1,
+                    p_color_attachments: &color_attachment,
+                    ..Default::default()
+                };
+                cmd.begin_dynamic_rendering(&light_rendering_info)?;
+                for obj in objects.iter() {
+                    match obj {
+                        #[cfg(feature = "scene3")]
+                        RenderObjectEnum::Shadows(objj) => objj.record_deferred_lighting(app, cmd, gbuffer_set, ao_set, &light_push, features)?,
+                        _ => {},
+                    }
                 }
-            ).collect();
+                cmd.end_dynamic_rendering()
+            });
+        }
 
-            // основной цикл
-            cmd_primary.execute_commands(
-                buff_vec.as_slice()
+        // ---- AA читает HDR (TAA еще и depth) и пишет свой таргет, его дальше берет bloom
+        let mut post_source = hdr;
+        let mut post_image = resources.hdr_images[idx].image;
+        if aa_mode != AaMode::None {
+            let antialias = resources.antialias.as_mut().ok_or("AA is not initialized")?;
+            let (aa_image, aa_view) = antialias.output(idx).ok_or("AA is not initialized")?;
+            let aa_output = graph.import_image(
+                aa_image, aa_view, color_range,
+                ResourceState::undefined_after(vk::PipelineStageFlags2::FRAGMENT_SHADER),
             );
-            cmd_primary.end_dynamic_rendering()?;
+            let mut aa_pass = PassDesc::new("aa")
+                .image(hdr, ImageUsage::SampledFragment)
+                .managed(aa_output, ImageUsage::SampledFragment);
+            if taa_depth {
+                aa_pass = aa_pass.image(depth, ImageUsage::DepthSampledFragment);
+            }
+            let view_proj = &resources.view_proj;
+            graph.add_pass(aa_pass, move |cmd, _| {
+                antialias.record(cmd, idx, &settings.aa, aa_mode, view_proj)
+            });
+            post_source = aa_output;
+            post_image = aa_image;
+        }
 
-            let image_memory_barrier = vk::ImageMemoryBarrier {
-                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                dst_access_mask: vk::AccessFlags::MEMORY_READ,
-                old_layout: vk::ImageLayout::ATTACHMENT_OPTIMAL,
-                new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-                image: app.swapchain.images[image_index as usize],
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                },
+        // ---- bloom + тонмап в свапчейн, потом ImGui поверх
+        let bloom = resources.bloom.as_ref().ok_or("Bloom is not initialized")?;
+        graph.add_pass(
+            PassDesc::new("bloom")
+                .image(post_source, ImageUsage::SampledFragment)
+                .image(swapchain, ImageUsage::ColorAttachment),
+            move |cmd, ctx| bloom.record(cmd, idx, ctx.view(swapchain), swap_extent, &settings.bloom),
+        );
+        graph.add_pass(PassDesc::new("ui").image(swapchain, ImageUsage::ColorAttachment), move |cmd, ctx| {
+            // ImGui поверх тонмапнутой картинки, без depth
+            let ui_color_attachment = vk::RenderingAttachmentInfo {
+                image_view: ctx.view(swapchain),
+                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                store_op: vk::AttachmentStoreOp::STORE,
                 ..Default::default()
             };
-            cmd_primary.pipeline_barrier(
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &[image_memory_barrier],
-            );
+            let ui_rendering_info = vk::RenderingInfo {
+                render_area: vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent: swap_extent },
+                layer_count: 1,
+                color_attachment_count: 1,
+                p_color_attachments: &ui_color_attachment,
+                flags: vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS,
+                ..Default::default()
+            };
+            cmd.begin_dynamic_rendering(&ui_rendering_info)?;
+            unsafe { cmd.execute_commands(ui_buff_vec.as_slice()); }
+            cmd.end_dynamic_rendering()
+        });
 
+        // ---- гистограмма яркости того, что ушло в bloom, считается на compute очереди после сабмита.
+        // Свое семейство - картинку надо отдать: release после bloom (при равенстве граф держит порядок объявления)
+        if settings.histogram.enabled {
+            if let Some(compute) = resources.async_compute.as_ref() {
+                let release = compute.ownership_barrier(
+                    post_image, color_range, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, true,
+                    SyncScope::new(vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::NONE),
+                    SyncScope::NONE,
+                );
+                if let Some(release) = release {
+                    graph.add_pass(
+                        PassDesc::new("histogram_release").image(post_source, ImageUsage::SampledFragment).side_effect(),
+                        move |cmd, _| {
+                            unsafe { cmd.image_barriers2(&[release]); }
+                            Ok(())
+                        },
+                    );
+                }
+                histogram_source = Some(post_image);
+            }
+        }
 
+        cmd_primary.reset(None)?;
+        cmd_primary.begin(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE, None)?;
+        if let Some(acquire) = compute_acquire {
+            unsafe { cmd_primary.image_barriers2(&[acquire]); }
         }
+        graph.execute(&app.core, cmd_primary, &mut resources.transient_pools[current_frame])?;
         cmd_primary.end()?;
     }
 
-    let render_finished = &(&resources.vec_sem[(image_index * 2 + 1) as usize]).semaphore;
+    let render_finished = resources.render_finished[image_index as usize].semaphore;
 
     // Submit
-    let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-
-
-    let shadow_submit = vk::SubmitInfo {
-        wait_semaphore_count: 1,
-        p_wait_semaphores: &image_available,
-        p_wait_dst_stage_mask: &vk::PipelineStageFlags::TOP_OF_PIPE,
-        command_buffer_count: shadow_command_buffers.len() as u32,
-        p_command_buffers: shadow_command_buffers.as_ptr(),
-        signal_semaphore_count: 1,
-        p_signal_semaphores: shadow_finished,
-        ..Default::default()
-    };
-
+    let mut wait_infos = vec![
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(image_available)
+            .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT),
+    ];
+    // прошлый compute еще может читать картинку, которую этот кадр перепишет
+    if let (Some(value), Some(compute)) = (compute_wait, resources.async_compute.as_ref()) {
+        wait_infos.push(compute.wait_info(value, POST_SOURCE_STAGES));
+    }
+    // render_finished для present, timeline для CPU и отложенного удаления
+    let signal_infos = [
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(render_finished)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS),
+        resources.frames.signal_info(),
+    ];
+    let cmd_infos = [
+        vk::CommandBufferSubmitInfo::default().command_buffer(cmd_primary._buffer),
+    ];
+    let submit_info = vk::SubmitInfo2::default()
+        .wait_semaphore_infos(&wait_infos)
+        .command_buffer_infos(&cmd_infos)
+        .signal_semaphore_infos(&signal_infos);
 
-    let submit_info = vk::SubmitInfo {
-        wait_semaphore_count: 1,
-        p_wait_semaphores: shadow_finished,
-        p_wait_dst_stage_mask: wait_stages.as_ptr(),
-        command_buffer_count: 1,
-        p_command_buffers: &cmd_primary._buffer,
-        signal_semaphore_count: 1,
-        p_signal_semaphores: render_finished,
-        ..Default::default()
-    };
+    app.core.queue_submit2(&[submit_info], vk::Fence::null())?;
+    let graphics_value = resources.frames.frame_value();
+    resources.frames.end_frame();
 
-    app.core.queue_submit(&[shadow_submit, submit_info], frame_sync)?;
+    // compute ждет этот кадр графики на timeline, bin'ы слота потом читает CPU
+    if let (Some(image), Some(compute), Some(histogram)) = (histogram_source, resources.async_compute.as_mut(), resources.histogram.as_mut()) {
+        let idx = image_index as usize;
+        let settings = resources.render_settings.histogram;
+        let range = color_subresource_range();
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        let compute_stage = vk::PipelineStageFlags2::COMPUTE_SHADER;
+        let acquire = compute.ownership_barrier(
+            image, range, layout, true,
+            SyncScope::new(compute_stage, vk::AccessFlags2::NONE),
+            SyncScope::new(compute_stage, vk::AccessFlags2::SHADER_SAMPLED_READ),
+        );
+        let release = compute.ownership_barrier(
+            image, range, layout, false,
+            SyncScope::new(compute_stage, vk::AccessFlags2::NONE),
+            SyncScope::NONE,
+        );
+        let waits = [resources.frames.timeline.submit_info(graphics_value, compute_stage)];
+        let value = compute.submit(&app.core, current_frame, &waits, |cmd| {
+            if let Some(acquire) = acquire {
+                unsafe { cmd.image_barriers2(&[acquire]); }
+            }
+            histogram.record(cmd, idx, current_frame, swap_extent, &settings)?;
+            if let Some(release) = release {
+                unsafe { cmd.image_barriers2(&[release]); }
+            }
+            Ok(())
+        })?;
+        resources.compute_wait = Some(value);
+        resources.compute_owned = release.map(|_| image);
+    }
 
-    // Present
-    let present_info = vk::PresentInfoKHR {
-        wait_semaphore_count: 1,
-        p_wait_semaphores: render_finished,
-        swapchain_count: 1,
-        p_swapchains: &app.swapchain.swapchain,
-        p_image_indices: &image_index,
-        ..Default::default()
-    };
-
-    // app.swapchain.queue_present(app.core._graphics_queue, &present_info)?;
-    match app.swapchain.queue_present(app.core._graphics_queue, &present_info) {
-        Ok(true) => {
-            // Все в порядке
-        },
-        Ok(false) => {
-            // Swapchain полностью устарел, нужно пересоздать
-            app.device_wait_idle()?;
-            app.recreate_swapchain()?;
-            resources.init_framebuffer(&app)?;
-            app.frame_index = (app.frame_index + 1) % app.image_count;
-            app.device_wait_idle()?;
-        },
-        Err(e) => {
-            return Err("Failed to present swapchain image");
-        }
-    };
+    // Present, пересоздание свапчейна если надо - там же
+    app.present_image(resources, image_index, render_finished)?;
 
     Ok(())
 
 }
+
+fn color_subresource_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
@@ -3,6 +3,9 @@ use std::f32::consts::PI;
 
 use super::super::super::vulkan_wr::{
     ImGui_wr::{ImguiResources},
+    post::settings::RenderSettings,
+    hot_reload::{ShaderError, render_errors},
+    assets::AssetProgress,
 };
 
 pub struct ImguiFrameResourcesLight {
@@ -18,6 +21,9 @@ pub struct ImguiFrameResourcesLight {
     pub radius_spotlight: f32,
     pub outer_cut_off: f32,
     pub inner_cut_off: f32,
+    pub render_settings: RenderSettings,
+    pub shader_errors: Vec<ShaderError>,  // hot reload, пишет update_imgui
+    pub loading: AssetProgress,  // фоновая загрузка, пишет update_imgui
 }
 
 impl ImguiResources for ImguiFrameResourcesLight {
@@ -39,7 +45,11 @@ impl ImguiResources for ImguiFrameResourcesLight {
             ui.separator();
             ui.text("Info:");
             ui.text(format!("Time: {:.2}", (self.prev_time - self.start_time).as_secs_f32()));
+
+            self.loading.render_ui(ui);
+            self.render_settings.render_ui(ui);
         });
+        render_errors(ui, &self.shader_errors);
 
     }
 }
@@ -59,6 +69,9 @@ impl Default for ImguiFrameResourcesLight {
             outer_cut_off: 20.0,
             coefficient_quadratic: 0.2,
             inner_cut_off: 20.0,
+            render_settings: RenderSettings::default(),
+            shader_errors: vec![],
+            loading: AssetProgress::default(),
         }
     }
 }
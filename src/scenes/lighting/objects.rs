@@ -1,18 +1,22 @@
 
-use crate::{scenes::lighting::uniform::LightsSSBO, vulkan_wr::types::{figures::{make_cube, make_plane, make_stub_rgba}, matrix::Matrix, model::{MaterialUBO, Mesh, MeshGPU, Model, SubMesh, Transform, TransformUBO}}};
+use crate::{scenes::lighting::uniform::{LightsSSBO, MAX_LIGHTS_IN_CAT}, vulkan_wr::types::{figures::{make_cube, make_plane, make_stub_rgba}, matrix::Matrix, model::{DrawPush, Mesh, MeshGPU, Model, SubMesh, Transform}}};
 
 use super::super::super::vulkan_wr::{
     app::VulkanApp,
-    descriptor::descriptor_set_layout::VulkanDescriptorSetLayout,
-    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline::VulkanPipelineBuilder},
-    shader::VulkanShader,
+    descriptor::{descriptor_set_layout::VulkanDescriptorSetLayout, bindless::{BindlessMaterial, BindlessTable, BINDLESS_MATERIALS_BINDING}},
+    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline_desc::{PipelineDesc, PipelineTarget}, specialization::SpecConstants},
+    shader::{VulkanShader, shader_path},
+    reflect::{ReflectedLayoutBuilder, ReflectedLayouts},
     buffer::buffer::VulkanBuffer,
-    types::{vertex::VulkanVertex, vector::VulkanVector},
+    types::vector::VulkanVector,
     pipeline::pipeline::VulkanPipeline,
     descriptor::descriptor_set::VulkanDescriptorSet,
     renderable_traits::{InitObject, RenderObject, UpdateObject, UpdateObjectResources,
         ShutdownObject, ShutdownObjectResources, InitFrameResources, RenderFrameResources},
-    texture::{TextureGPU}
+    texture::{TextureGPU},
+    assets::{StreamedModel, asset_dir},
+    hot_reload::touches,
+    post::{ssao::build_depth_prepass_pipeline, oit::OIT_FORMATS, fullscreen::as_bytes},
 };
 use std::{f32::consts::PI, mem::size_of};
 use ash::vk;
@@ -23,7 +27,7 @@ use std::collections::HashMap;
 use std::path::Path;
 
 use super::uniform::{Uniforms};
-use std::path::PathBuf;
+use std::ops::Range;
 
 
 pub struct Positions {
@@ -36,85 +40,86 @@ impl Default for Positions {
     }
 }
 
+/// DrawPush: model в вершинном, material_index и oit во фрагментном
+const DRAW_PUSH_STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::from_raw(
+    vk::ShaderStageFlags::VERTEX.as_raw() | vk::ShaderStageFlags::FRAGMENT.as_raw()
+);
+
+/// Варианты основного пайплайна, шейдеры одни и те же
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LightPipelineKind {
+    Opaque,
+    Transparent,  // alpha blend без записи глубины, внутри render pass
+    Oit,  // accum/revealage, dynamic rendering после render pass
+}
+
 pub struct LightObject {
     pub meshes: Vec<MeshGPU>,
     pub cmd_vec: Vec<VulkanCommandBuffer>,
     pub pipeline: VulkanPipeline,
+    pub transparent_pipeline: VulkanPipeline,  // сортированная прозрачная очередь
+    pub oit_pipeline: VulkanPipeline,  // прозрачная очередь в OitPass, всегда 1x
+    pub depth_pipeline: VulkanPipeline,  // depth префасс для SSAO, layout тот же
     pub pipeline_layout: VulkanPipelineLayout,
-    pub descriptor_set_layout: Vec<VulkanDescriptorSetLayout>,
+    pub _descriptor_set_layout: Vec<VulkanDescriptorSetLayout>,  // держит layout'ы живыми. По номеру сета, на месте 1 и 3 пустые: bindless и AO не наши, 2 не занят
 
-    pub sampler_set_layout: Vec<VulkanDescriptorSetLayout>,
-    pub material_set_layout: Vec<VulkanDescriptorSetLayout>,
-    pub model_set_layout: Vec<VulkanDescriptorSetLayout>,
 
     pub uniform_buffers: Vec<VulkanBuffer>,
     pub ssbo_light_buffer: Vec<VulkanBuffer>,
     pub descriptor_sets: Vec<VulkanDescriptorSet>,
-    pub material_sets: Vec<VulkanDescriptorSet>,
-    pub model_sets: Vec<VulkanDescriptorSet>,
+    pub bindless: BindlessTable,  // все текстуры и материалы, set 1
+    pub material_bases: Vec<u32>,  // id первого материала меша в bindless, дальше по сабмешам
+    pub model_stream: StreamedModel,  // машина, пока грузится - заглушка в stream_range
+    pub stream_range: Range<usize>,
 
     pub pos: Positions,
+    pub view: Matrix<4, 4>,  // камера этого кадра, по ней сортируются прозрачные
 }
 
 impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
     type OutObject = LightObject;
     fn init(app: & mut VulkanApp, resources: &mut InitFrameResources) -> Result<Self::OutObject, &'static str> {
 
-    let exe_path = std::env::current_exe()
-        .expect("Failed to get current executable path");
-    let exe_dir = exe_path
-        .parent()
-        .expect("Executable is in the root directory?")
-        .to_path_buf();
-
-    let obj_dir = exe_dir.join("obj_3d");
+    // модели и текстуры ищутся в рантайме, см. asset_dir
+    let obj_dir = asset_dir("obj_3d")?;
 
     let path_to_obj = obj_dir.join("car").join("Car.obj");
     let path_to_obj_str = path_to_obj.to_str().unwrap();
-    // загружаем модель через tobj
-    let mut model = Model::try_new(path_to_obj_str)?;
-    model.transform.rotation = VulkanVector::new([0.0 * PI, 0.0 * PI, 1.0 * PI]);
-    model.transform.position = VulkanVector::new([0.0, -1.0, 0.0]);
+    // машина грузится в фоне, пока на ее месте куб
+    let car_transform = Transform {
+        rotation: VulkanVector::new([0.0 * PI, 0.0 * PI, 1.0 * PI]),
+        position: VulkanVector::new([0.0, -1.0, 0.0]),
+        ..Default::default()
+    };
+    let model_stream = StreamedModel::request(app, path_to_obj_str, car_transform)?;
+    let mut model = Model {
+        meshes: vec![make_cube(None)],
+        transform: car_transform,
+    };
 
     let mut gpu_meshes = Vec::new();
     let mut material_map: HashMap<String, String> = HashMap::new();
 
-    let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-    let mat_size = std::mem::size_of::<MaterialUBO>() as u64;
-    let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-
-    
-    let sampler_layout = VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let sampler_set_layout = vec![sampler_layout];
-    
-    let material_layout = VulkanDescriptorSetLayout::try_new(
+    // 4. Layout'ы по шейдерам: set 0 - UBO + LightsSSBO, 1 - bindless, 3 - AO от SsaoPass.
+    // model и id материала на draw в DrawPush. Сами блоки против Rust сверяет create_pipeline
+    let mut bindless = BindlessTable::try_new(app, vk::ShaderStageFlags::FRAGMENT)?;
+    let mut light_shaders = PipelineDesc::load_shaders_of(&app.core._logical_device, &["light_opaque.toml", "light_transparent.toml", "light_oit.toml"])?;
+    light_shaders.push(VulkanShader::try_new(&app.core._logical_device, &shader_path("vert_depth_prepass_push.spv")?)?);
+    let ReflectedLayouts { pipeline_layout, set_layouts: descriptor_set_layout, .. } = ReflectedLayoutBuilder::new(
         &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let material_set_layout = vec![material_layout];
-
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+        &light_shaders.iter().collect::<Vec<_>>(),
+    )?
+        .external(1, bindless.layout())
+        .external(3, resources.ao_layout.ok_or("Light: AO layout is missing")?)
+        // model на draw, а прозрачный фрагмент по oit пишет обычный цвет или в OIT таргеты
+        .push_constant_size(size_of::<DrawPush>() as u32)
+        .push_constant_stages(DRAW_PUSH_STAGES)  // префасс читает только model, push один на все
+        .build()?;
+    drop(light_shaders);
+
+    // текстуры без своих сетов, все уходят в bindless таблицу
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
+    let stream_range = 0..gpu_meshes.len();
     let mut model = Model {
         meshes: vec![make_plane([1.0,0.0,0.0])],
         transform: Transform{
@@ -124,7 +129,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
     };
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.specular = Some([1.0, 1.0, 1.0]);
 
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
     let mut model = Model {
         meshes: vec![make_cube(None)],
         transform: Transform{
@@ -134,11 +139,10 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
             ..Default::default()
         },
     };
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
-    // gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
-    let texture1 = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("texture");
+    // gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
+    let texture1 = asset_dir("texture")?;
     let path_to_txt = texture1.join("1.png");
     let path_to_txt_str = path_to_txt.to_str().unwrap();  // TODO:
     let mut model = Model {
@@ -151,7 +155,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
         },
     };
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.diffuse_texture  = Some(path_to_txt_str.to_string());
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
     
     let mut model = Model {
@@ -166,158 +170,18 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
     let path_to_txt = texture1.join("2.png");
     let path_to_txt_str = path_to_txt.to_str().unwrap();  // TODO:
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.diffuse_texture  = Some(path_to_txt_str.to_string());
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
-    //
-    let model_layout= VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            // set 4 binding 0
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let model_set_layout = vec![model_layout];
-
-
-    // 4. Descriptor set layout - определяет структуру наборов дескрипторов
-    let desc_vec = vec![
-        // биндинг для юниформа location(set=0, binding=0)
-        vk::DescriptorSetLayoutBinding {
-            binding: 0,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1, //количество буферов
-            stage_flags: vk::ShaderStageFlags::VERTEX, // где доступен
-            ..Default::default()
-        }
-        ,
-        vk::DescriptorSetLayoutBinding {
-            binding: 1,
-            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-            descriptor_count: 1, //количество буферов
-            stage_flags: vk::ShaderStageFlags::FRAGMENT, // где доступен
-            ..Default::default()
-        }
-    ];
-
-    let descriptor_set_layout = vec![VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &desc_vec,
-        None
-    )?];
-
-
-    let layoyt_vec = vec![descriptor_set_layout[0].layout, sampler_set_layout[0].layout,
-        material_set_layout[0].layout, model_set_layout[0].layout];
-
-    // 5. Pipeline layout - интерфейс пайплайна к ресурсам
-    let pipeline_layout = VulkanPipelineLayout::try_new(
-        &app.core._logical_device,
-        layoyt_vec.as_slice(),
-        &[],
-    )?;
-
-    // 6. Shader stages. Стоит это все внутрь шейдера засунуть.
-
-    let exe_path = std::env::current_exe()
-        .expect("Failed to get current executable path");
-    let exe_dir = exe_path
-        .parent()
-        .expect("Executable is in the root directory?")
-        .to_path_buf();
-    let vert_path = exe_dir.join("shaders").join("vert_light.spv");
-    let frag_path = exe_dir.join("shaders").join("frag_light.spv");
-    let vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path.to_str().ok_or("Failed found shaders")?)?;
-    let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path.to_str().ok_or("Failed found shaders")?)?;
-
-
-    // let shader_dir = std::env::var("SHADER_PATH").unwrap();
-    // let vert_path = format!("{}/vert_light.spv", shader_dir);
-    // let frag_path = format!("{}/frag_light.spv", shader_dir);
-
-    // let vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path)?;
-    // let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path)?;
-
-    // entry_point для шейдера
-    let entry_point = std::ffi::CString::new("main").unwrap();
-
-    let shader_stages = vec![
-        vk::PipelineShaderStageCreateInfo {
-            module: vert_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::VERTEX,
-            ..Default::default()
-        },
-        vk::PipelineShaderStageCreateInfo {
-            module: frag_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::FRAGMENT,
-            ..Default::default()
-        }
-    ];
+    // 6-7. Shader stages + Pipeline
+    let render_pass = resources.render_pass.as_ref().ok_or("Render: Obj is not initialized")?.render_pass;
+    let pipeline = Self::create_pipeline(app, render_pass, &pipeline_layout, resources.samples, LightPipelineKind::Opaque)?;
+    let transparent_pipeline = Self::create_pipeline(app, render_pass, &pipeline_layout, resources.samples, LightPipelineKind::Transparent)?;
+    let oit_pipeline = Self::create_pipeline(app, vk::RenderPass::null(), &pipeline_layout, vk::SampleCountFlags::TYPE_1, LightPipelineKind::Oit)?;
+    let depth_pipeline = build_depth_prepass_pipeline(&app.core, pipeline_layout.layout)?;
 
-    // 7. Pipeline
-    // --- vertex input (binding + атрибуты)
-    // Описание формата вершин (binding)
-    let binding_description = VulkanVertex::get_binding_description(None);
-
-    // Описание атрибутов вершин (позиция и цвет, нормали текстурные коорд и тп)
-    let attribute_descriptions = VulkanVertex::get_attribute_descriptions();
-
-    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-        vertex_binding_description_count: 1,  // Одно описание binding
-        p_vertex_binding_descriptions: &binding_description,
-        vertex_attribute_description_count: attribute_descriptions.len() as u32,  // количнство отрибутов внутри одного экземпляра вершины
-        p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
-        ..Default::default()
-    };
-    // !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
-    // чтобы задавать viewport и scissor динамически — нужно добавить dynamic state.
-
-    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-        dynamic_state_count: dynamic_states.len() as u32,
-        p_dynamic_states: dynamic_states.as_ptr(),
-        ..Default::default()
-    };
-
-    // --- построение (create_graphics_pipelines)
-    let pipeline = VulkanPipelineBuilder::new(
-        &app.core._logical_device,
-        resources.render_pass.as_ref().ok_or("Render: Obj is not initialized")?.render_pass,
-        pipeline_layout.layout
-    )
-    .with_shader_stages(shader_stages)
-    .with_vertex_input(vertex_input_info)
-    // .with_viewport_state(viewport_state)
-    .with_dynamic_states(dynamic_state_info)
-    .with_input_assembly(
-        vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            primitive_restart_enable: vk::FALSE,
-            ..Default::default()
-        }
-    )
-    .with_subpass(0) // Используем первый (и единственный) субпасс
-    .with_depth_stencil(
-        vk::PipelineDepthStencilStateCreateInfo { // нужно для 3д фигур, иначе последние примитивы отрисуются поверх первых
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,  //запись в буфер глубины
-            depth_compare_op: vk::CompareOp::LESS,
-            ..Default::default()
-        }
-    )
-    .build()?;
-
-    // 8. Uniform buffers per swapchain image
+    // 8. Uniform buffers per frame in flight
     let mut uniform_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             size_of::<Uniforms>() as vk::DeviceSize,
@@ -330,7 +194,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
 
     // shader storage buffer object (SSBO) for lights
     let mut ssbo_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             size_of::<LightsSSBO>() as vk::DeviceSize,
@@ -344,15 +208,15 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
 
     // 9. Descriptor sets
     let mut descriptor_sets = vec![];
-    for _ in 0..app.image_count {  // сеты под юниформы
+    for _ in 0..app.frames_in_flight {  // сеты под юниформы
         descriptor_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
-            descriptor_set_layout.as_ref() // тут он один
+            &descriptor_set_layout[0..1]
         )?);
     }
 
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         // Создание записи для обновления uniform буфера в дескрипторном наборе,
         // то откуда читать в шейдер по этому дискриптору
         let (write, buf_info) = descriptor_sets[i].write_buffer(
@@ -375,7 +239,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
     // SSBO
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         let (write, buf_info) = descriptor_sets[i].write_buffer(
             1,  // binding = 1
             ssbo_buffers[i].buffer,  // дескриптор буфера
@@ -394,66 +258,178 @@ impl<'a> InitObject<InitFrameResources<'a>> for LightObject {
     );
 
 
-    let mut material_sets = vec![];
-    let mut model_sets = vec![];
-    for _ in 0..gpu_meshes.len() { 
-        material_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
-            material_set_layout.as_ref() // тут он один
-        )?);
-        model_sets.append(& mut app.descriptor_pool.allocate_descriptor_sets(
-            model_set_layout.as_ref() // тут он один
-        )?);
-    }
-
-
-    for (mi, ms) in gpu_meshes.iter().enumerate() {
-        let (mut write, info) = material_sets[mi].write_buffer(
-            0,
-            ms.material_ubo.buffer,
-            0, //ms as u64 * aligned_size,
-            aligned_size, //aligned_size, // TODO это неправильно для Dynamic
-            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-        );
-        write.p_buffer_info = &info;
-        app.descriptor_pool.update_descriptor_sets(&[write], &[]);
-    }
-
-    for ms in 0..gpu_meshes.len() as usize {
-        let (mut write, info) = model_sets[ms].write_buffer(
-            0,
-            gpu_meshes[ms].transform_ubo.buffer,
-            0,
-            aligned_size,
-            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-        );
-        write.p_buffer_info = &info;
-        app.descriptor_pool.update_descriptor_sets(&[write], &[]);
+    let mut material_bases = vec![];
+    for mesh in gpu_meshes.iter() {
+        material_bases.push(bindless.add_mesh(mesh)?);
     }
 
     // let start_time = std::time::Instant::now();
 
-    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.image_count, vk::CommandBufferLevel::SECONDARY)?;
+    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.frames_in_flight, vk::CommandBufferLevel::SECONDARY)?;
 
     Ok(Self {
         cmd_vec: vec_cmd_secondary,
         pipeline: pipeline,
+        transparent_pipeline: transparent_pipeline,
+        oit_pipeline: oit_pipeline,
+        depth_pipeline: depth_pipeline,
         pipeline_layout: pipeline_layout,
-        descriptor_set_layout: descriptor_set_layout,
+        _descriptor_set_layout: descriptor_set_layout,
         uniform_buffers: uniform_buffers,
         descriptor_sets: descriptor_sets,
         pos: Positions::default(),
+        view: Matrix::identity(),
         meshes: gpu_meshes,
-        sampler_set_layout: sampler_set_layout,
-        material_set_layout: material_set_layout,
-        material_sets: material_sets,
-        model_set_layout: model_set_layout,
-        model_sets: model_sets,
+        bindless: bindless,
+        material_bases: material_bases,
+        model_stream: model_stream,
+        stream_range: stream_range,
         ssbo_light_buffer: ssbo_buffers,
     })
     }
 }
 
 
+impl LightObject {
+    /// Основной пайплайн. Отдельно от init, т.к. пересобирается при смене MSAA
+    /// # Args
+    /// * `render_pass` - для Oit не используется, там dynamic rendering
+    fn create_pipeline(
+        app: &VulkanApp,
+        render_pass: vk::RenderPass,
+        layout: &VulkanPipelineLayout,
+        samples: vk::SampleCountFlags,
+        kind: LightPipelineKind,
+    ) -> Result<VulkanPipeline, &'static str> {
+        // 6-7. Шейдеры и состояние в pipelines/light_*.toml, варианты отличаются смешиванием и таргетом
+        let (file, target) = match kind {
+            LightPipelineKind::Opaque => ("light_opaque.toml", PipelineTarget::render_pass(app, render_pass, samples)),
+            LightPipelineKind::Transparent => ("light_transparent.toml", PipelineTarget::render_pass(app, render_pass, samples)),
+            LightPipelineKind::Oit => ("light_oit.toml", PipelineTarget::dynamic(app, samples)),
+        };
+        let desc = PipelineDesc::load(file)?;
+        if kind == LightPipelineKind::Oit && desc.color_formats(&target) != OIT_FORMATS {
+            println!("{}: color attachments {:?}, OIT targets are {:?}", file, desc.color_formats(&target), OIT_FORMATS);
+            return Err("Pipeline description does not match render targets");
+        }
+        let shaders = desc.load_shaders(&app.core._logical_device)?;
+        // блоки шейдера против gpu_struct! на Rust стороне
+        for shader in shaders.iter() {
+            shader.reflection.check_block::<Uniforms>(0, 0)?;
+            shader.reflection.check_block::<LightsSSBO>(0, 1)?;
+            shader.reflection.check_table::<BindlessMaterial>(1, BINDLESS_MATERIALS_BINDING)?;
+            shader.reflection.check_push_constants(size_of::<DrawPush>() as u32)?;
+        }
+        // MAX_LIGHTS (constant_id 0 в lights.glsl) из Rust, чтобы длина массивов шла от одного места
+        desc.build_variant(&app.core, layout.layout, &target, &shaders, &SpecConstants::new().set(0, MAX_LIGHTS_IN_CAT as u32))
+    }
+
+    /// Пересобирает пайплайн под новый render pass / sample count, девайс должен простаивать
+    pub fn rebuild_pipeline(&mut self, app: &VulkanApp, render_pass: vk::RenderPass, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        self.pipeline = Self::create_pipeline(app, render_pass, &self.pipeline_layout, samples, LightPipelineKind::Opaque)?;
+        self.transparent_pipeline = Self::create_pipeline(app, render_pass, &self.pipeline_layout, samples, LightPipelineKind::Transparent)?;
+        Ok(())
+    }
+
+    /// Hot reload: пересоздает пайплайны, чьи .spv поменялись, девайс должен простаивать.
+    /// Если новый не собрался, остается старый
+    pub fn reload_shaders(&mut self, app: &VulkanApp, changed: &[String], render_pass: vk::RenderPass, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        if PipelineDesc::touched_by("light_opaque.toml", changed) || PipelineDesc::touched_by("light_transparent.toml", changed) {
+            self.rebuild_pipeline(app, render_pass, samples)?;
+        }
+        if PipelineDesc::touched_by("light_oit.toml", changed) {
+            self.oit_pipeline = Self::create_pipeline(app, vk::RenderPass::null(), &self.pipeline_layout, vk::SampleCountFlags::TYPE_1, LightPipelineKind::Oit)?;
+        }
+        if touches(changed, &["vert_depth_prepass_push.spv"]) {
+            self.depth_pipeline = build_depth_prepass_pipeline(&app.core, self.pipeline_layout.layout)?;
+        }
+        Ok(())
+    }
+
+    /// Пары (меш, сабмеш): непрозрачные в порядке мешей и прозрачные от дальнего к ближнему
+    fn render_queues(&self) -> (Vec<(usize, usize)>, Vec<(usize, usize)>) {
+        let mut opaque = vec![];
+        let mut transparent = vec![];
+        for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
+            for (si, sm) in gpu_mesh.submeshes.iter().enumerate() {
+                if sm.is_transparent() {
+                    // камера смотрит в -z, глубина = -z в view
+                    let c = sm.center;
+                    let v = &self.view.data;
+                    let depth = -(v[2][0] * c[0] + v[2][1] * c[1] + v[2][2] * c[2] + v[2][3]);
+                    transparent.push((depth, (mi, si)));
+                } else {
+                    opaque.push((mi, si));
+                }
+            }
+        }
+        transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        (opaque, transparent.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Рисует сабмеши по очереди. Пайплайн и сеты 0, 1 и 3 уже привязаны
+    /// # Args
+    /// * `oit` - в DrawPush: 0 - обычный цвет, 1 - OIT таргеты
+    fn draw_queue(&self, cmd: &VulkanCommandBuffer, queue: &[(usize, usize)], oit: u32) {
+        unsafe {
+            for &(mi, si) in queue {
+                let gpu_mesh = &self.meshes[mi];
+                let sm = &gpu_mesh.submeshes[si];
+                cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
+                cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
+
+                // материал и его текстуру шейдер берет из bindless по id
+                cmd.push_constants(self.pipeline_layout.layout, DRAW_PUSH_STAGES, 0, as_bytes(&DrawPush { oit, ..self.draw_push(mi, si) }));
+
+                cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
+            }
+        }
+    }
+
+    /// Пишет прозрачную очередь в OIT таргеты. Рендеринг уже открыт через OitPass::begin
+    /// # Args
+    /// * `cmd` - primary буфер кадра
+    /// * `ao_set` - тот же AO что у основного прохода
+    pub fn record_transparent_oit(&self, app: &VulkanApp, cmd: &VulkanCommandBuffer, ao_set: vk::DescriptorSet) -> Result<(), &'static str> {
+        let current_frame = app.frame_index as usize;
+        let (_, transparent) = self.render_queues();
+        if transparent.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.oit_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[self.descriptor_sets[current_frame].set], &[]);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 1, &[self.bindless.set()], &[]);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 3, &[ao_set], &[]);
+        }
+        self.draw_queue(cmd, &transparent, 1);
+        Ok(())
+    }
+
+    /// Пишет геометрию в depth префасс SSAO. Рендеринг уже открыт через SsaoPass::begin_prepass
+    /// # Args
+    /// * `cmd` - primary буфер кадра
+    pub fn record_depth_prepass(&self, app: &VulkanApp, cmd: &VulkanCommandBuffer) -> Result<(), &'static str> {
+        let current_frame = app.frame_index as usize;
+        unsafe {
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.depth_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[self.descriptor_sets[current_frame].set], &[]);
+            for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
+                cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
+                cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
+                // материал глубине не нужен, model одна на меш
+                cmd.push_constants(self.pipeline_layout.layout, DRAW_PUSH_STAGES, 0, as_bytes(&self.draw_push(mi, 0)));
+                // прозрачные не закрывают то что за ними
+                for sm in gpu_mesh.submeshes.iter().filter(|sm| !sm.is_transparent()) {
+                    cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
 impl<'a> RenderObject<RenderFrameResources<'a>> for LightObject {
     fn render(&mut self,
             app: & mut VulkanApp,
@@ -506,35 +482,20 @@ impl<'a> RenderObject<RenderFrameResources<'a>> for LightObject {
                 ]
             );
 
-            let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-            for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
-
-                cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
-                cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
-
-                let ubo_ds = &self.descriptor_sets[current_frame];
-                cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[ubo_ds.set], &[]);
-                
-                let aligned_size = ((std::mem::size_of::<TransformUBO>() as u64 + alignment - 1) / alignment) * alignment;
-                let mfr_offset = aligned_size as u32 * current_frame as u32; //si
-                cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 3, &[self.model_sets[mi].set], &[mfr_offset]);
-                
-                for (si, sm) in gpu_mesh.submeshes.iter().enumerate() {
-                    let ubo_ms = &self.material_sets[mi];
-                    // 2) bind texture set (set = 1)
-                    let tex_id = sm.texture_id;
-                    let tex_ds = &gpu_mesh.texture[tex_id].descriptor_sets[current_frame];
-                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 1, &[tex_ds.set], &[]);
-
-                    let mat_size = std::mem::size_of::<MaterialUBO>() as u64;
-                    let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-                    
-                    let sm_offset = aligned_size as u32 * si as u32; //si
-                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 2, &[ubo_ms.set], &[sm_offset]);
+            let ao_set = resources.ao_set.ok_or("Light: AO set is missing")?;
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 3, &[ao_set], &[]);
+            let ubo_ds = &self.descriptor_sets[current_frame];
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[ubo_ds.set], &[]);
+            // все текстуры и материалы, дальше на draw только push constants
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 1, &[self.bindless.set()], &[]);
+        }
 
-                    cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
-               }
-            }
+        // сначала все непрозрачные, потом прозрачные поверх них. С OIT прозрачные рисует OitPass
+        let (opaque, transparent) = self.render_queues();
+        self.draw_queue(cmd, &opaque, 0);
+        if !resources.oit && !transparent.is_empty() {
+            unsafe { cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.transparent_pipeline.pipeline); }
+            self.draw_queue(cmd, &transparent, 0);
         }
         cmd.end()?;
     Ok(()) 
@@ -552,13 +513,53 @@ impl ShutdownObject<ShutdownLightObject> for LightObject {
 
 pub trait UpdateLightObject {
     fn update_light(&mut self, obj: &mut LightObject, app: & mut VulkanApp) -> Result<(), &'static str>;
+    /// Меши, которые больше не рисуются, но кадры в полете их еще читают
+    fn retire(&mut self, meshes: Vec<MeshGPU>);
 }
 
 impl<T, Resources: UpdateObjectResources<T> + UpdateLightObject> UpdateObject<T, Resources> for LightObject {
     fn update(&mut self, app: & mut VulkanApp, resources: &mut Resources) -> Result<(), &'static str> {
+        let retired = self.stream_model(app)?;
+        if !retired.is_empty() {
+            resources.retire(retired);
+        }
         resources.update_light(self, app)?;
         Ok(())
     }
 }
 
 
+impl LightObject {
+    /// Push constants одного draw. object_id - номер меша в self.meshes
+    fn draw_push(&self, mesh_index: usize, submesh_index: usize) -> DrawPush {
+        DrawPush {
+            model: self.meshes[mesh_index].model,
+            material_index: self.material_bases[mesh_index] + submesh_index as u32,
+            object_id: mesh_index as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Докачивает машину. Когда она вся на GPU, встает на место заглушки,
+    /// заглушка возвращается, чтобы дропнуть ее после кадров в полете
+    fn stream_model(&mut self, app: &mut VulkanApp) -> Result<Vec<MeshGPU>, &'static str> {
+        if self.model_stream.is_done() {
+            return Ok(vec![]);
+        }
+        let Some(new_meshes) = self.model_stream.step(app)? else {
+            return Ok(vec![]);
+        };
+
+        // как в init, новые слоты пишутся, пока старые кадры читают таблицу
+        let mut material_bases = vec![];
+        for mesh in new_meshes.iter() {
+            material_bases.push(self.bindless.add_mesh(mesh)?);
+        }
+
+        // слоты заглушки в bindless остаются, их немного
+        let range = self.stream_range.clone();
+        self.stream_range = range.start..range.start + new_meshes.len();
+        self.material_bases.splice(range.clone(), material_bases);
+        Ok(self.meshes.splice(range, new_meshes).collect())
+    }
+}
@@ -1,56 +1,59 @@
+use crate::vulkan_wr::types::gpu_layout::gpu_struct;
 
-#[repr(C)] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct Uniforms {
-    pub view_proj: [[f32;4];4], // локальное в NDC 
-    // pub world: [[f32;4];4], // локальное в NDC
-    pub camera: [f32; 4],
-    pub time: f32,
-    pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
-}
+// источников в категории, в GLSL это MAX_LIGHTS (constant_id 0) из lights.glsl
+pub const MAX_LIGHTS_IN_CAT: usize = 5;
 
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct LightsSSBO {
-    pub light_count_directional: u32,
-    pub light_count_point: u32,
-    pub light_count_spotlight: u32,
-    pub time: f32,
-    // pub _pad: u32,
+// раскладку проверяет gpu_struct!, GLSL сторона - shaders/include/lights.glsl
 
-    pub directional_lights: [DirectionalLight; 5],
-    pub point_lights: [PointLight; 5],
-    pub spotlights: [Spotlight; 5],
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct Uniforms: Std140 {
+        pub view_proj: [[f32;4];4], // локальное в NDC
+        pub camera: [f32; 4],
+        pub time: f32,
+        pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
+    }
 }
 
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct LightsSSBO: Std430 {
+        pub light_count_directional: u32,
+        pub light_count_point: u32,
+        pub light_count_spotlight: u32,
+        pub time: f32,
 
-#[repr(C, align(8))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct DirectionalLight {
-    pub direction: [f32; 4], // .w свободен
-    pub color: [f32; 4],     // .w = intensity
+        pub directional_lights: [DirectionalLight; MAX_LIGHTS_IN_CAT],
+        pub point_lights: [PointLight; MAX_LIGHTS_IN_CAT],
+        pub spotlights: [Spotlight; MAX_LIGHTS_IN_CAT],
+    }
 }
 
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct DirectionalLight: Std430 {
+        pub direction: [f32; 4], // .w свободен
+        pub color: [f32; 4],     // .w = intensity
+    }
+}
 
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct Spotlight {
-    pub position: [f32; 4],  // .w свободен
-    pub direction: [f32; 4], // .w = cutoff angle in radians
-    pub color: [f32; 4],     // .w = intensity
-    pub cut_off: [f32; 4],
-    // pub _pad: [f32; 3],      // выравнивание до 16 байт v4 float
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct Spotlight: Std430 {
+        pub position: [f32; 4],  // .w свободен
+        pub direction: [f32; 4], // .w = cutoff angle in radians
+        pub color: [f32; 4],     // .w = intensity
+        pub cut_off: [f32; 4],
+    }
 }
 
-// ssbo требует выравние для массивов даже в std430. не чет другое
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct PointLight {
-    pub position: [f32; 4], // .w свободен
-    pub color: [f32; 4],    // .w = intensity
-    pub coefficients: [f32; 4],
-    pub _pad: [f32; 4],     // выравнивание до 16 байт v4 float
-    // pub _pad1: [f32; 4],     // выравнивание до 16 байт v4 float
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct PointLight: Std430 {
+        pub position: [f32; 4], // .w свободен
+        pub color: [f32; 4],    // .w = intensity
+        pub coefficients: [f32; 4],
+    }
 }
 
 impl Default for LightsSSBO {
@@ -60,10 +63,9 @@ impl Default for LightsSSBO {
             light_count_point: 0,
             light_count_spotlight: 0,
             time: 0.0,
-            // _pad: 0,
-            directional_lights: [DirectionalLight {..Default::default()}; 5],
-            point_lights: [PointLight {..Default::default()}; 5],
-            spotlights: [Spotlight {..Default::default()}; 5],
+            directional_lights: [DirectionalLight {..Default::default()}; MAX_LIGHTS_IN_CAT],
+            point_lights: [PointLight {..Default::default()}; MAX_LIGHTS_IN_CAT],
+            spotlights: [Spotlight {..Default::default()}; MAX_LIGHTS_IN_CAT],
         }
     }
 }
@@ -84,7 +86,6 @@ impl Default for Spotlight {
             direction: [0.0; 4],
             color: [0.0; 4],
             cut_off: [1.0; 4],
-            // _pad: [0.0; 3],
         }
     }
 }
@@ -95,8 +96,6 @@ impl Default for PointLight {
             position: [0.0; 4],
             color: [0.0; 4],
             coefficients: [1.0; 4],
-            _pad: [0.0; 4],
-            // _pad1: [0.0; 4],
         }
     }
 }
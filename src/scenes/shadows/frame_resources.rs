@@ -3,6 +3,9 @@ use std::f32::consts::PI;
 
 use super::super::super::vulkan_wr::{
     ImGui_wr::{ImguiResources},
+    post::{settings::RenderSettings, histogram::HistogramStats},
+    hot_reload::{ShaderError, render_errors},
+    assets::AssetProgress,
 };
 
 pub struct ImguiFrameResourcesShadows {
@@ -18,6 +21,10 @@ pub struct ImguiFrameResourcesShadows {
     pub radius_spotlight: f32,
     pub outer_cut_off: f32,
     pub inner_cut_off: f32,
+    pub render_settings: RenderSettings,
+    pub shader_errors: Vec<ShaderError>,  // hot reload, пишет update_imgui
+    pub loading: AssetProgress,  // фоновая загрузка, пишет update_imgui
+    pub histogram: HistogramStats,  // compute гистограмма, пишет update_imgui
 }
 
 impl ImguiResources for ImguiFrameResourcesShadows {
@@ -39,7 +46,14 @@ impl ImguiResources for ImguiFrameResourcesShadows {
             ui.separator();
             ui.text("Info:");
             ui.text(format!("Time: {:.2}", (self.prev_time - self.start_time).as_secs_f32()));
+
+            self.loading.render_ui(ui);
+            self.render_settings.render_ui(ui);
+            if self.render_settings.histogram.enabled {
+                self.histogram.render_ui(ui);
+            }
         });
+        render_errors(ui, &self.shader_errors);
 
     }
 }
@@ -59,6 +73,10 @@ impl Default for ImguiFrameResourcesShadows {
             outer_cut_off: 20.0,
             coefficient_quadratic: 0.2,
             inner_cut_off: 20.0,
+            render_settings: RenderSettings::default(),
+            shader_errors: vec![],
+            loading: AssetProgress::default(),
+            histogram: HistogramStats::default(),
         }
     }
 }
@@ -1,31 +1,44 @@
 
-use crate::{scenes::shadows::uniform::{DirectionalLight, LightsSSBO, MAX_LIGHTS_IN_CAT, PointLight, ShadowsUniform, Spotlight}, vulkan_wr::types::{figures::{make_cube, make_plane, make_stub_rgba}, matrix::Matrix, model::{MaterialUBO, Mesh, MeshGPU, Model, SubMesh, Transform, TransformUBO}}};
+use crate::{scenes::shadows::uniform::{DirectionalLight, LightsSSBO, MAX_LIGHTS_IN_CAT, PointLight, ShadowsUniform, Spotlight}, vulkan_wr::types::{figures::{make_cube, make_plane, make_stub_rgba}, matrix::Matrix, model::{DrawPush, Mesh, MeshGPU, Model, SubMesh, Transform}}};
 
 use super::super::super::vulkan_wr::{
     app::VulkanApp,
-    descriptor::descriptor_set_layout::VulkanDescriptorSetLayout,
-    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline::VulkanPipelineBuilder},
-    shader::VulkanShader,
+    descriptor::{descriptor_set_layout::VulkanDescriptorSetLayout, bindless::{BindlessMaterial, BindlessTable, BINDLESS_MATERIALS_BINDING}},
+    pipeline::{
+        pipeline_layout::VulkanPipelineLayout,
+        pipeline_desc::{PipelineDesc, PipelineTarget},
+        permutation::{FeatureFlags, PermutationCache},
+        specialization::SpecConstants,
+    },
+    shader::{VulkanShader, shader_path},
+    reflect::{ReflectedLayoutBuilder, ReflectedLayouts},
     buffer::buffer::VulkanBuffer,
-    types::{vertex::VulkanVertex, vector::VulkanVector},
+    types::vector::VulkanVector,
     pipeline::pipeline::VulkanPipeline,
     descriptor::descriptor_set::VulkanDescriptorSet,
     renderable_traits::{InitObject, RenderObject, UpdateObject, UpdateObjectResources,
         ShutdownObject, ShutdownObjectResources, InitFrameResources, RenderFrameResources},
     texture::{TextureGPU},
+    assets::{StreamedModel, asset_dir},
+    hot_reload::touches,
     image::{image::{VulkanImage, VulkanImageBuilder}, image_view::{VulkanImageView, VulkanImageViewBuilder}},
     sampler::{VulkanSampler, VulkanSamplerBuilder},
+    post::{
+        fullscreen::{HDR_COLOR_FORMAT, as_bytes},
+        ssao::build_depth_prepass_pipeline,
+        gbuffer::{GBUFFER_FORMATS, DeferredLightPush},
+    },
 };
 use std::{f32::consts::PI, mem::size_of};
 use ash::vk;
 use super::super::super::vulkan_wr::{
-    command_pb::command_buffer::VulkanCommandBuffer,
+    command_pb::{command_buffer::VulkanCommandBuffer, parallel::RecordContext},
 };
 use std::collections::HashMap;
 use std::path::Path;
 
 use super::uniform::{Uniforms};
-use std::path::PathBuf;
+use std::ops::Range;
 
 
 pub struct Positions {
@@ -42,22 +55,48 @@ impl Default for Positions {
 const MAX_LIGHTS: usize = MAX_LIGHTS_IN_CAT;
 const SHADOW_MAP_RESOLUTION: u32 = 1024;
 
+// constant_id в shaders/include/lights.glsl и shadow_config.glsl, в GLSL их больше не дублируем
+const SPEC_MAX_LIGHTS: u32 = 0;
+const SPEC_SHADOW_MAP_SIZE: u32 = 1;
+
+/// DrawPush основного layout'а: model в вершинном, material_index во фрагментном
+const DRAW_PUSH_STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::from_raw(
+    vk::ShaderStageFlags::VERTEX.as_raw() | vk::ShaderStageFlags::FRAGMENT.as_raw()
+);
+
+/// Общие для всех вариантов шейдеров сцены
+fn shader_constants() -> SpecConstants {
+    SpecConstants::new()
+        .set(SPEC_MAX_LIGHTS, MAX_LIGHTS as u32)
+        .set(SPEC_SHADOW_MAP_SIZE, SHADOW_MAP_RESOLUTION)
+}
+
+/// Один источник в проходе теней: свой слой карты и свой кусок UBO теней
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowJob {
+    pub layer_index: usize,
+    pub uniform_offset: u32,  // dynamic offset в shadow_uniform_buffers
+}
+
 pub struct ShadowsObject {
     pub meshes: Vec<MeshGPU>,
     pub cmd_vec: Vec<VulkanCommandBuffer>,
-    pub pipeline: VulkanPipeline,
+    pub pipelines: PermutationCache,  // forward, вариант по фичам кадра и материала
+    pub depth_pipeline: VulkanPipeline,  // depth префасс для SSAO, layout тот же
+    pub gbuffer_pipelines: PermutationCache,  // deferred: геометрия в G-buffer, layout тот же, только фичи материала
     pub pipeline_layout: VulkanPipelineLayout,
-    pub descriptor_set_layout: Vec<VulkanDescriptorSetLayout>,
+    pub deferred_light_pipelines: PermutationCache,  // deferred: свет fullscreen проходом, только фичи кадра
+    pub deferred_light_layout: VulkanPipelineLayout,
+    pub descriptor_set_layout: Vec<VulkanDescriptorSetLayout>,  // по номеру сета, на месте 1 и 3 пустые: bindless и AO не наши
 
-    pub sampler_set_layout: Vec<VulkanDescriptorSetLayout>,
-    pub material_set_layout: Vec<VulkanDescriptorSetLayout>,
-    pub model_set_layout: Vec<VulkanDescriptorSetLayout>,
+    pub bindless: BindlessTable,  // все текстуры и материалы, set 1
 
     pub uniform_buffers: Vec<VulkanBuffer>,
     pub ssbo_light_buffer: Vec<VulkanBuffer>,
     pub descriptor_sets: Vec<VulkanDescriptorSet>,
-    pub material_sets: Vec<VulkanDescriptorSet>,
-    pub model_sets: Vec<VulkanDescriptorSet>,
+    pub material_bases: Vec<u32>,  // id первого материала меша в bindless, дальше по сабмешам
+    pub model_stream: StreamedModel,  // машина, пока грузится - заглушка в stream_range
+    pub stream_range: Range<usize>,
 
     pub lights_data: LightsSSBO,
 
@@ -67,15 +106,15 @@ pub struct ShadowsObject {
     pub shadow_map: VulkanImage,
     pub shadow_map_view_vec: Vec<VulkanImageView>,
     pub shadow_map_sampler: VulkanSampler,
-    pub shadow_descriptor_set_layout: Vec<VulkanDescriptorSetLayout>,
     pub shadow_descriptor_sets: Vec<VulkanDescriptorSet>,
     pub shadow_pipeline: VulkanPipeline,   // Pipeline для генерации теней shadow_pipeline
     pub shadow_pipeline_layout: VulkanPipelineLayout,
-    pub shadow_cmd_vec: Vec<VulkanCommandBuffer>,
     pub shadow_uniform_buffers: Vec<VulkanBuffer>,
 
     pub shadow_desc_uniform_layout: Vec<VulkanDescriptorSetLayout>,
     pub shadow_desc_uniform: Vec<VulkanDescriptorSet>,
+
+    pub samples: vk::SampleCountFlags,  // MSAA основного прохода, нужен в inheritance
 }
 
 
@@ -85,55 +124,29 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
     fn init(app: & mut VulkanApp, resources: &mut InitFrameResources) -> Result<Self::OutObject, &'static str> {
 
 
-    let obj_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("obj_3d");
+    // модели и текстуры ищутся в рантайме, см. asset_dir
+    let obj_dir = asset_dir("obj_3d")?;
 
     let path_to_obj = obj_dir.join("car").join("Car.obj");
     let path_to_obj_str = path_to_obj.to_str().unwrap();
-    // загружаем модель через tobj
-    let mut model = Model::try_new(path_to_obj_str)?;
-    model.transform.rotation = VulkanVector::new([0.0 * PI, 0.0 * PI, 1.0 * PI]);
-    model.transform.position = VulkanVector::new([0.0, -1.0, 0.0]);
+    // машина грузится в фоне, пока на ее месте куб
+    let car_transform = Transform {
+        rotation: VulkanVector::new([0.0 * PI, 0.0 * PI, 1.0 * PI]),
+        position: VulkanVector::new([0.0, -1.0, 0.0]),
+        ..Default::default()
+    };
+    let model_stream = StreamedModel::request(app, path_to_obj_str, car_transform)?;
+    let mut model = Model {
+        meshes: vec![make_cube(None)],
+        transform: car_transform,
+    };
 
     let mut gpu_meshes = Vec::new();
     let mut material_map: HashMap<String, String> = HashMap::new();
 
-    let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-    let mat_size = std::mem::size_of::<MaterialUBO>() as u64;
-    let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-
-    
-    let sampler_layout = VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let sampler_set_layout = vec![sampler_layout];
-    
-    let material_layout = VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let material_set_layout = vec![material_layout];
-
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    // текстуры без своих сетов, все уходят в bindless таблицу
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
+    let stream_range = 0..gpu_meshes.len();
     let mut model = Model {
         meshes: vec![make_plane([1.0,0.0,0.0])],
         transform: Transform{
@@ -143,7 +156,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
     };
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.specular = Some([1.0, 1.0, 1.0]);
 
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
     let mut model = Model {
         meshes: vec![make_cube(None)],
         transform: Transform{
@@ -153,11 +166,10 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
             ..Default::default()
         },
     };
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
-    // gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
-    let texture1 = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("texture");
+    // gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
+    let texture1 = asset_dir("texture")?;
     let path_to_txt = texture1.join("1.png");
     let path_to_txt_str = path_to_txt.to_str().unwrap();  // TODO:
     let mut model = Model {
@@ -170,7 +182,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
         },
     };
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.diffuse_texture  = Some(path_to_txt_str.to_string());
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
     
     let mut model = Model {
@@ -185,51 +197,25 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
     let path_to_txt = texture1.join("2.png");
     let path_to_txt_str = path_to_txt.to_str().unwrap();  // TODO:
     model.meshes[0].submeshes[0].material.as_mut().ok_or("Material err")?.diffuse_texture  = Some(path_to_txt_str.to_string());
-    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources, sampler_set_layout.as_slice(), alignment)?);
+    gpu_meshes.append(&mut model.to_gpu_meshes(app, resources)?);
 
-    //
-    let model_layout= VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            // set 4 binding 0
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            }
-        ],
-        None
-    )?;
-    let model_set_layout = vec![model_layout];
-
-
-    // 4. Descriptor set layout - определяет структуру наборов дескрипторов
-    let desc_vec = vec![
-        // биндинг для юниформа location(set=0, binding=0)
-        vk::DescriptorSetLayoutBinding {
-            binding: 0,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1, //количество буферов
-            stage_flags: vk::ShaderStageFlags::VERTEX, // где доступен
-            ..Default::default()
-        }
-        ,
-        vk::DescriptorSetLayoutBinding {
-            binding: 1,
-            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-            descriptor_count: 1, //количество буферов
-            stage_flags: vk::ShaderStageFlags::FRAGMENT, // где доступен
-            ..Default::default()
-        }
-    ];
 
-    let descriptor_set_layout = vec![VulkanDescriptorSetLayout::try_new(
+    // 4. Layout'ы по шейдерам: set 0 - UBO + LightsSSBO, 1 - bindless, 2 - карта теней, 3 - AO от SsaoPass.
+    // Сами блоки против Rust сверяет check_scene_shaders
+    let mut bindless = BindlessTable::try_new(app, vk::ShaderStageFlags::FRAGMENT)?;
+    let ao_layout = resources.ao_layout.ok_or("Shadows: AO layout is missing")?;
+    let mut main_shaders = PipelineDesc::load_shaders_of(&app.core._logical_device, &["shadows_forward.toml", "shadows_gbuffer.toml"])?;
+    main_shaders.push(VulkanShader::try_new(&app.core._logical_device, &shader_path("vert_depth_prepass_push.spv")?)?);
+    let ReflectedLayouts { pipeline_layout, set_layouts: descriptor_set_layout, .. } = ReflectedLayoutBuilder::new(
         &app.core._logical_device,
-        &desc_vec,
-        None
-    )?];
+        &main_shaders.iter().collect::<Vec<_>>(),
+    )?
+        .external(1, bindless.layout())
+        .external(3, ao_layout)
+        .push_constant_size(size_of::<DrawPush>() as u32)
+        .push_constant_stages(DRAW_PUSH_STAGES)  // один cmd.push_constants на forward, G-buffer и префасс
+        .build()?;
+    drop(main_shaders);
 
 
 
@@ -294,44 +280,27 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
         .build()?;
     
 
-    // 4. Создание descriptor set layout для теней
-    let shadow_desc_layout = vec![VulkanDescriptorSetLayout::try_new(
+    // 4. Layout прохода теней по его шейдеру: set 0 - матрица источника, dynamic offset на источник
+    let shadow_shaders = PipelineDesc::load_shaders_of(&app.core._logical_device, &["shadow_depth.toml"])?;
+    let ReflectedLayouts { pipeline_layout: shadow_pipeline_layout, set_layouts: shadow_desc_uniform_layout, .. } = ReflectedLayoutBuilder::new(
         &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }],
-        None
-    )?];
+        &shadow_shaders.iter().collect::<Vec<_>>(),
+    )?
+        .dynamic(0, 0)
+        .push_constant_size(size_of::<DrawPush>() as u32)  // model на draw
+        .build()?;
+    drop(shadow_shaders);
 
-    let shadow_desc_uniform_layout = vec![VulkanDescriptorSetLayout::try_new(
-        &app.core._logical_device,
-        &vec![
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            }
-        ],
-        None
-    )?];
-    
     // 5. Создание descriptor sets для теней
     let mut shadow_descriptor_sets = Vec::new();
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         shadow_descriptor_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
-            shadow_desc_layout.as_slice()
+            &descriptor_set_layout[2..3]
         )?);
     }
     
     let mut shadow_desc_uniform = Vec::new();
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         shadow_desc_uniform.append(&mut app.descriptor_pool.allocate_descriptor_sets(
             shadow_desc_uniform_layout.as_slice()
         )?);
@@ -343,7 +312,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
     let sh_aligned_size = ((sh_size + alignment - 1) / alignment) * alignment;
     
     let mut shadow_uniform_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             (sh_aligned_size * (MAX_LIGHTS as u64) * 4) as vk::DeviceSize,
@@ -357,7 +326,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
     // 6. Обновление descriptor sets с shadow map
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         let image_info = vk::DescriptorImageInfo {
             sampler: shadow_sampler.sampler,
             image_view: shadow_map_view_vec[shadow_map_view_vec.len() -1].view,
@@ -381,14 +350,14 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
 
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         // Создание записи для обновления uniform буфера в дескрипторном наборе,
         // то откуда читать в шейдер по этому дискриптору
         let (write, buf_info) = shadow_desc_uniform[i].write_buffer(
             0,  // binding = 1
             shadow_uniform_buffers[i].buffer,  // дескриптор буфера
             0,  // смещение в нем
-            aligned_size,  // конец необходимой части
+            sh_aligned_size,  // конец необходимой части
             vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
         );
         buffer_infos.push(buf_info);
@@ -403,129 +372,32 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
 
     
     // 7. Создание pipeline для генерации карт теней
-    let shadow_pipeline_layout = VulkanPipelineLayout::try_new(
-        &app.core._logical_device,
-        &[shadow_desc_uniform_layout[0].layout, model_set_layout[0].layout],
-        &[],
-    )?;
-    
     let shadow_pipeline = Self::create_shadow_pipeline(app, &shadow_pipeline_layout)?;
     
-    // 8. Создание command buffers для рендеринга теней
-    let shadow_cmd_buffers = app.command_pool.allocate_command_buffers(
-        app.image_count, 
-        vk::CommandBufferLevel::PRIMARY
-    )?;
-    
-    // 9. Обновление layout для основного pipeline
-    let layoyt_vec = vec![
-        descriptor_set_layout[0].layout,
-        sampler_set_layout[0].layout,
-        material_set_layout[0].layout,
-        model_set_layout[0].layout,
-        shadow_desc_layout[0].layout
-    ];
-
-    // 5. Pipeline layout - интерфейс пайплайна к ресурсам
-    let pipeline_layout = VulkanPipelineLayout::try_new(
-        &app.core._logical_device,
-        layoyt_vec.as_slice(),
-        &[],
-    )?;
-
-    // 6. Shader stages. Стоит это все внутрь шейдера засунуть.
-
-    // let shader_dir = std::env::var("SHADER_PATH").unwrap();
-    // let vert_path = format!("{}/vert_light_shadows.spv", shader_dir);
-    // let frag_path = format!("{}/frag_light_shadows.spv", shader_dir);
-    // let vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path)?;
-    // let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path)?;
-
-    let exe_path = std::env::current_exe()
-        .expect("Failed to get current executable path");
-    let exe_dir = exe_path
-        .parent()
-        .expect("Executable is in the root directory?")
-        .to_path_buf();
-    let vert_path = exe_dir.join("shaders").join("vert_light_shadows.spv");
-    let frag_path = exe_dir.join("shaders").join("frag_light_shadows.spv");
-    let vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path.to_str().ok_or("Failed found shaders")?)?;
-    let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path.to_str().ok_or("Failed found shaders")?)?;
-
-    // entry_point для шейдера
-    let entry_point = std::ffi::CString::new("main").unwrap();
-
-    let shader_stages = vec![
-        vk::PipelineShaderStageCreateInfo {
-            module: vert_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::VERTEX,
-            ..Default::default()
-        },
-        vk::PipelineShaderStageCreateInfo {
-            module: frag_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::FRAGMENT,
-            ..Default::default()
-        }
-    ];
-
-    // 7. Pipeline
-    // --- vertex input (binding + атрибуты)
-    // Описание формата вершин (binding)
-    let binding_description = VulkanVertex::get_binding_description(None);
-
-    // Описание атрибутов вершин (позиция и цвет, нормали текстурные коорд и тп)
-    let attribute_descriptions = VulkanVertex::get_attribute_descriptions();
-
-    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-        vertex_binding_description_count: 1,  // Одно описание binding
-        p_vertex_binding_descriptions: &binding_description,
-        vertex_attribute_description_count: attribute_descriptions.len() as u32,  // количнство отрибутов внутри одного экземпляра вершины
-        p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
-        ..Default::default()
-    };
-    // !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
-    // чтобы задавать viewport и scissor динамически — нужно добавить dynamic state.
-
-    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-        dynamic_state_count: dynamic_states.len() as u32,
-        p_dynamic_states: dynamic_states.as_ptr(),
-        ..Default::default()
-    };
-
-    // --- построение (create_graphics_pipelines)
-    let pipeline = VulkanPipelineBuilder::new_dynamic(
+    // 6-7. Shader stages + Pipeline
+    let pipelines = Self::create_main_pipelines(app, &pipeline_layout, resources.samples)?;
+    let depth_pipeline = build_depth_prepass_pipeline(&app.core, pipeline_layout.layout)?;
+    let gbuffer_pipelines = Self::create_gbuffer_pipelines(app, &pipeline_layout)?;
+
+    // deferred свет: тот же LightsSSBO (set 0) и та же карта теней, что у forward, сеты все чужие
+    let light_shaders = PipelineDesc::load_shaders_of(&app.core._logical_device, &["deferred_light.toml"])?;
+    let ReflectedLayouts { pipeline_layout: deferred_light_layout, .. } = ReflectedLayoutBuilder::new(
         &app.core._logical_device,
-        pipeline_layout.layout
-    )
-    .with_color_attachment_formats(vec![app.swapchain.color_format])
-    .with_depth_attachment_format(app.swapchain.depth_format)
-    .with_shader_stages(shader_stages)
-    .with_vertex_input(vertex_input_info)
-    .with_dynamic_states(dynamic_state_info)
-    .with_input_assembly(
-        vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            primitive_restart_enable: vk::FALSE,
-            ..Default::default()
-        }
-    )
-    .with_depth_stencil(
-        vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
-            ..Default::default()
-        }
-    )
-    .build()?;
+        &light_shaders.iter().collect::<Vec<_>>(),
+    )?
+        .external(0, descriptor_set_layout[0].layout)
+        .external(1, resources.gbuffer_layout.ok_or("Shadows: G-buffer layout is missing")?)
+        .external(2, descriptor_set_layout[2].layout)
+        .external(3, ao_layout)
+        .push_constant_size(size_of::<DeferredLightPush>() as u32)
+        .build()?;
+    drop(light_shaders);
+    let deferred_light_pipelines = Self::create_deferred_light_pipelines(app, &deferred_light_layout)?;
 
-    // 8. Uniform buffers per swapchain image
+    // 8. Uniform buffers per frame in flight
 
     let mut uniform_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             size_of::<Uniforms>() as vk::DeviceSize,
@@ -538,7 +410,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
 
     // shader storage buffer object (SSBO) for lights
     let mut ssbo_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             size_of::<LightsSSBO>() as vk::DeviceSize,
@@ -552,15 +424,15 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
 
     // 9. Descriptor sets
     let mut descriptor_sets = vec![];
-    for _ in 0..app.image_count {  // сеты под юниформы
+    for _ in 0..app.frames_in_flight {  // сеты под юниформы
         descriptor_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
-            descriptor_set_layout.as_ref() // тут он один
+            &descriptor_set_layout[0..1]
         )?);
     }
 
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         // Создание записи для обновления uniform буфера в дескрипторном наборе,
         // то откуда читать в шейдер по этому дискриптору
         let (write, buf_info) = descriptor_sets[i].write_buffer(
@@ -584,7 +456,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
 
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         let (write, buf_info) = descriptor_sets[i].write_buffer(
             1,  // binding = 1
             ssbo_buffers[i].buffer,  // дескриптор буфера
@@ -602,60 +474,33 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
         &[]
     );
 
-    let mut material_sets = vec![];
-    let mut model_sets = vec![];
-    for _ in 0..gpu_meshes.len() { 
-        material_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
-            material_set_layout.as_ref() // тут он один
-        )?);
-        model_sets.append(& mut app.descriptor_pool.allocate_descriptor_sets(
-            model_set_layout.as_ref() // тут он один
-        )?);
-    }
-
-    for (mi, ms) in gpu_meshes.iter().enumerate() {
-        let (mut write, info) = material_sets[mi].write_buffer(
-            0,
-            ms.material_ubo.buffer,
-            0, //ms as u64 * aligned_size,
-            aligned_size, //aligned_size, // TODO это неправильно для Dynamic
-            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-        );
-        write.p_buffer_info = &info;
-        app.descriptor_pool.update_descriptor_sets(&[write], &[]);
-    }
-
-    for ms in 0..gpu_meshes.len() as usize {
-        let (mut write, info) = model_sets[ms].write_buffer(
-            0,
-            gpu_meshes[ms].transform_ubo.buffer,
-            0,
-            aligned_size,
-            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-        );
-        write.p_buffer_info = &info;
-        app.descriptor_pool.update_descriptor_sets(&[write], &[]);
+    let mut material_bases = vec![];
+    for mesh in gpu_meshes.iter() {
+        material_bases.push(bindless.add_mesh(mesh)?);
     }
 
     // let start_time = std::time::Instant::now();
 
-    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.image_count, vk::CommandBufferLevel::SECONDARY)?;
+    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.frames_in_flight, vk::CommandBufferLevel::SECONDARY)?;
 
 
-    Ok(Self {
+    let mut obj = Self {
         cmd_vec: vec_cmd_secondary,
-        pipeline: pipeline,
+        pipelines: pipelines,
+        depth_pipeline: depth_pipeline,
+        gbuffer_pipelines: gbuffer_pipelines,
         pipeline_layout: pipeline_layout,
+        deferred_light_pipelines: deferred_light_pipelines,
+        deferred_light_layout: deferred_light_layout,
         descriptor_set_layout: descriptor_set_layout,
         uniform_buffers: uniform_buffers,
         descriptor_sets: descriptor_sets,
         pos: Positions::default(),
         meshes: gpu_meshes,
-        sampler_set_layout: sampler_set_layout,
-        material_set_layout: material_set_layout,
-        material_sets: material_sets,
-        model_set_layout: model_set_layout,
-        model_sets: model_sets,
+        bindless: bindless,
+        material_bases: material_bases,
+        model_stream: model_stream,
+        stream_range: stream_range,
         ssbo_light_buffer: ssbo_buffers,
 
         lights_data: LightsSSBO { ..Default::default() },
@@ -663,15 +508,17 @@ impl<'a> InitObject<InitFrameResources<'a>> for ShadowsObject {
         shadow_map: shadow_map,
         shadow_map_view_vec: shadow_map_view_vec,
         shadow_map_sampler: shadow_sampler,
-        shadow_descriptor_set_layout: shadow_desc_layout,
         shadow_pipeline: shadow_pipeline,
-        shadow_cmd_vec: shadow_cmd_buffers,
         shadow_descriptor_sets: shadow_descriptor_sets,
         shadow_pipeline_layout: shadow_pipeline_layout,
         shadow_uniform_buffers: shadow_uniform_buffers,
         shadow_desc_uniform: shadow_desc_uniform,
         shadow_desc_uniform_layout: shadow_desc_uniform_layout,
-    })
+
+        samples: resources.samples,
+    };
+    obj.prepare_variants(app)?;
+    Ok(obj)
     }
 }
 
@@ -680,20 +527,26 @@ impl<'a> RenderObject<RenderFrameResources<'a>> for ShadowsObject {
     fn render(&mut self,
             app: & mut VulkanApp,
             resources: &RenderFrameResources<'a>,
-            // frame_index: u32,
-            // cmd: &VulkanCommandBuffer,
         ) -> Result<(), &'static str> {
-        // когда то оно было статичным=
-        let current_frame = app.frame_index as usize;
-        let swap_extent = app.swapchain.extent;
+        let ctx = RecordContext::from_app(app);
+        self.record_main(&ctx, resources, &self.cmd_vec[ctx.frame])
+    }
+}
 
-        let color_format = vec![app.swapchain.color_format];
+impl ShadowsObject {
+    /// Основной проход объекта в secondary cmd. Ничего не мапит, можно звать из рабочего потока
+    pub fn record_main(&self, ctx: &RecordContext, resources: &RenderFrameResources<'_>, cmd: &VulkanCommandBuffer) -> Result<(), &'static str> {
+        let current_frame = ctx.frame;
+        let swap_extent = ctx.extent;
+
+        // deferred пишет в G-buffer, forward сразу в HDR
+        let color_format = if resources.deferred { GBUFFER_FORMATS.to_vec() } else { vec![HDR_COLOR_FORMAT] };
 
         let mut inheritance_rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
             .color_attachment_formats(color_format.as_slice())
-            .depth_attachment_format(app.swapchain.depth_format)
+            .depth_attachment_format(ctx.depth_format)
             .stencil_attachment_format(vk::Format::UNDEFINED)
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(self.samples)
             .flags(vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS);
 
         let inheritance_info = vk::CommandBufferInheritanceInfo::default()
@@ -704,16 +557,10 @@ impl<'a> RenderObject<RenderFrameResources<'a>> for ShadowsObject {
 
         unsafe {
 
-            let cmd = &self.cmd_vec[current_frame];
             cmd.begin(
-                vk::CommandBufferUsageFlags::SIMULTANEOUS_USE | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
                 Some(&inheritance_info)
             )?;
-            cmd.bind_pipeline(
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.pipeline
-            );
-
             cmd.set_viewport(
                 0,
                 &[
@@ -742,46 +589,48 @@ impl<'a> RenderObject<RenderFrameResources<'a>> for ShadowsObject {
             cmd.bind_descriptor_sets(
                 vk::PipelineBindPoint::GRAPHICS,
                 self.pipeline_layout.layout,
-                4, // Новое binding для теней
+                2, // Новое binding для теней
                 &[shadow_desc_set.set],
                 &[]
             );
+            let ao_set = resources.ao_set.ok_or("Shadows: AO set is missing")?;
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 3, &[ao_set], &[]);
 
+            let ubo_ds = &self.descriptor_sets[current_frame];
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[ubo_ds.set], &[]);
+            // все текстуры и материалы, дальше на draw только push constants
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 1, &[self.bindless.set()], &[]);
+
+            // layout у всех вариантов один, сеты и push constants при смене пайплайна остаются
+            let mut bound = vk::Pipeline::null();
 
-            let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
             for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
 
                 cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
                 cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
 
-                let ubo_ds = &self.descriptor_sets[current_frame];
-                cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[ubo_ds.set], &[]);
-                
-                let aligned_size = ((std::mem::size_of::<TransformUBO>() as u64 + alignment - 1) / alignment) * alignment;
-                let mfr_offset = aligned_size as u32 * current_frame as u32; //si
-                cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 3, &[self.model_sets[mi].set], &[mfr_offset]);
-                
                 for (si, sm) in gpu_mesh.submeshes.iter().enumerate() {
-                    let ubo_ms = &self.material_sets[mi];
-                    // 2) bind texture set (set = 1)
-                    let tex_id = sm.texture_id;
-                    let tex_ds = &gpu_mesh.texture[tex_id].descriptor_sets[current_frame];
-                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 1, &[tex_ds.set], &[]);
-
-                    let mat_size = std::mem::size_of::<MaterialUBO>() as u64;
-                    let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-                    
-                    let sm_offset = aligned_size as u32 * si as u32; //si
-                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 2, &[ubo_ms.set], &[sm_offset]);
+                    let pipeline = if resources.deferred {
+                        self.gbuffer_pipelines.get(sm.features())?
+                    } else {
+                        self.pipelines.get(resources.features | sm.features())?
+                    };
+                    if pipeline != bound {
+                        cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, pipeline);
+                        bound = pipeline;
+                    }
+
+                    // материал и его текстуру шейдер берет из bindless по id
+                    let push = self.draw_push(mi, si);
+                    cmd.push_constants(self.pipeline_layout.layout, DRAW_PUSH_STAGES, 0, as_bytes(&push));
 
                     cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
                }
             }
             cmd.end()?;
         }
-    Ok(()) 
+        Ok(())
     }
-    
 }
 
 
@@ -795,10 +644,18 @@ impl ShutdownObject<ShutdownShadowsObject> for ShadowsObject {
 
 pub trait UpdateShadowsObject {
     fn update_shadows(&mut self, obj: &mut ShadowsObject, app: & mut VulkanApp) -> Result<(), &'static str>;
+    /// Меши, которые больше не рисуются, но кадры в полете их еще читают
+    fn retire(&mut self, meshes: Vec<MeshGPU>);
 }
 
 impl<T, Resources: UpdateObjectResources<T> + UpdateShadowsObject> UpdateObject<T, Resources> for ShadowsObject {
     fn update(&mut self, app: & mut VulkanApp, resources: &mut Resources) -> Result<(), &'static str> {
+        let retired = self.stream_model(app)?;
+        if !retired.is_empty() {
+            resources.retire(retired);
+            // у докачанных материалов могут быть свои фичи
+            self.prepare_variants(app)?;
+        }
         resources.update_shadows(self, app)?;
         Ok(())
     }
@@ -857,259 +714,220 @@ impl ShadowsObject {
     }
 
 
-    fn create_shadow_pipeline(app: &VulkanApp, layout: &VulkanPipelineLayout) -> Result<VulkanPipeline, &'static str> {
-        // Загрузка шейдеров для теневого прохода
-        let exe_path = std::env::current_exe()
-            .expect("Failed to get current executable path");
-        let exe_dir = exe_path
-            .parent()
-            .expect("Executable is in the root directory?")
-            .to_path_buf();
-        let shadow_vert_path = exe_dir.join("shaders").join("vert_shadows.spv");
-        // let frag_path = exe_dir.join("shaders").join("frag_shadows.spv");
-        let shadow_vert_shader = VulkanShader::try_new(&app.core._logical_device, &shadow_vert_path.to_str().ok_or("Failed found shaders")?)?;
-        // let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path.to_str().ok_or("Failed found shaders")?)?;
-
-
-        // let shader_dir = std::env::var("SHADER_PATH").unwrap();
-        // let shadow_vert_path = format!("{}/vert_shadows.spv", shader_dir);
-        // // let shadow_frag_path = format!("{}/frag_shadows.spv", shader_dir);
-
-        // let shadow_vert_shader = VulkanShader::try_new(&app.core._logical_device, &shadow_vert_path)?;
-        // let shadow_frag_shader = VulkanShader::try_new(&app.core._logical_device, &shadow_frag_path)?;
-
-        let entry_point = std::ffi::CString::new("main").unwrap();
-        let shader_stages = vec![
-            vk::PipelineShaderStageCreateInfo {
-                module: shadow_vert_shader._shader,
-                p_name: entry_point.as_ptr(),
-                stage: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            },
-            // vk::PipelineShaderStageCreateInfo {
-            //     module: shadow_frag_shader._shader,
-            //     p_name: entry_point.as_ptr(),
-            //     stage: vk::ShaderStageFlags::FRAGMENT,
-            //     ..Default::default()
-            // }
-        ];
-
-        // Vertex input
-        let binding_description = VulkanVertex::get_binding_description(None);
-        let attribute_descriptions = VulkanVertex::get_attribute_descriptions();
-
-        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-            vertex_binding_description_count: 1,
-            p_vertex_binding_descriptions: &binding_description,
-            vertex_attribute_description_count: attribute_descriptions.len() as u32,
-            p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
-            ..Default::default()
-        };
-
-        // Dynamic states
-        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-            dynamic_state_count: dynamic_states.len() as u32,
-            p_dynamic_states: dynamic_states.as_ptr(),
-            ..Default::default()
-        };
+    /// Варианты основного прохода. Отдельно от init, т.к. пересобираются при смене MSAA
+    fn create_main_pipelines(app: &VulkanApp, layout: &VulkanPipelineLayout, samples: vk::SampleCountFlags) -> Result<PermutationCache, &'static str> {
+        Self::create_permutations(app, layout, samples, "shadows_forward.toml", &[HDR_COLOR_FORMAT], check_scene_shaders)
+    }
 
-        // Input assembly
-        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            primitive_restart_enable: vk::FALSE,
-            ..Default::default()
-        };
+    /// Геометрический проход deferred: тот же вершинник, материал пишется в G-buffer. Только без MSAA
+    fn create_gbuffer_pipelines(app: &VulkanApp, layout: &VulkanPipelineLayout) -> Result<PermutationCache, &'static str> {
+        Self::create_permutations(app, layout, vk::SampleCountFlags::TYPE_1, "shadows_gbuffer.toml", &GBUFFER_FORMATS, check_scene_shaders)
+    }
 
-        // Rasterizer с depth bias для предотвращения shadow acne
-        let rasterizer = vk::PipelineRasterizationStateCreateInfo {
-            depth_clamp_enable: vk::FALSE,
-            rasterizer_discard_enable: vk::FALSE,
-            polygon_mode: vk::PolygonMode::FILL,
-            cull_mode: vk::CullModeFlags::NONE,
-            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-            depth_bias_enable: vk::TRUE,
-            depth_bias_constant_factor: 1.25,
-            depth_bias_clamp: 0.0,
-            depth_bias_slope_factor: 1.75,
-            line_width: 1.0,
-            ..Default::default()
-        };
+    /// Проход света deferred: fullscreen в HDR
+    /// Сеты: 0 - UBO + LightsSSBO, 1 - G-buffer, 2 - карта теней, 3 - AO. Push - DeferredLightPush
+    fn create_deferred_light_pipelines(app: &VulkanApp, layout: &VulkanPipelineLayout) -> Result<PermutationCache, &'static str> {
+        Self::create_permutations(app, layout, vk::SampleCountFlags::TYPE_1, "deferred_light.toml", &[HDR_COLOR_FORMAT], check_light_shaders)
+    }
 
-        // Multisampling
-        let multisampling = vk::PipelineMultisampleStateCreateInfo {
-            sample_shading_enable: vk::FALSE,
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
-            ..Default::default()
-        };
+    /// Кэш вариантов из pipelines/, сами варианты собирает prepare_variants
+    /// # Args
+    /// * `file` - описание пайплайна
+    /// * `color_formats` - во что пишет проход, описание должно с этим совпадать
+    /// * `check` - блоки шейдеров против Rust, повторяется при hot reload
+    fn create_permutations(
+        app: &VulkanApp,
+        layout: &VulkanPipelineLayout,
+        samples: vk::SampleCountFlags,
+        file: &str,
+        color_formats: &[vk::Format],
+        check: fn(&[VulkanShader]) -> Result<(), &'static str>,
+    ) -> Result<PermutationCache, &'static str> {
+        let target = PipelineTarget::dynamic(app, samples);
+        let cache = PermutationCache::new(app, file, layout.layout, target, shader_constants())?;
+        if cache.desc().color_formats(&target) != color_formats {
+            println!("{}: color attachments {:?}, the pass renders to {:?}", file, cache.desc().color_formats(&target), color_formats);
+            return Err("Pipeline description does not match render targets");
+        }
+        check(cache.shaders())?;
+        Ok(cache)
+    }
 
-        // Depth stencil
-        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
-            ..Default::default()
-        };
+    /// Собирает варианты под все материалы сцены, с тенями и без: запись кадра идет
+    /// из потоков через &self и сама ничего не собирает
+    fn prepare_variants(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        let mut materials: Vec<FeatureFlags> = vec![];
+        for sm in self.meshes.iter().flat_map(|m| m.submeshes.iter()) {
+            if !materials.contains(&sm.features()) {
+                materials.push(sm.features());
+            }
+        }
+        for frame in [FeatureFlags::empty(), FeatureFlags::SHADOWS] {
+            for &material in materials.iter() {
+                self.pipelines.ensure(&app.core, frame | material)?;
+            }
+            self.deferred_light_pipelines.ensure(&app.core, frame)?;
+        }
+        for &material in materials.iter() {
+            self.gbuffer_pipelines.ensure(&app.core, material)?;
+        }
+        Ok(())
+    }
 
-        // Создание pipeline
-        let shadow_pipeline = VulkanPipelineBuilder::new_dynamic(
-            &app.core._logical_device,
-            layout.layout
-        )
-        .with_depth_attachment_format(vk::Format::D32_SFLOAT)
-        .with_shader_stages(shader_stages)
-        .with_vertex_input(vertex_input_info)
-        .with_dynamic_states(dynamic_state_info)
-        .with_input_assembly(input_assembly)
-        .with_rasterizer(rasterizer)
-        .with_multisampling(multisampling)
-        .with_depth_stencil(depth_stencil)
-        .build()?;
+    /// Пересобирает варианты основного прохода под новый sample count, девайс должен простаивать
+    pub fn rebuild_pipeline(&mut self, app: &VulkanApp, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        self.pipelines.rebuild(&app.core, PipelineTarget::dynamic(app, samples))?;
+        self.samples = samples;
+        Ok(())
+    }
 
-        Ok(shadow_pipeline)
+    /// Hot reload: пересоздает пайплайны, чьи .spv поменялись, девайс должен простаивать.
+    /// Если новый не собрался, остается старый
+    pub fn reload_shaders(&mut self, app: &VulkanApp, changed: &[String]) -> Result<(), &'static str> {
+        if self.pipelines.touched_by(changed) {
+            self.pipelines.reload(app, check_scene_shaders)?;
+        }
+        if self.gbuffer_pipelines.touched_by(changed) {
+            self.gbuffer_pipelines.reload(app, check_scene_shaders)?;
+        }
+        if touches(changed, &["vert_depth_prepass_push.spv"]) {
+            self.depth_pipeline = build_depth_prepass_pipeline(&app.core, self.pipeline_layout.layout)?;
+        }
+        if self.deferred_light_pipelines.touched_by(changed) {
+            self.deferred_light_pipelines.reload(app, check_light_shaders)?;
+        }
+        if PipelineDesc::touched_by("shadow_depth.toml", changed) {
+            self.shadow_pipeline = Self::create_shadow_pipeline(app, &self.shadow_pipeline_layout)?;
+        }
+        Ok(())
     }
 
-    pub fn render_shadow_pass(&mut self, app: &mut VulkanApp) -> Result<(), &'static str> {
+    /// Deferred проход света, рендеринг в HDR уже открыт
+    /// # Args
+    /// * `cmd` - primary буфер кадра
+    /// * `gbuffer_set` - G-buffer + depth картинки этого кадра, уже в SHADER_READ / DEPTH_READ_ONLY
+    pub fn record_deferred_lighting(
+        &self,
+        app: &VulkanApp,
+        cmd: &VulkanCommandBuffer,
+        gbuffer_set: vk::DescriptorSet,
+        ao_set: vk::DescriptorSet,
+        push: &DeferredLightPush,
+        features: FeatureFlags,
+    ) -> Result<(), &'static str> {
         let current_frame = app.frame_index as usize;
-        let shadow_cmd = &self.shadow_cmd_vec[current_frame];
-        shadow_cmd.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, None)?;
-
+        let swap_extent = app.swapchain.extent;
         unsafe {
-            let depth_barrier = vk::ImageMemoryBarrier {
-                src_access_mask: vk::AccessFlags::empty(),
-                dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                old_layout: vk::ImageLayout::UNDEFINED,
-                new_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-                image: self.shadow_map.image,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::DEPTH,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: (MAX_LIGHTS * 3) as u32,
-                },
-                ..Default::default()
-            };
-            shadow_cmd.pipeline_barrier(
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &[depth_barrier],
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.deferred_light_pipelines.get(features)?);
+            cmd.set_viewport(0, &[vk::Viewport {
+                x: 0.0, y: 0.0,
+                width: swap_extent.width as f32, height: swap_extent.height as f32,
+                min_depth: 0.0, max_depth: 1.0,
+            }]);
+            cmd.set_scissor(0, &[vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent: swap_extent }]);
+            cmd.bind_descriptor_sets(
+                vk::PipelineBindPoint::GRAPHICS, self.deferred_light_layout.layout, 0,
+                &[
+                    self.descriptor_sets[current_frame].set,
+                    gbuffer_set,
+                    self.shadow_descriptor_sets[current_frame].set,
+                    ao_set,
+                ],
+                &[]
             );
- 
-            // Рендеринг для каждого активного источника света
-            let scene_center = VulkanVector::new([0.0, 0.0, 0.0]);
-            let scene_size = 30.0;
-
-            // Directional lights - слои 0..MAX_LIGHTS-1
-            for i in 0..self.lights_data.light_count_directional as usize {
-                let light = &self.lights_data.directional_lights[i];
-                let light_dir = VulkanVector::new(light.direction).to3v();
-                let layer_index = i; // Слой для directional light
-                // println!("Rendering shadow for light {} in layer {}", i, layer_index / MAX_LIGHTS);
-                // println!("Using image view: {:?}", self.shadow_map_view_vec[layer_index].view);
-                // println!("Active directional lights: {}", self.lights_data.light_count_directional);
-                // println!("Active spotlights: {}", self.lights_data.light_count_spotlight);
-                self.render_shadow_for_light(
-                    shadow_cmd, 
-                    app, 
-                    &light_dir, 
-                    &scene_center, 
-                    scene_size, 
-                    layer_index, 
-                    current_frame,
-                    false,
-                    None
-                )?;
+            cmd.push_constants(self.deferred_light_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(push));
+            cmd.draw(3, 1, 0, 0);
+        }
+        Ok(())
+    }
 
+    /// Пишет геометрию в depth префасс SSAO. Рендеринг уже открыт через SsaoPass::begin_prepass
+    /// # Args
+    /// * `cmd` - primary буфер кадра
+    pub fn record_depth_prepass(&self, app: &VulkanApp, cmd: &VulkanCommandBuffer) -> Result<(), &'static str> {
+        let current_frame = app.frame_index as usize;
+        unsafe {
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.depth_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[self.descriptor_sets[current_frame].set], &[]);
+            for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
+                cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
+                cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
+                // материал глубине не нужен, model одна на меш
+                cmd.push_constants(self.pipeline_layout.layout, DRAW_PUSH_STAGES, 0, as_bytes(&self.draw_push(mi, 0)));
+                for sm in gpu_mesh.submeshes.iter() {
+                    cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
+                }
             }
+        }
+        Ok(())
+    }
 
-            // // Pointlights - слои MAX_LIGHTS*1..
-            // for i in 0..self.lights_data.light_count_point as usize {
-            //     let light = &self.lights_data.point_lights[i];
-            //     let light_dir = VulkanVector::new(light.direction).to3v();
-            //     let layer_index = MAX_LIGHTS * 1 + i; // Слой для spotlight
-                
-            //     self.render_shadow_for_light(
-            //         shadow_cmd, 
-            //         app, 
-            //         &light_dir, 
-            //         &scene_center, 
-            //         scene_size, 
-            //         layer_index, 
-            //         current_frame
-            //     )?;
-            // }
-            
-            // Spotlights - слои MAX_LIGHTS*2..
-            for i in 0..self.lights_data.light_count_spotlight as usize {
-                let light = &self.lights_data.spotlights[i];
-                let light_dir = VulkanVector::new(light.direction).to3v();
-                let light_pos = VulkanVector::new(light.position).to3v();
-                let layer_index = MAX_LIGHTS * 2 + i; // Слой для spotlight
-                // println!("Rendering shadow for light {} in layer {}", i, layer_index / MAX_LIGHTS);
-                // println!("Using image view: {:?}", self.shadow_map_view_vec[layer_index].view);
-                // println!("Active directional lights: {}", self.lights_data.light_count_directional);
-                // println!("Active spotlights: {}", self.lights_data.light_count_spotlight);
-                self.render_shadow_for_light(
-                    shadow_cmd, 
-                    app, 
-                    &light_dir, 
-                    &scene_center, 
-                    scene_size, 
-                    layer_index, 
-                    current_frame,
-                    true,
-                    Some(&light_pos)
-                )?;
-            }
-            
-            // Переход shadow map в layout для чтения в шейдере
-            let read_barrier = vk::ImageMemoryBarrier {
-                src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                dst_access_mask: vk::AccessFlags::SHADER_READ,
-                old_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                image: self.shadow_map.image,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::DEPTH,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: (MAX_LIGHTS * 3) as u32,
-                },
+    /// Depth-only пайплайн карт теней, depth bias задан в описании
+    fn create_shadow_pipeline(app: &VulkanApp, layout: &VulkanPipelineLayout) -> Result<VulkanPipeline, &'static str> {
+        let desc = PipelineDesc::load("shadow_depth.toml")?;
+        let shaders = desc.load_shaders(&app.core._logical_device)?;
+        for shader in shaders.iter() {
+            shader.reflection.check_block::<ShadowsUniform>(0, 0)?;
+            shader.reflection.check_push_constants(size_of::<DrawPush>() as u32)?;
+        }
+        desc.build_with(&app.core, layout.layout, &PipelineTarget::dynamic(app, vk::SampleCountFlags::TYPE_1), &shaders)
+    }
+
+    /// Пишет матрицы всех активных источников в UBO теней и отдает по работе на источник.
+    /// Мапит память, поэтому только на главном потоке, до параллельной записи
+    pub fn prepare_shadow_pass(&self, ctx: &RecordContext) -> Result<Vec<ShadowJob>, &'static str> {
+        let scene_center = VulkanVector::new([0.0, 0.0, 0.0]);
+        let scene_size = 30.0;
+        let aligned_size = ctx.aligned_size::<ShadowsUniform>();
+
+        // (слой, направление, позиция для spotlight)
+        let mut lights = vec![];
+        // Directional lights - слои 0..MAX_LIGHTS-1
+        for i in 0..self.lights_data.light_count_directional as usize {
+            let light = &self.lights_data.directional_lights[i];
+            lights.push((i, VulkanVector::new(light.direction).to3v(), None));
+        }
+        // Pointlights - слои MAX_LIGHTS*1.., пока без теней
+        // Spotlights - слои MAX_LIGHTS*2..
+        for i in 0..self.lights_data.light_count_spotlight as usize {
+            let light = &self.lights_data.spotlights[i];
+            lights.push((MAX_LIGHTS * 2 + i, VulkanVector::new(light.direction).to3v(), Some(VulkanVector::new(light.position).to3v())));
+        }
+
+        let mut jobs = Vec::with_capacity(lights.len());
+        for (layer_index, light_dir, light_pos) in lights {
+            let light_matrix = ShadowsObject::calculate_light_space_matrix(
+                &light_dir, &scene_center, scene_size, light_pos.is_some(), light_pos.as_ref()
+            );
+            let uniform_data = ShadowsUniform {
+                light_space_matrix: light_matrix.data,
+                indx: layer_index as u32,
                 ..Default::default()
             };
-            
-            shadow_cmd.pipeline_barrier(
-                vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-                vk::DependencyFlags::BY_REGION,
-                &[],
-                &[],
-                &[read_barrier],
-            ); 
-            shadow_cmd.end()?;
+            let uniform_offset = aligned_size * layer_index as u64;
+            unsafe {
+                self.shadow_uniform_buffers[ctx.frame].mem_copy(&[uniform_data], Some(uniform_offset), None, None)?;
+            }
+            jobs.push(ShadowJob { layer_index, uniform_offset: uniform_offset as u32 });
         }
-        Ok(())
+        Ok(jobs)
     }
 
-    fn render_shadow_for_light(
-        &self,
-        cmd: &VulkanCommandBuffer,
-        app: &VulkanApp,
-        light_dir: &VulkanVector<3>,
-        scene_center: &VulkanVector<3>,
-        scene_size: f32,
-        layer_index: usize,
-        current_frame: usize,
-        is_spotlight: bool,
-        light_pos: Option<&VulkanVector<3>>,
-    ) -> Result<(), &'static str> {
-        
+    /// Содержимое прохода одного источника в secondary cmd, рендеринг начинает primary в execute_shadow_pass
+    pub fn record_shadow_light(&self, ctx: &RecordContext, job: &ShadowJob, cmd: &VulkanCommandBuffer) -> Result<(), &'static str> {
+        let current_frame = ctx.frame;
+        let mut inheritance_rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
+            .depth_attachment_format(self.shadow_map.format)
+            .stencil_attachment_format(vk::Format::UNDEFINED)
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS);
+        let inheritance_info = vk::CommandBufferInheritanceInfo::default()
+            .push_next(&mut inheritance_rendering_info);
 
+        cmd.begin(
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+            Some(&inheritance_info)
+        )?;
         unsafe {
             // Установка viewport/scissor для карт теней
             cmd.set_viewport(0, &[vk::Viewport {
@@ -1120,103 +938,122 @@ impl ShadowsObject {
                 min_depth: 0.0,
                 max_depth: 1.0,
             }]);
-            
             cmd.set_scissor(0, &[vk::Rect2D {
-                offset: vk::Offset2D { 
-                    x: 0, 
-                    y: 0,
-                },
-                extent: vk::Extent2D {
-                    width: SHADOW_MAP_RESOLUTION,
-                    height: SHADOW_MAP_RESOLUTION
-                }
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D { width: SHADOW_MAP_RESOLUTION, height: SHADOW_MAP_RESOLUTION },
             }]);
+
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.shadow_pipeline.pipeline);
+            cmd.bind_descriptor_sets(
+                vk::PipelineBindPoint::GRAPHICS,
+                self.shadow_pipeline_layout.layout,
+                0,
+                &[self.shadow_desc_uniform[current_frame].set],
+                &[job.uniform_offset]
+            );
+
+            // Рендеринг всех объектов
+            for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
+                cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
+                cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
+                cmd.push_constants(self.shadow_pipeline_layout.layout, vk::ShaderStageFlags::VERTEX, 0, as_bytes(&self.draw_push(mi, 0)));
+                for sm in gpu_mesh.submeshes.iter() {
+                    cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
+                }
+            }
         }
+        cmd.end()
+    }
 
-        let depth_attachment_info = vk::RenderingAttachmentInfo {
-            image_view: self.shadow_map_view_vec[layer_index].view,  //self.shadow_map_view_vec.len() - 1
-            image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            clear_value: vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
+    /// Карты теней всех источников: по рендерингу на слой, внутри уже записанный secondary
+    /// Барьеры ставит render graph: shadow_map к этому моменту в DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+    /// # Args
+    /// * `secondary` - буферы record_shadow_light в том же порядке, что и jobs
+    pub fn execute_shadow_pass(&self, cmd: &VulkanCommandBuffer, jobs: &[ShadowJob], secondary: &[vk::CommandBuffer]) -> Result<(), &'static str> {
+        if jobs.len() != secondary.len() {
+            return Err("Shadow pass: job and command buffer count mismatch");
+        }
+        for (job, buffer) in jobs.iter().zip(secondary.iter()) {
+            let depth_attachment_info = vk::RenderingAttachmentInfo {
+                image_view: self.shadow_map_view_vec[job.layer_index].view,
+                image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                clear_value: vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
                 },
-            },
-            
-            ..Default::default()
-        };
-        
-        let rendering_info = vk::RenderingInfo {
-            render_area: vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: vk::Extent2D {
-                    width: SHADOW_MAP_RESOLUTION,
-                    height: SHADOW_MAP_RESOLUTION,
+                ..Default::default()
+            };
+            let rendering_info = vk::RenderingInfo {
+                render_area: vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: vk::Extent2D { width: SHADOW_MAP_RESOLUTION, height: SHADOW_MAP_RESOLUTION },
                 },
-            },
-            layer_count: 1,
-            color_attachment_count: 0,
-            p_color_attachments: std::ptr::null(),
-            p_depth_attachment: &depth_attachment_info,
-            p_stencil_attachment: std::ptr::null(),
-            
-            ..Default::default()
-        };
-        unsafe {
-        
-        cmd.begin_dynamic_rendering(&rendering_info)?;
-
-        // === 1. SHADOW PASS ===
-        // Привязка shadow pipeline
-        cmd.bind_pipeline(
-            vk::PipelineBindPoint::GRAPHICS,
-            self.shadow_pipeline.pipeline
-        );
-
-        let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-        let aligned_size = ((std::mem::size_of::<ShadowsUniform>() as u64 + alignment - 1) / alignment) * alignment;
-        let sh_offset = aligned_size as u32 * layer_index as u32; //si
-        // Обновление матрицы в uniform буфере
+                layer_count: 1,
+                p_depth_attachment: &depth_attachment_info,
+                flags: vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS,
+                ..Default::default()
+            };
+            cmd.begin_dynamic_rendering(&rendering_info)?;
+            unsafe { cmd.execute_commands(&[*buffer]); }
+            cmd.end_dynamic_rendering()?;
+        }
+        Ok(())
+    }
+}
 
-        let light_matrix = ShadowsObject::calculate_light_space_matrix(&light_dir, &scene_center, scene_size, is_spotlight, light_pos);
 
-        let uniform_data = ShadowsUniform {
-            light_space_matrix: light_matrix.data,
-            indx: layer_index as u32,
+impl ShadowsObject {
+    /// Push constants одного draw. object_id - номер меша в self.meshes
+    fn draw_push(&self, mesh_index: usize, submesh_index: usize) -> DrawPush {
+        DrawPush {
+            model: self.meshes[mesh_index].model,
+            material_index: self.material_bases[mesh_index] + submesh_index as u32,
+            object_id: mesh_index as u32,
             ..Default::default()
-        };
-        self.shadow_uniform_buffers[current_frame].mem_copy(
-            &[uniform_data], Some(sh_offset as u64), None, None
-        )?;
-
-        // Привязка descriptor sets для shadow pass
-        cmd.bind_descriptor_sets(
-            vk::PipelineBindPoint::GRAPHICS,
-            self.shadow_pipeline_layout.layout,
-            0,
-            &[self.shadow_desc_uniform[current_frame].set],
-            &[sh_offset]
-        );
-        
-        // Рендеринг всех объектов
-        let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-        for (mi, gpu_mesh) in self.meshes.iter().enumerate() {
+        }
+    }
 
-            cmd.bind_vertex_buffers(0, &[gpu_mesh.vertex_buf.buffer], &[0]);
-            cmd.bind_index_buffer(gpu_mesh.index_buf.buffer, 0, vk::IndexType::UINT32);
-            
-            let aligned_size = ((std::mem::size_of::<TransformUBO>() as u64 + alignment - 1) / alignment) * alignment;
-            let mfr_offset = aligned_size as u32 * current_frame as u32; //si
-            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.shadow_pipeline_layout.layout, 1, &[self.model_sets[mi].set], &[mfr_offset]);
-            
-            for (si, sm) in gpu_mesh.submeshes.iter().enumerate() {
-                cmd.draw_indexed(sm.index_count as u32, 1, sm.index_offset as u32, 0, 0);
-            }
+    /// Докачивает машину. Когда она вся на GPU, встает на место заглушки,
+    /// заглушка возвращается, чтобы дропнуть ее после кадров в полете
+    fn stream_model(&mut self, app: &mut VulkanApp) -> Result<Vec<MeshGPU>, &'static str> {
+        if self.model_stream.is_done() {
+            return Ok(vec![]);
         }
-        cmd.end_dynamic_rendering()?;
+        let Some(new_meshes) = self.model_stream.step(app)? else {
+            return Ok(vec![]);
+        };
+
+        // как в init, новые слоты пишутся, пока старые кадры читают таблицу
+        let mut material_bases = vec![];
+        for mesh in new_meshes.iter() {
+            material_bases.push(self.bindless.add_mesh(mesh)?);
         }
-        Ok(())
+
+        // слоты заглушки в bindless остаются, их немного
+        let range = self.stream_range.clone();
+        self.stream_range = range.start..range.start + new_meshes.len();
+        self.material_bases.splice(range.clone(), material_bases);
+        Ok(self.meshes.splice(range, new_meshes).collect())
+    }
+}
+
+/// Блоки шейдеров геометрии против gpu_struct! на Rust стороне
+fn check_scene_shaders(shaders: &[VulkanShader]) -> Result<(), &'static str> {
+    for shader in shaders.iter() {
+        shader.reflection.check_block::<Uniforms>(0, 0)?;
+        shader.reflection.check_block::<LightsSSBO>(0, 1)?;
+        shader.reflection.check_table::<BindlessMaterial>(1, BINDLESS_MATERIALS_BINDING)?;
+        shader.reflection.check_push_constants(size_of::<DrawPush>() as u32)?;
+    }
+    Ok(())
+}
+
+/// То же для прохода света deferred, у него материалов нет
+fn check_light_shaders(shaders: &[VulkanShader]) -> Result<(), &'static str> {
+    for shader in shaders.iter() {
+        shader.reflection.check_block::<Uniforms>(0, 0)?;
+        shader.reflection.check_block::<LightsSSBO>(0, 1)?;
     }
+    Ok(())
 }
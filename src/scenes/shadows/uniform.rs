@@ -1,21 +1,29 @@
 
-pub const MAX_LIGHTS_IN_CAT: usize = 5;
+use crate::vulkan_wr::types::gpu_layout::gpu_struct;
 
+// одно значение с scene2: дефолт MAX_LIGHTS в lights.glsl общий
+pub use crate::scenes::lighting::uniform::MAX_LIGHTS_IN_CAT;
 
-#[repr(C)] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct Uniforms {
-    pub view_proj: [[f32;4];4], // локальное в NDC 
-    // pub world: [[f32;4];4], // локальное в NDC
-    pub camera: [f32; 4],
-    pub time: f32,
-    pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
+// раскладку проверяет gpu_struct!, GLSL сторона - shaders/include/lights.glsl
+
+
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct Uniforms: Std140 {
+        pub view_proj: [[f32;4];4], // локальное в NDC
+        pub camera: [f32; 4],
+        pub time: f32,
+        pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
+    }
 }
 
-pub struct ShadowsUniform {
-    pub light_space_matrix: [[f32;4];4], // локальное в NDC
-    pub indx: u32,
-    pub _pad: [f32; 3],
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct ShadowsUniform: Std140 {
+        pub light_space_matrix: [[f32;4];4], // локальное в NDC
+        pub indx: u32,
+        pub _pad: [f32; 3],
+    }
 }
 
 impl Default for ShadowsUniform {
@@ -24,56 +32,48 @@ impl Default for ShadowsUniform {
     }
 }
 
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct LightsSSBO {
-    pub light_count_directional: u32,
-    pub light_count_point: u32,
-    pub light_count_spotlight: u32,
-    pub time: f32,
-    // pub _pad: u32,
-
-    pub directional_lights: [DirectionalLight; MAX_LIGHTS_IN_CAT],
-    pub point_lights: [PointLight; MAX_LIGHTS_IN_CAT],
-    pub spotlights: [Spotlight; MAX_LIGHTS_IN_CAT],
-
-
-    // pub directional_light_matrices: [[[f32; 4]; 4]; 5], // ViewProj для каждого направленного света
-    // pub point_light_far_planes: [f32; 5], // Far plane для точечных источников
-    pub _pad: [f32; 3], // Выравнивание
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct LightsSSBO: Std430 {
+        pub light_count_directional: u32,
+        pub light_count_point: u32,
+        pub light_count_spotlight: u32,
+        pub time: f32,
+
+        pub directional_lights: [DirectionalLight; MAX_LIGHTS_IN_CAT],
+        pub point_lights: [PointLight; MAX_LIGHTS_IN_CAT],
+        pub spotlights: [Spotlight; MAX_LIGHTS_IN_CAT],
+    }
 }
 
-
-#[repr(C, align(8))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct DirectionalLight {
-    pub direction: [f32; 4], // .w свободен
-    pub color: [f32; 4],     // .w = intensity
-    pub light_matrices: [[f32; 4]; 4],
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct DirectionalLight: Std430 {
+        pub direction: [f32; 4], // .w свободен
+        pub color: [f32; 4],     // .w = intensity
+        pub light_matrices: [[f32; 4]; 4],
+    }
 }
 
-
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct Spotlight {
-    pub position: [f32; 4],  // .w свободен
-    pub direction: [f32; 4], // .w = cutoff angle in radians
-    pub color: [f32; 4],     // .w = intensity
-    pub cut_off: [f32; 4],
-    // pub _pad: [f32; 3],      // выравнивание до 16 байт v4 float
-    pub light_matrices: [[f32; 4]; 4],
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct Spotlight: Std430 {
+        pub position: [f32; 4],  // .w свободен
+        pub direction: [f32; 4], // .w = cutoff angle in radians
+        pub color: [f32; 4],     // .w = intensity
+        pub cut_off: [f32; 4],
+        pub light_matrices: [[f32; 4]; 4],
+    }
 }
 
-// ssbo требует выравние для массивов даже в std430. не чет другое
-#[repr(C, align(16))] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct PointLight {
-    pub position: [f32; 4], // .w свободен
-    pub color: [f32; 4],    // .w = intensity
-    pub coefficients: [f32; 4],
-    pub _pad: [f32; 4],     // выравнивание до 16 байт v4 float
-    // pub _pad1: [f32; 4],     // выравнивание до 16 байт v4 float
-    pub light_matrices: [[f32; 4]; 4],
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct PointLight: Std430 {
+        pub position: [f32; 4], // .w свободен
+        pub color: [f32; 4],    // .w = intensity
+        pub coefficients: [f32; 4],
+        pub light_matrices: [[f32; 4]; 4],
+    }
 }
 
 impl Default for LightsSSBO {
@@ -83,14 +83,9 @@ impl Default for LightsSSBO {
             light_count_point: 0,
             light_count_spotlight: 0,
             time: 0.0,
-            // _pad: 0,
             directional_lights: [DirectionalLight {..Default::default()}; MAX_LIGHTS_IN_CAT],
             point_lights: [PointLight {..Default::default()}; MAX_LIGHTS_IN_CAT],
             spotlights: [Spotlight {..Default::default()}; MAX_LIGHTS_IN_CAT],
-            // directional_light_matrices: [[[0.0; 4]; 4]; 5],
-            // point_light_far_planes: [0.0; 5],
-            _pad: [0.0; 3],
-        
         }
     }
 }
@@ -113,7 +108,6 @@ impl Default for Spotlight {
             color: [0.0; 4],
             cut_off: [1.0; 4],
             light_matrices: [[0.0; 4]; 4]
-            // _pad: [0.0; 3],
         }
     }
 }
@@ -124,9 +118,7 @@ impl Default for PointLight {
             position: [0.0; 4],
             color: [0.0; 4],
             coefficients: [1.0; 4],
-            _pad: [0.0; 4],
             light_matrices: [[0.0; 4]; 4]
-            // _pad1: [0.0; 4],
         }
     }
 }
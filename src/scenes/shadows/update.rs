@@ -9,7 +9,7 @@ use super::{
 };
 use super::super::super::vulkan_wr::{
     app::VulkanApp,
-    types::{matrix::Matrix},
+    types::{matrix::Matrix, model::MeshGPU},
     ImGui_wr::{UpdateImguiResources, VulkanImgui},
     renderable_traits::UpdateObjectResources,
 };
@@ -17,6 +17,9 @@ use super::frame_resources::{ImguiFrameResourcesShadows};
 use super::super::super::window::{KEY_CODES, key_to_index};
 use super::super::dynamic::frame_resources::{FrameResources, Camera};
 use crate::vulkan_wr::ImGui_wr::ImguiResources;
+use crate::vulkan_wr::post::settings::RenderSettings;
+use crate::vulkan_wr::post::histogram::HistogramStats;
+use crate::vulkan_wr::present::present_mode_mask;
 
 pub struct ResourcesShadows {
     mvp: Matrix<4, 4>,
@@ -41,15 +44,30 @@ pub struct ResourcesShadows {
     light_count_spotlight: u32,
     camera: Camera,
     time: f32,
+    render_settings: RenderSettings,
+    jitter: [f32; 2],  // TAA, в NDC
+    view_proj: Matrix<4, 4>,  // без jitter, для репроекции в TAA
+    proj: Matrix<4, 4>,  // c jitter, как рисовался кадр, по ней SSAO восстанавливает позицию
+    retired: Vec<MeshGPU>,  // в write уходят в frames.defer_drop
+    histogram: HistogramStats,  // из FrameResources в UI
 }
 
 impl<R: ImguiResources + Default> UpdateObjectResources<FrameResources<R>> for ResourcesShadows {
     fn read(&mut self, arg: &mut FrameResources<R>) -> Result<(), &'static str> {
         self.camera = arg.camera;
+        self.render_settings = arg.render_settings;
+        self.jitter = arg.taa_jitter();
+        self.histogram = arg.histogram.as_ref().map(|h| h.stats()).unwrap_or_default();
         Ok(())
     }
     fn write(&mut self, arg: &mut FrameResources<R>) -> Result<(), &'static str> {
         arg.camera = self.camera;
+        arg.render_settings = self.render_settings;
+        arg.view_proj = self.view_proj;
+        arg.proj = self.proj;
+        for mesh in self.retired.drain(..) {
+            arg.frames.defer_drop(mesh);
+        }
         Ok(())
     }
 }
@@ -97,7 +115,10 @@ impl UpdateShadowsObject for ResourcesShadows {
             let w = h * aspect;
             Matrix::orthographic(-w, w, -h, h, 0.1, 100.0)
         };
-        self.mvp = (proj_matrix * self.camera.view_matrix()?).transpose();
+        let view_matrix = self.camera.view_matrix()?;
+        self.view_proj = proj_matrix * view_matrix;
+        self.proj = proj_matrix.jitter(self.jitter[0], self.jitter[1]);
+        self.mvp = (self.proj * view_matrix).transpose();
 
         let u = Uniforms {
             view_proj: self.mvp.data,
@@ -225,6 +246,10 @@ impl UpdateShadowsObject for ResourcesShadows {
 
         Ok(())
     }
+
+    fn retire(&mut self, meshes: Vec<MeshGPU>) {
+        self.retired.extend(meshes);
+    }
 }
 
 impl UpdateImguiResources<ImguiFrameResourcesShadows> for ResourcesShadows {
@@ -258,6 +283,15 @@ impl UpdateImguiResources<ImguiFrameResourcesShadows> for ResourcesShadows {
         self.outer_cut_off = imgui.resources.outer_cut_off;
         self.inner_cut_off = imgui.resources.inner_cut_off;
         self.time = (imgui.resources.prev_time - imgui.resources.start_time).as_secs_f32();
+        // что умеет девайс знает только app, UI без этого не покажет лишние варианты
+        imgui.resources.render_settings.msaa.supported = app.core.supported_msaa_samples;
+        imgui.resources.render_settings.present.supported = present_mode_mask(&app.swapchain.supported_present_modes);
+        // а что умеет сцена - FrameResources, пришло через read()
+        imgui.resources.render_settings.keep_scene_support(&self.render_settings);
+        self.render_settings = imgui.resources.render_settings;
+        imgui.resources.shader_errors = app.shader_reload.errors().to_vec();
+        imgui.resources.loading = app.assets.progress();
+        imgui.resources.histogram = self.histogram;
 
         Ok(())
     }
@@ -287,6 +321,12 @@ impl Default for ResourcesShadows {
             coefficient_quadratic: 0.2,
             inner_cut_off: 20.0,
             time: 0.0,
+            render_settings: RenderSettings::default(),
+            jitter: [0.0, 0.0],
+            view_proj: Matrix::identity(),
+            proj: Matrix::identity(),
+            retired: vec![],
+            histogram: HistogramStats::default(),
         }
     }
 }
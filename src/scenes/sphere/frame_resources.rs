@@ -1,6 +1,8 @@
 
 use super::super::super::vulkan_wr::{
     ImGui_wr::{ImguiResources},
+    post::settings::RenderSettings,
+    hot_reload::{ShaderError, render_errors},
 };
 
 pub struct ImguiFrameResourcesSphere {
@@ -12,6 +14,8 @@ pub struct ImguiFrameResourcesSphere {
     pub start_time: std::time::Instant,
     pub prev_time: std::time::Instant,
     pub aimation_time: f32,
+    pub render_settings: RenderSettings,
+    pub shader_errors: Vec<ShaderError>,  // hot reload, пишет update_imgui
 }
 
 impl ImguiResources for ImguiFrameResourcesSphere {
@@ -37,7 +41,10 @@ impl ImguiResources for ImguiFrameResourcesSphere {
             ui.text("Info:");
             ui.text(format!("Pulse Scale: {:.2}", self.pulse_scale));
             ui.text(format!("Time: {:.2}", (self.prev_time - self.start_time).as_secs_f32()));
+
+            self.render_settings.render_ui(ui);
         });
+        render_errors(ui, &self.shader_errors);
 
     }
 }
@@ -54,6 +61,8 @@ impl Default for ImguiFrameResourcesSphere {
             start_time: time,
             prev_time: time,
             aimation_time: 0.0,
+            render_settings: RenderSettings::default(),
+            shader_errors: vec![],
         }
     }
 }
@@ -2,8 +2,7 @@
 use super::super::super::vulkan_wr::{
     app::VulkanApp,
     descriptor::descriptor_set_layout::VulkanDescriptorSetLayout,
-    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline::VulkanPipelineBuilder},
-    shader::VulkanShader,
+    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline_desc::{PipelineDesc, PipelineTarget}},
     buffer::buffer::VulkanBuffer,
     types::vertex::VulkanVertex,
     pipeline::pipeline::VulkanPipeline,
@@ -87,31 +86,13 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
         }
     }
 
-    let vertex_buffers = vec![VulkanBuffer::try_new(
-        &app.core,
-        (size_of::<VulkanVertex>() * vertices.len()) as u64,
-        vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-        None, None, None, None
-    )?];
-
-    unsafe {
-        vertex_buffers[0].mem_copy(vertices.as_slice(), None, None, None)?;  // not as_ref. надо потом везде поменять
-    }
-
     let index_count = indices.len();
-    // create index buffer
-    let index_buffers = vec![VulkanBuffer::try_new(
-        &app.core,
-        (std::mem::size_of::<u32>() * index_count) as u64,
-        vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        None, None, None, None
-    )?];
-
-    unsafe {
-        index_buffers[0].mem_copy(indices.as_slice(), None, None, None)?;
-    }
+    // геометрия не меняется, кладем в DEVICE_LOCAL через transfer очередь
+    let (vb, _) = app.uploader.upload_buffer(&app.core, vertices.as_slice(), vk::BufferUsageFlags::VERTEX_BUFFER)?;
+    let (ib, ready) = app.uploader.upload_buffer(&app.core, indices.as_slice(), vk::BufferUsageFlags::INDEX_BUFFER)?;
+    app.uploader.wait(ready)?;  // тикеты идут по порядку, последний покрывает оба
+    let vertex_buffers = vec![vb];
+    let index_buffers = vec![ib];
 
     // 4. Descriptor set layout - определяет структуру наборов дескрипторов
     let desc_vec = vec![
@@ -139,100 +120,17 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
         &[],
     )?;
 
-    // 6. Shader stages. Стоит это все внутрь шейдера засунуть.
-
-    let exe_path = std::env::current_exe()
-        .expect("Failed to get current executable path");
-    let exe_dir = exe_path
-        .parent()
-        .expect("Executable is in the root directory?")
-        .to_path_buf();
-
-    // let shader_dir = std::env::var("SHADER_PATH").unwrap();
-    let vert_path = exe_dir.join("shaders").join("vert_sphere.spv");
-    //.to_str().ok_or("Failed found shaders")?;
-    //format!("{}/shaders/vert_sphere.spv", exe_dir.r);
-    let frag_path = exe_dir.join("shaders").join("frag_sphere.spv");
-    //.to_str().ok_or("Failed found shaders")?;
-    //format!("{}/shaders/frag_sphere.spv", exe_dir);
-
-    let vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path.to_str().ok_or("Failed found shaders")?)?;
-    let frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path.to_str().ok_or("Failed found shaders")?)?;
-
-    // entry_point для шейдера
-    let entry_point = std::ffi::CString::new("main").unwrap();
-
-    let shader_stages = vec![
-        vk::PipelineShaderStageCreateInfo {
-            module: vert_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::VERTEX,
-            ..Default::default()
-        },
-        vk::PipelineShaderStageCreateInfo {
-            module: frag_shader._shader,
-            p_name: entry_point.as_ptr(),
-            stage: vk::ShaderStageFlags::FRAGMENT,
-            ..Default::default()
-        }
-    ];
-
-    // 7. Pipeline
-    // --- vertex input (binding + атрибуты)
-    // Описание формата вершин (binding)
-    let binding_description = VulkanVertex::get_binding_description(None);
-
-    // Описание атрибутов вершин (позиция и цвет, нормали текстурные коорд и тп)
-    let attribute_descriptions = VulkanVertex::get_attribute_descriptions();
-
-    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-        vertex_binding_description_count: 1,  // Одно описание binding
-        p_vertex_binding_descriptions: &binding_description,
-        vertex_attribute_description_count: attribute_descriptions.len() as u32,  // количнство отрибутов внутри одного экземпляра вершины
-        p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
-        ..Default::default()
-    };
-    // !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
-    // чтобы задавать viewport и scissor динамически — нужно добавить dynamic state.
-
-    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-        dynamic_state_count: dynamic_states.len() as u32,
-        p_dynamic_states: dynamic_states.as_ptr(),
-        ..Default::default()
-    };
-
-    // --- построение (create_graphics_pipelines)
-    let pipeline = VulkanPipelineBuilder::new(
-        &app.core._logical_device,
+    // 6-7. Shader stages + Pipeline
+    let pipeline = Self::create_pipeline(
+        app,
         resources.render_pass.as_ref().ok_or("Render: Obj is not initialized")?.render_pass,
-        pipeline_layout.layout
-    )
-    .with_shader_stages(shader_stages)
-    .with_vertex_input(vertex_input_info)
-    // .with_viewport_state(viewport_state)
-    .with_dynamic_states(dynamic_state_info)
-    .with_input_assembly(
-        vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            primitive_restart_enable: vk::FALSE,
-            ..Default::default()
-        }
-    )
-    .with_subpass(0) // Используем первый (и единственный) субпасс
-    .with_depth_stencil(
-        vk::PipelineDepthStencilStateCreateInfo { // нужно для 3д фигур, иначе последние примитивы отрисуются поверх первых
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,  //запись в буфер глубины
-            depth_compare_op: vk::CompareOp::LESS,
-            ..Default::default()
-        }
-    )
-    .build()?;
+        &pipeline_layout,
+        resources.samples,
+    )?;
 
-    // 8. Uniform buffers per swapchain image
+    // 8. Uniform buffers per frame in flight
     let mut uniform_buffers = vec![];
-    for _ in 0..app.image_count {
+    for _ in 0..app.frames_in_flight {
         let buf = VulkanBuffer::try_new(
             &app.core,
             size_of::<Uniforms>() as vk::DeviceSize,
@@ -245,7 +143,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
 
     // 9. Descriptor sets
     let mut descriptor_sets = vec![];
-    for _ in 0..app.image_count {  // сеты под юниформы
+    for _ in 0..app.frames_in_flight {  // сеты под юниформы
         descriptor_sets.append(&mut app.descriptor_pool.allocate_descriptor_sets(
             descriptor_set_layout.as_ref() // тут он один
         )?);
@@ -253,7 +151,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
 
     let mut buffer_infos = Vec::new();
     let mut writes = Vec::new();
-    for i in 0..app.image_count as usize {
+    for i in 0..app.frames_in_flight as usize {
         // Создание записи для обновления uniform буфера в дескрипторном наборе,
         // то откуда читать в шейдер по этому дискриптору
         let (write, buf_info) = descriptor_sets[i].write_buffer(
@@ -275,7 +173,7 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
 
     // let start_time = std::time::Instant::now();
 
-    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.image_count, vk::CommandBufferLevel::SECONDARY)?;
+    let vec_cmd_secondary = app.command_pool.allocate_command_buffers(app.frames_in_flight, vk::CommandBufferLevel::SECONDARY)?;
 
     Ok(Self {
         cmd_vec: vec_cmd_secondary,
@@ -292,6 +190,39 @@ impl<'a> InitObject<InitFrameResources<'a>> for SphereObject {
 }
 
 
+impl SphereObject {
+    /// Основной пайплайн. Отдельно от init, т.к. пересобирается при смене MSAA
+    fn create_pipeline(
+        app: &VulkanApp,
+        render_pass: vk::RenderPass,
+        layout: &VulkanPipelineLayout,
+        samples: vk::SampleCountFlags,
+    ) -> Result<VulkanPipeline, &'static str> {
+        // 6-7. Шейдеры и состояние пайплайна в pipelines/sphere.toml
+        let desc = PipelineDesc::load("sphere.toml")?;
+        let shaders = desc.load_shaders(&app.core._logical_device)?;
+        for shader in shaders.iter() {
+            shader.reflection.check_block::<Uniforms>(0, 0)?;
+        }
+        desc.build_with(&app.core, layout.layout, &PipelineTarget::render_pass(app, render_pass, samples), &shaders)
+    }
+
+    /// Пересобирает пайплайн под новый render pass / sample count, девайс должен простаивать
+    pub fn rebuild_pipeline(&mut self, app: &VulkanApp, render_pass: vk::RenderPass, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        self.pipeline = Self::create_pipeline(app, render_pass, &self.pipeline_layout, samples)?;
+        Ok(())
+    }
+
+    /// Hot reload: то же пересоздание, если поменялся свой .spv
+    pub fn reload_shaders(&mut self, app: &VulkanApp, changed: &[String], render_pass: vk::RenderPass, samples: vk::SampleCountFlags) -> Result<(), &'static str> {
+        if PipelineDesc::touched_by("sphere.toml", changed) {
+            self.rebuild_pipeline(app, render_pass, samples)?;
+        }
+        Ok(())
+    }
+}
+
+
 impl<'a> RenderObject<RenderFrameResources<'a>> for SphereObject {
     fn render(&mut self,
             app: & mut VulkanApp,
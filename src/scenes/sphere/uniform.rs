@@ -1,8 +1,10 @@
+use crate::vulkan_wr::types::gpu_layout::gpu_struct;
 
-#[repr(C)] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct Uniforms {
-    pub mvp: [[f32;4];4], // локальное в NDC 
-    pub time: f32,
-    pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct Uniforms: Std140 {
+        pub mvp: [[f32;4];4], // локальное в NDC
+        pub time: f32,
+        pub(super) _pad: [f32;3], // выравнивание до 16 байт v4 float
+    }
 }
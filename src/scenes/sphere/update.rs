@@ -10,18 +10,27 @@ use super::super::super::vulkan_wr::{
     renderable_traits::UpdateObjectResources,
 };
 use super::frame_resources::ImguiFrameResourcesSphere;
+use super::super::common::frame_resources::FrameResources;
+use crate::vulkan_wr::{ImGui_wr::ImguiResources, post::settings::RenderSettings, present::present_mode_mask};
 
 pub struct ResourcesSphere {
     mvp: Matrix<4, 4>,
     // resources: &'a FrameResources,
     animation_time: f32,  // self.aimation_time
+    render_settings: RenderSettings,
+    jitter: [f32; 2],  // TAA, в NDC
+    view_proj: Matrix<4, 4>,  // без jitter, для репроекции в TAA
 }
 
-impl<T> UpdateObjectResources<T> for ResourcesSphere {
-    fn read(&mut self, arg: &mut T) -> Result<(), &'static str> {
+impl<R: ImguiResources + Default> UpdateObjectResources<FrameResources<R>> for ResourcesSphere {
+    fn read(&mut self, arg: &mut FrameResources<R>) -> Result<(), &'static str> {
+        self.render_settings = arg.render_settings;
+        self.jitter = arg.taa_jitter();
         Ok(())
     }
-    fn write(&mut self, arg: &mut T) -> Result<(), &'static str> {
+    fn write(&mut self, arg: &mut FrameResources<R>) -> Result<(), &'static str> {
+        arg.render_settings = self.render_settings;
+        arg.view_proj = self.view_proj;
         Ok(())
     }
 }
@@ -87,13 +96,27 @@ impl UpdateImguiResources<ImguiFrameResourcesSphere> for ResourcesSphere {
         } else {
             Matrix::orthographic(-w, w, -h, h, 0.1, 10.0)
         };
-        self.mvp = (proj_matrix * view_matrix * model_matrix).transpose();
+        self.view_proj = proj_matrix * view_matrix;
+        self.mvp = (proj_matrix.jitter(self.jitter[0], self.jitter[1]) * view_matrix * model_matrix).transpose();
+        // что умеет девайс знает только app, UI без этого не покажет лишние варианты
+        imgui.resources.render_settings.msaa.supported = app.core.supported_msaa_samples;
+        imgui.resources.render_settings.present.supported = present_mode_mask(&app.swapchain.supported_present_modes);
+        // а что умеет сцена - FrameResources, пришло через read()
+        imgui.resources.render_settings.keep_scene_support(&self.render_settings);
+        self.render_settings = imgui.resources.render_settings;
+        imgui.resources.shader_errors = app.shader_reload.errors().to_vec();
         Ok(())
     }
 }
 
 impl Default for ResourcesSphere {
     fn default() -> Self {
-        Self { mvp: Matrix::identity(), animation_time: 0.0 }
+        Self {
+            mvp: Matrix::identity(),
+            animation_time: 0.0,
+            render_settings: RenderSettings::default(),
+            jitter: [0.0, 0.0],
+            view_proj: Matrix::identity(),
+        }
     }
 }
@@ -16,17 +16,15 @@ use super::app::{VulkanApp, SceneResources};
 use super::{
     render_pass::pass::VulkanRenderPass,
     descriptor::{descriptor_set_layout::VulkanDescriptorSetLayout, descriptor_set::VulkanDescriptorSet},
-    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline::{VulkanPipeline, VulkanPipelineBuilder}},
+    pipeline::{pipeline_layout::VulkanPipelineLayout, pipeline::VulkanPipeline, pipeline_desc::{PipelineDesc, PipelineTarget}},
     buffer::buffer::VulkanBuffer,
     framebuffer::VulkanFramebuffer,
     image::{image_view::{VulkanImageView, VulkanImageViewBuilder}, image::{VulkanImage, VulkanImageBuilder}},
     command_pb::command_buffer::VulkanCommandBuffer,
     sync::{
         semaphore::VulkanSemaphore,
-        fence::VulkanFence,
     },
     sampler::{VulkanSampler, VulkanSamplerBuilder},
-    shader::VulkanShader,
     renderable_traits::{
         InitObject, InitObjectResources,
         RenderObject, RenderObjectResources,
@@ -132,112 +130,8 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
         };
 
         // 11. Создание пайплайна для ImGui
-        // 11.1. Загрузка шейдеров ImGui
-        let exe_path = std::env::current_exe()
-            .expect("Failed to get current executable path");
-        let exe_dir = exe_path
-            .parent()
-            .expect("Executable is in the root directory?")
-            .to_path_buf();
-        let vert_path = exe_dir.join("shaders").join("imgui_vert.spv");
-        let frag_path = exe_dir.join("shaders").join("imgui_frag.spv");
-        let imgui_vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path.to_str().ok_or("Failed found shaders")?)?;
-        let imgui_frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path.to_str().ok_or("Failed found shaders")?)?;
-
-        // let shader_dir = std::env::var("SHADER_PATH").unwrap();
-        // let vert_path = format!("{}/imgui_vert.spv", shader_dir);
-        // let frag_path = format!("{}/imgui_frag.spv", shader_dir);
-        // let imgui_vert_shader = VulkanShader::try_new(&app.core._logical_device, &vert_path)?;
-        // let imgui_frag_shader = VulkanShader::try_new(&app.core._logical_device, &frag_path)?;
-
-        let entry_point = std::ffi::CString::new("main").unwrap();
-        let imgui_shader_stages = vec![
-            vk::PipelineShaderStageCreateInfo {
-                module: imgui_vert_shader._shader,
-                p_name: entry_point.as_ptr(),
-                stage: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            },
-            vk::PipelineShaderStageCreateInfo {
-                module: imgui_frag_shader._shader,
-                p_name: entry_point.as_ptr(),
-                stage: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            }
-        ];
-
-        // 11.2. Вершинный формат для ImGui
-
-        let imgui_binding_description = ImGUIVertex::get_binding_description();
-        let imgui_attribute_descriptions = ImGUIVertex::get_attribute_descriptions();
-
-        let imgui_vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-            vertex_binding_description_count: 1,
-            p_vertex_binding_descriptions: &imgui_binding_description,
-            vertex_attribute_description_count: imgui_attribute_descriptions.len() as u32,
-            p_vertex_attribute_descriptions: imgui_attribute_descriptions.as_ptr(),
-            ..Default::default()
-        };
-
-        // 11.3. Настройка viewport/scissor (динамические)
-        let imgui_viewport_state = vk::PipelineViewportStateCreateInfo {
-            viewport_count: 1,
-            scissor_count: 1,
-            ..Default::default()
-        };
-
-        // 11.4. Растеризатор
-        let imgui_rasterizer = vk::PipelineRasterizationStateCreateInfo {
-            depth_clamp_enable: 0,
-            rasterizer_discard_enable: 0,
-            polygon_mode: vk::PolygonMode::FILL,
-            cull_mode: vk::CullModeFlags::NONE,
-            front_face: vk::FrontFace::CLOCKWISE,
-            line_width: 1.0,
-            ..Default::default()
-        };
-
-        // 11.5. Multisampling
-        let imgui_multisampling = vk::PipelineMultisampleStateCreateInfo {
-            sample_shading_enable: 0,
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
-            ..Default::default()
-        };
-
-        // 11.6. Depth stencil
-        let imgui_depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: vk::FALSE,
-            depth_write_enable: vk::FALSE,
-            ..Default::default()
-        };
-
-        // 11.7. Color blending
-        let imgui_color_blend_attachment = vk::PipelineColorBlendAttachmentState {
-            blend_enable: vk::TRUE,
-            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
-            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
-            color_blend_op: vk::BlendOp::ADD,
-            src_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
-            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-            alpha_blend_op: vk::BlendOp::ADD,
-            color_write_mask: vk::ColorComponentFlags::RGBA,
-            
-        }; 
-
-        let imgui_color_blend = vk::PipelineColorBlendStateCreateInfo {
-            logic_op_enable: 0,
-            attachment_count: 1,
-            p_attachments: &imgui_color_blend_attachment,
-            ..Default::default()
-        };
-
-        // 11.8. Динамические состояния (viewport/scissor)
-        let imgui_dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-        let imgui_dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-            dynamic_state_count: imgui_dynamic_states.len() as u32,
-            p_dynamic_states: imgui_dynamic_states.as_ptr(),
-            ..Default::default()
-        };
+        // 11.1. Шейдеры, вершины, смешивание - в pipelines/imgui.toml
+        let imgui_desc = PipelineDesc::load("imgui.toml")?;
 
         // 11.9. Descriptor set layout для ImGui
         let imgui_desc_vec = vec![
@@ -273,55 +167,17 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
         )?;
 
         // 11.11. Создание пайплайна
-        let imgui_pipeline;
-        #[cfg(any(feature = "scene1", feature = "scene2"))] {
-            imgui_pipeline = VulkanPipelineBuilder::new(
-                &app.core._logical_device,
-                resources.render_pass.as_ref().ok_or("RENDERPASS Imgui is not initialized")?.render_pass,
-                imgui_pipeline_layout.layout
-            )
-            .with_shader_stages(imgui_shader_stages)
-            .with_vertex_input(imgui_vertex_input_info)
-            .with_viewport_state(imgui_viewport_state)
-            .with_rasterizer(imgui_rasterizer)
-            .with_multisampling(imgui_multisampling)
-            .with_depth_stencil(imgui_depth_stencil)
-            .with_color_blend(imgui_color_blend)
-            .with_dynamic_states(imgui_dynamic_state_info) // Добавляем метод для динамических состояний
-            .with_input_assembly(
-                vk::PipelineInputAssemblyStateCreateInfo {
-                    topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-                    primitive_restart_enable: vk::FALSE,
-                ..Default::default()
-            })
-            .with_subpass(0)
-            .build()?;
-        }
-        #[cfg(feature = "scene3")] {
-            imgui_pipeline = VulkanPipelineBuilder::new_dynamic(
-                &app.core._logical_device,
-                imgui_pipeline_layout.layout
-            )
-            .with_color_attachment_formats(vec![app.swapchain.color_format])
-            .with_depth_attachment_format(app.swapchain.depth_format)
-            .with_shader_stages(imgui_shader_stages)
-            .with_vertex_input(imgui_vertex_input_info)
-            .with_viewport_state(imgui_viewport_state)
-            .with_rasterizer(imgui_rasterizer)
-            .with_multisampling(imgui_multisampling)
-            .with_depth_stencil(imgui_depth_stencil)
-            .with_color_blend(imgui_color_blend)
-            .with_dynamic_states(imgui_dynamic_state_info)
-            .with_input_assembly(
-                vk::PipelineInputAssemblyStateCreateInfo {
-                    topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-                    primitive_restart_enable: vk::FALSE,
-                    ..Default::default()
-                }
-            )
-            .build()?;
-        }
-        
+        #[cfg(any(feature = "scene1", feature = "scene2"))]
+        let imgui_target = PipelineTarget::render_pass(
+            app,
+            resources.render_pass.as_ref().ok_or("RENDERPASS Imgui is not initialized")?.render_pass,
+            vk::SampleCountFlags::TYPE_1,
+        );
+        // рисуется отдельным проходом после тонмапа, глубина не нужна (и не совпала бы по MSAA)
+        #[cfg(feature = "scene3")]
+        let imgui_target = PipelineTarget::dynamic(app, vk::SampleCountFlags::TYPE_1);
+        let imgui_pipeline = imgui_desc.build(&app.core, imgui_pipeline_layout.layout, &imgui_target)?;
+
 
 
         // 12. Создание текстуры шрифта для ImGui
@@ -347,7 +203,7 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
 
         let mut imgui_uniform_buffers = vec![];
         // Создание uniform буферов для каждого кадра
-        for _ in 0..app.image_count {
+        for _ in 0..app.frames_in_flight {
             let buf = VulkanBuffer::try_new(
                 &app.core,
                 std::mem::size_of::<ImGUIUniform>() as vk::DeviceSize,
@@ -364,7 +220,7 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
         let index_buffer_size = (MAX_INDICES * std::mem::size_of::<imgui::DrawIdx>()) as u64;
 
         let mut vertex_vec = vec![];
-        for _ in 0..app.image_count {
+        for _ in 0..app.frames_in_flight {
             let buf = VulkanBuffer::try_new(
                 &app.core,
                 vertex_buffer_size,
@@ -376,7 +232,7 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
         }
 
         let mut index_vec = vec![];
-        for _ in 0..app.image_count {
+        for _ in 0..app.frames_in_flight {
             let buf = VulkanBuffer::try_new(
                 &app.core,
                 index_buffer_size,
@@ -389,7 +245,7 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
 
         let mut imgui_descriptor_sets = vec![];
         // 15. Создание descriptor sets для ImGui
-        for _ in 0..app.image_count {
+        for _ in 0..app.frames_in_flight {
             let descriptor_set = app.descriptor_pool.allocate_descriptor_sets(
                 imgui_descriptor_set_layout.as_slice()
             )?[0].clone();
@@ -398,7 +254,7 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
         }
 
         // Обновление descriptor sets для ImGui
-        for i in 0..app.image_count as usize {
+        for i in 0..app.frames_in_flight as usize {
             // Обновление uniform буфера
             let (mut write_uniform, buf_info) = imgui_descriptor_sets[i].write_buffer(
                 1,
@@ -432,12 +288,10 @@ impl <'a, R: ImguiResources + Default> InitObject<InitFrameResources<'a>> for Vu
             );
         }
 
-        let upload_cmd = resources.upload_cmd.as_ref().ok_or("CMD Imgui is not initialized")?;
-        let fence = resources.fence.as_ref().ok_or("FENCE Imgui is not initialized")?;
-        font_image.upload_from_slice(app, upload_cmd, fence, atlas.data, None)?;
+        font_image.upload_from_slice(app, atlas.data)?;
 
         // TODO: None
-        let vec_cmd_secondary_imgui = app.command_pool.allocate_command_buffers(app.image_count, vk::CommandBufferLevel::SECONDARY)?;
+        let vec_cmd_secondary_imgui = app.command_pool.allocate_command_buffers(app.frames_in_flight, vk::CommandBufferLevel::SECONDARY)?;
 
         Ok(Self {
             context: imgui,
@@ -467,6 +321,9 @@ impl <'a, R: ImguiResources + Default> RenderObject<RenderFrameResources<'a>> fo
         // Подготовка uniform данных для ImGui
         let io = self.context.io_mut();
         app.window.update_imgui_io(io);
+        // рисуем в картинку свапчейна, пока окно тянут она может отставать от его размера
+        let extent = app.swapchain.extent;
+        io.display_size = [extent.width as f32, extent.height as f32];
         let frame_index = app.frame_index as usize;
 
         let w;
@@ -524,7 +381,7 @@ impl <'a, R: ImguiResources + Default> RenderObject<RenderFrameResources<'a>> fo
 
             let mut inheritance_rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
                 .color_attachment_formats(color_format.as_slice())
-                .depth_attachment_format(app.swapchain.depth_format)
+                .depth_attachment_format(vk::Format::UNDEFINED)
                 .stencil_attachment_format(vk::Format::UNDEFINED)
                 .rasterization_samples(vk::SampleCountFlags::TYPE_1)
                 .flags(vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS);
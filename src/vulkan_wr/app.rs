@@ -9,28 +9,53 @@
 
 use ash::{vk};
 use super::core::{VulkanCore, VulkanCoreBuilder};
-use super::swapchain::{VulkanSwapchain, VulkanSwapchainBuilder};
+use super::swapchain::{VulkanSwapchain, VulkanSwapchainBuilder, SwapchainState};
 use crate::window::Window;
 use super::command_pb::command_pool::VulkanCommandPool;
 use super::descriptor::descriptor_pool::VulaknDescriptorPool;
+use super::present::FrameLimiter;
+use super::upload::VulkanUploader;
+use super::assets::AssetLoader;
+use super::hot_reload::ShaderHotReload;
 
 pub type AppVkResult<T> = Result<T, &'static str>;
 
+/// Сколько кадров CPU может записать, пока GPU рисует прошлые.
+/// Все per-frame ресурсы (UBO, сеты, cmd buf) режутся по нему, а не по картинкам свапчейна:
+/// картинок может стать больше или меньше после recreate_swapchain, кадров в полете - нет
+pub const MAX_FRAMES_IN_FLIGHT: u32 = 2;
+
 // Трейт для использования шаблонов в типах ресурсов
 pub trait SceneResources {
     type ReturnType: SceneResources;
     fn get_frame_resources(app: &VulkanApp, image_count: u32) -> AppVkResult<Self::ReturnType>;
     fn init_framebuffer(&mut self, app: &VulkanApp) -> AppVkResult<()>;
+
+    /// Свапчейн пересоздан: пересобрать все, что зависит от его картинок и размера.
+    /// Старый свапчейн отдается сюда, его картинки могут быть еще в кадрах в полете.
+    /// По умолчанию ждет весь device, сцены с планировщиком кадров ждут меньше
+    fn on_swapchain_recreated(&mut self, app: &VulkanApp, old_swapchain: VulkanSwapchain) -> AppVkResult<()> {
+        app.device_wait_idle()?;
+        drop(old_swapchain);
+        self.init_framebuffer(app)
+    }
 }
 
 pub struct VulkanApp {
-    pub frame_index: u32,
+    pub frame_index: u32,  // слот кадра в полете, 0..frames_in_flight, ставит планировщик сцены
+    pub frames_in_flight: u32,  // фиксирован на всю жизнь приложения
     pub command_pool: VulkanCommandPool,
     pub descriptor_pool: VulaknDescriptorPool,
+    pub uploader: VulkanUploader,  // текстуры и меши через transfer очередь
+    pub assets: AssetLoader,  // разбор моделей и картинок в фоне
+    pub shader_reload: ShaderHotReload,  // только debug, см. hot_reload
     pub swapchain: VulkanSwapchain,
     pub core: VulkanCore,
     pub window: Window,
-    pub image_count: u32,
+    pub image_count: u32,  // картинки свапчейна, per-image ресурсы. Меняется при recreate_swapchain
+    swapchain_dirty: bool,  // свапчейн надо пересоздать до следующего acquire
+    present_mode: vk::PresentModeKHR,  // запрошенный, уходит в следующий recreate_swapchain
+    pub frame_limiter: FrameLimiter,
 }
 
 impl VulkanApp {
@@ -39,8 +64,10 @@ impl VulkanApp {
             .api_version(1, 4, 0)
             .enable_validation(cfg!(debug_assertions))
             .build(&window)?;
+        let present_mode = vk::PresentModeKHR::FIFO;
         let vk_swapchain = VulkanSwapchainBuilder::new(&vk_core)
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .present_mode(present_mode)
             .build()?;
 
 
@@ -51,32 +78,37 @@ impl VulkanApp {
             Some(&vk_core._instance)
         )?;
 
+        let uploader = VulkanUploader::try_new(&vk_core)?;
+
         let image_count = vk_swapchain.images.len() as u32;
+        // пул живет все приложение, а картинок после пересоздания может стать больше,
+        // поэтому считаем по кадрам в полете с запасом, а не по текущему свапчейну
+        let pool_scale = MAX_FRAMES_IN_FLIGHT.max(image_count) + 1;
 
         let pool_size = vec![
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: image_count * 20
+                descriptor_count: pool_scale * 20
             },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: image_count * 200
+                descriptor_count: pool_scale * 200
             },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: image_count * 200
+                descriptor_count: pool_scale * 200
             },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                descriptor_count: image_count * 200
+                descriptor_count: pool_scale * 200
             },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::STORAGE_BUFFER,
-                descriptor_count: image_count * 200
+                descriptor_count: pool_scale * 200
             },
         ];
 
-        let max_sets = image_count * 200;
+        let max_sets = pool_scale * 200;
         let dsc_pool = VulaknDescriptorPool::try_new(
             &vk_core._logical_device,
             &pool_size, 
@@ -88,9 +120,16 @@ impl VulkanApp {
             swapchain: vk_swapchain,
             command_pool: cmd_pool,
             descriptor_pool: dsc_pool,
+            uploader: uploader,
+            assets: AssetLoader::new(None),
+            shader_reload: ShaderHotReload::new(),
             frame_index: 0,
+            frames_in_flight: MAX_FRAMES_IN_FLIGHT,
             window: window,
             image_count: image_count,
+            swapchain_dirty: false,
+            present_mode: present_mode,
+            frame_limiter: FrameLimiter::new(),
         })
     }
 
@@ -100,7 +139,7 @@ impl VulkanApp {
     // - descriptor sets/layouts
     // - framebuffers (по одному на swapchain image)
     // - vertex/uniform buffers
-    // - загрузить текстуры (через staging + fence)
+    // - загрузить текстуры и меши (app.uploader, transfer очередь)
     pub fn init<R: SceneResources>(
         &mut self,
         init: fn(app: &mut VulkanApp, resources: &mut R) -> AppVkResult<()>,
@@ -115,6 +154,8 @@ impl VulkanApp {
             resources: &mut R
         ) -> AppVkResult<()> {
         self.window.process_events();
+        self.assets.poll();
+        self.shader_reload.poll();
         update(self, resources)
     }
 
@@ -125,7 +166,8 @@ impl VulkanApp {
         ) -> AppVkResult<()>,
         resources: &mut R
     ) -> Result<(), &'static str> {
-        self.frame_index = (self.frame_index + 1) % self.image_count;
+        // frame_index тут не крутим: пропущенный кадр (свернутое окно) слот не тратит,
+        // сцена берет его у своего FrameScheduler
         present(self, resources)
     }
 
@@ -149,6 +191,10 @@ impl VulkanApp {
         self.image_count as u32
     }
 
+    pub fn get_frames_in_flight(&self) -> u32 {
+        self.frames_in_flight
+    }
+
     pub fn device_wait_idle(&self) -> Result<(), &'static str>{
         unsafe {
             self.core._logical_device.device_wait_idle().map_err(|_| "Err device_wait_idle")
@@ -159,53 +205,93 @@ impl VulkanApp {
         self.window.should_close()
     }
 
-    // #[cfg(any(feature = "scene1", feature = "scene2"))]
-    pub fn recreate_swapchain(&mut self) -> AppVkResult<()> {
-        self.device_wait_idle()?;
+    /// Берет картинку под кадр. Пересоздает свапчейн, если окно меняло размер или acquire сказал OutOfDate,
+    /// и сразу пробует еще раз - кадр при ресайзе не теряется.
+    /// None: рисовать некуда (свернутое окно), image_available не сигналился
+    /// # Args
+    /// * `image_available` - сигналится когда картинка готова
+    pub fn acquire_image<R: SceneResources>(&mut self, resources: &mut R, image_available: vk::Semaphore) -> AppVkResult<Option<u32>> {
+        if self.window.take_resized() {
+            self.swapchain_dirty = true;
+        }
+        if self.swapchain_dirty && !self.recreate_swapchain(resources)? {
+            return Ok(None);
+        }
+        for _ in 0..2 {
+            match self.swapchain.acquire_next_image(Some(image_available), None)? {
+                (index, SwapchainState::Optimal) => return Ok(Some(index)),
+                (index, SwapchainState::Suboptimal) => {
+                    // семафор уже сигналится, дорисуем и пересоздадим после present
+                    self.swapchain_dirty = true;
+                    return Ok(Some(index));
+                },
+                (_, SwapchainState::OutOfDate) => {
+                    if !self.recreate_swapchain(resources)? {
+                        return Ok(None);
+                    }
+                },
+            }
+        }
+        // новый свапчейн тоже протух, окно еще тянут. Попробуем в следующем кадре
+        self.swapchain_dirty = true;
+        Ok(None)
+    }
+
+    /// Показывает картинку, если свапчейн устарел - пересоздает его сразу, до следующего acquire
+    /// # Args
+    /// * `wait` - семафор конца рендера этой картинки
+    pub fn present_image<R: SceneResources>(&mut self, resources: &mut R, image_index: u32, wait: vk::Semaphore) -> AppVkResult<()> {
+        let present_info = vk::PresentInfoKHR {
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &wait,
+            swapchain_count: 1,
+            p_swapchains: &self.swapchain.swapchain,
+            p_image_indices: &image_index,
+            ..Default::default()
+        };
+        let state = self.swapchain.queue_present(self.core._graphics_queue, &present_info)?;
+        if state != SwapchainState::Optimal || self.window.take_resized() {
+            self.swapchain_dirty = true;
+        }
+        if self.swapchain_dirty {
+            self.recreate_swapchain(resources)?;
+        }
+        Ok(())
+    }
 
+    /// Единая точка пересоздания свапчейна: новый свапчейн из старого, потом сцена пересобирает все,
+    /// что от него зависит (SceneResources::on_swapchain_recreated). Device целиком не ждем.
+    /// false - окно свернуто, пересоздание отложено до следующего кадра
+    pub fn recreate_swapchain<R: SceneResources>(&mut self, resources: &mut R) -> AppVkResult<bool> {
         let (width, height) = self.window.get_width_height();
         if width == 0 || height == 0 {
             // Минимизированное окно, пропускаем пересоздание
-            return Ok(());
+            self.swapchain_dirty = true;
+            return Ok(false);
         }
-        
-        // // Уничтожаем старые ресурсы
-        // self.swapchain.destroy_framebuffers();
-        // self.swapchain.destroy_images();
-        
-        // Пересоздаем swapchain
+
         let vk_swapchain = VulkanSwapchainBuilder::new(&self.core)
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
             .extent(vk::Extent2D { width, height })
+            .present_mode(self.present_mode)
             .old_swapchain(self.swapchain.swapchain)
             .build()?;
-            
-        // std::mem::swap(&mut self.swapchain, &mut vk_swapchain);
-        self.swapchain = vk_swapchain;
+
+        let old_swapchain = std::mem::replace(&mut self.swapchain, vk_swapchain);
         self.image_count = self.swapchain.images.len() as u32;
-        // Обновляем extent в resources
-        Ok(())
+        self.swapchain_dirty = false;
+        resources.on_swapchain_recreated(self, old_swapchain)?;
+        Ok(true)
     }
 
-    // #[cfg(feature = "scene3")]
-    // pub fn recreate_swapchain(&mut self) -> AppVkResult<()> {
-    //     self.device_wait_idle()?;
-    //     let (width, height) = self.window.get_width_height();
-    //     if width == 0 || height == 0 {
-    //         return Ok(());
-    //     }
-        
-    //     // Пересоздаем swapchain
-    //     let vk_swapchain = VulkanSwapchainBuilder::new(&self.core)
-    //         .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
-    //         .extent(vk::Extent2D { width, height })
-    //         .old_swapchain(self.swapchain.swapchain)
-    //         .build()?;
-        
-    //     self.swapchain = vk_swapchain;
-    //     self.image_count = self.swapchain.images.len() as u32;
-    //     Ok(())
-    // }
+    /// Present mode меняется только пересозданием свапчейна, оно случится на следующем acquire.
+    /// Неподдерживаемый режим свапчейн сам заменит на FIFO
+    pub fn set_present_mode(&mut self, mode: vk::PresentModeKHR) {
+        if mode != self.present_mode {
+            self.present_mode = mode;
+            self.swapchain_dirty = true;
+        }
+    }
 
     pub fn get_swapchain_extent(&self) -> vk::Extent2D {
         self.swapchain.extent
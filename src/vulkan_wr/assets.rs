@@ -0,0 +1,276 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: фоновая загрузка ассетов: разбор OBJ/glTF и разжатие картинок в потоках,
+// заливка на GPU кусками по кадрам
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+
+use super::{
+    app::VulkanApp,
+    texture::DecodedImage,
+    types::model::{MeshGPU, Model, Transform},
+    upload::UploadTicket,
+};
+
+/// Больше потоков упирается в диск, а не в разжатие
+const MAX_LOAD_WORKERS: usize = 4;
+
+/// Сколько мешей модели заливать за кадр. Меш со всеми текстурами - это десятки мегабайт staging
+const MESHES_PER_FRAME: usize = 1;
+
+pub type AssetId = u64;
+
+/// Каталог ассетов (obj_3d, texture) в рантайме: рядом с бинарником, иначе в текущем каталоге.
+/// Сборка их не копирует, cargo run из корня репозитория находит их через cwd.
+/// Нет нигде - путь рядом с бинарником, чего не хватает напишет уже загрузчик
+pub fn asset_dir(name: &str) -> Result<PathBuf, &'static str> {
+    let exe_path = std::env::current_exe()
+        .map_err(|_| "Failed to get current executable path")?;
+    let exe_dir = exe_path
+        .parent()
+        .ok_or("Executable is in the root directory?")?
+        .join(name);
+    if exe_dir.is_dir() {
+        return Ok(exe_dir);
+    }
+    match std::env::current_dir() {
+        Ok(cwd) if cwd.join(name).is_dir() => Ok(cwd.join(name)),
+        _ => Ok(exe_dir),
+    }
+}
+
+/// Все, что можно сделать без GPU
+pub struct LoadedModel {
+    pub model: Model,
+    pub images: HashMap<String, DecodedImage>,
+}
+
+struct LoadJob {
+    id: AssetId,
+    path: String,
+}
+
+type LoadResult = (AssetId, Result<LoadedModel, &'static str>);
+
+/// Счетчики для UI
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AssetProgress {
+    pub requested: u32,
+    pub decoded: u32,  // разобраны на CPU
+    pub uploaded: u32,  // на GPU и уже рисуются
+    pub failed: u32,
+}
+
+impl AssetProgress {
+    pub fn is_busy(&self) -> bool {
+        self.uploaded + self.failed < self.requested
+    }
+
+    pub fn render_ui(&self, ui: &imgui::Ui) {
+        if self.requested == 0 {
+            return;
+        }
+        ui.separator();
+        ui.text("Assets:");
+        // половина пути - разбор на CPU, вторая - заливка
+        let fraction = (self.decoded + self.uploaded + self.failed * 2) as f32 / (self.requested * 2) as f32;
+        imgui::ProgressBar::new(fraction)
+            .overlay_text(format!("decoded {}/{}, on GPU {}/{}", self.decoded, self.requested, self.uploaded, self.requested))
+            .build(ui);
+        if self.failed > 0 {
+            ui.text(format!("failed: {}", self.failed));
+        }
+    }
+}
+
+/// Пул потоков, которые читают и разбирают файлы. GPU не трогают:
+/// результат забирает главный поток через take и сам заливает, см. StreamedModel
+pub struct AssetLoader {
+    jobs: Option<mpsc::Sender<LoadJob>>,  // None - потоки останавливаются
+    results: mpsc::Receiver<LoadResult>,
+    workers: Vec<JoinHandle<()>>,
+    ready: HashMap<AssetId, Result<LoadedModel, &'static str>>,
+    next_id: AssetId,
+    progress: AssetProgress,
+}
+
+impl AssetLoader {
+    /// # Args
+    /// * `workers` - сколько потоков разбирает файлы, None - по числу ядер, но не больше MAX_LOAD_WORKERS
+    pub fn new(workers: Option<usize>) -> Self {
+        let workers = workers
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1)))
+            .clamp(1, MAX_LOAD_WORKERS);
+        let (jobs_tx, jobs_rx) = mpsc::channel::<LoadJob>();
+        let (results_tx, results_rx) = mpsc::channel::<LoadResult>();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+
+        let workers = (0..workers).map(|_| {
+            let jobs = Arc::clone(&jobs_rx);
+            let results = results_tx.clone();
+            std::thread::spawn(move || loop {
+                // lock только на время recv, разбор идет без него
+                let job = match jobs.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let loaded = Model::load(&job.path).map(|model| {
+                    let images = model.decode_textures();
+                    LoadedModel { model, images }
+                });
+                if results.send((job.id, loaded)).is_err() {
+                    break;
+                }
+            })
+        }).collect();
+
+        Self {
+            jobs: Some(jobs_tx),
+            results: results_rx,
+            workers,
+            ready: HashMap::new(),
+            next_id: 0,
+            progress: AssetProgress::default(),
+        }
+    }
+
+    /// Ставит модель в очередь, не блокирует
+    pub fn request_model(&mut self, path: &str) -> Result<AssetId, &'static str> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.as_ref()
+            .ok_or("Asset loader is stopped")?
+            .send(LoadJob { id, path: path.to_string() })
+            .map_err(|_| "Asset loader threads are gone")?;
+        self.progress.requested += 1;
+        Ok(id)
+    }
+
+    /// Забирает готовое из потоков. Звать раз за кадр
+    pub fn poll(&mut self) {
+        while let Ok((id, result)) = self.results.try_recv() {
+            match &result {
+                Ok(_) => self.progress.decoded += 1,
+                Err(err) => {
+                    println!("asset {}: {}", id, err);
+                    self.progress.failed += 1;
+                }
+            }
+            self.ready.insert(id, result);
+        }
+    }
+
+    /// Разобранная модель, если уже готова. Отдается один раз
+    pub fn take(&mut self, id: AssetId) -> Option<Result<LoadedModel, &'static str>> {
+        self.ready.remove(&id)
+    }
+
+    /// Модель залита и рисуется
+    pub fn mark_uploaded(&mut self) {
+        self.progress.uploaded += 1;
+    }
+
+    pub fn progress(&self) -> AssetProgress {
+        self.progress
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // закрытый канал - сигнал потокам выйти после текущей работы
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                println!("Asset loader thread panicked");
+            }
+        }
+    }
+}
+
+enum StreamState {
+    Decoding,
+    Uploading {
+        loaded: LoadedModel,
+        meshes: Vec<MeshGPU>,
+        last_ticket: Option<UploadTicket>,
+    },
+    Done,
+}
+
+/// Модель, которая приходит в фоне. Пока ее нет, объект рисует заглушку на ее месте
+pub struct StreamedModel {
+    id: AssetId,
+    transform: Transform,  // перекрывает трансформ из файла, как в синхронной загрузке
+    state: StreamState,
+}
+
+impl StreamedModel {
+    pub fn request(app: &mut VulkanApp, path: &str, transform: Transform) -> Result<Self, &'static str> {
+        Ok(Self {
+            id: app.assets.request_model(path)?,
+            transform,
+            state: StreamState::Decoding,
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, StreamState::Done)
+    }
+
+    /// Шаг за кадр: заливает до MESHES_PER_FRAME мешей, не ждет GPU.
+    /// Some - все меши на GPU, их можно ставить вместо заглушки. Если файл не загрузился,
+    /// заглушка остается навсегда, ошибка только в лог
+    pub fn step(
+        &mut self,
+        app: &mut VulkanApp,
+    ) -> Result<Option<Vec<MeshGPU>>, &'static str> {
+        if let StreamState::Decoding = self.state {
+            match app.assets.take(self.id) {
+                None => return Ok(None),
+                Some(Err(_)) => {
+                    self.state = StreamState::Done;
+                    return Ok(None);
+                }
+                Some(Ok(mut loaded)) => {
+                    loaded.model.transform = self.transform;
+                    self.state = StreamState::Uploading { loaded, meshes: vec![], last_ticket: None };
+                }
+            }
+        }
+
+        let StreamState::Uploading { loaded, meshes, last_ticket } = &mut self.state else {
+            return Ok(None);
+        };
+        let total = loaded.model.meshes.len();
+        for _ in 0..MESHES_PER_FRAME {
+            if meshes.len() == total {
+                break;
+            }
+            let (mesh, ticket) = loaded.model.mesh_to_gpu(app, meshes.len(), &loaded.images)?;
+            meshes.push(mesh);
+            *last_ticket = Some(ticket);
+        }
+        if meshes.len() < total {
+            return Ok(None);
+        }
+        // тикеты завершаются по порядку, последний покрывает все меши
+        if let Some(ticket) = *last_ticket {
+            if !app.uploader.is_done(ticket)? {
+                return Ok(None);
+            }
+        }
+        app.uploader.collect()?;
+
+        let StreamState::Uploading { meshes, .. } = std::mem::replace(&mut self.state, StreamState::Done) else {
+            return Ok(None);
+        };
+        app.assets.mark_uploaded();
+        Ok(Some(meshes))
+    }
+}
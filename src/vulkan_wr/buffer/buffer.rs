@@ -73,6 +73,30 @@ impl VulkanBuffer {
         Ok(Self { buffer, memory, size, device: core._logical_device.clone() })
     }
 
+    /// Storage buffer для compute. TRANSFER_DST всегда, чтобы можно было fill_buffer/copy в него
+    /// # Аргументы
+    /// * `extra_usage` - например INDIRECT_BUFFER для dispatch_indirect или VERTEX_BUFFER для частиц
+    /// * `readback` - HOST_VISIBLE | HOST_COHERENT, чтобы читать результат через mem_read, иначе DEVICE_LOCAL
+    pub fn try_new_storage(
+            core: &VulkanCore,
+            size: vk::DeviceSize,
+            extra_usage: vk::BufferUsageFlags,
+            readback: bool,
+        ) -> Result<Self, &'static str> {
+        let props = if readback {
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+        } else {
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+        };
+        Self::try_new(
+            core,
+            size,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST | extra_usage,
+            props,
+            None, None, None, None
+        )
+    }
+
     /// Копирует данные CPU -> GPU через map/unmap.
     ///
     /// # Аргументы
@@ -138,6 +162,31 @@ impl VulkanBuffer {
         Ok(())
     }
 
+    /// Копирует GPU -> CPU через map/unmap, обратное mem_copy.
+    ///
+    /// # Безопасность
+    /// Память должна быть HOST_VISIBLE | HOST_COHERENT, а запись GPU уже закончена
+    /// и сделана видимой хосту (барьер в HOST + ожидание fence/timeline)
+    pub unsafe fn mem_read<T: Copy>(&self, data: &mut [T], offset: Option<vk::DeviceSize>) -> Result<(), &'static str> {
+        if data.is_empty() {
+            return Err("Data slice is empty");
+        }
+        let data_size = (data.len() * size_of::<T>()) as vk::DeviceSize;
+        let offset = offset.unwrap_or(0);
+        if offset + data_size > self.size {
+            return Err("Read past the end of buffer");
+        }
+
+        unsafe {
+            let ptr = self.device.map_memory(self.memory, offset, data_size, vk::MemoryMapFlags::empty())
+                .map_err(|_| "map_memory failed")?;
+            std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr() as *mut u8, data_size as usize);
+            self.device.unmap_memory(self.memory);
+        }
+
+        Ok(())
+    }
+
 }
 
 impl Drop for VulkanBuffer {
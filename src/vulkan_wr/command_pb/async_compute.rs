@@ -0,0 +1,147 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: compute работа на своей очереди параллельно с графикой, синхронизация через timeline
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::command_pool::VulkanCommandPool;
+use super::command_buffer::VulkanCommandBuffer;
+use super::super::{app::VulkanApp, core::VulkanCore, sync::{timeline::VulkanTimelineSemaphore, barrier::{SyncScope, image_barrier2}}};
+
+/// Один сабмит compute на слот кадра. Графика ждет его через wait_info, а не на CPU.
+/// Без отдельного семейства работает так же, просто на графической очереди.
+/// Ресурсы, которые пишет compute и читает графика, либо CONCURRENT,
+/// либо release/acquire в record (семейства - compute_family / graphics_family)
+pub struct AsyncCompute {
+    pools: Vec<VulkanCommandPool>,  // per-frame, сбрасываются целиком
+    cmds: Vec<VulkanCommandBuffer>,
+    timeline: VulkanTimelineSemaphore,
+    submitted: u64,
+    frame_values: Vec<u64>,  // что последним сигналил слот, до этого пул трогать нельзя
+    compute_family: u32,
+    graphics_family: u32,
+}
+
+impl AsyncCompute {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let core = &app.core;
+        let mut pools = Vec::with_capacity(app.frames_in_flight as usize);
+        let mut cmds = Vec::with_capacity(app.frames_in_flight as usize);
+        for _ in 0..app.frames_in_flight {
+            let pool = VulkanCommandPool::try_new(
+                &core._logical_device,
+                core._compute_queue_index,
+                vk::CommandPoolCreateFlags::TRANSIENT,
+                None
+            )?;
+            cmds.push(pool.allocate_command_buffers(1, vk::CommandBufferLevel::PRIMARY)?.remove(0));
+            pools.push(pool);
+        }
+        Ok(Self {
+            pools,
+            cmds,
+            timeline: VulkanTimelineSemaphore::try_new(&core._logical_device, 0)?,
+            submitted: 0,
+            frame_values: vec![0; app.frames_in_flight as usize],
+            compute_family: core._compute_queue_index,
+            graphics_family: core._graphics_queue_index,
+        })
+    }
+
+    /// Идет ли compute правда параллельно с графикой
+    pub fn is_async(&self) -> bool {
+        self.compute_family != self.graphics_family
+    }
+
+    pub fn compute_family(&self) -> u32 {
+        self.compute_family
+    }
+
+    pub fn graphics_family(&self) -> u32 {
+        self.graphics_family
+    }
+
+    /// Пишет и отправляет compute работу слота кадра.
+    /// Возвращает значение timeline, его ждет графика через wait_info
+    /// # Args
+    /// * `waits` - что ждать до старта (например, прошлый кадр графики, если compute читает его результат)
+    /// * `record` - сам пишет dispatch и барьеры, begin/end делаются тут
+    pub fn submit<F>(&mut self, core: &VulkanCore, frame: usize, waits: &[vk::SemaphoreSubmitInfo<'_>], record: F) -> Result<u64, &'static str>
+        where F: FnOnce(&VulkanCommandBuffer) -> Result<(), &'static str>
+    {
+        // обычно уже готово: кадр графики этого слота ждал этот compute
+        self.timeline.wait_forever(self.frame_values[frame], "async compute")?;
+        self.pools[frame].reset()?;
+        let cmd = &self.cmds[frame];
+        cmd.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, None)?;
+        record(cmd)?;
+        cmd.end()?;
+
+        let value = self.submitted + 1;
+        let cmd_infos = [vk::CommandBufferSubmitInfo::default().command_buffer(cmd._buffer)];
+        let signal = [self.timeline.submit_info(value, vk::PipelineStageFlags2::COMPUTE_SHADER)];
+        let submit = vk::SubmitInfo2::default()
+            .wait_semaphore_infos(waits)
+            .command_buffer_infos(&cmd_infos)
+            .signal_semaphore_infos(&signal);
+        core.queue_submit2_compute(&[submit], vk::Fence::null())?;
+
+        self.submitted = value;
+        self.frame_values[frame] = value;
+        Ok(value)
+    }
+
+    /// Ждет compute слота кадра на CPU, перед чтением того что он насчитал
+    pub fn wait_frame(&self, frame: usize) -> Result<(), &'static str> {
+        self.timeline.wait_forever(self.frame_values[frame], "async compute")
+    }
+
+    /// Передача владения картинкой между графикой и compute, layout не меняется.
+    /// Один и тот же барьер пишется дважды: release на отдающей очереди (dst = NONE)
+    /// и acquire на принимающей (src = стадия ожидания семафора). None если семейство одно
+    /// # Args
+    /// * `to_compute` - графика отдает compute, иначе обратно
+    pub fn ownership_barrier(
+        &self,
+        image: vk::Image,
+        range: vk::ImageSubresourceRange,
+        layout: vk::ImageLayout,
+        to_compute: bool,
+        src: SyncScope,
+        dst: SyncScope,
+    ) -> Option<vk::ImageMemoryBarrier2<'static>> {
+        if !self.is_async() {
+            return None;
+        }
+        let (from, to) = if to_compute {
+            (self.graphics_family, self.compute_family)
+        } else {
+            (self.compute_family, self.graphics_family)
+        };
+        let mut barrier = image_barrier2(image, range, src, dst, layout, layout);
+        barrier.src_queue_family_index = from;
+        barrier.dst_queue_family_index = to;
+        Some(barrier)
+    }
+
+    /// Для SubmitInfo2 графики: дождаться compute `value` перед стадией stage
+    pub fn wait_info(&self, value: u64, stage: vk::PipelineStageFlags2) -> vk::SemaphoreSubmitInfo<'static> {
+        self.timeline.submit_info(value, stage)
+    }
+
+    /// Ждет весь отправленный compute, перед удалением того, что он трогает
+    pub fn wait_idle(&self) -> Result<(), &'static str> {
+        self.timeline.wait_forever(self.submitted, "async compute")
+    }
+}
+
+impl Drop for AsyncCompute {
+    fn drop(&mut self) {
+        if self.wait_idle().is_err() {
+            println!("Something went wrong with the compute queue wait");
+        }
+    }
+}
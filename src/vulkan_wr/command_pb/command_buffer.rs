@@ -127,6 +127,19 @@ impl VulkanCommandBuffer {
         }
     }
 
+    /// Рисование без индексного буфера. Для fullscreen треугольника хватает draw(3, 1, 0, 0)
+    pub unsafe fn draw(&self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
+        unsafe {
+            self._device.cmd_draw(
+                self._buffer,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance
+            )
+        }
+    }
+
     pub unsafe fn begin_render_pass(&self, render_pass_begin: &vk::RenderPassBeginInfo<'_>, contents: vk::SubpassContents) {
         unsafe {
             self._device.cmd_begin_render_pass(
@@ -169,6 +182,37 @@ impl VulkanCommandBuffer {
         }
     }
 
+    /// Барьер synchronization2: стадии и доступы лежат в самих барьерах, а не общие на вызов
+    pub unsafe fn pipeline_barrier2(&self, dependency_info: &vk::DependencyInfo<'_>) {
+        unsafe {
+            self._device.cmd_pipeline_barrier2(self._buffer, dependency_info);
+        }
+    }
+
+    /// pipeline_barrier2 только с барьерами картинок. Пустой список ничего не пишет
+    pub unsafe fn image_barriers2(&self, barriers: &[vk::ImageMemoryBarrier2<'_>]) {
+        if barriers.is_empty() {
+            return;
+        }
+        unsafe { self.pipeline_barrier2(&vk::DependencyInfo::default().image_memory_barriers(barriers)); }
+    }
+
+    /// pipeline_barrier2 только с барьерами буферов
+    pub unsafe fn buffer_barriers2(&self, barriers: &[vk::BufferMemoryBarrier2<'_>]) {
+        if barriers.is_empty() {
+            return;
+        }
+        unsafe { self.pipeline_barrier2(&vk::DependencyInfo::default().buffer_memory_barriers(barriers)); }
+    }
+
+    /// pipeline_barrier2 только с глобальными барьерами памяти
+    pub unsafe fn memory_barriers2(&self, barriers: &[vk::MemoryBarrier2<'_>]) {
+        if barriers.is_empty() {
+            return;
+        }
+        unsafe { self.pipeline_barrier2(&vk::DependencyInfo::default().memory_barriers(barriers)); }
+    }
+
     pub unsafe fn copy_buffer_to_image(&self, src_buffer: vk::Buffer, dst_image: vk::Image,
         dst_image_layout: vk::ImageLayout, regions: &[vk::BufferImageCopy]
     ) {
@@ -179,6 +223,31 @@ impl VulkanCommandBuffer {
         }
     }
 
+    pub unsafe fn copy_buffer(&self, src_buffer: vk::Buffer, dst_buffer: vk::Buffer, regions: &[vk::BufferCopy]) {
+        unsafe {
+            self._device.cmd_copy_buffer(self._buffer, src_buffer, dst_buffer, regions);
+        }
+    }
+    /// Число групп, не потоков. Сколько групп на картинку - см. compute::group_count
+    pub unsafe fn dispatch(&self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        unsafe {
+            self._device.cmd_dispatch(self._buffer, group_count_x, group_count_y, group_count_z);
+        }
+    }
+    /// Группы берутся из vk::DispatchIndirectCommand в buffer (нужен INDIRECT_BUFFER),
+    /// так GPU culling сам решает сколько работы дальше
+    pub unsafe fn dispatch_indirect(&self, buffer: vk::Buffer, offset: vk::DeviceSize) {
+        unsafe {
+            self._device.cmd_dispatch_indirect(self._buffer, buffer, offset);
+        }
+    }
+    /// Заливает буфер одним u32 (обычно обнулить счетчики перед compute), это TRANSFER запись
+    pub unsafe fn fill_buffer(&self, buffer: vk::Buffer, offset: vk::DeviceSize, size: vk::DeviceSize, data: u32) {
+        unsafe {
+            self._device.cmd_fill_buffer(self._buffer, buffer, offset, size, data);
+        }
+    }
+
     pub fn begin_dynamic_rendering(&self, rendering_info: &vk::RenderingInfo<'_>) -> Result<(), &'static str> {
         let dev_ext = self._dynamic_rendering.as_ref().ok_or("")?;
         unsafe {
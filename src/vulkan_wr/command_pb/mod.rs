@@ -1,3 +1,5 @@
 
 pub mod command_pool;
 pub mod command_buffer;
+pub mod parallel;
+pub mod async_compute;
@@ -0,0 +1,157 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: запись secondary буферов в нескольких потоках, у каждого потока свой пул
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::command_pool::VulkanCommandPool;
+use super::command_buffer::VulkanCommandBuffer;
+use super::super::app::VulkanApp;
+
+/// Больше потоков на запись обычно не окупается, драйвер упирается сам в себя
+const MAX_RECORD_WORKERS: usize = 8;
+
+/// Что рабочему потоку нужно знать о кадре. VulkanApp в поток не отдать - окно не Sync
+#[derive(Clone, Copy, Debug)]
+pub struct RecordContext {
+    pub frame: usize,  // слот кадра в полете
+    pub extent: vk::Extent2D,
+    pub depth_format: vk::Format,
+    pub min_ubo_alignment: u64,
+}
+
+impl RecordContext {
+    pub fn from_app(app: &VulkanApp) -> Self {
+        Self {
+            frame: app.frame_index as usize,
+            extent: app.swapchain.extent,
+            depth_format: app.swapchain.depth_format,
+            min_ubo_alignment: app.get_min_ubo_alignment(),
+        }
+    }
+
+    /// Размер куска dynamic UBO под T с учетом выравнивания девайса
+    pub fn aligned_size<T>(&self) -> u64 {
+        let alignment = self.min_ubo_alignment;
+        ((std::mem::size_of::<T>() as u64 + alignment - 1) / alignment) * alignment
+    }
+}
+
+/// Пул одного потока на один слот кадра. Буферы не освобождаются, после reset пула пишутся заново
+struct WorkerPool {
+    pool: VulkanCommandPool,
+    buffers: Vec<VulkanCommandBuffer>,
+    used: usize,
+}
+
+impl WorkerPool {
+    fn next_buffer(&mut self) -> Result<&VulkanCommandBuffer, &'static str> {
+        if self.used == self.buffers.len() {
+            let mut more = self.pool.allocate_command_buffers(1, vk::CommandBufferLevel::SECONDARY)?;
+            self.buffers.append(&mut more);
+        }
+        self.used += 1;
+        Ok(&self.buffers[self.used - 1])
+    }
+
+    /// Пишет свою пачку работ по порядку
+    fn record_chunk<J, F>(&mut self, jobs: &[J], record: &F) -> Result<Vec<vk::CommandBuffer>, &'static str>
+        where F: Fn(&J, &VulkanCommandBuffer) -> Result<(), &'static str>
+    {
+        let mut out = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            let cmd = self.next_buffer()?;
+            record(job, cmd)?;
+            out.push(cmd._buffer);
+        }
+        Ok(out)
+    }
+}
+
+/// Работы режутся на подряд идущие куски по потокам, результат склеивается в порядке работ,
+/// поэтому порядок execute_commands не зависит от того, какой поток успел первым
+pub struct ParallelRecorder {
+    frames: Vec<Vec<WorkerPool>>,  // [слот кадра][поток]
+}
+
+impl ParallelRecorder {
+    /// # Args
+    /// * `workers` - сколько потоков пишет, None - по числу ядер
+    pub fn try_new(app: &VulkanApp, workers: Option<usize>) -> Result<Self, &'static str> {
+        let workers = workers
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+            .clamp(1, MAX_RECORD_WORKERS);
+        let mut frames = Vec::with_capacity(app.frames_in_flight as usize);
+        for _ in 0..app.frames_in_flight {
+            let mut pools = Vec::with_capacity(workers);
+            for _ in 0..workers {
+                pools.push(WorkerPool {
+                    pool: VulkanCommandPool::try_new(
+                        &app.core._logical_device,
+                        app.core._graphics_queue_index,
+                        vk::CommandPoolCreateFlags::TRANSIENT,
+                        Some(&app.core._instance)
+                    )?,
+                    buffers: vec![],
+                    used: 0,
+                });
+            }
+            frames.push(pools);
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn workers(&self) -> usize {
+        self.frames.first().map_or(1, |pools| pools.len())
+    }
+
+    /// Сбрасывает пулы слота. Звать когда GPU отпустил слот (после FrameScheduler::begin_frame)
+    pub fn begin_frame(&mut self, frame: usize) -> Result<(), &'static str> {
+        for worker in self.frames[frame].iter_mut() {
+            worker.pool.reset()?;
+            worker.used = 0;
+        }
+        Ok(())
+    }
+
+    /// Пишет каждую работу в свой secondary буфер. Первый кусок пишет вызывающий поток.
+    /// Возвращает буферы в порядке jobs
+    /// # Args
+    /// * `record` - сам делает begin/end, inheritance у разных работ бывает разный
+    pub fn record<J, F>(&mut self, frame: usize, jobs: &[J], record: F) -> Result<Vec<vk::CommandBuffer>, &'static str>
+        where J: Sync, F: Fn(&J, &VulkanCommandBuffer) -> Result<(), &'static str> + Sync
+    {
+        if jobs.is_empty() {
+            return Ok(vec![]);
+        }
+        let pools = &mut self.frames[frame];
+        let chunk = jobs.len().div_ceil(pools.len());
+        let mut chunks = jobs.chunks(chunk);
+        let mut workers = pools.iter_mut();
+        let (first_jobs, first_worker) = match (chunks.next(), workers.next()) {
+            (Some(jobs), Some(worker)) => (jobs, worker),
+            _ => return Ok(vec![]),
+        };
+        let record = &record;
+
+        let results = std::thread::scope(|scope| {
+            let handles: Vec<_> = workers.zip(chunks)
+                .map(|(worker, jobs)| scope.spawn(move || worker.record_chunk(jobs, record)))
+                .collect();
+            let mut results = vec![first_worker.record_chunk(first_jobs, record)];
+            for handle in handles {
+                results.push(handle.join().unwrap_or(Err("Recording thread panicked")));
+            }
+            results
+        });
+
+        let mut out = Vec::with_capacity(jobs.len());
+        for result in results {
+            out.extend(result?);
+        }
+        Ok(out)
+    }
+}
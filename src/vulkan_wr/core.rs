@@ -11,6 +11,7 @@ use ash::{self, vk, Entry, Instance, Device};
 use ash::khr;
 use ash::ext::debug_utils;
 use crate::window::Window;
+use super::pipeline::pipeline_cache::VulkanPipelineCache;
 use std::ffi::CString;
 
 // так много super, уже нет
@@ -22,7 +23,16 @@ pub struct VulkanCore {
     pub _logical_device: Device,
     pub _graphics_queue: vk::Queue,
     pub _graphics_queue_index: u32,
+    // отдельные семейства, если девайс их дает. Иначе это та же графическая очередь
+    pub _transfer_queue: vk::Queue,
+    pub _transfer_queue_index: u32,
+    pub _compute_queue: vk::Queue,
+    pub _compute_queue_index: u32,
     pub min_uniform_buffer_offset_alignment: u64,
+    pub supported_msaa_samples: vk::SampleCountFlags,  // color & depth, MSAA attachments нужны оба
+    pub supports_bindless: bool,  // descriptor indexing под BindlessTable, без него только сцены на обычных сетах
+    pub max_bindless_textures: u32,  // update-after-bind семплеров и sampled images на стадию и на сет, что меньше
+    pub pipeline_cache: VulkanPipelineCache,  // им пользуется каждый VulkanPipelineBuilder
 
     #[cfg(debug_assertions)]
     _debug_messenger: vk::DebugUtilsMessengerEXT,
@@ -78,6 +88,32 @@ impl VulkanCore {
         }
     }
 
+    /// Сабмит synchronization2: семафоры (и timeline значения) задаются прямо в SemaphoreSubmitInfo
+    pub fn queue_submit2(&self, submits: &[vk::SubmitInfo2<'_>], fence: vk::Fence) -> CoreVkResult<()> {
+        unsafe {
+            self._logical_device.queue_submit2(self._graphics_queue, submits, fence).map_err(|_| "queue_submit2 failed")
+        }
+    }
+
+    /// То же на очередь копирования
+    pub fn queue_submit2_transfer(&self, submits: &[vk::SubmitInfo2<'_>], fence: vk::Fence) -> CoreVkResult<()> {
+        unsafe {
+            self._logical_device.queue_submit2(self._transfer_queue, submits, fence).map_err(|_| "queue_submit2 (transfer) failed")
+        }
+    }
+
+    /// То же на compute очередь
+    pub fn queue_submit2_compute(&self, submits: &[vk::SubmitInfo2<'_>], fence: vk::Fence) -> CoreVkResult<()> {
+        unsafe {
+            self._logical_device.queue_submit2(self._compute_queue, submits, fence).map_err(|_| "queue_submit2 (compute) failed")
+        }
+    }
+
+    /// Копирование идет в другом семействе: ресурсам нужна передача владения (release/acquire)
+    pub fn has_dedicated_transfer(&self) -> bool {
+        self._transfer_queue_index != self._graphics_queue_index
+    }
+
 }
 
 impl Drop for VulkanCore {
@@ -86,6 +122,10 @@ impl Drop for VulkanCore {
             if self._logical_device.device_wait_idle().is_err() {
                 println!("Something went wrong with the logical device wait");
             }
+            if let Err(err) = self.pipeline_cache.save(&self._logical_device) {
+                println!("pipeline cache was not saved: {}", err);
+            }
+            self.pipeline_cache.destroy(&self._logical_device);
             self._logical_device.destroy_device(None);
             let surface_device = khr::surface::Instance::new(&self._entry, &self._instance);
             surface_device.destroy_surface(self._surface, None);
@@ -304,17 +344,57 @@ impl VulkanCoreBuilder {
             surface,
             self.requested_queue_family_flags
         )?;
-
-        // prepare device queue create infos
-        let queue_priorities = if self.requested_queue_priorities.is_empty() { vec![1.0f32] } else { self.requested_queue_priorities };
-        let queue_priorities_ptr = queue_priorities.as_ptr();
-
-        let queue_create_info = vk::DeviceQueueCreateInfo {
-            queue_family_index: q_family_idx,
-            queue_count: queue_priorities.len() as u32,
-            p_queue_priorities: queue_priorities_ptr,
-            ..Default::default()
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+        let supported_msaa_samples = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        let mut indexing_props = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+        {
+            let mut props2 = vk::PhysicalDeviceProperties2::default().push_next(&mut indexing_props);
+            unsafe { instance.get_physical_device_properties2(physical_device, &mut props2) };
+        }
+        // combined image sampler считается и как семплер, и как sampled image - берем минимум всех четырех
+        let max_bindless_textures = indexing_props.max_per_stage_descriptor_update_after_bind_samplers
+            .min(indexing_props.max_per_stage_descriptor_update_after_bind_sampled_images)
+            .min(indexing_props.max_descriptor_set_update_after_bind_samplers)
+            .min(indexing_props.max_descriptor_set_update_after_bind_sampled_images);
+        let supports_bindless = Self::supports_bindless(&instance, physical_device);
+
+        // transfer и compute: ищем семейства без графики, иначе делим графическое
+        let families = unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let transfer_family = Self::pick_dedicated_family(&families, vk::QueueFlags::TRANSFER, vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            .or_else(|| Self::pick_dedicated_family(&families, vk::QueueFlags::TRANSFER, vk::QueueFlags::GRAPHICS))
+            .unwrap_or(q_family_idx);
+        let compute_family = Self::pick_dedicated_family(&families, vk::QueueFlags::COMPUTE, vk::QueueFlags::GRAPHICS)
+            .unwrap_or(q_family_idx);
+
+        // prepare device queue create infos, по одному на семейство
+        let graphics_priorities = if self.requested_queue_priorities.is_empty() { vec![1.0f32] } else { self.requested_queue_priorities };
+        let mut family_priorities: Vec<(u32, Vec<f32>)> = vec![(q_family_idx, graphics_priorities)];
+        // (семейство, индекс очереди в нем). Если очередей в семействе не хватило - делим последнюю
+        let mut queue_slot = |family: u32| -> (u32, u32) {
+            let max = families[family as usize].queue_count as usize;
+            let priorities = match family_priorities.iter().position(|(f, _)| *f == family) {
+                Some(i) => &mut family_priorities[i].1,
+                None => {
+                    family_priorities.push((family, vec![]));
+                    &mut family_priorities.last_mut().unwrap().1
+                }
+            };
+            if priorities.len() < max {
+                priorities.push(1.0);
+            }
+            (family, priorities.len() as u32 - 1)
         };
+        let transfer_slot = if transfer_family == q_family_idx { (q_family_idx, 0) } else { queue_slot(transfer_family) };
+        let compute_slot = if compute_family == q_family_idx { (q_family_idx, 0) } else { queue_slot(compute_family) };
+
+        let queue_create_infos: Vec<vk::DeviceQueueCreateInfo> = family_priorities.iter()
+            .map(|(family, priorities)| vk::DeviceQueueCreateInfo {
+                queue_family_index: *family,
+                queue_count: priorities.len() as u32,
+                p_queue_priorities: priorities.as_ptr(),
+                ..Default::default()
+            })
+            .collect();
 
         // device extensions -> CString -> pointers
         let device_ext_cstrings: Vec<CString> = self.requested_device_extensions
@@ -324,9 +404,29 @@ impl VulkanCoreBuilder {
         let device_ext_ptrs: Vec<*const i8> = device_ext_cstrings.iter().map(|c| c.as_ptr()).collect();
         let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default().dynamic_rendering(true);
 
+        // bindless: общий массив текстур и таблица материалов, см. descriptor::bindless.
+        // Нет поддержки - не включаем, BindlessTable::try_new тогда откажет
+        let bindless = if supports_bindless { vk::TRUE } else { vk::FALSE };
+        let mut descriptor_indexing_features = vk::PhysicalDeviceDescriptorIndexingFeatures {
+            p_next: &mut dynamic_rendering_features as *mut _ as *mut _,
+            shader_sampled_image_array_non_uniform_indexing: bindless,
+            descriptor_binding_sampled_image_update_after_bind: bindless,
+            descriptor_binding_update_unused_while_pending: bindless,
+            descriptor_binding_partially_bound: bindless,
+            runtime_descriptor_array: bindless,
+            ..Default::default()
+        };
+
+        // timeline семафор считает кадры, см. sync::frame_scheduler
+        let mut timeline_semaphore_features = vk::PhysicalDeviceTimelineSemaphoreFeatures {
+            p_next: &mut descriptor_indexing_features as *mut _ as *mut _,
+            timeline_semaphore: vk::TRUE,
+            ..Default::default()
+        };
+
         let mut synchronization2_features = vk::PhysicalDeviceSynchronization2Features {
             s_type: vk::StructureType::PHYSICAL_DEVICE_SYNCHRONIZATION_2_FEATURES,
-            p_next: &mut dynamic_rendering_features as *mut _ as *mut _,
+            p_next: &mut timeline_semaphore_features as *mut _ as *mut _,
             synchronization2: vk::TRUE,
             ..Default::default()
         };
@@ -335,8 +435,8 @@ impl VulkanCoreBuilder {
         // device create info
         let device_info = vk::DeviceCreateInfo {
             p_next: &mut synchronization2_features as *mut _ as *const _,
-            queue_create_info_count: 1,
-            p_queue_create_infos: &queue_create_info,
+            queue_create_info_count: queue_create_infos.len() as u32,
+            p_queue_create_infos: queue_create_infos.as_ptr(),
             p_enabled_features: &self.requested_device_features,
             enabled_extension_count: device_ext_ptrs.len() as u32,
             pp_enabled_extension_names: if device_ext_ptrs.is_empty() { std::ptr::null() } else { device_ext_ptrs.as_ptr() },
@@ -346,6 +446,10 @@ impl VulkanCoreBuilder {
         let logical_device = unsafe { instance.create_device(physical_device, &device_info, None).map_err(|_| "Device creation failed")? };
 
         let graphics_queue = unsafe { logical_device.get_device_queue(q_family_idx, 0) };
+        let transfer_queue = unsafe { logical_device.get_device_queue(transfer_slot.0, transfer_slot.1) };
+        let compute_queue = unsafe { logical_device.get_device_queue(compute_slot.0, compute_slot.1) };
+
+        let pipeline_cache = VulkanPipelineCache::try_new(&instance, physical_device, &logical_device)?;
 
         Ok(VulkanCore {
             _entry: entry,
@@ -355,9 +459,17 @@ impl VulkanCoreBuilder {
             _logical_device: logical_device,
             _graphics_queue: graphics_queue,
             _graphics_queue_index: q_family_idx,
+            _transfer_queue: transfer_queue,
+            _transfer_queue_index: transfer_slot.0,
+            _compute_queue: compute_queue,
+            _compute_queue_index: compute_slot.0,
             #[cfg(debug_assertions)]
             _debug_messenger: debug_messenger.unwrap_or_else(|| vk::DebugUtilsMessengerEXT::null()),
             min_uniform_buffer_offset_alignment: mem_limit,
+            supported_msaa_samples,
+            supports_bindless,
+            max_bindless_textures,
+            pipeline_cache,
         })
     }
 
@@ -400,6 +512,27 @@ impl VulkanCoreBuilder {
         }).map(|(pd, qf, _, mem)| (pd, qf, mem)).ok_or("No suitable GPU found")
     }
 
+    /// Все, что включается в descriptor_indexing_features при создании девайса
+    fn supports_bindless(instance: &Instance, pd: vk::PhysicalDevice) -> bool {
+        let mut indexing = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+        {
+            let mut features2 = vk::PhysicalDeviceFeatures2::default().push_next(&mut indexing);
+            unsafe { instance.get_physical_device_features2(pd, &mut features2) };
+        }
+        indexing.shader_sampled_image_array_non_uniform_indexing == vk::TRUE
+            && indexing.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+            && indexing.descriptor_binding_update_unused_while_pending == vk::TRUE
+            && indexing.descriptor_binding_partially_bound == vk::TRUE
+            && indexing.runtime_descriptor_array == vk::TRUE
+    }
+
+    /// Семейство, где есть `want` и нет ничего из `avoid`
+    fn pick_dedicated_family(families: &[vk::QueueFamilyProperties], want: vk::QueueFlags, avoid: vk::QueueFlags) -> Option<u32> {
+        families.iter()
+            .position(|q| q.queue_count > 0 && q.queue_flags.contains(want) && !q.queue_flags.intersects(avoid))
+            .map(|i| i as u32)
+    }
+
     #[cfg(debug_assertions)]
     unsafe extern "system" fn debug_callback(
         severity: vk::DebugUtilsMessageSeverityFlagsEXT,
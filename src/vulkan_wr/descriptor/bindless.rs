@@ -0,0 +1,210 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: bindless: один сет на все текстуры (runtime массив) и все материалы (storage буфер).
+// Шейдер берет материал по id из push constants, сеты на draw не биндятся
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout};
+use super::super::{
+    app::VulkanApp,
+    buffer::buffer::VulkanBuffer,
+    texture::TextureGPU,
+    types::{gpu_layout::gpu_struct, model::{MaterialUBO, MeshGPU}},
+};
+
+type DResult<T> = Result<T, &'static str>;
+
+pub const MAX_BINDLESS_TEXTURES: u32 = 4096;  // еще режется лимитом девайса
+pub const MAX_BINDLESS_MATERIALS: u32 = 4096;
+
+pub const BINDLESS_TEXTURES_BINDING: u32 = 0;
+pub const BINDLESS_MATERIALS_BINDING: u32 = 1;
+
+// GLSL сторона - shaders/include/bindless.glsl
+gpu_struct! {
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct BindlessMaterial: Std430 {
+        pub ambient: [f32; 4],
+        pub diffuse: [f32; 4],  // .w - непрозрачность (d)
+        pub specular: [f32; 4],
+        pub extra: [f32; 4],  // extra[0] = shininess
+        pub texture: u32,  // индекс в массиве текстур
+        pub _pad: [f32; 3],
+    }
+}
+
+impl BindlessMaterial {
+    pub fn new(material: &MaterialUBO, texture: u32) -> Self {
+        Self {
+            ambient: material.ambient,
+            diffuse: material.diffuse,
+            specular: material.specular,
+            extra: material.extra,
+            texture,
+            ..Default::default()
+        }
+    }
+}
+
+/// Слоты только добавляются: пишутся в еще не используемые индексы, поэтому
+/// сет можно обновлять, пока кадры в полете его читают (UPDATE_UNUSED_WHILE_PENDING)
+pub struct BindlessTable {
+    set: VulkanDescriptorSet,
+    pool: VulaknDescriptorPool,  // свой, с UPDATE_AFTER_BIND, общий пул app такого не умеет
+    layout: Vec<VulkanDescriptorSetLayout>,
+    materials: VulkanBuffer,
+    texture_count: u32,
+    material_count: u32,
+    max_textures: u32,
+}
+
+impl BindlessTable {
+    /// Девайс без descriptor indexing - ошибка, сцена на bindless там не запускается
+    /// # Args
+    /// * `stages` - где видны текстуры и материалы
+    pub fn try_new(app: &VulkanApp, stages: vk::ShaderStageFlags) -> DResult<Self> {
+        if !app.core.supports_bindless {
+            return Err("Bindless needs descriptor indexing, the device does not support it");
+        }
+        let device = &app.core._logical_device;
+        // max_bindless_textures уже минимум update-after-bind лимитов на семплеры и sampled images
+        let max_textures = MAX_BINDLESS_TEXTURES.min(app.core.max_bindless_textures);
+
+        let bindings = vec![
+            vk::DescriptorSetLayoutBinding {
+                binding: BINDLESS_TEXTURES_BINDING,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: max_textures,
+                stage_flags: stages,
+                ..Default::default()
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: BINDLESS_MATERIALS_BINDING,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+                stage_flags: stages,
+                ..Default::default()
+            },
+        ];
+        // незаписанные слоты не трогаются шейдером, значит могут быть пустыми
+        let binding_flags = [
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+            vk::DescriptorBindingFlags::empty(),
+        ];
+        let layout = vec![VulkanDescriptorSetLayout::try_new_with_binding_flags(
+            device,
+            &bindings,
+            Some(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL),
+            &binding_flags,
+        )?];
+
+        let pool = VulaknDescriptorPool::try_new(
+            device,
+            &vec![
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: max_textures },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1 },
+            ],
+            1,
+            Some(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND),
+        )?;
+        let set = pool.allocate_descriptor_sets(layout.as_slice())?.remove(0);
+
+        let materials = VulkanBuffer::try_new(
+            &app.core,
+            (size_of::<BindlessMaterial>() as u64 * MAX_BINDLESS_MATERIALS as u64) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            None, None, None, None
+        )?;
+        let (mut write, info) = set.write_buffer(
+            BINDLESS_MATERIALS_BINDING,
+            materials.buffer,
+            0,
+            vk::WHOLE_SIZE,
+            vk::DescriptorType::STORAGE_BUFFER,
+        );
+        write.p_buffer_info = &info;
+        pool.update_descriptor_sets(&[write], &[]);
+
+        Ok(Self { set, pool, layout, materials, texture_count: 0, material_count: 0, max_textures })
+    }
+
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.layout[0].layout
+    }
+
+    /// Биндится раз на кадр, id в шейдере остаются валидны
+    pub fn set(&self) -> vk::DescriptorSet {
+        self.set.set
+    }
+
+    /// Кладет текстуру в следующий свободный слот. Текстура должна жить, пока ее слот читают
+    pub fn add_texture(&mut self, texture: &TextureGPU) -> DResult<u32> {
+        if self.texture_count == self.max_textures {
+            return Err("Bindless texture array is full");
+        }
+        let index = self.texture_count;
+        let image_info = vk::DescriptorImageInfo {
+            sampler: texture.sampler.sampler,
+            image_view: texture.view.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let write = vk::WriteDescriptorSet {
+            dst_set: self.set.set,
+            dst_binding: BINDLESS_TEXTURES_BINDING,
+            dst_array_element: index,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+        self.pool.update_descriptor_sets(&[write], &[]);
+        self.texture_count += 1;
+        Ok(index)
+    }
+
+    pub fn add_material(&mut self, material: BindlessMaterial) -> DResult<u32> {
+        if self.material_count == MAX_BINDLESS_MATERIALS {
+            return Err("Bindless material table is full");
+        }
+        let index = self.material_count;
+        let offset = size_of::<BindlessMaterial>() as u64 * index as u64;
+        unsafe { self.materials.mem_copy(&[material], Some(offset), None, None)?; }
+        self.material_count += 1;
+        Ok(index)
+    }
+
+    /// Текстуры и материалы всех сабмешей. Каждая текстура меша кладется один раз,
+    /// материалы идут подряд: id материала сабмеша si - возвращенное значение + si.
+    /// Место проверяется заранее, чтобы при ошибке не осталось половины меша
+    pub fn add_mesh(&mut self, mesh: &MeshGPU) -> DResult<u32> {
+        let mut used: Vec<usize> = mesh.submeshes.iter().map(|sm| sm.texture_id).collect();
+        used.sort_unstable();
+        used.dedup();
+        if used.iter().any(|&id| id >= mesh.texture.len()) {
+            return Err("Submesh texture id is out of range");
+        }
+        if self.texture_count as usize + used.len() > self.max_textures as usize {
+            return Err("Bindless texture array is full");
+        }
+        if self.material_count as usize + mesh.submeshes.len().min(mesh.materials.len()) > MAX_BINDLESS_MATERIALS as usize {
+            return Err("Bindless material table is full");
+        }
+
+        let mut slots = vec![0u32; mesh.texture.len()];
+        for &id in used.iter() {
+            slots[id] = self.add_texture(&mesh.texture[id])?;
+        }
+        let base = self.material_count;
+        for (sm, material) in mesh.submeshes.iter().zip(mesh.materials.iter()) {
+            self.add_material(BindlessMaterial::new(material, slots[sm.texture_id]))?;
+        }
+        Ok(base)
+    }
+}
@@ -38,6 +38,40 @@ impl VulkanDescriptorSetLayout {
         })
     }
 
+    /// То же, но с флагами на каждый binding (descriptor indexing: partially bound, update after bind)
+    /// # Аргументы
+    /// * `binding_flags` — по одному на элемент `bindings`, в том же порядке
+    pub fn try_new_with_binding_flags(
+        device: &Device,
+        bindings: &Vec<vk::DescriptorSetLayoutBinding>,
+        flag: Option<vk::DescriptorSetLayoutCreateFlags>,
+        binding_flags: &[vk::DescriptorBindingFlags],
+    ) -> DResult<Self> {
+        if binding_flags.len() != bindings.len() {
+            return Err("Binding flags count differs from bindings count");
+        }
+        let mut flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo {
+            binding_count: binding_flags.len() as u32,
+            p_binding_flags: binding_flags.as_ptr(),
+            ..Default::default()
+        };
+        let create_info = vk::DescriptorSetLayoutCreateInfo {
+            binding_count: bindings.len() as u32,
+            p_bindings: bindings.as_ptr(),
+            flags: flag.unwrap_or(vk::DescriptorSetLayoutCreateFlags::empty()),
+            ..Default::default()
+        }.push_next(&mut flags_info);
+
+        let layout = unsafe {
+            device.create_descriptor_set_layout(&create_info, None).map_err(|_| "Err create_descriptor_set_layout")?
+        };
+
+        Ok(Self {
+            layout,
+            _device: device.clone()
+        })
+    }
+
 }
 
 impl Drop for VulkanDescriptorSetLayout {
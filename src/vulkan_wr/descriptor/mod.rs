@@ -1,3 +1,4 @@
 pub mod descriptor_pool;
 pub mod descriptor_set;
 pub mod descriptor_set_layout;
+pub mod bindless;
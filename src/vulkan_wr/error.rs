@@ -0,0 +1,25 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: ошибки с подробностями при Result<T, &'static str>: текст собирается один раз и живет до конца программы
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
+static MESSAGES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+/// Строка ошибки со значениями внутри (set, binding, строка файла).
+/// Одинаковый текст отдается тот же, поэтому повторы (hot reload) память не копят
+pub fn describe(message: String) -> &'static str {
+    let mut messages = MESSAGES.get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&known) = messages.get(message.as_str()) {
+        return known;
+    }
+    let leaked: &'static str = Box::leak(message.into_boxed_str());
+    messages.insert(leaked);
+    leaked
+}
@@ -0,0 +1,221 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: hot reload шейдеров для разработки: следит за shaders/, пересобирает glslc,
+// и за pipelines/*.toml. Объекты потом сами пересоздают свои пайплайны
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::shader::shader_dir;
+use super::pipeline::pipeline_desc::pipeline_dir;
+use super::shader_list::{discover_shaders, spv_name, SHADER_INCLUDE_DIR};
+
+/// Чаще смотреть mtime смысла нет, редактор все равно сохраняет не мгновенно
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Шейдер, который не собрался. Рисуется старый пайплайн, пока не починят
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    pub source: String,
+    pub log: String,
+}
+
+/// Следит за исходниками шейдеров. Включен только в debug сборке и только если
+/// рядом лежит папка shaders/ из репозитория
+pub struct ShaderHotReload {
+    enabled: bool,
+    src_dir: PathBuf,
+    out_dir: PathBuf,
+    pipeline_src: PathBuf,
+    stamps: HashMap<PathBuf, SystemTime>,
+    last_scan: Instant,
+    errors: Vec<ShaderError>,
+    changed: Vec<String>,  // .spv и .toml, которые обновились и ждут пересоздания пайплайнов
+}
+
+impl ShaderHotReload {
+    pub fn new() -> Self {
+        let src_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("shaders");
+        // туда же, откуда их грузит VulkanShader
+        let out_dir = shader_dir().unwrap_or_default();
+        let enabled = cfg!(debug_assertions) && src_dir.is_dir() && out_dir.is_dir();
+        // PipelineDesc::load читает их прямо оттуда
+        let pipeline_src = pipeline_dir().unwrap_or_default();
+
+        let mut watcher = Self {
+            enabled,
+            src_dir,
+            out_dir,
+            pipeline_src,
+            stamps: HashMap::new(),
+            last_scan: Instant::now(),
+            errors: vec![],
+            changed: vec![],
+        };
+        // то, что уже есть, собрал build.rs
+        for path in watcher.watched() {
+            if let Some(time) = modified(&path) {
+                watcher.stamps.insert(path, time);
+            }
+        }
+        watcher
+    }
+
+    /// Исходники, все из include/ и описания пайплайнов
+    fn watched(&self) -> Vec<PathBuf> {
+        let include_dir = self.src_dir.join(SHADER_INCLUDE_DIR);
+        let mut paths = discover_shaders(&self.src_dir).unwrap_or_default()
+            .into_iter()
+            .map(|src| self.src_dir.join(src))
+            .collect::<Vec<_>>();
+        if let Ok(entries) = std::fs::read_dir(&include_dir) {
+            paths.extend(entries.flatten().map(|e| e.path()));
+        }
+        if let Ok(entries) = std::fs::read_dir(&self.pipeline_src) {
+            paths.extend(entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "toml")));
+        }
+        paths
+    }
+
+    /// Смотрит mtime исходников и пересобирает изменившиеся. Звать раз за кадр, сам ограничивает частоту
+    pub fn poll(&mut self) {
+        if !self.enabled || self.last_scan.elapsed() < SCAN_INTERVAL {
+            return;
+        }
+        self.last_scan = Instant::now();
+
+        let include_dir = self.src_dir.join(SHADER_INCLUDE_DIR);
+        let mut dirty = vec![];
+        let mut pipelines = vec![];
+        let mut include_changed = false;
+        for path in self.watched() {
+            let Some(time) = modified(&path) else {
+                continue;
+            };
+            if self.stamps.get(&path) == Some(&time) {
+                continue;
+            }
+            self.stamps.insert(path.clone(), time);
+            if path.starts_with(&self.pipeline_src) {
+                pipelines.push(path);
+            } else if path.starts_with(&include_dir) {
+                include_changed = true;
+            } else {
+                dirty.push(path);
+            }
+        }
+        // кто что включает не отслеживаем, поменялся include - собираем все
+        let sources = if include_changed {
+            discover_shaders(&self.src_dir).unwrap_or_default()
+        } else {
+            dirty.iter()
+                .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .collect()
+        };
+
+        for src in sources {
+            let Some(dst) = spv_name(&src) else {
+                continue;
+            };
+            // и ошибки пайплайнов, где он был среди пересобранных
+            self.errors.retain(|e| !e.source.split(", ").any(|s| s == src));
+            match self.compile(&src, &dst) {
+                Ok(()) => {
+                    println!("shader reloaded: {}", src);
+                    if !self.changed.contains(&dst) {
+                        self.changed.push(dst);
+                    }
+                }
+                Err(log) => {
+                    println!("shader {} failed:\n{}", src, log);
+                    self.errors.push(ShaderError { source: src, log });
+                }
+            }
+        }
+
+        // .toml компилировать не надо, PipelineDesc::load и так читает их с диска.
+        // Ошибки разбора всплывут при пересоздании пайплайна через report
+        for path in pipelines {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            self.errors.retain(|e| !e.source.split(", ").any(|s| s == name));
+            println!("pipeline description reloaded: {}", name);
+            if !self.changed.contains(&name) {
+                self.changed.push(name);
+            }
+        }
+    }
+
+    /// Собирает во временный файл и подменяет .spv только при успехе,
+    /// иначе старый остается для следующих пересозданий
+    fn compile(&self, src: &str, dst: &str) -> Result<(), String> {
+        let src_path = self.src_dir.join(src);
+        let dst_path = self.out_dir.join(dst);
+        let tmp_path = self.out_dir.join(format!("{}.tmp", dst));
+        let output = Command::new("glslc")
+            .arg("-I")
+            .arg(self.src_dir.join(SHADER_INCLUDE_DIR))
+            .arg(&src_path)
+            .arg("-o")
+            .arg(&tmp_path)
+            .output()
+            .map_err(|e| format!("failed to run glslc: {}", e))?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+        std::fs::rename(&tmp_path, &dst_path).map_err(|e| format!("failed to replace {}: {}", dst, e))
+    }
+
+    /// Пересобранные .spv и обновленные .toml с прошлого вызова. Кто забрал, тот и пересоздает пайплайны
+    pub fn take_changed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Пайплайн из новых .spv не собрался, рисуем старым и показываем ошибку
+    pub fn report(&mut self, changed: &[String], err: &'static str) {
+        let sources = discover_shaders(&self.src_dir).unwrap_or_default();
+        let source = changed.iter()
+            .map(|spv| sources.iter().find(|src| spv_name(src).as_ref() == Some(spv)).unwrap_or(spv).as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("pipeline for {} was not rebuilt: {}", source, err);
+        self.errors.retain(|e| e.source != source);
+        self.errors.push(ShaderError { source, log: err.to_string() });
+    }
+
+    pub fn errors(&self) -> &[ShaderError] {
+        &self.errors
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Что-то из changed используется этим пайплайном
+pub fn touches(changed: &[String], shaders: &[&str]) -> bool {
+    shaders.iter().any(|s| changed.iter().any(|c| c == s))
+}
+
+/// Отдельное окно поверх всего, чтобы ошибку было видно даже со свернутыми настройками
+pub fn render_errors(ui: &imgui::Ui, errors: &[ShaderError]) {
+    if errors.is_empty() {
+        return;
+    }
+    ui.window("Shader errors")
+        .position([10.0, 10.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            for e in errors {
+                ui.text_colored([1.0, 0.3, 0.3, 1.0], &e.source);
+                ui.text_wrapped(&e.log);
+                ui.separator();
+            }
+        });
+}
@@ -5,14 +5,15 @@
 // #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
 
 
+use std::sync::Mutex;
+
 use ash::{vk, Device};
 
 use crate::vulkan_wr::app::VulkanApp;
 
 use super::super::{
     command_pb::command_buffer::VulkanCommandBuffer,
-    sync::fence::VulkanFence,
-    buffer::buffer::VulkanBuffer,
+    sync::barrier::{SyncScope, image_barrier2, is_write_access},
     core::VulkanCore};
 
 // =====================================================================
@@ -25,115 +26,131 @@ pub struct VulkanImage {
     pub format: vk::Format,  // Формат пикселей (R8G8B8A8_UNORM, D32_SFLOAT...)
     pub extent: vk::Extent3D,  // размеры изображения
     pub usage: vk::ImageUsageFlags,  // Цель использования: COLOR_ATTACHMENT, DEPTH_STENCIL_ATTACHMENT, SAMPLED, TRANSFER_DST...
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    // layout и последний доступ каждого subresource, индекс mip * array_layers + layer
+    // Mutex: барьеры пишут и те, у кого картинка только по &
+    states: Mutex<Vec<SubresourceState>>,
     _device: Device,
 }
 
+/// Где subresource сейчас и кто трогал его последним, это src следующего барьера
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubresourceState {
+    pub layout: vk::ImageLayout,
+    pub stage: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+impl SubresourceState {
+    pub const UNDEFINED: SubresourceState = SubresourceState {
+        layout: vk::ImageLayout::UNDEFINED,
+        stage: vk::PipelineStageFlags2::NONE,
+        access: vk::AccessFlags2::NONE,
+    };
+}
+
+/// Какие аспекты у формата: depth/stencil или color
+pub fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::D32_SFLOAT | vk::Format::X8_D24_UNORM_PACK32 => vk::ImageAspectFlags::DEPTH,
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT =>
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
 impl VulkanImage {
-    pub fn upload_from_slice(&self,
-        app: &VulkanApp, cmd: &VulkanCommandBuffer, fence: &VulkanFence, data: &[u8],
-        barriers: Option<[vk::ImageMemoryBarrier; 2]>
-    ) -> Result<(), &'static str> {
-        let staging_buffer = VulkanBuffer::try_new(
-            &app.core,
-            (data.len() * size_of::<u8>()) as u64,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            None, None, None, None
-        )?;
-        unsafe {
-            staging_buffer.mem_copy(data, None, None, None)?;
+    /// Вся картинка: все mip, все слои, все аспекты формата
+    pub fn full_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: format_aspect(self.format),
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.array_layers,
         }
+    }
 
-        let copy_region = vk::BufferImageCopy {
-            buffer_offset: 0,
-            buffer_row_length: 0,
-            buffer_image_height: 0,
-            image_subresource: vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
-            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
-            image_extent: self.extent,
-        };
-        let barrier1;
-        let barrier2;
-        if !barriers.is_none() {
-            [barrier1, barrier2] = barriers.unwrap();
-        } else {
-            barrier1 = vk::ImageMemoryBarrier {
-                old_layout: vk::ImageLayout::UNDEFINED,
-                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                image: self.image,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    level_count: 1,
-                    layer_count: 1,
-                    ..Default::default()
-                },
-                ..Default::default()
-            };
-            barrier2 = vk::ImageMemoryBarrier {
-                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                image: self.image,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    level_count: 1,
-                    layer_count: 1,
-                    ..Default::default()
-                },
-                ..Default::default()
+    /// Состояние одного subresource
+    pub fn state(&self, mip: u32, layer: u32) -> SubresourceState {
+        self.states.lock().unwrap()[(mip * self.array_layers + layer) as usize]
+    }
+
+    /// Переводит всю картинку в layout, содержимое сохраняется. Барьер пишется только если нужен
+    /// # Args
+    /// * `stage`, `access` - кто будет ее трогать дальше
+    pub fn transition_to(&self, cmd: &VulkanCommandBuffer, layout: vk::ImageLayout, stage: vk::PipelineStageFlags2, access: vk::AccessFlags2) {
+        self.transition_range(cmd, self.full_range(), layout, SyncScope::new(stage, access), false);
+    }
+
+    /// Как transition_to, но старое содержимое не нужно: переход из UNDEFINED, ждем только прошлых пользователей
+    pub fn discard_to(&self, cmd: &VulkanCommandBuffer, layout: vk::ImageLayout, stage: vk::PipelineStageFlags2, access: vk::AccessFlags2) {
+        self.transition_range(cmd, self.full_range(), layout, SyncScope::new(stage, access), true);
+    }
+
+    /// Переход части картинки. Соседние слои с одинаковым старым состоянием идут одним барьером
+    /// # Args
+    /// * `range` - REMAINING_* тут не поддерживаются, только явные числа
+    /// * `discard` - старое содержимое не нужно
+    pub fn transition_range(&self, cmd: &VulkanCommandBuffer, range: vk::ImageSubresourceRange, layout: vk::ImageLayout, dst: SyncScope, discard: bool) {
+        let mut states = self.states.lock().unwrap();
+        let mut barriers: Vec<vk::ImageMemoryBarrier2> = vec![];
+        for mip in range.base_mip_level..range.base_mip_level + range.level_count {
+            let mut run: Option<(u32, SubresourceState)> = None;  // начало и старое состояние текущей пачки слоев
+            for layer in range.base_array_layer..=range.base_array_layer + range.layer_count {
+                let old = if layer < range.base_array_layer + range.layer_count {
+                    Some(states[(mip * self.array_layers + layer) as usize])
+                } else {
+                    None
+                };
+                if let Some((first, run_state)) = run {
+                    if old != Some(run_state) {
+                        // чтение после чтения в том же layout барьера не требует
+                        let needed = discard || run_state.layout != layout || is_write_access(run_state.access) || is_write_access(dst.access);
+                        if needed {
+                            barriers.push(image_barrier2(
+                                self.image,
+                                vk::ImageSubresourceRange { base_mip_level: mip, level_count: 1, base_array_layer: first, layer_count: layer - first, ..range },
+                                SyncScope::new(run_state.stage, run_state.access), dst,
+                                if discard { vk::ImageLayout::UNDEFINED } else { run_state.layout }, layout,
+                            ));
+                        }
+                        for l in first..layer {
+                            let state = &mut states[(mip * self.array_layers + l) as usize];
+                            *state = if needed {
+                                SubresourceState { layout, stage: dst.stage, access: dst.access }
+                            } else {
+                                SubresourceState { layout, stage: run_state.stage | dst.stage, access: run_state.access | dst.access }
+                            };
+                        }
+                        run = None;
+                    }
+                }
+                if run.is_none() {
+                    run = old.map(|o| (layer, o));
+                }
             }
         }
+        drop(states);
+        unsafe { cmd.image_barriers2(&barriers); }
+    }
 
-        cmd.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, None)?;
-        // Копирование из staging buffer в атлас. это должно быть один раз
-        unsafe {
-            cmd.pipeline_barrier(
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-                vk::DependencyFlags::empty(), &[], &[], &[barrier1]
-            );
-
-            // копирование шрифтов 
-            cmd.copy_buffer_to_image(
-                staging_buffer.buffer,
-                self.image,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[copy_region]
-            );
-
-            cmd.pipeline_barrier(
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-                vk::DependencyFlags::empty(), &[], &[], &[barrier2]
-            );
-        }
-        cmd.end()?;
-
-        let fence = fence;
-
-        let submit_info = vk::SubmitInfo {
-            // wait_semaphore_count: 1,
-            // p_wait_semaphores: &image_available.semaphore,
-            // p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &cmd._buffer,
-            // signal_semaphore_count: 1,
-            // p_signal_semaphores: &render_finished.semaphore,
-            ..Default::default()
-        };
-        unsafe {
-            app.core._logical_device.reset_fences(&[fence.fence]).map_err(|_| "Err upload_cmd::reset_fences")?;
-        }
-        app.core.queue_submit(&[submit_info], fence.fence)?;
-        unsafe {
-            app.core._logical_device.wait_for_fences(&[fence.fence], true, u64::MAX).map_err(|_| "Err upload_cmd::wait_for_fences")?;
+    /// Картинку перевел кто-то другой (render pass, render graph), просто запоминаем
+    pub fn assume(&self, range: vk::ImageSubresourceRange, state: SubresourceState) {
+        let mut states = self.states.lock().unwrap();
+        for mip in range.base_mip_level..range.base_mip_level + range.level_count {
+            for layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                states[(mip * self.array_layers + layer) as usize] = state;
+            }
         }
+    }
 
-        Ok(())
+    /// Заливает data через очередь копирования и ждет. Картинка остается в SHADER_READ_ONLY_OPTIMAL
+    pub fn upload_from_slice(&self, app: &VulkanApp, data: &[u8]) -> Result<(), &'static str> {
+        let ticket = app.uploader.upload_image(&app.core, self, data)?;
+        app.uploader.wait(ticket)
     }
 }
 
@@ -246,6 +263,9 @@ impl<'a> VulkanImageBuilder<'a> {
                 format: self.create_info.format,
                 extent: self.create_info.extent,
                 usage: self.create_info.usage,
+                mip_levels: self.create_info.mip_levels,
+                array_layers: self.create_info.array_layers,
+                states: Mutex::new(vec![SubresourceState::UNDEFINED; (self.create_info.mip_levels * self.create_info.array_layers) as usize]),
                 _device: device.clone(),
             });
         }
@@ -272,6 +292,9 @@ impl<'a> VulkanImageBuilder<'a> {
             format: self.create_info.format,
             extent: self.create_info.extent,
             usage: self.create_info.usage,
+            mip_levels: self.create_info.mip_levels,
+            array_layers: self.create_info.array_layers,
+            states: Mutex::new(vec![SubresourceState::UNDEFINED; (self.create_info.mip_levels * self.create_info.array_layers) as usize]),
             _device: device.clone(),
         })
     }
@@ -5,6 +5,7 @@ pub mod app;
 pub mod command_pb;
 pub mod pipeline;
 pub mod shader;
+pub mod reflect;
 pub mod render_pass;
 pub mod descriptor;
 pub mod image;
@@ -16,3 +17,11 @@ pub mod sampler;
 pub mod ImGui_wr;
 pub mod renderable_traits;
 pub mod texture;
+pub mod post;
+pub mod render_graph;
+pub mod present;
+pub mod upload;
+pub mod assets;
+pub mod shader_list;
+pub mod hot_reload;
+pub mod error;
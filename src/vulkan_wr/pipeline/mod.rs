@@ -1,3 +1,8 @@
 
 pub mod pipeline_layout;
-pub mod pipeline;
\ No newline at end of file
+pub mod pipeline;
+pub mod pipeline_cache;
+pub mod toml_lite;
+pub mod pipeline_desc;
+pub mod specialization;
+pub mod permutation;
@@ -0,0 +1,195 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: перестановки шейдеров: один исходник, варианты пайплайна по флагам фич
+// (тени, alpha test) через specialization constants, кэш собранных вариантов
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::collections::HashMap;
+use std::ops::{BitOr, BitOrAssign};
+
+use ash::vk;
+
+use super::super::{app::VulkanApp, core::VulkanCore, shader::VulkanShader};
+use super::pipeline::VulkanPipeline;
+use super::pipeline_desc::{PipelineDesc, PipelineTarget};
+use super::specialization::SpecConstants;
+
+type PResult<T> = Result<T, &'static str>;
+
+/// Бит i флагов -> layout(constant_id = FEATURE_SPEC_BASE + i) const bool в shaders/include/features.glsl
+pub const FEATURE_SPEC_BASE: u32 = 100;
+
+/// Флаги фич материала/кадра, ключ варианта в PermutationCache
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FeatureFlags(u32);
+
+impl FeatureFlags {
+    pub const SHADOWS: Self = Self(1 << 0);  // USE_SHADOWS
+    pub const ALPHA_TEST: Self = Self(1 << 1);  // USE_ALPHA_TEST
+
+    const ALL: [(Self, &'static str); 2] = [
+        (Self::SHADOWS, "shadows"),
+        (Self::ALPHA_TEST, "alpha_test"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: Self, on: bool) {
+        if on { self.0 |= other.0 } else { self.0 &= !other.0 }
+    }
+
+    /// Все флаги явно, выключенные тоже: дефолт в GLSL может быть любым
+    pub fn spec_constants(self) -> SpecConstants {
+        let mut out = SpecConstants::new();
+        for (i, (flag, _)) in Self::ALL.iter().enumerate() {
+            out.insert(FEATURE_SPEC_BASE + i as u32, self.contains(*flag).into());
+        }
+        out
+    }
+
+    /// Для логов: "shadows|alpha_test"
+    pub fn names(self) -> String {
+        let names: Vec<&str> = Self::ALL.iter().filter(|(f, _)| self.contains(*f)).map(|(_, n)| *n).collect();
+        if names.is_empty() { "none".to_string() } else { names.join("|") }
+    }
+}
+
+impl BitOr for FeatureFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for FeatureFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Варианты одного pipelines/*.toml. Запись кадра идет из потоков через &self,
+/// поэтому варианты собираются заранее через ensure, а get только ищет
+pub struct PermutationCache {
+    file: String,
+    desc: PipelineDesc,
+    shaders: Vec<VulkanShader>,
+    layout: vk::PipelineLayout,
+    target: PipelineTarget,
+    constants: SpecConstants,  // общие для всех вариантов (MAX_LIGHTS и тп)
+    variants: HashMap<FeatureFlags, VulkanPipeline>,
+}
+
+impl PermutationCache {
+    /// Грузит описание и шейдеры, варианты пока не собирает
+    /// # Args
+    /// * `constants` - значения, одинаковые для всех вариантов, флаги фич идут поверх
+    pub fn new(
+        app: &VulkanApp,
+        file: &str,
+        layout: vk::PipelineLayout,
+        target: PipelineTarget,
+        constants: SpecConstants,
+    ) -> PResult<Self> {
+        let desc = PipelineDesc::load(file)?;
+        let shaders = desc.load_shaders(&app.core._logical_device)?;
+        Ok(Self { file: file.to_string(), desc, shaders, layout, target, constants, variants: HashMap::new() })
+    }
+
+    /// Описание, чтобы вызывающий сверил форматы со своим проходом
+    pub fn desc(&self) -> &PipelineDesc {
+        &self.desc
+    }
+
+    /// Шейдеры, чтобы вызывающий проверил свои блоки через check_block
+    pub fn shaders(&self) -> &[VulkanShader] {
+        &self.shaders
+    }
+
+    fn build(&self, core: &VulkanCore, flags: FeatureFlags) -> PResult<VulkanPipeline> {
+        let spec = self.constants.merged(&flags.spec_constants());
+        self.desc.build_variant(core, self.layout, &self.target, &self.shaders, &spec).map_err(|e| {
+            println!("{}: variant {} failed", self.file, flags.names());
+            e
+        })
+    }
+
+    /// Собирает вариант, если его еще нет
+    pub fn ensure(&mut self, core: &VulkanCore, flags: FeatureFlags) -> PResult<vk::Pipeline> {
+        if let Some(pipeline) = self.variants.get(&flags) {
+            return Ok(pipeline.pipeline);
+        }
+        let pipeline = self.build(core, flags)?;
+        let handle = pipeline.pipeline;
+        self.variants.insert(flags, pipeline);
+        Ok(handle)
+    }
+
+    pub fn get(&self, flags: FeatureFlags) -> PResult<vk::Pipeline> {
+        match self.variants.get(&flags) {
+            Some(pipeline) => Ok(pipeline.pipeline),
+            None => {
+                println!("{}: variant {} was not built before recording", self.file, flags.names());
+                Err("Pipeline variant is missing")
+            }
+        }
+    }
+
+    /// Пересобирает все уже собранные варианты под новый target (MSAA). Девайс должен простаивать.
+    /// Не собрался хоть один - остаются старые
+    pub fn rebuild(&mut self, core: &VulkanCore, target: PipelineTarget) -> PResult<()> {
+        let old = std::mem::replace(&mut self.target, target);
+        match self.build_all(core) {
+            Ok(variants) => {
+                self.variants = variants;
+                Ok(())
+            }
+            Err(e) => {
+                self.target = old;
+                Err(e)
+            }
+        }
+    }
+
+    /// Hot reload: заново читает файл и шейдеры, пересобирает те же варианты.
+    /// Не вышло - остается старое
+    /// # Args
+    /// * `check` - те же проверки шейдеров, что вызывающий делал после new
+    pub fn reload(&mut self, app: &VulkanApp, check: impl Fn(&[VulkanShader]) -> PResult<()>) -> PResult<()> {
+        let fresh = Self::new(app, &self.file, self.layout, self.target, self.constants.clone())?;
+        check(fresh.shaders())?;
+        let old_desc = std::mem::replace(&mut self.desc, fresh.desc);
+        let old_shaders = std::mem::replace(&mut self.shaders, fresh.shaders);
+        match self.build_all(&app.core) {
+            Ok(variants) => {
+                self.variants = variants;
+                Ok(())
+            }
+            Err(e) => {
+                self.desc = old_desc;
+                self.shaders = old_shaders;
+                Err(e)
+            }
+        }
+    }
+
+    /// Поменялся сам файл или один из его шейдеров
+    pub fn touched_by(&self, changed: &[String]) -> bool {
+        PipelineDesc::touched_by(&self.file, changed)
+    }
+
+    fn build_all(&self, core: &VulkanCore) -> PResult<HashMap<FeatureFlags, VulkanPipeline>> {
+        let mut out = HashMap::new();
+        for flags in self.variants.keys() {
+            out.insert(*flags, self.build(core, *flags)?);
+        }
+        Ok(out)
+    }
+}
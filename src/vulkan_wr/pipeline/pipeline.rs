@@ -7,6 +7,9 @@
 
 use ash::{vk, Device};
 
+use super::super::{core::VulkanCore, shader::VulkanShader};
+use super::specialization::SpecConstants;
+
 
 
 // =====================================================================
@@ -34,6 +37,8 @@ impl Drop for VulkanPipeline {
 pub struct VulkanPipelineBuilder<'a> {
     /// Vulkan устройство, через которое будет создан пайплайн
     device: &'a Device,
+    /// Общий кэш из VulkanCore, с ним повторный запуск не компилирует пайплайны заново
+    cache: vk::PipelineCache,
     /// Список шейдерных стадий (vertex, fragment, geometry и т.д.)
     shader_stages: Vec<vk::PipelineShaderStageCreateInfo<'a>>,
     /// Structure specifying parameters of a newly created pipeline vertex input state (формат, layout, атрибуты)
@@ -50,12 +55,16 @@ pub struct VulkanPipelineBuilder<'a> {
     depth_stencil: vk::PipelineDepthStencilStateCreateInfo<'a>,
     /// Настройки смешивания для одного цветового attachment
     color_blend_attachment: vk::PipelineColorBlendAttachmentState,
+    /// Если не пусто - по одному на каждый attachment (MRT), перекрывает color_blend_attachment
+    color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
     /// Общие параметры смешивания для пайплайна
     color_blend: vk::PipelineColorBlendStateCreateInfo<'a>,
     /// Настройки тесселяции
     tessellation: vk::PipelineTessellationStateCreateInfo<'a>,
     /// Bitmask controlling how a pipeline is created
     flags: vk::PipelineCreateFlags,
+    /// Specialization constants, одни на все стадии: (map entries, данные)
+    specialization: Option<(Vec<vk::SpecializationMapEntry>, Vec<u8>)>,
 
     /// Целевой рендер-пасс, с которым ассоциируется пайплайн
     render_pass: vk::RenderPass,
@@ -78,7 +87,7 @@ pub struct VulkanPipelineBuilder<'a> {
 }
 
 impl<'a> VulkanPipelineBuilder<'a> {
-    pub fn new(device: &'a Device, render_pass: vk::RenderPass, layout: vk::PipelineLayout) -> Self {
+    pub fn new(core: &'a VulkanCore, render_pass: vk::RenderPass, layout: vk::PipelineLayout) -> Self {
         let vertex_input = vk::PipelineVertexInputStateCreateInfo::default();
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
@@ -142,7 +151,8 @@ impl<'a> VulkanPipelineBuilder<'a> {
         };
 
         Self {
-            device: device,
+            device: &core._logical_device,
+            cache: core.pipeline_cache.cache,
             shader_stages: Vec::new(),
             vertex_input: vertex_input,
             input_assembly: input_assembly,
@@ -151,8 +161,10 @@ impl<'a> VulkanPipelineBuilder<'a> {
             multisampling: multisampling,
             depth_stencil: depth_stencil,
             color_blend_attachment: color_blend_attachment,
+            color_blend_attachments: Vec::new(),
             color_blend: color_blend,
             flags: vk::PipelineCreateFlags::default(),
+            specialization: None,
 
             render_pass: render_pass,
             subpass: 0,
@@ -168,8 +180,8 @@ impl<'a> VulkanPipelineBuilder<'a> {
         }
     }
 
-    pub fn new_dynamic(device: &'a Device, layout: vk::PipelineLayout) -> Self {
-        let mut builder = Self::new(device, vk::RenderPass::null(), layout);
+    pub fn new_dynamic(core: &'a VulkanCore, layout: vk::PipelineLayout) -> Self {
+        let mut builder = Self::new(core, vk::RenderPass::null(), layout);
         builder.use_dynamic_rendering = true;
         builder
     }
@@ -236,6 +248,12 @@ impl<'a> VulkanPipelineBuilder<'a> {
         self
     }
 
+    /// Меняет только rasterization_samples, остальное из multisampling остается
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.multisampling.rasterization_samples = samples;
+        self
+    }
+
     pub fn with_depth_stencil(mut self, state: vk::PipelineDepthStencilStateCreateInfo<'a>) -> Self {
         self.depth_stencil = state;
         self
@@ -252,6 +270,12 @@ impl<'a> VulkanPipelineBuilder<'a> {
         self
     }
 
+    /// Смешивание для нескольких color attachments (G-buffer и тп), attachment_count берется из длины
+    pub fn with_color_blend_attachments(mut self, attachments: Vec<vk::PipelineColorBlendAttachmentState>) -> Self {
+        self.color_blend_attachments = attachments;
+        self
+    }
+
     pub fn with_subpass(mut self, subpass: u32) -> Self {
         self.subpass = subpass;
         self
@@ -272,8 +296,26 @@ impl<'a> VulkanPipelineBuilder<'a> {
         self
     }
 
+    /// Ставится во все стадии, у которых своего p_specialization_info нет
+    pub fn with_specialization(mut self, spec: &SpecConstants) -> Self {
+        self.specialization = if spec.is_empty() { None } else { Some(spec.map_entries()) };
+        self
+    }
+
     pub fn build(mut self) -> Result<VulkanPipeline, &'static str> {
-        self.color_blend.p_attachments = &self.color_blend_attachment;
+        if self.color_blend_attachments.is_empty() {
+            self.color_blend.p_attachments = &self.color_blend_attachment;
+        } else {
+            self.color_blend.attachment_count = self.color_blend_attachments.len() as u32;
+            self.color_blend.p_attachments = self.color_blend_attachments.as_ptr();
+        }
+
+        let spec_info = self.specialization.as_ref().map(|(entries, data)| spec_info(entries, data));
+        if let Some(info) = spec_info.as_ref() {
+            for stage in self.shader_stages.iter_mut().filter(|s| s.p_specialization_info.is_null()) {
+                stage.p_specialization_info = (info as *const vk::SpecializationInfo<'_>).cast();
+            }
+        }
 
         let mut pipeline_rendering_create_info = if self.use_dynamic_rendering {
             Some(vk::PipelineRenderingCreateInfo {
@@ -320,7 +362,7 @@ impl<'a> VulkanPipelineBuilder<'a> {
 
         let pipelines = unsafe {
             self.device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
+                .create_graphics_pipelines(self.cache, &[create_info], None)
                 .map_err(|_| "Failed to create graphics pipeline")?
         };
 
@@ -332,3 +374,95 @@ impl<'a> VulkanPipelineBuilder<'a> {
         )
     }
 }
+
+
+
+// =====================================================================
+// VulkanComputePipelineBuilder — построитель compute пайплайна
+// Одна стадия, entry point и проверка стадии берутся из рефлексии шейдера
+// =====================================================================
+pub struct VulkanComputePipelineBuilder<'a> {
+    device: &'a Device,
+    /// Тот же общий кэш, что и у графических
+    cache: vk::PipelineCache,
+    shader: Option<&'a VulkanShader>,
+    pipeline_layout: vk::PipelineLayout,
+    flags: vk::PipelineCreateFlags,
+    specialization: SpecConstants,
+}
+
+impl<'a> VulkanComputePipelineBuilder<'a> {
+    pub fn new(core: &'a VulkanCore, layout: vk::PipelineLayout) -> Self {
+        Self {
+            device: &core._logical_device,
+            cache: core.pipeline_cache.cache,
+            shader: None,
+            pipeline_layout: layout,
+            flags: vk::PipelineCreateFlags::empty(),
+            specialization: SpecConstants::new(),
+        }
+    }
+
+    pub fn with_shader(mut self, shader: &'a VulkanShader) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+
+    pub fn change_flags(mut self, flags: vk::PipelineCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Например размер workgroup через local_size_x_id
+    pub fn with_specialization(mut self, spec: &SpecConstants) -> Self {
+        self.specialization = spec.clone();
+        self
+    }
+
+    pub fn build(self) -> Result<VulkanPipeline, &'static str> {
+        let shader = self.shader.ok_or("Compute pipeline has no shader")?;
+        if shader.reflection.stages != vk::ShaderStageFlags::COMPUTE {
+            println!("compute pipeline: shader stage is {:?}", shader.reflection.stages);
+            return Err("Compute pipeline needs a compute shader");
+        }
+        let entry_point = std::ffi::CString::new(shader.reflection.entry_point.as_str())
+            .map_err(|_| "Shader entry point is not a valid C string")?;
+        shader.reflection.check_specialization(&self.specialization)?;
+        let (entries, data) = self.specialization.map_entries();
+        let spec_info = spec_info(&entries, &data);
+
+        let create_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                module: shader._shader,
+                p_name: entry_point.as_ptr(),
+                stage: vk::ShaderStageFlags::COMPUTE,
+                p_specialization_info: if entries.is_empty() { std::ptr::null() } else { &spec_info },
+                ..Default::default()
+            },
+            layout: self.pipeline_layout,
+            flags: self.flags,
+            ..Default::default()
+        };
+
+        let pipelines = unsafe {
+            self.device
+                .create_compute_pipelines(self.cache, &[create_info], None)
+                .map_err(|_| "Failed to create compute pipeline")?
+        };
+
+        Ok(
+            VulkanPipeline{
+                pipeline: pipelines[0],
+                device: self.device.clone()
+            }
+        )
+    }
+}
+
+
+/// SpecializationInfo поверх уже разложенных констант, entries и data должны пережить create
+fn spec_info<'s>(entries: &'s [vk::SpecializationMapEntry], data: &'s [u8]) -> vk::SpecializationInfo<'s> {
+    vk::SpecializationInfo::default()
+        .map_entries(entries)
+        .data(data)
+}
@@ -0,0 +1,86 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: vk::PipelineCache, который переживает перезапуск: грузится с диска при
+// создании девайса и сохраняется при выходе. Живет в VulkanCore
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::path::PathBuf;
+
+use ash::{vk, Device, Instance};
+
+/// Заголовок кэша по спеке: длина, версия, vendor, device, uuid
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+pub struct VulkanPipelineCache {
+    pub cache: vk::PipelineCache,
+    path: Option<PathBuf>,  // None - некуда сохранять, работаем без диска
+}
+
+impl VulkanPipelineCache {
+    /// Файл зависит от vendor/device/драйвера, после обновления драйвера просто начнется новый
+    pub fn try_new(instance: &Instance, physical_device: vk::PhysicalDevice, device: &Device) -> Result<Self, &'static str> {
+        let props = unsafe { instance.get_physical_device_properties(physical_device) };
+        let path = std::env::current_exe().ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("pipeline_cache")))
+            .map(|dir| dir.join(format!(
+                "{:04x}_{:04x}_{:08x}.bin", props.vendor_id, props.device_id, props.driver_version
+            )));
+
+        let data = path.as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .filter(|data| Self::header_matches(data, &props))
+            .unwrap_or_default();
+
+        let cache = match Self::create(device, &data) {
+            Ok(cache) => cache,
+            // драйвер все равно может отказаться от данных, тогда с нуля
+            Err(_) if !data.is_empty() => Self::create(device, &[])?,
+            Err(err) => return Err(err),
+        };
+        Ok(Self { cache, path })
+    }
+
+    fn create(device: &Device, data: &[u8]) -> Result<vk::PipelineCache, &'static str> {
+        let info = vk::PipelineCacheCreateInfo {
+            initial_data_size: data.len(),
+            p_initial_data: data.as_ptr() as *const _,
+            ..Default::default()
+        };
+        unsafe { device.create_pipeline_cache(&info, None) }.map_err(|_| "Failed to create pipeline cache")
+    }
+
+    /// Чужой или битый файл драйверу лучше не отдавать
+    fn header_matches(data: &[u8], props: &vk::PhysicalDeviceProperties) -> bool {
+        if data.len() < HEADER_SIZE {
+            return false;
+        }
+        let word = |i: usize| u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
+        word(0) as usize >= HEADER_SIZE
+            && word(1) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && word(2) == props.vendor_id
+            && word(3) == props.device_id
+            && data[16..HEADER_SIZE] == props.pipeline_cache_uuid
+    }
+
+    /// Пишет через временный файл, чтобы упавший процесс не оставил половину кэша
+    pub fn save(&self, device: &Device) -> Result<(), &'static str> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        let data = unsafe { device.get_pipeline_cache_data(self.cache) }
+            .map_err(|_| "Failed to read pipeline cache data")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|_| "Failed to create pipeline cache directory")?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, &data).map_err(|_| "Failed to write pipeline cache")?;
+        std::fs::rename(&tmp, path).map_err(|_| "Failed to replace pipeline cache")
+    }
+
+    /// Девайс уничтожает VulkanCore, поэтому не Drop
+    pub fn destroy(&self, device: &Device) {
+        unsafe { device.destroy_pipeline_cache(self.cache, None) };
+    }
+}
@@ -0,0 +1,762 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: графический пайплайн из файла pipelines/*.toml: шейдеры, вершины, растеризатор,
+// глубина, смешивание и форматы. Новый материал - новый файл, без правок в Rust
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+
+use ash::{vk, Device};
+
+use super::super::{
+    app::VulkanApp,
+    core::VulkanCore,
+    shader::{VulkanShader, shader_path},
+    error::describe,
+    types::vertex::VulkanVertex,
+    ImGui_wr::ImGUIVertex,
+    post::fullscreen::HDR_COLOR_FORMAT,
+};
+use super::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use super::specialization::{SpecConstants, SpecValue};
+use super::toml_lite::{self, Table, Value};
+
+type PResult<T> = Result<T, &'static str>;
+
+// EMBEDDED_PIPELINES: (имя .toml, текст), собирает build.rs из pipelines/
+include!(concat!(env!("OUT_DIR"), "/embedded_pipelines.rs"));
+
+/// Откуда файлы перекрывают встроенные описания: pipelines/ репозитория, за ним следит hot reload,
+/// иначе pipelines/ рядом с бинарником
+pub fn pipeline_dir() -> PResult<PathBuf> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("pipelines");
+    if source.is_dir() {
+        return Ok(source);
+    }
+    let exe_path = std::env::current_exe()
+        .map_err(|_| "Failed to get current executable path")?;
+    let exe_dir = exe_path
+        .parent()
+        .ok_or("Executable is in the root directory?")?;
+    Ok(exe_dir.join("pipelines"))
+}
+
+/// Встроенное описание по имени файла
+fn embedded(file: &str) -> Option<&'static str> {
+    EMBEDDED_PIPELINES.iter().find(|(n, _)| *n == file).map(|(_, text)| *text)
+}
+
+/// Формат attachment'а. Свапчейн и его depth известны только в рантайме
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FormatRef {
+    Swapchain,
+    Hdr,  // HDR_COLOR_FORMAT
+    Depth,  // depth свапчейна
+    Fixed(vk::Format),
+}
+
+/// Какая Rust структура лежит в вершинном буфере
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VertexLayout {
+    None,  // fullscreen и тп, вершины из gl_VertexIndex
+    Mesh,  // VulkanVertex
+    ImGui,  // ImGUIVertex
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterDesc {
+    pub polygon: vk::PolygonMode,
+    pub cull: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub depth_bias: Option<[f32; 2]>,  // constant, slope - против shadow acne
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthDesc {
+    pub format: FormatRef,
+    pub test: bool,
+    pub write: bool,
+    pub compare: vk::CompareOp,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ColorDesc {
+    pub format: FormatRef,
+    pub blend: vk::PipelineColorBlendAttachmentState,
+}
+
+// у структур ash нет PartialEq
+impl PartialEq for ColorDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.format == other.format && blend_eq(&self.blend, &other.blend)
+    }
+}
+
+/// Все, что раньше руками писалось в VulkanPipelineBuilder. Layout и sample count не тут:
+/// layout идет от дескрипторов объекта, samples от настроек MSAA
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineDesc {
+    pub shaders: Vec<String>,  // имена .spv, стадия берется из рефлексии
+    pub vertex: VertexLayout,
+    pub topology: vk::PrimitiveTopology,
+    pub raster: RasterDesc,
+    pub depth: Option<DepthDesc>,  // None - без теста и записи
+    pub color: Vec<ColorDesc>,  // пусто - depth-only
+    pub dynamic: Vec<vk::DynamicState>,
+    pub constants: Table,  // [constants]: имя spec constant в шейдере -> значение
+}
+
+/// Во что рисует пайплайн, это знает только вызывающий
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineTarget {
+    pub swapchain_format: vk::Format,
+    pub depth_format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    pub render_pass: Option<vk::RenderPass>,  // None - dynamic rendering, форматы из описания
+}
+
+impl PipelineTarget {
+    /// Dynamic rendering
+    pub fn dynamic(app: &VulkanApp, samples: vk::SampleCountFlags) -> Self {
+        Self {
+            swapchain_format: app.swapchain.color_format,
+            depth_format: app.swapchain.depth_format,
+            samples,
+            render_pass: None,
+        }
+    }
+
+    /// Subpass 0 у render_pass, форматы attachment'ов задает сам render pass
+    pub fn render_pass(app: &VulkanApp, render_pass: vk::RenderPass, samples: vk::SampleCountFlags) -> Self {
+        Self { render_pass: Some(render_pass), ..Self::dynamic(app, samples) }
+    }
+}
+
+// ---------------------------------------------------------------------
+// имена в файле <-> значения vk
+// ---------------------------------------------------------------------
+
+const TOPOLOGIES: &[(&str, vk::PrimitiveTopology)] = &[
+    ("triangle_list", vk::PrimitiveTopology::TRIANGLE_LIST),
+    ("triangle_strip", vk::PrimitiveTopology::TRIANGLE_STRIP),
+    ("line_list", vk::PrimitiveTopology::LINE_LIST),
+    ("line_strip", vk::PrimitiveTopology::LINE_STRIP),
+    ("point_list", vk::PrimitiveTopology::POINT_LIST),
+];
+
+const VERTEX_LAYOUTS: &[(&str, VertexLayout)] = &[
+    ("none", VertexLayout::None),
+    ("mesh", VertexLayout::Mesh),
+    ("imgui", VertexLayout::ImGui),
+];
+
+const POLYGON_MODES: &[(&str, vk::PolygonMode)] = &[
+    ("fill", vk::PolygonMode::FILL),
+    ("line", vk::PolygonMode::LINE),
+    ("point", vk::PolygonMode::POINT),
+];
+
+const CULL_MODES: &[(&str, vk::CullModeFlags)] = &[
+    ("none", vk::CullModeFlags::NONE),
+    ("front", vk::CullModeFlags::FRONT),
+    ("back", vk::CullModeFlags::BACK),
+    ("front_and_back", vk::CullModeFlags::FRONT_AND_BACK),
+];
+
+const FRONT_FACES: &[(&str, vk::FrontFace)] = &[
+    ("ccw", vk::FrontFace::COUNTER_CLOCKWISE),
+    ("cw", vk::FrontFace::CLOCKWISE),
+];
+
+const COMPARE_OPS: &[(&str, vk::CompareOp)] = &[
+    ("never", vk::CompareOp::NEVER),
+    ("less", vk::CompareOp::LESS),
+    ("equal", vk::CompareOp::EQUAL),
+    ("less_or_equal", vk::CompareOp::LESS_OR_EQUAL),
+    ("greater", vk::CompareOp::GREATER),
+    ("not_equal", vk::CompareOp::NOT_EQUAL),
+    ("greater_or_equal", vk::CompareOp::GREATER_OR_EQUAL),
+    ("always", vk::CompareOp::ALWAYS),
+];
+
+const BLEND_FACTORS: &[(&str, vk::BlendFactor)] = &[
+    ("zero", vk::BlendFactor::ZERO),
+    ("one", vk::BlendFactor::ONE),
+    ("src_color", vk::BlendFactor::SRC_COLOR),
+    ("one_minus_src_color", vk::BlendFactor::ONE_MINUS_SRC_COLOR),
+    ("dst_color", vk::BlendFactor::DST_COLOR),
+    ("one_minus_dst_color", vk::BlendFactor::ONE_MINUS_DST_COLOR),
+    ("src_alpha", vk::BlendFactor::SRC_ALPHA),
+    ("one_minus_src_alpha", vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+    ("dst_alpha", vk::BlendFactor::DST_ALPHA),
+    ("one_minus_dst_alpha", vk::BlendFactor::ONE_MINUS_DST_ALPHA),
+    ("constant_color", vk::BlendFactor::CONSTANT_COLOR),
+    ("one_minus_constant_color", vk::BlendFactor::ONE_MINUS_CONSTANT_COLOR),
+];
+
+const BLEND_OPS: &[(&str, vk::BlendOp)] = &[
+    ("add", vk::BlendOp::ADD),
+    ("subtract", vk::BlendOp::SUBTRACT),
+    ("reverse_subtract", vk::BlendOp::REVERSE_SUBTRACT),
+    ("min", vk::BlendOp::MIN),
+    ("max", vk::BlendOp::MAX),
+];
+
+const DYNAMIC_STATES: &[(&str, vk::DynamicState)] = &[
+    ("viewport", vk::DynamicState::VIEWPORT),
+    ("scissor", vk::DynamicState::SCISSOR),
+    ("line_width", vk::DynamicState::LINE_WIDTH),
+    ("depth_bias", vk::DynamicState::DEPTH_BIAS),
+    ("blend_constants", vk::DynamicState::BLEND_CONSTANTS),
+    ("cull_mode", vk::DynamicState::CULL_MODE),
+    ("front_face", vk::DynamicState::FRONT_FACE),
+    ("depth_test_enable", vk::DynamicState::DEPTH_TEST_ENABLE),
+    ("depth_write_enable", vk::DynamicState::DEPTH_WRITE_ENABLE),
+    ("depth_compare_op", vk::DynamicState::DEPTH_COMPARE_OP),
+];
+
+/// Кроме swapchain/hdr/depth
+const FORMATS: &[(&str, vk::Format)] = &[
+    ("r8_unorm", vk::Format::R8_UNORM),
+    ("r8g8b8a8_unorm", vk::Format::R8G8B8A8_UNORM),
+    ("r8g8b8a8_srgb", vk::Format::R8G8B8A8_SRGB),
+    ("b8g8r8a8_unorm", vk::Format::B8G8R8A8_UNORM),
+    ("b8g8r8a8_srgb", vk::Format::B8G8R8A8_SRGB),
+    ("a2b10g10r10_unorm", vk::Format::A2B10G10R10_UNORM_PACK32),
+    ("b10g11r11_ufloat", vk::Format::B10G11R11_UFLOAT_PACK32),
+    ("r16_sfloat", vk::Format::R16_SFLOAT),
+    ("r16g16_sfloat", vk::Format::R16G16_SFLOAT),
+    ("r16g16b16a16_sfloat", vk::Format::R16G16B16A16_SFLOAT),
+    ("r32_sfloat", vk::Format::R32_SFLOAT),
+    ("r32g32b32a32_sfloat", vk::Format::R32G32B32A32_SFLOAT),
+    ("d16_unorm", vk::Format::D16_UNORM),
+    ("d32_sfloat", vk::Format::D32_SFLOAT),
+    ("d24_unorm_s8_uint", vk::Format::D24_UNORM_S8_UINT),
+    ("d32_sfloat_s8_uint", vk::Format::D32_SFLOAT_S8_UINT),
+];
+
+const NO_BLEND: vk::PipelineColorBlendAttachmentState = vk::PipelineColorBlendAttachmentState {
+    blend_enable: vk::FALSE,
+    src_color_blend_factor: vk::BlendFactor::ONE,
+    dst_color_blend_factor: vk::BlendFactor::ZERO,
+    color_blend_op: vk::BlendOp::ADD,
+    src_alpha_blend_factor: vk::BlendFactor::ONE,
+    dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+    alpha_blend_op: vk::BlendOp::ADD,
+    color_write_mask: vk::ColorComponentFlags::RGBA,
+};
+
+/// blend = "..." в [[color]]. Отдельные ключи src/dst/... поверх пресета
+const BLEND_PRESETS: &[(&str, vk::PipelineColorBlendAttachmentState)] = &[
+    ("opaque", NO_BLEND),
+    // src * a + dst * (1 - a)
+    ("alpha", vk::PipelineColorBlendAttachmentState {
+        blend_enable: vk::TRUE,
+        src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        ..NO_BLEND
+    }),
+    // цвет уже умножен на альфу
+    ("premultiplied", vk::PipelineColorBlendAttachmentState {
+        blend_enable: vk::TRUE,
+        src_color_blend_factor: vk::BlendFactor::ONE,
+        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        ..NO_BLEND
+    }),
+    ("additive", vk::PipelineColorBlendAttachmentState {
+        blend_enable: vk::TRUE,
+        src_color_blend_factor: vk::BlendFactor::ONE,
+        dst_color_blend_factor: vk::BlendFactor::ONE,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ONE,
+        ..NO_BLEND
+    }),
+];
+
+fn lookup<T: Copy>(names: &[(&str, T)], name: &str, what: &str) -> PResult<T> {
+    names.iter().find(|(n, _)| *n == name).map(|(_, v)| *v).ok_or_else(|| {
+        let all = names.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", ");
+        describe(format!("Pipeline description: unknown {} '{}', expected one of: {}", what, name, all))
+    })
+}
+
+fn name_of<T: PartialEq>(names: &[(&'static str, T)], value: &T, what: &str) -> PResult<&'static str> {
+    names.iter().find(|(_, v)| v == value).map(|(n, _)| *n)
+        .ok_or_else(|| describe(format!("Pipeline description: {} has no name in the file format", what)))
+}
+
+fn missing(key: &str) -> &'static str {
+    describe(format!("Pipeline description: '{}' is required", key))
+}
+
+fn get_named<T: Copy>(table: &Table, key: &str, names: &[(&str, T)], default: T) -> PResult<T> {
+    match table.str(key)? {
+        Some(name) => lookup(names, name, key),
+        None => Ok(default),
+    }
+}
+
+fn str_list<'t>(table: &'t Table, key: &str) -> PResult<Option<Vec<&'t str>>> {
+    let Some(items) = table.array(key)? else {
+        return Ok(None);
+    };
+    items.iter()
+        .map(|v| v.as_str().ok_or_else(|| describe(format!(
+            "Pipeline description{}: '{}' must be a list of strings", table.at(key), key
+        ))))
+        .collect::<PResult<Vec<_>>>()
+        .map(Some)
+}
+
+fn parse_format(name: &str) -> PResult<FormatRef> {
+    Ok(match name {
+        "swapchain" => FormatRef::Swapchain,
+        "hdr" => FormatRef::Hdr,
+        "depth" => FormatRef::Depth,
+        fixed => FormatRef::Fixed(lookup(FORMATS, fixed, "format")?),
+    })
+}
+
+fn format_name(format: &FormatRef) -> PResult<&'static str> {
+    Ok(match format {
+        FormatRef::Swapchain => "swapchain",
+        FormatRef::Hdr => "hdr",
+        FormatRef::Depth => "depth",
+        FormatRef::Fixed(f) => name_of(FORMATS, f, "format")?,
+    })
+}
+
+/// "rgba", "r", "rg" и тп
+fn parse_write_mask(mask: &str) -> PResult<vk::ColorComponentFlags> {
+    let mut flags = vk::ColorComponentFlags::empty();
+    for c in mask.chars() {
+        flags |= match c {
+            'r' => vk::ColorComponentFlags::R,
+            'g' => vk::ColorComponentFlags::G,
+            'b' => vk::ColorComponentFlags::B,
+            'a' => vk::ColorComponentFlags::A,
+            _ => return Err(describe(format!("Pipeline description: write_mask '{}' may contain only r, g, b, a", mask))),
+        };
+    }
+    Ok(flags)
+}
+
+fn write_mask_name(flags: vk::ColorComponentFlags) -> String {
+    [(vk::ColorComponentFlags::R, 'r'), (vk::ColorComponentFlags::G, 'g'), (vk::ColorComponentFlags::B, 'b'), (vk::ColorComponentFlags::A, 'a')]
+        .iter()
+        .filter(|(f, _)| flags.contains(*f))
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+fn blend_eq(a: &vk::PipelineColorBlendAttachmentState, b: &vk::PipelineColorBlendAttachmentState) -> bool {
+    a.blend_enable == b.blend_enable
+        && a.src_color_blend_factor == b.src_color_blend_factor
+        && a.dst_color_blend_factor == b.dst_color_blend_factor
+        && a.color_blend_op == b.color_blend_op
+        && a.src_alpha_blend_factor == b.src_alpha_blend_factor
+        && a.dst_alpha_blend_factor == b.dst_alpha_blend_factor
+        && a.alpha_blend_op == b.alpha_blend_op
+        && a.color_write_mask == b.color_write_mask
+}
+
+const FACTOR_KEYS: [&str; 6] = ["src", "dst", "op", "src_alpha", "dst_alpha", "alpha_op"];
+
+fn parse_color(table: &Table) -> PResult<ColorDesc> {
+    let mut allowed = vec!["format", "blend", "write_mask"];
+    allowed.extend(FACTOR_KEYS);
+    table.only_keys(&allowed, "[[color]]")?;
+
+    let format = parse_format(table.str("format")?.ok_or_else(|| missing("color.format"))?)?;
+    let mut blend = get_named(table, "blend", BLEND_PRESETS, NO_BLEND)?;
+    // свой фактор - значит смешивание включено, даже если пресет был opaque
+    if FACTOR_KEYS.iter().any(|k| table.get(k).is_some()) {
+        blend.blend_enable = vk::TRUE;
+    }
+    blend.src_color_blend_factor = get_named(table, "src", BLEND_FACTORS, blend.src_color_blend_factor)?;
+    blend.dst_color_blend_factor = get_named(table, "dst", BLEND_FACTORS, blend.dst_color_blend_factor)?;
+    blend.color_blend_op = get_named(table, "op", BLEND_OPS, blend.color_blend_op)?;
+    blend.src_alpha_blend_factor = get_named(table, "src_alpha", BLEND_FACTORS, blend.src_alpha_blend_factor)?;
+    blend.dst_alpha_blend_factor = get_named(table, "dst_alpha", BLEND_FACTORS, blend.dst_alpha_blend_factor)?;
+    blend.alpha_blend_op = get_named(table, "alpha_op", BLEND_OPS, blend.alpha_blend_op)?;
+    if let Some(mask) = table.str("write_mask")? {
+        blend.color_write_mask = parse_write_mask(mask)?;
+    }
+    Ok(ColorDesc { format, blend })
+}
+
+fn write_color(color: &ColorDesc) -> PResult<Table> {
+    let mut t = Table::new();
+    t.insert("format", Value::Str(format_name(&color.format)?.into()));
+    let b = &color.blend;
+    let mask = b.color_write_mask;
+    let plain = vk::PipelineColorBlendAttachmentState { color_write_mask: vk::ColorComponentFlags::RGBA, ..*b };
+    let preset = if b.blend_enable == vk::FALSE { Some("opaque") } else {
+        BLEND_PRESETS.iter().find(|(_, p)| blend_eq(p, &plain)).map(|(n, _)| *n)
+    };
+    match preset {
+        Some(name) => t.insert("blend", Value::Str(name.into())),
+        None => {
+            // не пресет - все факторы явно
+            t.insert("blend", Value::Str("alpha".into()));
+            let factors = [b.src_color_blend_factor, b.dst_color_blend_factor];
+            let alpha = [b.src_alpha_blend_factor, b.dst_alpha_blend_factor];
+            t.insert("src", Value::Str(name_of(BLEND_FACTORS, &factors[0], "blend factor")?.into()));
+            t.insert("dst", Value::Str(name_of(BLEND_FACTORS, &factors[1], "blend factor")?.into()));
+            t.insert("op", Value::Str(name_of(BLEND_OPS, &b.color_blend_op, "blend op")?.into()));
+            t.insert("src_alpha", Value::Str(name_of(BLEND_FACTORS, &alpha[0], "blend factor")?.into()));
+            t.insert("dst_alpha", Value::Str(name_of(BLEND_FACTORS, &alpha[1], "blend factor")?.into()));
+            t.insert("alpha_op", Value::Str(name_of(BLEND_OPS, &b.alpha_blend_op, "blend op")?.into()));
+        }
+    }
+    if mask != vk::ColorComponentFlags::RGBA {
+        t.insert("write_mask", Value::Str(write_mask_name(mask)));
+    }
+    Ok(t)
+}
+
+impl VertexLayout {
+    fn descriptions(self) -> (Vec<vk::VertexInputBindingDescription>, Vec<vk::VertexInputAttributeDescription>) {
+        match self {
+            VertexLayout::None => (vec![], vec![]),
+            VertexLayout::Mesh => (vec![VulkanVertex::get_binding_description(None)], VulkanVertex::get_attribute_descriptions().to_vec()),
+            VertexLayout::ImGui => (vec![ImGUIVertex::get_binding_description()], ImGUIVertex::get_attribute_descriptions().to_vec()),
+        }
+    }
+}
+
+impl FormatRef {
+    pub fn resolve(&self, target: &PipelineTarget) -> vk::Format {
+        match self {
+            FormatRef::Swapchain => target.swapchain_format,
+            FormatRef::Hdr => HDR_COLOR_FORMAT,
+            FormatRef::Depth => target.depth_format,
+            FormatRef::Fixed(f) => *f,
+        }
+    }
+}
+
+impl PipelineDesc {
+    /// Читает pipelines/<file> с диска, если его там нет - встроенный в бинарник.
+    /// В ошибке разбора имя файла
+    pub fn load(file: &str) -> PResult<Self> {
+        let text = match std::fs::read_to_string(pipeline_dir()?.join(file)) {
+            Ok(text) => text,
+            Err(_) => embedded(file).ok_or_else(|| describe(format!("Pipeline description {} not found", file)))?.to_string(),
+        };
+        Self::from_toml(&text).map_err(|e| describe(format!("{}: {}", file, e)))
+    }
+
+    pub fn from_toml(text: &str) -> PResult<Self> {
+        let root = toml_lite::parse(text)?;
+        root.only_keys(&["shaders", "vertex", "topology", "dynamic", "raster", "depth", "color", "constants"], "pipeline")?;
+
+        let shaders: Vec<String> = str_list(&root, "shaders")?
+            .ok_or_else(|| missing("shaders"))?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        if shaders.is_empty() {
+            return Err(missing("shaders"));
+        }
+        let vertex = get_named(&root, "vertex", VERTEX_LAYOUTS, VertexLayout::Mesh)?;
+        let topology = get_named(&root, "topology", TOPOLOGIES, vk::PrimitiveTopology::TRIANGLE_LIST)?;
+        let dynamic = match str_list(&root, "dynamic")? {
+            Some(names) => names.into_iter().map(|n| lookup(DYNAMIC_STATES, n, "dynamic state")).collect::<PResult<Vec<_>>>()?,
+            None => vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+        };
+
+        let empty = Table::new();
+        let raster_t = root.table("raster")?.unwrap_or(&empty);
+        raster_t.only_keys(&["polygon", "cull", "front_face", "depth_bias"], "[raster]")?;
+        let depth_bias = match raster_t.array("depth_bias")? {
+            None => None,
+            Some([c, s]) => match (c.as_float(), s.as_float()) {
+                (Some(c), Some(s)) => Some([c as f32, s as f32]),
+                _ => return Err(missing("raster.depth_bias = [constant, slope]")),
+            },
+            Some(_) => return Err(missing("raster.depth_bias = [constant, slope]")),
+        };
+        let raster = RasterDesc {
+            polygon: get_named(raster_t, "polygon", POLYGON_MODES, vk::PolygonMode::FILL)?,
+            cull: get_named(raster_t, "cull", CULL_MODES, vk::CullModeFlags::NONE)?,
+            front_face: get_named(raster_t, "front_face", FRONT_FACES, vk::FrontFace::COUNTER_CLOCKWISE)?,
+            depth_bias,
+        };
+
+        let depth = match root.table("depth")? {
+            None => None,
+            Some(t) => {
+                t.only_keys(&["format", "test", "write", "compare"], "[depth]")?;
+                Some(DepthDesc {
+                    format: parse_format(t.str("format")?.unwrap_or("depth"))?,
+                    test: t.bool("test")?.unwrap_or(true),
+                    write: t.bool("write")?.unwrap_or(true),
+                    compare: get_named(t, "compare", COMPARE_OPS, vk::CompareOp::LESS)?,
+                })
+            }
+        };
+
+        let color = root.tables("color")?.into_iter().map(parse_color).collect::<PResult<Vec<_>>>()?;
+
+        // имена проверяются по рефлексии уже при сборке, тут только типы
+        let constants = root.table("constants")?.cloned().unwrap_or_default();
+        for key in constants.keys() {
+            if !matches!(constants.get(key), Some(Value::Bool(_) | Value::Int(_) | Value::Float(_))) {
+                return Err(describe(format!(
+                    "Pipeline description{}: constants.{} must be bool, integer or float", constants.at(key), key
+                )));
+            }
+        }
+
+        Ok(Self { shaders, vertex, topology, raster, depth, color, dynamic, constants })
+    }
+
+    /// Обратно в текст, from_toml(to_toml()) дает то же описание
+    pub fn to_toml(&self) -> PResult<String> {
+        let mut root = Table::new();
+        root.insert("shaders", Value::Array(self.shaders.iter().map(|s| Value::Str(s.clone())).collect()));
+        root.insert("vertex", Value::Str(name_of(VERTEX_LAYOUTS, &self.vertex, "vertex layout")?.into()));
+        root.insert("topology", Value::Str(name_of(TOPOLOGIES, &self.topology, "topology")?.into()));
+        let dynamic = self.dynamic.iter()
+            .map(|d| name_of(DYNAMIC_STATES, d, "dynamic state").map(|n| Value::Str(n.into())))
+            .collect::<PResult<Vec<_>>>()?;
+        root.insert("dynamic", Value::Array(dynamic));
+
+        let mut raster = Table::new();
+        raster.insert("polygon", Value::Str(name_of(POLYGON_MODES, &self.raster.polygon, "polygon mode")?.into()));
+        raster.insert("cull", Value::Str(name_of(CULL_MODES, &self.raster.cull, "cull mode")?.into()));
+        raster.insert("front_face", Value::Str(name_of(FRONT_FACES, &self.raster.front_face, "front face")?.into()));
+        if let Some([c, s]) = self.raster.depth_bias {
+            raster.insert("depth_bias", Value::Array(vec![Value::Float(c as f64), Value::Float(s as f64)]));
+        }
+        root.insert("raster", Value::Table(raster));
+
+        if let Some(d) = &self.depth {
+            let mut depth = Table::new();
+            depth.insert("format", Value::Str(format_name(&d.format)?.into()));
+            depth.insert("test", Value::Bool(d.test));
+            depth.insert("write", Value::Bool(d.write));
+            depth.insert("compare", Value::Str(name_of(COMPARE_OPS, &d.compare, "compare op")?.into()));
+            root.insert("depth", Value::Table(depth));
+        }
+        if !self.color.is_empty() {
+            let colors = self.color.iter().map(|c| write_color(c).map(Value::Table)).collect::<PResult<Vec<_>>>()?;
+            root.insert("color", Value::Array(colors));
+        }
+        if self.constants.keys().next().is_some() {
+            root.insert("constants", Value::Table(self.constants.clone()));
+        }
+        toml_lite::write(&root)
+    }
+
+    /// Форматы color attachments под конкретный target
+    pub fn color_formats(&self, target: &PipelineTarget) -> Vec<vk::Format> {
+        self.color.iter().map(|c| c.format.resolve(target)).collect()
+    }
+
+    /// Шейдеры отдельно, чтобы вызывающий мог проверить свои блоки через check_block
+    pub fn load_shaders(&self, device: &Device) -> PResult<Vec<VulkanShader>> {
+        self.shaders.iter().map(|name| VulkanShader::try_new(device, &shader_path(name)?)).collect()
+    }
+
+    /// Шейдеры нескольких описаний разом, по ним собираются общие layout'ы сцены
+    pub fn load_shaders_of(device: &Device, files: &[&str]) -> PResult<Vec<VulkanShader>> {
+        let mut shaders = vec![];
+        for file in files {
+            shaders.append(&mut Self::load(file)?.load_shaders(device)?);
+        }
+        Ok(shaders)
+    }
+
+    pub fn build(&self, core: &VulkanCore, layout: vk::PipelineLayout, target: &PipelineTarget) -> PResult<VulkanPipeline> {
+        let shaders = self.load_shaders(&core._logical_device)?;
+        self.build_with(core, layout, target, &shaders)
+    }
+
+    /// Сборка из уже загруженных шейдеров (тех же, что в load_shaders)
+    pub fn build_with(
+        &self,
+        core: &VulkanCore,
+        layout: vk::PipelineLayout,
+        target: &PipelineTarget,
+        shaders: &[VulkanShader],
+    ) -> PResult<VulkanPipeline> {
+        self.build_variant(core, layout, target, shaders, &SpecConstants::new())
+    }
+
+    /// [constants] по именам из рефлексии в constant_id. Целое из файла подходит и под uint, и под float
+    pub fn spec_constants(&self, shaders: &[VulkanShader]) -> PResult<SpecConstants> {
+        let mut out = SpecConstants::new();
+        for name in self.constants.keys() {
+            let Some(c) = shaders.iter().flat_map(|s| s.reflection.spec_constants.iter()).find(|c| c.name == name) else {
+                return Err(describe(format!("Pipeline description: no shader declares specialization constant '{}'", name)));
+            };
+            let value = match (c.default, self.constants.get(name)) {
+                (SpecValue::Bool(_), Some(Value::Bool(v))) => SpecValue::Bool(*v),
+                (SpecValue::Int(_), Some(Value::Int(v))) => SpecValue::Int(*v as i32),
+                (SpecValue::UInt(_), Some(Value::Int(v))) if *v >= 0 => SpecValue::UInt(*v as u32),
+                (SpecValue::Float(_), Some(Value::Float(v))) => SpecValue::Float(*v as f32),
+                (SpecValue::Float(_), Some(Value::Int(v))) => SpecValue::Float(*v as f32),
+                (default, value) => return Err(describe(format!(
+                    "Pipeline description: constants.{} = {:?}, shader declares {:?}", name, value, default
+                ))),
+            };
+            out.insert(c.id, value);
+        }
+        Ok(out)
+    }
+
+    /// Вариант пайплайна: spec поверх [constants] из файла
+    pub fn build_variant(
+        &self,
+        core: &VulkanCore,
+        layout: vk::PipelineLayout,
+        target: &PipelineTarget,
+        shaders: &[VulkanShader],
+        spec: &SpecConstants,
+    ) -> PResult<VulkanPipeline> {
+        let spec = self.spec_constants(shaders)?.merged(spec);
+        for shader in shaders {
+            shader.reflection.check_specialization(&spec)?;
+        }
+
+        // стадии и entry point из рефлексии, руками в файле их не пишем
+        let mut stages = vk::ShaderStageFlags::empty();
+        for shader in shaders {
+            if stages.intersects(shader.reflection.stages) {
+                return Err(describe(format!("Pipeline description: two shaders for stage {:?}", shader.reflection.stages)));
+            }
+            stages |= shader.reflection.stages;
+        }
+        if !stages.contains(vk::ShaderStageFlags::VERTEX) || stages.contains(vk::ShaderStageFlags::COMPUTE) {
+            return Err(describe(format!("Pipeline description: graphics pipeline needs a vertex shader and no compute, got {:?}", stages)));
+        }
+        let entry_points = shaders.iter()
+            .map(|s| CString::new(s.reflection.entry_point.as_str()).map_err(|_| "Bad shader entry point"))
+            .collect::<PResult<Vec<_>>>()?;
+        let shader_stages = shaders.iter().zip(entry_points.iter())
+            .map(|(s, entry)| vk::PipelineShaderStageCreateInfo {
+                module: s._shader,
+                p_name: entry.as_ptr(),
+                stage: s.reflection.stages,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let (bindings, attributes) = self.vertex.descriptions();
+        for shader in shaders.iter().filter(|s| s.reflection.stages.contains(vk::ShaderStageFlags::VERTEX)) {
+            shader.reflection.check_vertex_input(&attributes)?;
+        }
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo {
+            vertex_binding_description_count: bindings.len() as u32,
+            p_vertex_binding_descriptions: bindings.as_ptr(),
+            vertex_attribute_description_count: attributes.len() as u32,
+            p_vertex_attribute_descriptions: attributes.as_ptr(),
+            ..Default::default()
+        };
+
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo {
+            polygon_mode: self.raster.polygon,
+            cull_mode: self.raster.cull,
+            front_face: self.raster.front_face,
+            depth_bias_enable: self.raster.depth_bias.is_some() as vk::Bool32,
+            depth_bias_constant_factor: self.raster.depth_bias.map_or(0.0, |b| b[0]),
+            depth_bias_slope_factor: self.raster.depth_bias.map_or(0.0, |b| b[1]),
+            line_width: 1.0,
+            ..Default::default()
+        };
+
+        let depth_stencil = match &self.depth {
+            Some(d) => vk::PipelineDepthStencilStateCreateInfo {
+                depth_test_enable: d.test as vk::Bool32,
+                depth_write_enable: d.write as vk::Bool32,
+                depth_compare_op: d.compare,
+                ..Default::default()
+            },
+            None => vk::PipelineDepthStencilStateCreateInfo::default(),
+        };
+
+        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: self.dynamic.len() as u32,
+            p_dynamic_states: self.dynamic.as_ptr(),
+            ..Default::default()
+        };
+
+        let mut builder = match target.render_pass {
+            Some(render_pass) => VulkanPipelineBuilder::new(core, render_pass, layout).with_subpass(0),
+            None => {
+                let builder = VulkanPipelineBuilder::new_dynamic(core, layout)
+                    .with_color_attachment_formats(self.color_formats(target));
+                match &self.depth {
+                    Some(d) => builder.with_depth_attachment_format(d.format.resolve(target)),
+                    None => builder,
+                }
+            }
+        };
+
+        // depth-only: ни одного attachment, а по умолчанию у билдера один
+        let no_attachment = NO_BLEND;
+        builder = if self.color.is_empty() {
+            builder.with_color_blend(vk::PipelineColorBlendStateCreateInfo {
+                attachment_count: 0,
+                p_attachments: &no_attachment,
+                ..Default::default()
+            })
+        } else {
+            builder.with_color_blend_attachments(self.color.iter().map(|c| c.blend).collect())
+        };
+
+        builder
+            .with_shader_stages(shader_stages)
+            .with_vertex_input(vertex_input)
+            .with_input_assembly(vk::PipelineInputAssemblyStateCreateInfo {
+                topology: self.topology,
+                primitive_restart_enable: vk::FALSE,
+                ..Default::default()
+            })
+            .with_rasterizer(rasterizer)
+            .with_depth_stencil(depth_stencil)
+            .with_dynamic_states(dynamic_state_info)
+            .with_samples(target.samples)
+            .with_specialization(&spec)
+            .build()
+    }
+
+    /// Для hot reload: поменялся сам файл или один из его шейдеров
+    pub fn touched_by(file: &str, changed: &[String]) -> bool {
+        if changed.iter().any(|c| c == file) {
+            return true;
+        }
+        Self::load(file).is_ok_and(|desc| desc.shaders.iter().any(|s| changed.contains(s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_descriptions_parse_and_round_trip() {
+        assert!(!EMBEDDED_PIPELINES.is_empty());
+        for (file, text) in EMBEDDED_PIPELINES {
+            let desc = PipelineDesc::from_toml(text).unwrap_or_else(|e| panic!("{}: {}", file, e));
+            assert_eq!(PipelineDesc::from_toml(&desc.to_toml().unwrap()).unwrap(), desc, "{}", file);
+        }
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let err = PipelineDesc::from_toml("shaders = [\"a.spv\"]\ntopology = \"quads\"\n").unwrap_err();
+        assert!(err.contains("topology") && err.contains("quads"), "{}", err);
+        let err = PipelineDesc::from_toml("vertex = \"mesh\"\n").unwrap_err();
+        assert!(err.contains("'shaders' is required"), "{}", err);
+    }
+}
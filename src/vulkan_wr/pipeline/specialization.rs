@@ -0,0 +1,102 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: specialization constants: значения для layout(constant_id = N) в шейдерах,
+// чтобы константы вроде MAX_LIGHTS задавались из Rust, а не дублировались в GLSL
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+/// Значение одной константы. Все по 4 байта, bool идет как VkBool32
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecValue {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Float(f32),
+}
+
+impl SpecValue {
+    fn bytes(self) -> [u8; 4] {
+        match self {
+            SpecValue::Bool(v) => (v as vk::Bool32).to_ne_bytes(),
+            SpecValue::Int(v) => v.to_ne_bytes(),
+            SpecValue::UInt(v) => v.to_ne_bytes(),
+            SpecValue::Float(v) => v.to_ne_bytes(),
+        }
+    }
+
+    /// Тот же тип в шейдере, значения не сравниваются
+    pub fn same_kind(self, other: SpecValue) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+}
+
+impl From<bool> for SpecValue {
+    fn from(v: bool) -> Self { SpecValue::Bool(v) }
+}
+
+impl From<i32> for SpecValue {
+    fn from(v: i32) -> Self { SpecValue::Int(v) }
+}
+
+impl From<u32> for SpecValue {
+    fn from(v: u32) -> Self { SpecValue::UInt(v) }
+}
+
+impl From<f32> for SpecValue {
+    fn from(v: f32) -> Self { SpecValue::Float(v) }
+}
+
+/// Набор constant_id -> значение. Id, которых нет в шейдере, драйвер просто игнорирует
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpecConstants {
+    values: Vec<(u32, SpecValue)>,  // по id
+}
+
+impl SpecConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, id: u32, value: impl Into<SpecValue>) -> Self {
+        self.insert(id, value.into());
+        self
+    }
+
+    pub fn insert(&mut self, id: u32, value: SpecValue) {
+        match self.values.binary_search_by_key(&id, |(i, _)| *i) {
+            Ok(pos) => self.values[pos].1 = value,
+            Err(pos) => self.values.insert(pos, (id, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, SpecValue)> + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Копия, где значения из over перекрывают наши
+    pub fn merged(&self, over: &SpecConstants) -> Self {
+        let mut out = self.clone();
+        for (id, value) in over.iter() {
+            out.insert(id, value);
+        }
+        out
+    }
+
+    /// Map entries и данные для vk::SpecializationInfo, данные живут у вызывающего
+    pub fn map_entries(&self) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+        let mut entries = Vec::with_capacity(self.values.len());
+        let mut data = Vec::with_capacity(self.values.len() * 4);
+        for (id, value) in self.values.iter() {
+            entries.push(vk::SpecializationMapEntry { constant_id: *id, offset: data.len() as u32, size: 4 });
+            data.extend_from_slice(&value.bytes());
+        }
+        (entries, data)
+    }
+}
@@ -0,0 +1,500 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: маленький TOML без serde, ровно столько, сколько нужно описаниям пайплайнов:
+// key = value, [table], [[array of tables]], строки, числа, bool, массивы, комментарии
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use super::super::error::describe;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+/// Порядок ключей сохраняется, чтобы запись обратно читалась так же, как исходник.
+/// У ключа из файла запомнена строка для ошибок, 0 - вставлен из кода
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    entries: Vec<(String, Value, usize)>,
+}
+
+/// Строки не в счет: то же описание из другого места файла равно
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries.len() == other.entries.len()
+            && self.entries.iter().zip(other.entries.iter()).all(|(a, b)| a.0 == b.0 && a.1 == b.1)
+    }
+}
+
+type TResult<T> = Result<T, &'static str>;
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, v, _)| v)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.entries.iter_mut().find(|(k, _, _)| k == key).map(|(_, v, _)| v)
+    }
+
+    /// Заменяет, если ключ уже есть
+    pub fn insert(&mut self, key: &str, value: Value) {
+        self.insert_at(key, value, 0);
+    }
+
+    fn insert_at(&mut self, key: &str, value: Value, line: usize) {
+        match self.entries.iter_mut().find(|(k, _, _)| k == key) {
+            Some(entry) => { entry.1 = value; entry.2 = line; }
+            None => self.entries.push((key.to_string(), value, line)),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _, _)| k.as_str())
+    }
+
+    /// Строка, где ключ задан в файле
+    pub fn line(&self, key: &str) -> Option<usize> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, _, line)| *line).filter(|&line| line > 0)
+    }
+
+    // типизированные геттеры: нет ключа - None, не тот тип - ошибка
+
+    pub fn str(&self, key: &str) -> TResult<Option<&str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Str(s)) => Ok(Some(s)),
+            Some(_) => Err(self.wrong_type(key, "a string")),
+        }
+    }
+
+    /// Целое тоже подходит, depth_bias = [1, 2] писать естественно
+    pub fn float(&self, key: &str) -> TResult<Option<f64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => v.as_float().map(Some).ok_or_else(|| self.wrong_type(key, "a number")),
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> TResult<Option<bool>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(_) => Err(self.wrong_type(key, "true or false")),
+        }
+    }
+
+    pub fn array(&self, key: &str) -> TResult<Option<&[Value]>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Array(a)) => Ok(Some(a)),
+            Some(_) => Err(self.wrong_type(key, "an array")),
+        }
+    }
+
+    pub fn table(&self, key: &str) -> TResult<Option<&Table>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Table(t)) => Ok(Some(t)),
+            Some(_) => Err(self.wrong_type(key, "a [table]")),
+        }
+    }
+
+    /// [[key]] секции
+    pub fn tables(&self, key: &str) -> TResult<Vec<&Table>> {
+        let Some(items) = self.array(key)? else {
+            return Ok(vec![]);
+        };
+        items.iter()
+            .map(|v| match v {
+                Value::Table(t) => Ok(t),
+                _ => Err(self.wrong_type(key, "[[tables]]")),
+            })
+            .collect()
+    }
+
+    /// Лишний ключ почти всегда опечатка, молча его игнорировать хуже
+    pub fn only_keys(&self, allowed: &[&str], section: &str) -> TResult<()> {
+        for key in self.keys() {
+            if !allowed.contains(&key) {
+                return Err(describe(format!(
+                    "TOML{}: unknown key '{}' in {}, expected one of: {}", self.at(key), key, section, allowed.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn wrong_type(&self, key: &str, expected: &str) -> &'static str {
+        describe(format!("TOML{}: '{}' must be {}", self.at(key), key, expected))
+    }
+
+    /// " line N" для текста ошибки, если ключ из файла
+    pub fn at(&self, key: &str) -> String {
+        self.line(key).map(|line| format!(" line {}", line)).unwrap_or_default()
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(f) => Some(*f),
+            Value::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+fn syntax(line: usize, what: &str) -> &'static str {
+    describe(format!("TOML line {}: {}", line, what))
+}
+
+/// Куда пишут key = value после последнего заголовка
+enum Section {
+    Root,
+    Table(String),
+    ArrayItem(String),
+}
+
+pub fn parse(text: &str) -> TResult<Table> {
+    let mut root = Table::new();
+    let mut section = Section::Root;
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line_no = i + 1;
+        let mut line = strip_comment(lines[i]).trim().to_string();
+        i += 1;
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            let name = bare_key(name.trim()).ok_or_else(|| syntax(line_no, "bad [[table]] name"))?;
+            match root.get_mut(name) {
+                None => root.insert_at(name, Value::Array(vec![Value::Table(Table::new())]), line_no),
+                Some(Value::Array(items)) => items.push(Value::Table(Table::new())),
+                Some(_) => return Err(syntax(line_no, &format!("[[{}]] clashes with a key", name))),
+            }
+            section = Section::ArrayItem(name.to_string());
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = bare_key(name.trim()).ok_or_else(|| syntax(line_no, "bad [table] name"))?;
+            if root.get(name).is_some() {
+                return Err(syntax(line_no, &format!("[{}] is already defined", name)));
+            }
+            root.insert_at(name, Value::Table(Table::new()), line_no);
+            section = Section::Table(name.to_string());
+            continue;
+        }
+
+        let (key, _) = line.split_once('=').ok_or_else(|| syntax(line_no, "expected key = value"))?;
+        let key = bare_key(key.trim()).ok_or_else(|| syntax(line_no, "bad key (only [A-Za-z0-9_-], no dots)"))?.to_string();
+        // массив может продолжаться на следующих строках
+        while bracket_depth(&line) > 0 && i < lines.len() {
+            line.push(' ');
+            line.push_str(strip_comment(lines[i]).trim());
+            i += 1;
+        }
+        let value_text = line.split_once('=').map(|(_, v)| v).unwrap_or("");
+        let mut cursor = Cursor { chars: value_text.chars().collect(), pos: 0 };
+        let value = cursor.value().map_err(|what| syntax(line_no, &format!("'{}': {}", key, what)))?;
+        cursor.skip_ws();
+        if cursor.peek().is_some() {
+            return Err(syntax(line_no, &format!("'{}': garbage after value", key)));
+        }
+
+        let target = match &section {
+            Section::Root => &mut root,
+            Section::Table(name) => match root.get_mut(name) {
+                Some(Value::Table(t)) => t,
+                _ => return Err(syntax(line_no, "lost current table")),
+            },
+            Section::ArrayItem(name) => match root.get_mut(name) {
+                Some(Value::Array(items)) => match items.last_mut() {
+                    Some(Value::Table(t)) => t,
+                    _ => return Err(syntax(line_no, "lost current [[table]]")),
+                },
+                _ => return Err(syntax(line_no, "lost current [[table]]")),
+            },
+        };
+        if let Some(first) = target.line(&key) {
+            return Err(syntax(line_no, &format!("'{}' is already defined at line {}", key, first)));
+        }
+        target.insert_at(&key, value, line_no);
+    }
+    Ok(root)
+}
+
+fn bare_key(key: &str) -> Option<&str> {
+    let ok = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    ok.then_some(key)
+}
+
+/// # вне строки - до конца строки комментарий
+fn strip_comment(line: &str) -> &str {
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_str && !escaped => { escaped = true; continue; }
+            '"' if !escaped => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn bracket_depth(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '\\' if in_str && !escaped => { escaped = true; continue; }
+            '"' if !escaped => in_str = !in_str,
+            '[' if !in_str => depth += 1,
+            ']' if !in_str => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, &'static str> {
+        self.skip_ws();
+        match self.peek() {
+            Some('"') => self.string().map(Value::Str),
+            Some('[') => self.array(),
+            Some(_) => self.scalar(),
+            None => Err("missing value"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let e = self.peek().ok_or("unterminated string")?;
+                    self.pos += 1;
+                    out.push(match e {
+                        'n' => '\n',
+                        't' => '\t',
+                        '"' => '"',
+                        '\\' => '\\',
+                        _ => return Err("unknown escape in string"),
+                    });
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, &'static str> {
+        self.pos += 1;
+        let mut items = vec![];
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err("expected , or ] in array"),
+            }
+        }
+    }
+
+    /// true/false или число до разделителя
+    fn scalar(&mut self) -> Result<Value, &'static str> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && c != ',' && c != ']') {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().filter(|&&c| c != '_').collect();
+        match word.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        if word.contains(['.', 'e', 'E']) || word.contains("inf") || word.contains("nan") {
+            word.parse::<f64>().map(Value::Float).map_err(|_| "bad number")
+        } else {
+            word.parse::<i64>().map(Value::Int).map_err(|_| "bad value (strings need quotes)")
+        }
+    }
+}
+
+/// Обратно в текст: сначала простые ключи, потом [table], потом [[array of tables]].
+/// Таблицы глубже одного уровня описаниям не нужны и не пишутся
+pub fn write(root: &Table) -> TResult<String> {
+    let mut out = String::new();
+    let is_table_array = |v: &Value| matches!(v, Value::Array(items) if !items.is_empty() && items.iter().all(|i| matches!(i, Value::Table(_))));
+
+    write_plain(&mut out, root)?;
+    for (key, value, _) in root.entries.iter() {
+        if let Value::Table(t) = value {
+            out.push_str(&format!("\n[{}]\n", key));
+            write_plain(&mut out, t)?;
+        }
+    }
+    for (key, value, _) in root.entries.iter().filter(|(_, v, _)| is_table_array(v)) {
+        if let Value::Array(items) = value {
+            for item in items {
+                if let Value::Table(t) = item {
+                    out.push_str(&format!("\n[[{}]]\n", key));
+                    write_plain(&mut out, t)?;
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn write_plain(out: &mut String, table: &Table) -> TResult<()> {
+    for (key, value, _) in table.entries.iter() {
+        match value {
+            Value::Table(_) => continue,
+            Value::Array(items) if !items.is_empty() && items.iter().all(|i| matches!(i, Value::Table(_))) => continue,
+            v => out.push_str(&format!("{} = {}\n", key, inline(v)?)),
+        }
+    }
+    Ok(())
+}
+
+fn inline(value: &Value) -> TResult<String> {
+    Ok(match value {
+        Value::Str(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t")),
+        Value::Int(i) => i.to_string(),
+        // {:?} у f64 всегда с точкой: 1.0, а не 1
+        Value::Float(f) => format!("{:?}", f),
+        Value::Bool(b) => b.to_string(),
+        Value::Array(items) => format!("[{}]", items.iter().map(inline).collect::<TResult<Vec<_>>>()?.join(", ")),
+        Value::Table(_) => return Err("Nested tables are not supported by the TOML writer"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_and_plain_keys() {
+        let root = parse("name = \"sphere\"\n\n[raster]\ncull = \"back\"\nwireframe = false\n").unwrap();
+        assert_eq!(root.str("name").unwrap(), Some("sphere"));
+        let raster = root.table("raster").unwrap().unwrap();
+        assert_eq!(raster.str("cull").unwrap(), Some("back"));
+        assert_eq!(raster.bool("wireframe").unwrap(), Some(false));
+        assert_eq!(root.keys().collect::<Vec<_>>(), ["name", "raster"]);
+    }
+
+    #[test]
+    fn arrays_of_tables_keep_order() {
+        let root = parse("[[color]]\nformat = \"hdr\"\n\n[[color]]\nformat = \"swapchain\"\nblend = \"alpha\"\n").unwrap();
+        let color = root.tables("color").unwrap();
+        assert_eq!(color.len(), 2);
+        assert_eq!(color[0].str("format").unwrap(), Some("hdr"));
+        assert_eq!(color[1].str("format").unwrap(), Some("swapchain"));
+        assert_eq!(color[1].str("blend").unwrap(), Some("alpha"));
+        assert!(root.tables("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn comments_are_skipped_outside_strings() {
+        let root = parse("# шапка\nshaders = [\"a.spv\", # первый\n  \"b#.spv\"]  # хвост\n").unwrap();
+        let shaders = root.array("shaders").unwrap().unwrap();
+        assert_eq!(shaders, [Value::Str("a.spv".into()), Value::Str("b#.spv".into())]);
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        let root = parse("s = \"a\\\"b\\\\c\\n\"\ni = -1_000\nf = 0.5\ne = 1e3\nbias = [1, 2.5]\n").unwrap();
+        assert_eq!(root.str("s").unwrap(), Some("a\"b\\c\n"));
+        assert_eq!(root.get("i"), Some(&Value::Int(-1000)));
+        assert_eq!(root.get("f"), Some(&Value::Float(0.5)));
+        assert_eq!(root.get("e"), Some(&Value::Float(1000.0)));
+        // целое годится туда, где ждут число
+        let bias: Vec<f64> = root.array("bias").unwrap().unwrap().iter().filter_map(Value::as_float).collect();
+        assert_eq!(bias, [1.0, 2.5]);
+        assert_eq!(root.float("i").unwrap(), Some(-1000.0));
+    }
+
+    #[test]
+    fn write_reads_back_the_same() {
+        let text = "shaders = [\"a.spv\"]\n\n[depth]\ntest = true\n\n[[color]]\nformat = \"hdr\"\n\n[[color]]\nformat = \"swapchain\"\n";
+        let root = parse(text).unwrap();
+        assert_eq!(write(&root).unwrap(), text);
+    }
+
+    #[test]
+    fn syntax_errors_name_line_and_key() {
+        let err = parse("a = 1\n\nb = [1, 2\n").unwrap_err();
+        assert!(err.contains("line 3") && err.contains("'b'"), "{}", err);
+        let err = parse("a = 1\nc = \"open\n").unwrap_err();
+        assert!(err.contains("line 2") && err.contains("unterminated string"), "{}", err);
+        let err = parse("a = 1\na = 2\n").unwrap_err();
+        assert!(err.contains("line 2") && err.contains("line 1"), "{}", err);
+        let err = parse("[depth]\n[depth]\n").unwrap_err();
+        assert!(err.contains("line 2") && err.contains("[depth]"), "{}", err);
+        let err = parse("mode = fill\n").unwrap_err();
+        assert!(err.contains("strings need quotes"), "{}", err);
+        let err = parse("a.b = 1\n").unwrap_err();
+        assert!(err.contains("line 1") && err.contains("bad key"), "{}", err);
+    }
+
+    #[test]
+    fn type_and_key_errors_name_line_and_key() {
+        let root = parse("# описание\nshaders = \"a.spv\"\ntypo = 1\n").unwrap();
+        let err = root.array("shaders").unwrap_err();
+        assert!(err.contains("line 2") && err.contains("'shaders'"), "{}", err);
+        let err = root.only_keys(&["shaders"], "pipeline").unwrap_err();
+        assert!(err.contains("line 3") && err.contains("'typo'") && err.contains("pipeline"), "{}", err);
+    }
+}
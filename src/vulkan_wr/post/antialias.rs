@@ -0,0 +1,345 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: пост AA: FXAA и TAA (jitter + история + клемп по соседям)
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    pipeline::{pipeline::VulkanPipeline, pipeline_layout::VulkanPipelineLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+    shader::VulkanShader,
+    types::matrix::Matrix,
+};
+use super::fullscreen::{HDR_COLOR_FORMAT, PostTarget, shader_path, build_fullscreen_pipeline, begin_fullscreen, write_sampler, as_bytes};
+
+/// Длина последовательности Halton(2, 3) для jitter
+const TAA_JITTER_PHASES: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AaMode {
+    None,
+    Fxaa,
+    Taa,
+}
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug)]
+pub struct AntiAliasSettings {
+    pub mode: AaMode,
+    pub fxaa_span_max: f32,  // насколько далеко FXAA ищет вдоль края, в пикселях
+    pub fxaa_edge_threshold: f32,  // ниже этого перепада яркости край не трогаем
+    pub taa_feedback: f32,  // вес истории, больше - глаже, но дольше тянется хвост
+}
+
+impl Default for AntiAliasSettings {
+    fn default() -> Self {
+        Self { mode: AaMode::None, fxaa_span_max: 8.0, fxaa_edge_threshold: 0.125, taa_feedback: 0.9 }
+    }
+}
+
+impl AntiAliasSettings {
+    /// # Args
+    /// * `taa_allowed` - TAA читает depth как текстуру, с MSAA глубина multisample и так нельзя
+    pub fn render_ui(&mut self, ui: &imgui::Ui, taa_allowed: bool) {
+        ui.separator();
+        ui.text("Post AA:");
+        if ui.radio_button_bool("Off", self.mode == AaMode::None) { self.mode = AaMode::None; }
+        ui.same_line();
+        if ui.radio_button_bool("FXAA", self.mode == AaMode::Fxaa) { self.mode = AaMode::Fxaa; }
+        ui.same_line();
+        if ui.radio_button_bool("TAA", self.mode == AaMode::Taa) { self.mode = AaMode::Taa; }
+        match self.mode {
+            AaMode::Fxaa => {
+                ui.slider("FXAA span", 1.0, 16.0, &mut self.fxaa_span_max);
+                ui.slider("FXAA edge threshold", 0.03, 0.5, &mut self.fxaa_edge_threshold);
+            },
+            AaMode::Taa => {
+                if !taa_allowed {
+                    ui.text("TAA works only with MSAA 1x");
+                }
+                ui.slider("TAA feedback", 0.5, 0.98, &mut self.taa_feedback);
+            },
+            AaMode::None => {},
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct FxaaPush {
+    texel: [f32; 2],
+    span_max: f32,
+    edge_threshold: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct TaaPush {
+    reproject: [[f32; 4]; 4],  // prev_view_proj * inverse(view_proj), column-major для GLSL
+    texel_jitter: [f32; 4],  // xy - текстель, zw - jitter в uv
+    params: [f32; 4],  // x - feedback, y - 1 если истории нет
+}
+
+pub struct AntiAliasPass {
+    outputs: Vec<PostTarget>,  // по одному на HDR таргет, они же история для TAA
+    fxaa_sets: Vec<VulkanDescriptorSet>,  // [cur]
+    taa_sets: Vec<VulkanDescriptorSet>,  // [cur * n + prev], на диагонали вместо истории текущий HDR
+    descriptor_pool: Option<VulaknDescriptorPool>,  // пересоздается при resize
+
+    sampler: VulkanSampler,
+    depth_sampler: VulkanSampler,
+    fxaa_layout: Vec<VulkanDescriptorSetLayout>,
+    taa_layout: Vec<VulkanDescriptorSetLayout>,
+    fxaa_pipeline_layout: VulkanPipelineLayout,
+    taa_pipeline_layout: VulkanPipelineLayout,
+    fxaa_pipeline: VulkanPipeline,
+    taa_pipeline: VulkanPipeline,
+
+    // состояние истории TAA
+    frame: u32,
+    prev_index: Option<usize>,
+    prev_view_proj: Matrix<4, 4>,
+}
+
+impl AntiAliasPass {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+        // глубину не интерполируем
+        let depth_sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+
+        let sampler_binding = |binding: u32| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        };
+        let fxaa_layout = vec![VulkanDescriptorSetLayout::try_new(device, &vec![sampler_binding(0)], None)?];
+        // 0 - текущий кадр, 1 - depth, 2 - история
+        let taa_layout = vec![VulkanDescriptorSetLayout::try_new(
+            device,
+            &vec![sampler_binding(0), sampler_binding(1), sampler_binding(2)],
+            None
+        )?];
+
+        let fxaa_pipeline_layout = VulkanPipelineLayout::try_new(
+            device,
+            &[fxaa_layout[0].layout],
+            &[vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: size_of::<FxaaPush>() as u32,
+            }]
+        )?;
+        let taa_pipeline_layout = VulkanPipelineLayout::try_new(
+            device,
+            &[taa_layout[0].layout],
+            &[vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: size_of::<TaaPush>() as u32,
+            }]
+        )?;
+
+        let vert = VulkanShader::try_new(device, &shader_path("vert_fullscreen.spv")?)?;
+        let fxaa = VulkanShader::try_new(device, &shader_path("frag_fxaa.spv")?)?;
+        let taa = VulkanShader::try_new(device, &shader_path("frag_taa.spv")?)?;
+
+        let fxaa_pipeline = build_fullscreen_pipeline(
+            &app.core, fxaa_pipeline_layout.layout, &vert, &fxaa, HDR_COLOR_FORMAT, None
+        )?;
+        let taa_pipeline = build_fullscreen_pipeline(
+            &app.core, taa_pipeline_layout.layout, &vert, &taa, HDR_COLOR_FORMAT, None
+        )?;
+
+        Ok(Self {
+            outputs: vec![],
+            fxaa_sets: vec![],
+            taa_sets: vec![],
+            descriptor_pool: None,
+            sampler,
+            depth_sampler,
+            fxaa_layout,
+            taa_layout,
+            fxaa_pipeline_layout,
+            taa_pipeline_layout,
+            fxaa_pipeline,
+            taa_pipeline,
+            frame: 0,
+            prev_index: None,
+            prev_view_proj: Matrix::identity(),
+        })
+    }
+
+    /// Пересоздает выходы и сеты. Вызывается из init_framebuffer после HDR и depth таргетов
+    /// # Args
+    /// * `depth_views` - None если depth нельзя сэмплить (MSAA), тогда TAA недоступен
+    pub fn resize(&mut self, app: &VulkanApp, hdr_views: &[vk::ImageView], depth_views: Option<&[vk::ImageView]>) -> Result<(), &'static str> {
+        self.fxaa_sets = vec![];
+        self.taa_sets = vec![];
+        self.outputs = vec![];
+        self.descriptor_pool = None;
+        self.prev_index = None;
+
+        let n = hdr_views.len() as u32;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: n + n * n * 3,
+            }],
+            n + n * n,
+            None
+        )?;
+
+        for hdr_view in hdr_views {
+            self.outputs.push(PostTarget::try_new(
+                &app.core, app.swapchain.extent, HDR_COLOR_FORMAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+            )?);
+            let set = pool.allocate_descriptor_sets(&self.fxaa_layout)?.remove(0);
+            write_sampler(&pool, &set, 0, self.sampler.sampler, *hdr_view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            self.fxaa_sets.push(set);
+        }
+
+        if let Some(depth_views) = depth_views {
+            for cur in 0..hdr_views.len() {
+                for prev in 0..hdr_views.len() {
+                    let history = if cur == prev { hdr_views[cur] } else { self.outputs[prev].view.view };
+                    let set = pool.allocate_descriptor_sets(&self.taa_layout)?.remove(0);
+                    write_sampler(&pool, &set, 0, self.sampler.sampler, hdr_views[cur], vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+                    write_sampler(&pool, &set, 1, self.depth_sampler.sampler, depth_views[cur], vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+                    write_sampler(&pool, &set, 2, self.sampler.sampler, history, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+                    self.taa_sets.push(set);
+                }
+            }
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    /// Выходы AA, их читает bloom вместо HDR
+    pub fn output_views(&self) -> Vec<vk::ImageView> {
+        self.outputs.iter().map(|o| o.view.view).collect()
+    }
+
+    /// Выход AA картинки index (image, view), для render graph
+    pub fn output(&self, index: usize) -> Option<(vk::Image, vk::ImageView)> {
+        self.outputs.get(index).map(|o| (o.image.image, o.view.view))
+    }
+
+    /// Субпиксельный сдвиг проекции на текущий кадр, уже в NDC (см. Matrix::jitter)
+    pub fn jitter(&self) -> [f32; 2] {
+        let extent = match self.outputs.first() {
+            Some(o) => o.extent(),
+            None => return [0.0, 0.0],
+        };
+        let i = self.frame % TAA_JITTER_PHASES + 1;
+        [
+            (halton(i, 2) - 0.5) * 2.0 / extent.width as f32,
+            (halton(i, 3) - 0.5) * 2.0 / extent.height as f32,
+        ]
+    }
+
+    /// Записывает AA в primary буфер, вне рендерпасса. Результат остается в SHADER_READ_ONLY_OPTIMAL
+    /// # Args
+    /// * `index` - индекс HDR таргета (тот же что при resize)
+    /// * `view_proj` - view_proj этого кадра без jitter
+    /// HDR (и depth для TAA) к этому моменту должны быть доступны для чтения
+    pub fn record(
+        &mut self,
+        cmd: &VulkanCommandBuffer,
+        index: usize,
+        settings: &AntiAliasSettings,
+        mode: AaMode,
+        view_proj: &Matrix<4, 4>,
+    ) -> Result<(), &'static str> {
+        let output = self.outputs.get(index).ok_or("AA is not initialized")?;
+        let extent = output.extent();
+        let texel = [1.0 / extent.width as f32, 1.0 / extent.height as f32];
+
+        unsafe {
+            output.image.discard_to(cmd, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+            begin_fullscreen(cmd, output.view.view, extent, vk::AttachmentLoadOp::DONT_CARE)?;
+
+            match mode {
+                AaMode::Fxaa => {
+                    let push = FxaaPush {
+                        texel,
+                        span_max: settings.fxaa_span_max,
+                        edge_threshold: settings.fxaa_edge_threshold,
+                    };
+                    cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.fxaa_pipeline.pipeline);
+                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.fxaa_pipeline_layout.layout, 0, &[self.fxaa_sets[index].set], &[]);
+                    cmd.push_constants(self.fxaa_pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+                },
+                AaMode::Taa => {
+                    // история валидна только если прошлый кадр тоже был TAA и в другую картинку
+                    let (prev, reset) = match self.prev_index {
+                        Some(p) if p != index => (p, false),
+                        _ => (index, true),
+                    };
+                    let (reproject, reset) = match view_proj.inverse() {
+                        Ok(inv) => (self.prev_view_proj * inv, reset),
+                        Err(_) => (Matrix::identity(), true),
+                    };
+                    let prev = if reset { index } else { prev };
+                    let set = self.taa_sets.get(index * self.outputs.len() + prev).ok_or("TAA needs sampled depth")?;
+                    let jitter = self.jitter();
+                    let push = TaaPush {
+                        reproject: reproject.transpose().data,
+                        texel_jitter: [texel[0], texel[1], jitter[0] * 0.5, jitter[1] * 0.5],
+                        params: [settings.taa_feedback, reset as u32 as f32, 0.0, 0.0],
+                    };
+                    cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.taa_pipeline.pipeline);
+                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.taa_pipeline_layout.layout, 0, &[set.set], &[]);
+                    cmd.push_constants(self.taa_pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+                },
+                AaMode::None => {},
+            }
+            cmd.draw(3, 1, 0, 0);
+            cmd.end_dynamic_rendering()?;
+
+            output.image.transition_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+        }
+
+        if mode == AaMode::Taa {
+            self.prev_index = Some(index);
+            self.prev_view_proj = *view_proj;
+            self.frame = self.frame.wrapping_add(1);
+        } else {
+            self.prev_index = None;
+        }
+        Ok(())
+    }
+}
+
+/// Radical inverse по основанию base, index с 1
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
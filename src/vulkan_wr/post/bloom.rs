@@ -0,0 +1,338 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: bloom (dual filter) + композит с тонмаппингом
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    core::VulkanCore,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    pipeline::{pipeline::VulkanPipeline, pipeline_layout::VulkanPipelineLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+    shader::VulkanShader,
+    reflect::ReflectedLayoutBuilder,
+    hot_reload::touches,
+};
+use super::fullscreen::{HDR_COLOR_FORMAT, PostTarget, shader_path, build_fullscreen_pipeline, begin_fullscreen, write_sampler, as_bytes};
+
+/// Сколько раз делим картинку пополам
+const BLOOM_MIP_COUNT: usize = 6;
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self { enabled: true, threshold: 1.0, knee: 0.5, intensity: 0.6, radius: 1.0 }
+    }
+}
+
+impl BloomSettings {
+    /// Кусок интерфейса, вызывается внутри окна сцены
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        ui.separator();
+        ui.text("Bloom:");
+        ui.checkbox("Bloom enabled", &mut self.enabled);
+        ui.slider("Threshold", 0.0, 5.0, &mut self.threshold);
+        ui.slider("Knee", 0.0, 1.0, &mut self.knee);
+        ui.slider("Intensity", 0.0, 3.0, &mut self.intensity);
+        ui.slider("Radius", 0.1, 3.0, &mut self.radius);
+    }
+}
+
+// одинаковый для всех трех шейдеров
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct BloomPush {
+    texel: [f32; 2],  // размер текселя источника
+    threshold: f32,
+    knee: f32,
+    radius: f32,
+    intensity: f32,
+    prefilter: u32,  // 1 только для первого downsample
+    enabled: u32,
+}
+
+/// Цепочка mip для одного HDR таргета
+struct BloomChain {
+    mips: Vec<PostTarget>,
+    hdr_set: VulkanDescriptorSet,  // sampler на HDR сцену
+    mip_sets: Vec<VulkanDescriptorSet>,  // sampler на mip i
+    composite_set: VulkanDescriptorSet,  // HDR + mip 0
+}
+
+pub struct BloomPass {
+    chains: Vec<BloomChain>,  // по одной на HDR таргет (на картинку свапчейна)
+    descriptor_pool: Option<VulaknDescriptorPool>,  // пересоздается при resize
+
+    sampler: VulkanSampler,
+    single_layout: Vec<VulkanDescriptorSetLayout>,
+    composite_layout: Vec<VulkanDescriptorSetLayout>,
+    pipeline_layout: VulkanPipelineLayout,
+    composite_pipeline_layout: VulkanPipelineLayout,
+    downsample_pipeline: VulkanPipeline,
+    upsample_pipeline: VulkanPipeline,
+    composite_pipeline: VulkanPipeline,
+    output_format: vk::Format,  // для пересоздания композита
+}
+
+impl BloomPass {
+    /// # Args
+    /// * `output_format` - формат куда пишет композит (обычно swapchain)
+    pub fn try_new(app: &VulkanApp, output_format: vk::Format) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+
+        let vert = VulkanShader::try_new(device, &shader_path("vert_fullscreen.spv")?)?;
+        let down = VulkanShader::try_new(device, &shader_path("frag_bloom_down.spv")?)?;
+        let up = VulkanShader::try_new(device, &shader_path("frag_bloom_up.spv")?)?;
+        let composite = VulkanShader::try_new(device, &shader_path("frag_bloom_composite.spv")?)?;
+
+        // layout'ы по шейдерам: down и up делят один, у композита два сэмплера
+        let push_size = size_of::<BloomPush>() as u32;
+        let single = ReflectedLayoutBuilder::new(device, &[&vert, &down, &up])?
+            .push_constant_size(push_size)
+            .build()?;
+        let composite_layouts = ReflectedLayoutBuilder::new(device, &[&vert, &composite])?
+            .push_constant_size(push_size)
+            .build()?;
+        let single_layout = single.set_layouts;
+        let composite_layout = composite_layouts.set_layouts;
+        let pipeline_layout = single.pipeline_layout;
+        let composite_pipeline_layout = composite_layouts.pipeline_layout;
+
+        let (downsample_pipeline, upsample_pipeline, composite_pipeline) = Self::create_pipelines(
+            &app.core, &pipeline_layout, &composite_pipeline_layout, output_format
+        )?;
+
+        Ok(Self {
+            chains: vec![],
+            descriptor_pool: None,
+            sampler,
+            single_layout,
+            composite_layout,
+            pipeline_layout,
+            composite_pipeline_layout,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            output_format,
+        })
+    }
+
+    /// down, up, composite. Шейдеры читаются с диска заново, это же и hot reload
+    fn create_pipelines(
+        core: &VulkanCore,
+        pipeline_layout: &VulkanPipelineLayout,
+        composite_pipeline_layout: &VulkanPipelineLayout,
+        output_format: vk::Format,
+    ) -> Result<(VulkanPipeline, VulkanPipeline, VulkanPipeline), &'static str> {
+        let device = &core._logical_device;
+        let vert = VulkanShader::try_new(device, &shader_path("vert_fullscreen.spv")?)?;
+        let down = VulkanShader::try_new(device, &shader_path("frag_bloom_down.spv")?)?;
+        let up = VulkanShader::try_new(device, &shader_path("frag_bloom_up.spv")?)?;
+        let composite = VulkanShader::try_new(device, &shader_path("frag_bloom_composite.spv")?)?;
+
+        let downsample_pipeline = build_fullscreen_pipeline(
+            core, pipeline_layout.layout, &vert, &down, HDR_COLOR_FORMAT, None
+        )?;
+        // upsample складывается с тем, что уже лежит в mip ниже
+        let upsample_pipeline = build_fullscreen_pipeline(
+            core, pipeline_layout.layout, &vert, &up, HDR_COLOR_FORMAT,
+            Some(vk::PipelineColorBlendAttachmentState {
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+                blend_enable: vk::TRUE,
+                src_color_blend_factor: vk::BlendFactor::ONE,
+                dst_color_blend_factor: vk::BlendFactor::ONE,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                alpha_blend_op: vk::BlendOp::ADD,
+            })
+        )?;
+        let composite_pipeline = build_fullscreen_pipeline(
+            core, composite_pipeline_layout.layout, &vert, &composite, output_format, None
+        )?;
+        Ok((downsample_pipeline, upsample_pipeline, composite_pipeline))
+    }
+
+    /// Hot reload: все три пайплайна разом, они делят вершинник. Девайс должен простаивать
+    pub fn reload_shaders(&mut self, app: &VulkanApp, changed: &[String]) -> Result<(), &'static str> {
+        if !touches(changed, &["vert_fullscreen.spv", "frag_bloom_down.spv", "frag_bloom_up.spv", "frag_bloom_composite.spv"]) {
+            return Ok(());
+        }
+        let (down, up, composite) = Self::create_pipelines(
+            &app.core, &self.pipeline_layout, &self.composite_pipeline_layout, self.output_format
+        )?;
+        self.downsample_pipeline = down;
+        self.upsample_pipeline = up;
+        self.composite_pipeline = composite;
+        Ok(())
+    }
+
+    /// Пересоздает цепочки под новые HDR таргеты. Вызывается из init_framebuffer после их создания
+    /// # Args
+    /// * `hdr_views` - откуда читать сцену: HDR таргеты или выход AA
+    pub fn resize(&mut self, app: &VulkanApp, hdr_views: &[vk::ImageView]) -> Result<(), &'static str> {
+        self.chains = vec![];
+        self.descriptor_pool = None;
+
+        let count = hdr_views.len() as u32;
+        let sets_per_chain = (BLOOM_MIP_COUNT + 2) as u32;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: count * (sets_per_chain + 1),
+            }],
+            count * sets_per_chain,
+            None
+        )?;
+
+        let extent = app.swapchain.extent;
+        for hdr_view in hdr_views {
+            let mut mips = vec![];
+            let mut mip_extent = extent;
+            for _ in 0..BLOOM_MIP_COUNT {
+                mip_extent = vk::Extent2D {
+                    width: (mip_extent.width / 2).max(1),
+                    height: (mip_extent.height / 2).max(1),
+                };
+                mips.push(PostTarget::try_new(
+                    &app.core, mip_extent, HDR_COLOR_FORMAT,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+                )?);
+            }
+
+            let hdr_set = pool.allocate_descriptor_sets(&self.single_layout)?.remove(0);
+            self.write_sampler(&pool, &hdr_set, 0, *hdr_view);
+
+            let mut mip_sets = vec![];
+            for mip in &mips {
+                let set = pool.allocate_descriptor_sets(&self.single_layout)?.remove(0);
+                self.write_sampler(&pool, &set, 0, mip.view.view);
+                mip_sets.push(set);
+            }
+
+            let composite_set = pool.allocate_descriptor_sets(&self.composite_layout)?.remove(0);
+            self.write_sampler(&pool, &composite_set, 0, *hdr_view);
+            self.write_sampler(&pool, &composite_set, 1, mips[0].view.view);
+
+            self.chains.push(BloomChain { mips, hdr_set, mip_sets, composite_set });
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    fn write_sampler(&self, pool: &VulaknDescriptorPool, set: &VulkanDescriptorSet, binding: u32, view: vk::ImageView) {
+        write_sampler(pool, set, binding, self.sampler.sampler, view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    /// Записывает bloom и композит в primary буфер, вне рендерпасса
+    /// # Args
+    /// * `index` - индекс HDR таргета (тот же что при resize)
+    /// * `target_view` - куда писать результат, должен быть в COLOR_ATTACHMENT_OPTIMAL
+    /// HDR таргет к этому моменту должен быть в SHADER_READ_ONLY_OPTIMAL
+    pub fn record(
+        &self,
+        cmd: &VulkanCommandBuffer,
+        index: usize,
+        target_view: vk::ImageView,
+        target_extent: vk::Extent2D,
+        settings: &BloomSettings,
+    ) -> Result<(), &'static str> {
+        let chain = self.chains.get(index).ok_or("Bloom is not initialized")?;
+
+        let mut push = BloomPush {
+            texel: [1.0 / target_extent.width.max(1) as f32, 1.0 / target_extent.height.max(1) as f32],
+            threshold: settings.threshold,
+            knee: settings.knee,
+            radius: settings.radius,
+            intensity: settings.intensity,
+            prefilter: 0,
+            enabled: settings.enabled as u32,
+        };
+
+        unsafe {
+            // содержимое mip нам не нужно, просто готовим к записи
+            for m in chain.mips.iter() {
+                m.image.discard_to(cmd, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+            }
+
+            if settings.enabled {
+                // ---- downsample: hdr -> mip0 -> mip1 -> ...
+                let mut src_extent = target_extent;
+                for (i, mip) in chain.mips.iter().enumerate() {
+                    let src_set = if i == 0 { &chain.hdr_set } else { &chain.mip_sets[i - 1] };
+                    push.texel = [1.0 / src_extent.width.max(1) as f32, 1.0 / src_extent.height.max(1) as f32];
+                    push.prefilter = (i == 0) as u32;
+
+                    begin_fullscreen(cmd, mip.view.view, mip.extent(), vk::AttachmentLoadOp::DONT_CARE)?;
+                    cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.downsample_pipeline.pipeline);
+                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[src_set.set], &[]);
+                    cmd.push_constants(self.pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+                    cmd.draw(3, 1, 0, 0);
+                    cmd.end_dynamic_rendering()?;
+
+                    Self::to_read(cmd, mip);
+                    src_extent = mip.extent();
+                }
+                push.prefilter = 0;
+
+                // ---- upsample: mipN -> mipN-1 (+=) ... -> mip0
+                for i in (1..chain.mips.len()).rev() {
+                    let src = &chain.mips[i];
+                    let dst = &chain.mips[i - 1];
+                    push.texel = [1.0 / src.extent().width as f32, 1.0 / src.extent().height as f32];
+
+                    dst.image.transition_to(
+                        cmd, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                        vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    );
+                    begin_fullscreen(cmd, dst.view.view, dst.extent(), vk::AttachmentLoadOp::LOAD)?;
+                    cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.upsample_pipeline.pipeline);
+                    cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[chain.mip_sets[i].set], &[]);
+                    cmd.push_constants(self.pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+                    cmd.draw(3, 1, 0, 0);
+                    cmd.end_dynamic_rendering()?;
+
+                    Self::to_read(cmd, dst);
+                }
+            } else {
+                // композит все равно читает mip0, layout должен быть валидным
+                Self::to_read(cmd, &chain.mips[0]);
+            }
+
+            // ---- composite + tonemap
+            push.texel = [1.0 / target_extent.width.max(1) as f32, 1.0 / target_extent.height.max(1) as f32];
+            begin_fullscreen(cmd, target_view, target_extent, vk::AttachmentLoadOp::DONT_CARE)?;
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.composite_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.composite_pipeline_layout.layout, 0, &[chain.composite_set.set], &[]);
+            cmd.push_constants(self.composite_pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+            cmd.draw(3, 1, 0, 0);
+            cmd.end_dynamic_rendering()?;
+        }
+        Ok(())
+    }
+
+    fn to_read(cmd: &VulkanCommandBuffer, target: &PostTarget) {
+        target.image.transition_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+    }
+}
@@ -0,0 +1,104 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: общие куски для compute проходов: storage image/buffer, дескрипторы, размер dispatch
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    core::VulkanCore,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet},
+};
+use super::fullscreen::PostTarget;
+
+/// Сколько групп нужно, чтобы покрыть size потоков (хвост добивается лишней группой)
+pub fn group_count(size: u32, local_size: u32) -> u32 {
+    size.div_ceil(local_size.max(1))
+}
+
+/// Группы на картинку extent, для cmd.dispatch.
+/// local_size - reflection.workgroup_size шейдера, один поток на пиксель
+pub fn groups_for_extent(local_size: [u32; 3], extent: vk::Extent2D) -> [u32; 3] {
+    [group_count(extent.width, local_size[0]), group_count(extent.height, local_size[1]), 1]
+}
+
+/// Картинка, в которую compute пишет через imageStore, а потом ее сэмплят.
+/// Не каждый формат умеет STORAGE_IMAGE (sRGB обычно нет), проверяем заранее
+pub fn storage_target(core: &VulkanCore, extent: vk::Extent2D, format: vk::Format) -> Result<PostTarget, &'static str> {
+    let props = unsafe {
+        core._instance.get_physical_device_format_properties(core._physical_device, format)
+    };
+    if !props.optimal_tiling_features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+        println!("compute: {:?} can not be a storage image on this device", format);
+        return Err("Format does not support storage images");
+    }
+    PostTarget::try_new(core, extent, format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED)
+}
+
+/// Пишет один STORAGE_IMAGE, картинка во время dispatch должна быть в GENERAL
+pub fn write_storage_image(
+    pool: &VulaknDescriptorPool,
+    set: &VulkanDescriptorSet,
+    binding: u32,
+    view: vk::ImageView,
+) {
+    let image_info = vk::DescriptorImageInfo {
+        sampler: vk::Sampler::null(),
+        image_view: view,
+        image_layout: vk::ImageLayout::GENERAL,
+    };
+    let write = vk::WriteDescriptorSet {
+        dst_set: set.set,
+        dst_binding: binding,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+        p_image_info: &image_info,
+        ..Default::default()
+    };
+    pool.update_descriptor_sets(&[write], &[]);
+}
+
+/// Пишет один STORAGE_BUFFER. range - vk::WHOLE_SIZE для всего буфера
+pub fn write_storage_buffer(
+    pool: &VulaknDescriptorPool,
+    set: &VulkanDescriptorSet,
+    binding: u32,
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    range: vk::DeviceSize,
+) {
+    let buffer_info = vk::DescriptorBufferInfo { buffer, offset, range };
+    let write = vk::WriteDescriptorSet {
+        dst_set: set.set,
+        dst_binding: binding,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+        p_buffer_info: &buffer_info,
+        ..Default::default()
+    };
+    pool.update_descriptor_sets(&[write], &[]);
+}
+
+/// Барьер на весь буфер между двумя стадиями внутри одного пасса
+/// (fill -> compute, compute -> indirect/vertex, compute -> host)
+pub fn buffer_barrier(
+    cmd: &VulkanCommandBuffer,
+    buffer: vk::Buffer,
+    src: (vk::PipelineStageFlags2, vk::AccessFlags2),
+    dst: (vk::PipelineStageFlags2, vk::AccessFlags2),
+) {
+    let barrier = vk::BufferMemoryBarrier2::default()
+        .src_stage_mask(src.0)
+        .src_access_mask(src.1)
+        .dst_stage_mask(dst.0)
+        .dst_access_mask(dst.1)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE);
+    unsafe { cmd.buffer_barriers2(&[barrier]) };
+}
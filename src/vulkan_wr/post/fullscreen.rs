@@ -0,0 +1,195 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: общие куски для fullscreen пост-эффектов
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    core::VulkanCore,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet},
+    image::{image::{VulkanImage, VulkanImageBuilder}, image_view::{VulkanImageView, VulkanImageViewBuilder}},
+    pipeline::pipeline::{VulkanPipeline, VulkanPipelineBuilder},
+    shader::VulkanShader,
+};
+
+/// Формат, в который рисуется сцена перед пост-обработкой
+pub const HDR_COLOR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+pub use super::super::shader::shader_path;
+
+/// Картинка + view, в которую рисует пост-проход и из которой потом читают
+pub struct PostTarget {
+    pub image: VulkanImage,
+    pub view: VulkanImageView,
+}
+
+impl PostTarget {
+    pub fn try_new(core: &VulkanCore, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags) -> Result<Self, &'static str> {
+        let image = VulkanImageBuilder::new(core)
+            .format(format)
+            .extent(extent.width.max(1), extent.height.max(1), 1)
+            .usage(usage)
+            .build()?;
+        let view = VulkanImageViewBuilder::new(&core._logical_device, image.image)
+            .format(format)
+            .build()?;
+        Ok(Self { image, view })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D { width: self.image.extent.width, height: self.image.extent.height }
+    }
+}
+
+/// Барьер на смену layout одного color image
+pub fn color_barrier(
+    image: vk::Image,
+    old_layout: vk::ImageLayout, new_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags, dst_access_mask: vk::AccessFlags,
+) -> vk::ImageMemoryBarrier<'static> {
+    vk::ImageMemoryBarrier {
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        ..Default::default()
+    }
+}
+
+/// Пайплайн на fullscreen треугольник: без вершин, без глубины, viewport/scissor динамические
+/// # Args
+/// * `blend` - None значит перезапись, иначе состояние смешивания для единственного attachment
+pub fn build_fullscreen_pipeline(
+    core: &VulkanCore,
+    layout: vk::PipelineLayout,
+    vert: &VulkanShader,
+    frag: &VulkanShader,
+    color_format: vk::Format,
+    blend: Option<vk::PipelineColorBlendAttachmentState>,
+) -> Result<VulkanPipeline, &'static str> {
+    let entry_point = std::ffi::CString::new("main").unwrap();
+    let shader_stages = vec![
+        vk::PipelineShaderStageCreateInfo {
+            module: vert._shader,
+            p_name: entry_point.as_ptr(),
+            stage: vk::ShaderStageFlags::VERTEX,
+            ..Default::default()
+        },
+        vk::PipelineShaderStageCreateInfo {
+            module: frag._shader,
+            p_name: entry_point.as_ptr(),
+            stage: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        }
+    ];
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
+        dynamic_state_count: dynamic_states.len() as u32,
+        p_dynamic_states: dynamic_states.as_ptr(),
+        ..Default::default()
+    };
+
+    let blend_attachment = blend.unwrap_or(vk::PipelineColorBlendAttachmentState {
+        color_write_mask: vk::ColorComponentFlags::RGBA,
+        blend_enable: vk::FALSE,
+        ..Default::default()
+    });
+    let color_blend = vk::PipelineColorBlendStateCreateInfo {
+        logic_op_enable: vk::FALSE,
+        attachment_count: 1,
+        p_attachments: &blend_attachment,
+        ..Default::default()
+    };
+
+    VulkanPipelineBuilder::new_dynamic(core, layout)
+        .with_shader_stages(shader_stages)
+        .with_vertex_input(vk::PipelineVertexInputStateCreateInfo::default())
+        .with_dynamic_states(dynamic_state_info)
+        .with_color_blend(color_blend)
+        .with_depth_stencil(vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: vk::FALSE,
+            depth_write_enable: vk::FALSE,
+            ..Default::default()
+        })
+        .with_color_attachment_formats(vec![color_format])
+        .build()
+}
+
+/// Открывает dynamic rendering в один color attachment и ставит viewport/scissor на весь extent
+pub fn begin_fullscreen(
+    cmd: &VulkanCommandBuffer,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+    load_op: vk::AttachmentLoadOp,
+) -> Result<(), &'static str> {
+    let color_attachment = vk::RenderingAttachmentInfo {
+        image_view: view,
+        image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        load_op: load_op,
+        store_op: vk::AttachmentStoreOp::STORE,
+        clear_value: vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 0.0] } },
+        ..Default::default()
+    };
+    let rendering_info = vk::RenderingInfo {
+        render_area: vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent },
+        layer_count: 1,
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment,
+        ..Default::default()
+    };
+    cmd.begin_dynamic_rendering(&rendering_info)?;
+    unsafe {
+        cmd.set_viewport(0, &[vk::Viewport {
+            x: 0.0, y: 0.0,
+            width: extent.width as f32, height: extent.height as f32,
+            min_depth: 0.0, max_depth: 1.0,
+        }]);
+        cmd.set_scissor(0, &[vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent }]);
+    }
+    Ok(())
+}
+
+/// Пишет один COMBINED_IMAGE_SAMPLER. image_info живет до update, в отличие от write_combined_image_sampler
+pub fn write_sampler(
+    pool: &VulaknDescriptorPool,
+    set: &VulkanDescriptorSet,
+    binding: u32,
+    sampler: vk::Sampler,
+    view: vk::ImageView,
+    layout: vk::ImageLayout,
+) {
+    let image_info = vk::DescriptorImageInfo {
+        sampler,
+        image_view: view,
+        image_layout: layout,
+    };
+    let write = vk::WriteDescriptorSet {
+        dst_set: set.set,
+        dst_binding: binding,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        p_image_info: &image_info,
+        ..Default::default()
+    };
+    pool.update_descriptor_sets(&[write], &[]);
+}
+
+/// Push constant структура как байты
+pub fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
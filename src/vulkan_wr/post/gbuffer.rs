@@ -0,0 +1,169 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: G-buffer для deferred пути: таргеты геометрического прохода и сет, через который их читает проход света
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+};
+use super::fullscreen::{PostTarget, write_sampler};
+
+/// Порядок совпадает с location в gbuffer.frag, binding в deferred_light.frag
+/// и [[color]] в pipelines/shadows_gbuffer.toml
+/// 0 - albedo.rgb, 1 - нормаль.xyz + shininess в w, 2 - specular (Ks), 3 - ambient (Ka)
+/// Глубина не своя: берется depth основного прохода, binding 4
+pub const GBUFFER_FORMATS: [vk::Format; 4] = [
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::R8G8B8A8_UNORM,
+];
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug)]
+pub struct ShadingSettings {
+    pub supported: bool,  // сцена умеет deferred, иначе переключатель не показываем
+    pub deferred: bool,
+    pub shadows: bool,  // вариант шейдеров без PCF, карты теней все равно рисуются
+}
+
+impl Default for ShadingSettings {
+    fn default() -> Self {
+        Self { supported: false, deferred: false, shadows: true }
+    }
+}
+
+impl ShadingSettings {
+    /// # Args
+    /// * `msaa_off` - G-buffer single-sample, с MSAA работает только forward
+    /// `supported` приходит от сцены (RenderSettings::keep_scene_support), без него
+    /// у сцены нет вариантов пайплайна и оба переключателя ничего не меняют
+    pub fn render_ui(&mut self, ui: &imgui::Ui, msaa_off: bool) {
+        if !self.supported {
+            return;
+        }
+        ui.separator();
+        ui.text("Shading:");
+        // варианты с тенями и без есть и у forward и у deferred
+        ui.checkbox("Shadows", &mut self.shadows);
+        if ui.radio_button_bool("Forward", !self.deferred) { self.deferred = false; }
+        ui.same_line();
+        if ui.radio_button_bool("Deferred", self.deferred) { self.deferred = true; }
+        if self.deferred && !msaa_off {
+            ui.text("Deferred works only with MSAA 1x");
+        }
+    }
+}
+
+/// Push constant прохода света, совпадает с deferred_light.frag
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DeferredLightPush {
+    pub inv_view_proj: [[f32; 4]; 4],  // с jitter, как рисовалась геометрия
+    pub cam_pos: [f32; 4],
+}
+
+pub struct GBuffer {
+    targets: Vec<Vec<PostTarget>>,  // [картинка свапчейна][GBUFFER_FORMATS]
+    sets: Vec<VulkanDescriptorSet>,
+    descriptor_pool: Option<VulaknDescriptorPool>,  // пересоздается при resize
+    sampler: VulkanSampler,
+    layout: Vec<VulkanDescriptorSetLayout>,
+}
+
+impl GBuffer {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+        // читаем ровно пиксель в пиксель
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..=GBUFFER_FORMATS.len() as u32).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        }).collect();
+        let layout = vec![VulkanDescriptorSetLayout::try_new(device, &bindings, None)?];
+        Ok(Self { targets: vec![], sets: vec![], descriptor_pool: None, sampler, layout })
+    }
+
+    /// Layout сета с G-buffer + depth для прохода света
+    pub fn set_layout(&self) -> vk::DescriptorSetLayout {
+        self.layout[0].layout
+    }
+
+    /// Сет картинки index, None если G-buffer сейчас не создан (MSAA)
+    pub fn set(&self, index: usize) -> Option<vk::DescriptorSet> {
+        self.sets.get(index).map(|s| s.set)
+    }
+
+    /// Пересоздает таргеты. Вызывается из init_framebuffer
+    /// # Args
+    /// * `depth_views` - depth основного прохода с SAMPLED, None - глубина multisample, G-buffer не нужен
+    pub fn resize(&mut self, app: &VulkanApp, depth_views: Option<&[vk::ImageView]>) -> Result<(), &'static str> {
+        self.sets = vec![];
+        self.descriptor_pool = None;
+        self.targets = vec![];
+        let Some(depth_views) = depth_views else {
+            return Ok(());
+        };
+
+        let n = depth_views.len() as u32;
+        let per_set = GBUFFER_FORMATS.len() as u32 + 1;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: n * per_set,
+            }],
+            n,
+            None
+        )?;
+        for depth_view in depth_views {
+            let set = pool.allocate_descriptor_sets(&self.layout)?.remove(0);
+            let mut targets = vec![];
+            for (i, format) in GBUFFER_FORMATS.iter().enumerate() {
+                let target = PostTarget::try_new(
+                    &app.core, app.swapchain.extent, *format,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+                )?;
+                write_sampler(&pool, &set, i as u32, self.sampler.sampler, target.view.view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+                targets.push(target);
+            }
+            write_sampler(&pool, &set, GBUFFER_FORMATS.len() as u32, self.sampler.sampler, *depth_view, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+            self.targets.push(targets);
+            self.sets.push(set);
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    /// Color attachments геометрического прохода, все чистятся в 0
+    pub fn attachments(&self, index: usize) -> Vec<vk::RenderingAttachmentInfo<'static>> {
+        self.targets.get(index).map(|targets| targets.iter().map(|t| vk::RenderingAttachmentInfo {
+            image_view: t.view.view,
+            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            clear_value: vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 0.0] } },
+            ..Default::default()
+        }).collect()).unwrap_or_default()
+    }
+
+    /// Таргеты картинки index (image, view) в порядке GBUFFER_FORMATS, барьеры на них ставит render graph
+    pub fn images(&self, index: usize) -> Vec<(vk::Image, vk::ImageView)> {
+        self.targets.get(index).map(|targets| targets.iter().map(|t| (t.image.image, t.view.view)).collect()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,286 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: гистограмма яркости HDR картинки на compute, пример compute прохода.
+// Результат читается на CPU с отставанием в frames_in_flight кадров
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    buffer::buffer::VulkanBuffer,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    pipeline::{pipeline::{VulkanPipeline, VulkanComputePipelineBuilder}, pipeline_layout::VulkanPipelineLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+    shader::VulkanShader,
+    reflect::ReflectedLayoutBuilder,
+    hot_reload::touches,
+};
+use super::fullscreen::{shader_path, write_sampler, as_bytes};
+use super::compute::{groups_for_extent, write_storage_buffer, buffer_barrier};
+
+/// Должно совпадать с BINS в histogram.comp. bin 0 - почти черное
+pub const HISTOGRAM_BINS: usize = 64;
+
+const HISTOGRAM_SHADER: &str = "comp_histogram.spv";
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug)]
+pub struct HistogramSettings {
+    pub supported: bool,  // проход есть только у сцен на render graph
+    pub enabled: bool,
+    pub min_log: f32,  // log2 яркости, диапазон bin'ов 1..BINS
+    pub max_log: f32,
+}
+
+impl Default for HistogramSettings {
+    fn default() -> Self {
+        Self { supported: false, enabled: true, min_log: -8.0, max_log: 4.0 }
+    }
+}
+
+impl HistogramSettings {
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        if !self.supported {
+            return;
+        }
+        ui.separator();
+        ui.text("Luminance histogram:");
+        ui.checkbox("Histogram enabled", &mut self.enabled);
+        ui.slider("Min log2", -16.0, 0.0, &mut self.min_log);
+        ui.slider("Max log2", 0.0, 16.0, &mut self.max_log);
+    }
+}
+
+/// Что насчитал GPU. valid = false пока ни одного результата не дошло
+#[derive(Clone, Copy, Debug)]
+pub struct HistogramStats {
+    pub bins: [u32; HISTOGRAM_BINS],
+    pub min_log: f32,  // с какими границами считался именно этот результат
+    pub max_log: f32,
+    pub valid: bool,
+}
+
+impl Default for HistogramStats {
+    fn default() -> Self {
+        Self { bins: [0; HISTOGRAM_BINS], min_log: 0.0, max_log: 0.0, valid: false }
+    }
+}
+
+impl HistogramStats {
+    /// Средний log2 яркости без черного bin'а, его обычно ждет auto exposure
+    pub fn average_log(&self) -> Option<f32> {
+        let step = (self.max_log - self.min_log) / (HISTOGRAM_BINS - 2) as f32;
+        let mut count = 0u64;
+        let mut sum = 0.0f64;
+        for (i, &n) in self.bins.iter().enumerate().skip(1) {
+            let center = self.min_log + (i as f32 - 0.5) * step;
+            count += n as u64;
+            sum += n as f64 * center as f64;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some((sum / count as f64) as f32)
+    }
+
+    pub fn render_ui(&self, ui: &imgui::Ui) {
+        if !self.valid {
+            return;
+        }
+        let values: Vec<f32> = self.bins.iter().map(|&n| n as f32).collect();
+        ui.plot_histogram("##luminance", &values)
+            .graph_size([0.0, 60.0])
+            .build();
+        match self.average_log() {
+            Some(avg) => ui.text(format!("avg log2 luminance: {:.2} (black: {})", avg, self.bins[0])),
+            None => ui.text("frame is black"),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct HistogramPush {
+    width: u32,
+    height: u32,
+    min_log: f32,
+    inv_range: f32,
+}
+
+pub struct HistogramPass {
+    source_sets: Vec<VulkanDescriptorSet>,  // sampler на вход, по картинке свапчейна
+    descriptor_pool: Option<VulaknDescriptorPool>,  // под source_sets, пересоздается при resize
+
+    bin_sets: Vec<VulkanDescriptorSet>,  // storage buffer, по кадру в полете
+    bin_buffers: Vec<VulkanBuffer>,
+    bin_pool: VulaknDescriptorPool,
+    pending: Vec<Option<(f32, f32)>>,  // в слоте кадра лежит результат, посчитанный с этими границами
+
+    sampler: VulkanSampler,
+    pipeline: VulkanPipeline,
+    pipeline_layout: VulkanPipelineLayout,  // раньше сетов, как в ReflectedLayouts
+    set_layouts: Vec<VulkanDescriptorSetLayout>,
+    workgroup_size: [u32; 3],  // для перевода extent в группы, из шейдера
+    stats: HistogramStats,
+}
+
+impl HistogramPass {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+
+        let shader = VulkanShader::try_new(device, &shader_path(HISTOGRAM_SHADER)?)?;
+        let layouts = ReflectedLayoutBuilder::new(device, &[&shader])?
+            .push_constant_size(size_of::<HistogramPush>() as u32)
+            .build()?;
+        let workgroup_size = layouts.reflection.workgroup_size.ok_or("Histogram shader has no workgroup size")?;
+        let pipeline = VulkanComputePipelineBuilder::new(&app.core, layouts.pipeline_layout.layout)
+            .with_shader(&shader)
+            .build()?;
+
+        // буферы и их сеты не зависят от свапчейна, создаются один раз
+        let frames = app.frames_in_flight;
+        let bin_pool = VulaknDescriptorPool::try_new(
+            device,
+            &vec![vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: frames }],
+            frames,
+            None
+        )?;
+        let bins_size = (HISTOGRAM_BINS * size_of::<u32>()) as vk::DeviceSize;
+        let mut bin_buffers = vec![];
+        let mut bin_sets = vec![];
+        for _ in 0..frames {
+            let buffer = VulkanBuffer::try_new_storage(&app.core, bins_size, vk::BufferUsageFlags::empty(), true)?;
+            let set = bin_pool.allocate_descriptor_sets(&layouts.set_layouts[1..2])?.remove(0);
+            write_storage_buffer(&bin_pool, &set, 0, buffer.buffer, 0, vk::WHOLE_SIZE);
+            bin_buffers.push(buffer);
+            bin_sets.push(set);
+        }
+
+        Ok(Self {
+            source_sets: vec![],
+            descriptor_pool: None,
+            bin_sets,
+            bin_buffers,
+            bin_pool,
+            pending: vec![None; frames as usize],
+            sampler,
+            pipeline,
+            pipeline_layout: layouts.pipeline_layout,
+            set_layouts: layouts.set_layouts,
+            workgroup_size,
+            stats: HistogramStats::default(),
+        })
+    }
+
+    /// Новый пайплайн, если hot reload пересобрал шейдер. Layout старый, набор binding'ов менять нельзя
+    pub fn reload_shaders(&mut self, app: &VulkanApp, changed: &[String]) -> Result<(), &'static str> {
+        if !touches(changed, &[HISTOGRAM_SHADER]) {
+            return Ok(());
+        }
+        let shader = VulkanShader::try_new(&app.core._logical_device, &shader_path(HISTOGRAM_SHADER)?)?;
+        let workgroup_size = shader.reflection.workgroup_size.ok_or("Histogram shader has no workgroup size")?;
+        self.pipeline = VulkanComputePipelineBuilder::new(&app.core, self.pipeline_layout.layout)
+            .with_shader(&shader)
+            .build()?;
+        self.workgroup_size = workgroup_size;
+        Ok(())
+    }
+
+    /// Пересоздает сеты под новые входы (HDR таргеты или выходы AA), как у bloom
+    pub fn resize(&mut self, app: &VulkanApp, source_views: &[vk::ImageView]) -> Result<(), &'static str> {
+        self.source_sets = vec![];
+        self.descriptor_pool = None;
+
+        let count = source_views.len() as u32;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize { ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: count }],
+            count,
+            None
+        )?;
+        for view in source_views {
+            let set = pool.allocate_descriptor_sets(&self.set_layouts[0..1])?.remove(0);
+            write_sampler(&pool, &set, 0, self.sampler.sampler, *view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            self.source_sets.push(set);
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    /// Забирает результат, который слот frame посчитал frames_in_flight кадров назад.
+    /// Звать после того как планировщик отдал слот (GPU его прошел)
+    pub fn collect(&mut self, frame: usize) -> Result<(), &'static str> {
+        let Some((min_log, max_log)) = self.pending.get_mut(frame).and_then(|p| p.take()) else {
+            return Ok(());
+        };
+        let mut bins = [0u32; HISTOGRAM_BINS];
+        unsafe { self.bin_buffers[frame].mem_read(&mut bins, None)? };
+        self.stats = HistogramStats { bins, min_log, max_log, valid: true };
+        Ok(())
+    }
+
+    pub fn stats(&self) -> HistogramStats {
+        self.stats
+    }
+
+    /// Буфер слота, для render graph (import_buffer)
+    pub fn bins_buffer(&self, frame: usize) -> Option<vk::Buffer> {
+        self.bin_buffers.get(frame).map(|b| b.buffer)
+    }
+
+    /// Обнуляет bin'ы и считает гистограмму источника index в буфер слота frame.
+    /// Источник к этому моменту в SHADER_READ_ONLY_OPTIMAL и виден COMPUTE_SHADER
+    pub fn record(
+        &mut self,
+        cmd: &VulkanCommandBuffer,
+        index: usize,
+        frame: usize,
+        extent: vk::Extent2D,
+        settings: &HistogramSettings,
+    ) -> Result<(), &'static str> {
+        let source_set = self.source_sets.get(index).ok_or("Histogram is not initialized")?;
+        let bin_set = self.bin_sets.get(frame).ok_or("Histogram is not initialized")?;
+        let buffer = self.bin_buffers[frame].buffer;
+
+        // max_log <= min_log из UI не должен делить на ноль
+        let range = (settings.max_log - settings.min_log).max(0.01);
+        let push = HistogramPush {
+            width: extent.width,
+            height: extent.height,
+            min_log: settings.min_log,
+            inv_range: 1.0 / range,
+        };
+        let [x, y, z] = groups_for_extent(self.workgroup_size, extent);
+
+        unsafe {
+            cmd.fill_buffer(buffer, 0, vk::WHOLE_SIZE, 0);
+            buffer_barrier(
+                cmd, buffer,
+                (vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_WRITE),
+                (vk::PipelineStageFlags2::COMPUTE_SHADER, vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE),
+            );
+            cmd.bind_pipeline(vk::PipelineBindPoint::COMPUTE, self.pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, self.pipeline_layout.layout, 0, &[source_set.set, bin_set.set], &[]);
+            cmd.push_constants(self.pipeline_layout.layout, vk::ShaderStageFlags::COMPUTE, 0, as_bytes(&push));
+            cmd.dispatch(x, y, z);
+            // ожидание слота делает запись видимой только вместе с этим барьером
+            buffer_barrier(
+                cmd, buffer,
+                (vk::PipelineStageFlags2::COMPUTE_SHADER, vk::AccessFlags2::SHADER_STORAGE_WRITE),
+                (vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_READ),
+            );
+        }
+        self.pending[frame] = Some((settings.min_log, settings.min_log + range));
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+pub mod fullscreen;
+pub mod bloom;
+pub mod msaa;
+pub mod settings;
+pub mod antialias;
+pub mod ssao;
+pub mod gbuffer;
+pub mod oit;
+pub mod compute;
+pub mod histogram;
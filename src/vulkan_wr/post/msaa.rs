@@ -0,0 +1,55 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: MSAA настройки основного прохода
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+/// Варианты, которые предлагаются в UI
+pub const MSAA_CHOICES: [u32; 4] = [1, 2, 4, 8];
+
+/// Самый большой поддерживаемый sample count, не больше запрошенного
+/// # Args
+/// * `requested` - 1/2/4/8
+/// * `supported` - маска color & depth sample counts устройства
+pub fn clamp_sample_count(requested: u32, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+    MSAA_CHOICES.iter().rev()
+        .map(|n| vk::SampleCountFlags::from_raw(*n))
+        .find(|f| f.as_raw() <= requested && supported.contains(*f))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MsaaSettings {
+    pub samples: u32,  // запрошенное значение, реальное режется по supported
+    pub supported: vk::SampleCountFlags,  // заполняется из VulkanCore
+}
+
+impl Default for MsaaSettings {
+    fn default() -> Self {
+        Self { samples: 4, supported: vk::SampleCountFlags::TYPE_1 }
+    }
+}
+
+impl MsaaSettings {
+    pub fn sample_count(&self) -> vk::SampleCountFlags {
+        clamp_sample_count(self.samples, self.supported)
+    }
+
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        ui.separator();
+        ui.text("MSAA:");
+        for n in MSAA_CHOICES {
+            if n != 1 && !self.supported.contains(vk::SampleCountFlags::from_raw(n)) {
+                continue;
+            }
+            if ui.radio_button_bool(format!("{}x", n), self.samples == n) {
+                self.samples = n;
+            }
+            ui.same_line();
+        }
+        ui.new_line();
+    }
+}
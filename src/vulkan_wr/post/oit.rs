@@ -0,0 +1,253 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: прозрачность: настройки и weighted blended OIT (accum + revealage, потом композит в HDR)
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    pipeline::{pipeline::VulkanPipeline, pipeline_layout::VulkanPipelineLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+    shader::VulkanShader,
+};
+use super::fullscreen::{HDR_COLOR_FORMAT, PostTarget, shader_path, build_fullscreen_pipeline, begin_fullscreen, color_barrier, write_sampler};
+
+/// Порядок совпадает с location в light.frag (режим OIT), binding в oit_composite.frag
+/// и [[color]] в pipelines/light_oit.toml, там же смешивание
+/// 0 - accum: sum(rgb * a * w), sum(a * w), 1 - revealage: prod(1 - a)
+pub const OIT_FORMATS: [vk::Format; 2] = [
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::Format::R16_SFLOAT,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransparencyMode {
+    #[default]
+    Sorted,  // back-to-front с обычным alpha blend внутри основного прохода
+    WeightedOit,
+}
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransparencySettings {
+    pub supported: bool,  // в сцене есть прозрачная очередь, иначе переключатель не показываем
+    pub mode: TransparencyMode,
+}
+
+impl TransparencySettings {
+    /// # Args
+    /// * `msaa_off` - OIT проход рисует с single-sample глубиной основного прохода
+    pub fn render_ui(&mut self, ui: &imgui::Ui, msaa_off: bool) {
+        if !self.supported {
+            return;
+        }
+        ui.separator();
+        ui.text("Transparency:");
+        if ui.radio_button_bool("Sorted", self.mode == TransparencyMode::Sorted) { self.mode = TransparencyMode::Sorted; }
+        ui.same_line();
+        if ui.radio_button_bool("Weighted OIT", self.mode == TransparencyMode::WeightedOit) { self.mode = TransparencyMode::WeightedOit; }
+        if self.mode == TransparencyMode::WeightedOit && !msaa_off {
+            ui.text("OIT works only with MSAA 1x, using sorted");
+        }
+    }
+}
+
+pub struct OitPass {
+    targets: Vec<Vec<PostTarget>>,  // [картинка свапчейна][OIT_FORMATS]
+    sets: Vec<VulkanDescriptorSet>,
+    descriptor_pool: Option<VulaknDescriptorPool>,  // пересоздается при resize
+
+    sampler: VulkanSampler,
+    layout: Vec<VulkanDescriptorSetLayout>,
+    pipeline_layout: VulkanPipelineLayout,
+    composite_pipeline: VulkanPipeline,
+}
+
+impl OitPass {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..OIT_FORMATS.len() as u32).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        }).collect();
+        let layout = vec![VulkanDescriptorSetLayout::try_new(device, &bindings, None)?];
+        let pipeline_layout = VulkanPipelineLayout::try_new(device, &[layout[0].layout], &[])?;
+
+        let vert = VulkanShader::try_new(device, &shader_path("vert_fullscreen.spv")?)?;
+        let frag = VulkanShader::try_new(device, &shader_path("frag_oit_composite.spv")?)?;
+        // средний цвет прозрачных поверх HDR с покрытием 1 - revealage
+        let composite_pipeline = build_fullscreen_pipeline(
+            &app.core, pipeline_layout.layout, &vert, &frag, HDR_COLOR_FORMAT,
+            Some(vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::TRUE,
+                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ZERO,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                alpha_blend_op: vk::BlendOp::ADD,
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+            })
+        )?;
+
+        Ok(Self { targets: vec![], sets: vec![], descriptor_pool: None, sampler, layout, pipeline_layout, composite_pipeline })
+    }
+
+    /// Пересоздает таргеты под новый extent. Вызывается из init_framebuffer
+    pub fn resize(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        self.sets = vec![];
+        self.descriptor_pool = None;
+        self.targets = vec![];
+
+        let n = app.swapchain.images.len() as u32;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: n * OIT_FORMATS.len() as u32,
+            }],
+            n,
+            None
+        )?;
+        for _ in 0..n {
+            let set = pool.allocate_descriptor_sets(&self.layout)?.remove(0);
+            let mut targets = vec![];
+            for (i, format) in OIT_FORMATS.iter().enumerate() {
+                let target = PostTarget::try_new(
+                    &app.core, app.swapchain.extent, *format,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+                )?;
+                write_sampler(&pool, &set, i as u32, self.sampler.sampler, target.view.view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+                targets.push(target);
+            }
+            self.targets.push(targets);
+            self.sets.push(set);
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    /// Открывает dynamic rendering в accum/revealage с depth основного прохода только на чтение.
+    /// Закрывать через cmd.end_dynamic_rendering(), потом composite
+    /// # Args
+    /// * `depth_view` - single-sample depth основного прохода, уже в DEPTH_STENCIL_READ_ONLY_OPTIMAL
+    pub fn begin(&self, cmd: &VulkanCommandBuffer, index: usize, depth_view: vk::ImageView) -> Result<(), &'static str> {
+        let targets = self.targets.get(index).ok_or("OIT is not initialized")?;
+        let extent = targets[0].extent();
+        let barriers: Vec<vk::ImageMemoryBarrier> = targets.iter().map(|t| color_barrier(
+            t.image.image,
+            vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            vk::AccessFlags::empty(), vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        )).collect();
+        unsafe {
+            // FRAGMENT_SHADER: прошлый кадр с этой картинкой читал таргеты в композите
+            // глубину только что дописал основной проход, дальше ее читает depth test
+            cmd.pipeline_barrier(
+                vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+                    ..Default::default()
+                }],
+                &[],
+                barriers.as_slice(),
+            );
+        }
+
+        let clears = [[0.0, 0.0, 0.0, 0.0], [1.0, 1.0, 1.0, 1.0]];
+        let color_attachments: Vec<vk::RenderingAttachmentInfo> = targets.iter().zip(clears).map(|(t, clear)| vk::RenderingAttachmentInfo {
+            image_view: t.view.view,
+            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            clear_value: vk::ClearValue { color: vk::ClearColorValue { float32: clear } },
+            ..Default::default()
+        }).collect();
+        // NONE: глубину не трогаем, ее еще читает TAA
+        let depth_attachment = vk::RenderingAttachmentInfo {
+            image_view: depth_view,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            store_op: vk::AttachmentStoreOp::NONE,
+            ..Default::default()
+        };
+        let rendering_info = vk::RenderingInfo {
+            render_area: vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent },
+            layer_count: 1,
+            color_attachment_count: color_attachments.len() as u32,
+            p_color_attachments: color_attachments.as_ptr(),
+            p_depth_attachment: &depth_attachment,
+            ..Default::default()
+        };
+        cmd.begin_dynamic_rendering(&rendering_info)?;
+        unsafe {
+            cmd.set_viewport(0, &[vk::Viewport {
+                x: 0.0, y: 0.0,
+                width: extent.width as f32, height: extent.height as f32,
+                min_depth: 0.0, max_depth: 1.0,
+            }]);
+            cmd.set_scissor(0, &[vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent }]);
+        }
+        Ok(())
+    }
+
+    /// Смешивает накопленное в HDR. HDR до и после в SHADER_READ_ONLY_OPTIMAL
+    /// # Args
+    /// * `hdr_image`, `hdr_view` - HDR таргет этой же картинки свапчейна
+    pub fn composite(&self, cmd: &VulkanCommandBuffer, index: usize, hdr_image: vk::Image, hdr_view: vk::ImageView) -> Result<(), &'static str> {
+        let targets = self.targets.get(index).ok_or("OIT is not initialized")?;
+        let extent = targets[0].extent();
+        let mut barriers: Vec<vk::ImageMemoryBarrier> = targets.iter().map(|t| color_barrier(
+            t.image.image,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE, vk::AccessFlags::SHADER_READ,
+        )).collect();
+        barriers.push(color_barrier(
+            hdr_image,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE, vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ));
+        unsafe {
+            cmd.pipeline_barrier(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::DependencyFlags::empty(), &[], &[],
+                barriers.as_slice(),
+            );
+            begin_fullscreen(cmd, hdr_view, extent, vk::AttachmentLoadOp::LOAD)?;
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.composite_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout.layout, 0, &[self.sets[index].set], &[]);
+            cmd.draw(3, 1, 0, 0);
+            cmd.end_dynamic_rendering()?;
+
+            cmd.pipeline_barrier(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(), &[], &[],
+                &[color_barrier(
+                    hdr_image,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE, vk::AccessFlags::SHADER_READ,
+                )]
+            );
+        }
+        Ok(())
+    }
+}
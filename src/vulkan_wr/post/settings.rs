@@ -0,0 +1,76 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: все настройки рендера, которые крутятся из ImGui
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{present::PresentSettings, pipeline::permutation::FeatureFlags};
+use super::{antialias::{AaMode, AntiAliasSettings}, bloom::BloomSettings, msaa::MsaaSettings, ssao::SsaoSettings, gbuffer::ShadingSettings, oit::{TransparencySettings, TransparencyMode}, histogram::HistogramSettings};
+
+/// Едет из ImGui в FrameResources одним куском, чтобы не тащить каждое поле по сценам
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderSettings {
+    pub msaa: MsaaSettings,
+    pub bloom: BloomSettings,
+    pub aa: AntiAliasSettings,
+    pub ssao: SsaoSettings,
+    pub shading: ShadingSettings,
+    pub transparency: TransparencySettings,
+    pub histogram: HistogramSettings,
+    pub present: PresentSettings,
+}
+
+impl RenderSettings {
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        self.present.render_ui(ui);
+        self.msaa.render_ui(ui);
+        self.shading.render_ui(ui, self.msaa.sample_count() == vk::SampleCountFlags::TYPE_1);
+        self.transparency.render_ui(ui, self.msaa.sample_count() == vk::SampleCountFlags::TYPE_1);
+        self.aa.render_ui(ui, self.taa_allowed());
+        self.bloom.render_ui(ui);
+        self.ssao.render_ui(ui);
+        self.histogram.render_ui(ui);
+    }
+
+    /// Что умеет сцена ставит FrameResources, а копия в ImGui стартует с default.
+    /// Без этого UI затирает флаги сцены при записи обратно
+    pub fn keep_scene_support(&mut self, scene: &RenderSettings) {
+        self.shading.supported = scene.shading.supported;
+        self.transparency.supported = scene.transparency.supported;
+        self.histogram.supported = scene.histogram.supported;
+    }
+
+    /// TAA нужна single-sample глубина, с MSAA он выключается
+    pub fn taa_allowed(&self) -> bool {
+        self.msaa.sample_count() == vk::SampleCountFlags::TYPE_1
+    }
+
+    /// Deferred реально работает: сцена умеет и MSAA выключен
+    pub fn deferred_active(&self) -> bool {
+        self.shading.supported && self.shading.deferred && self.msaa.sample_count() == vk::SampleCountFlags::TYPE_1
+    }
+
+    /// Фичи кадра для вариантов пайплайна, материал добавляет свои
+    pub fn features(&self) -> FeatureFlags {
+        let mut flags = FeatureFlags::empty();
+        flags.set(FeatureFlags::SHADOWS, self.shading.shadows);
+        flags
+    }
+
+    /// Прозрачные идут отдельным OIT проходом, с MSAA откат на сортировку
+    pub fn oit_active(&self) -> bool {
+        self.transparency.supported && self.transparency.mode == TransparencyMode::WeightedOit
+            && self.msaa.sample_count() == vk::SampleCountFlags::TYPE_1
+    }
+
+    /// Какой AA реально работает в этом кадре
+    pub fn aa_mode(&self) -> AaMode {
+        match self.aa.mode {
+            AaMode::Taa if !self.taa_allowed() => AaMode::None,
+            mode => mode,
+        }
+    }
+}
@@ -0,0 +1,524 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: SSAO: depth префасс, полусфера с шумом, блюр 4x4. Результат читает ambient в шейдерах освещения
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{
+    app::VulkanApp,
+    core::VulkanCore,
+    buffer::buffer::VulkanBuffer,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    descriptor::{descriptor_pool::VulaknDescriptorPool, descriptor_set::VulkanDescriptorSet, descriptor_set_layout::VulkanDescriptorSetLayout},
+    image::{image::{VulkanImage, VulkanImageBuilder}, image_view::{VulkanImageView, VulkanImageViewBuilder}},
+    pipeline::{pipeline::{VulkanPipeline, VulkanPipelineBuilder}, pipeline_layout::VulkanPipelineLayout},
+    sampler::{VulkanSampler, VulkanSamplerBuilder},
+    shader::VulkanShader,
+    types::{matrix::Matrix, vertex::VulkanVertex},
+};
+use super::fullscreen::{PostTarget, shader_path, build_fullscreen_pipeline, begin_fullscreen, write_sampler, as_bytes};
+
+/// Размер ядра в UBO, sample_count из UI не больше этого
+pub const SSAO_MAX_KERNEL: usize = 64;
+/// Шум тайлится по экрану, блюр того же размера его убирает
+const SSAO_NOISE_SIZE: u32 = 4;
+const AO_FORMAT: vk::Format = vk::Format::R8_UNORM;
+/// Своя глубина префасса, без stencil, как у карты теней - ее можно читать в шейдере
+pub const SSAO_DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+/// Параметры из ImGui
+#[derive(Clone, Copy, Debug)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    pub radius: f32,  // радиус полусферы во view space
+    pub bias: f32,  // против самозатенения на плоских поверхностях
+    pub sample_count: u32,
+    pub intensity: f32,  // степень, в которую возводится AO
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self { enabled: true, radius: 0.5, bias: 0.025, sample_count: 32, intensity: 1.0 }
+    }
+}
+
+impl SsaoSettings {
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        ui.separator();
+        ui.text("SSAO:");
+        ui.checkbox("SSAO enabled", &mut self.enabled);
+        ui.slider("AO radius", 0.05, 3.0, &mut self.radius);
+        ui.slider("AO bias", 0.0, 0.2, &mut self.bias);
+        ui.slider("AO samples", 4, SSAO_MAX_KERNEL as u32, &mut self.sample_count);
+        ui.slider("AO intensity", 0.1, 4.0, &mut self.intensity);
+    }
+}
+
+// std140, совпадает с ssao.frag
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct SsaoUniform {
+    proj: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    params: [f32; 4],  // radius, bias, intensity, sample_count
+    noise_scale: [f32; 4],  // xy - экран / размер шума
+    kernel: [[f32; 4]; SSAO_MAX_KERNEL],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct BlurPush {
+    texel: [f32; 2],
+    enabled: u32,  // 0 - просто пишем 1.0, AO выключен
+    _pad: u32,
+}
+
+pub struct SsaoPass {
+    // по одному на картинку свапчейна, как HDR
+    depth_images: Vec<VulkanImage>,
+    depth_views: Vec<VulkanImageView>,
+    raw: Vec<PostTarget>,
+    blurred: Vec<PostTarget>,
+    input_sets: Vec<VulkanDescriptorSet>,  // depth + шум
+    blur_sets: Vec<VulkanDescriptorSet>,  // raw
+    ao_sets: Vec<VulkanDescriptorSet>,  // blurred, их биндят шейдеры освещения
+    descriptor_pool: Option<VulaknDescriptorPool>,  // пересоздается при resize
+
+    // по одному на кадр в полете, пишутся каждый кадр
+    uniform_buffers: Vec<VulkanBuffer>,
+    uniform_sets: Vec<VulkanDescriptorSet>,
+    _uniform_pool: VulaknDescriptorPool,
+
+    kernel: [[f32; 4]; SSAO_MAX_KERNEL],
+    _noise: VulkanImage,
+    noise_view: VulkanImageView,
+    noise_sampler: VulkanSampler,
+    sampler: VulkanSampler,
+    depth_sampler: VulkanSampler,
+    uniform_layout: Vec<VulkanDescriptorSetLayout>,
+    input_layout: Vec<VulkanDescriptorSetLayout>,
+    single_layout: Vec<VulkanDescriptorSetLayout>,
+    ssao_pipeline_layout: VulkanPipelineLayout,
+    blur_pipeline_layout: VulkanPipelineLayout,
+    ssao_pipeline: VulkanPipeline,
+    blur_pipeline: VulkanPipeline,
+}
+
+impl SsaoPass {
+    pub fn try_new(app: &VulkanApp) -> Result<Self, &'static str> {
+        let device = &app.core._logical_device;
+
+        let sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+        let depth_sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+        // шум повторяется по экрану
+        let noise_sampler = VulkanSamplerBuilder::new(device)
+            .address_mode(vk::SamplerAddressMode::REPEAT)
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .anisotropy_enable(vk::FALSE)
+            .max_anisotropy(1.0)
+            .build()?;
+
+        // ---- шум: случайные повороты вокруг нормали, z = 0
+        let mut noise_data = vec![];
+        for i in 0..SSAO_NOISE_SIZE * SSAO_NOISE_SIZE {
+            noise_data.push((random01(i * 2 + 1000) * 255.0) as u8);
+            noise_data.push((random01(i * 2 + 1001) * 255.0) as u8);
+            noise_data.push(128);
+            noise_data.push(255);
+        }
+        let noise = VulkanImageBuilder::new(&app.core)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .format(vk::Format::R8G8B8A8_UNORM)
+            .extent(SSAO_NOISE_SIZE, SSAO_NOISE_SIZE, 1)
+            .build()?;
+        noise.upload_from_slice(app, noise_data.as_slice())?;
+        let noise_view = VulkanImageViewBuilder::new(device, noise.image)
+            .format(vk::Format::R8G8B8A8_UNORM)
+            .build()?;
+
+        // ---- ядро: точки в полусфере z > 0, гуще к центру
+        let mut kernel = [[0.0; 4]; SSAO_MAX_KERNEL];
+        for (i, k) in kernel.iter_mut().enumerate() {
+            let i = i as u32;
+            let v = [
+                random01(i * 3) * 2.0 - 1.0,
+                random01(i * 3 + 1) * 2.0 - 1.0,
+                random01(i * 3 + 2),
+            ];
+            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt().max(1e-4);
+            let t = i as f32 / SSAO_MAX_KERNEL as f32;
+            let scale = (0.1 + 0.9 * t * t) * random01(i + 500).max(0.1);
+            *k = [v[0] / len * scale, v[1] / len * scale, v[2] / len * scale, 0.0];
+        }
+
+        let sampler_binding = |binding: u32| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        };
+        let uniform_layout = vec![VulkanDescriptorSetLayout::try_new(
+            device,
+            &vec![vk::DescriptorSetLayoutBinding {
+                binding: 0,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                ..Default::default()
+            }],
+            None
+        )?];
+        // 0 - depth, 1 - шум
+        let input_layout = vec![VulkanDescriptorSetLayout::try_new(device, &vec![sampler_binding(0), sampler_binding(1)], None)?];
+        let single_layout = vec![VulkanDescriptorSetLayout::try_new(device, &vec![sampler_binding(0)], None)?];
+
+        let ssao_pipeline_layout = VulkanPipelineLayout::try_new(
+            device,
+            &[uniform_layout[0].layout, input_layout[0].layout],
+            &[]
+        )?;
+        let blur_pipeline_layout = VulkanPipelineLayout::try_new(
+            device,
+            &[single_layout[0].layout],
+            &[vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: size_of::<BlurPush>() as u32,
+            }]
+        )?;
+
+        let vert = VulkanShader::try_new(device, &shader_path("vert_fullscreen.spv")?)?;
+        let ssao = VulkanShader::try_new(device, &shader_path("frag_ssao.spv")?)?;
+        let blur = VulkanShader::try_new(device, &shader_path("frag_ssao_blur.spv")?)?;
+        let ssao_pipeline = build_fullscreen_pipeline(&app.core, ssao_pipeline_layout.layout, &vert, &ssao, AO_FORMAT, None)?;
+        let blur_pipeline = build_fullscreen_pipeline(&app.core, blur_pipeline_layout.layout, &vert, &blur, AO_FORMAT, None)?;
+
+        // ---- UBO на каждый кадр в полете
+        let frames = app.frames_in_flight;
+        let uniform_pool = VulaknDescriptorPool::try_new(
+            device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: frames,
+            }],
+            frames,
+            None
+        )?;
+        let mut uniform_buffers = vec![];
+        let mut uniform_sets = vec![];
+        for _ in 0..frames {
+            let buf = VulkanBuffer::try_new(
+                &app.core,
+                size_of::<SsaoUniform>() as vk::DeviceSize,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                None, None, None, None
+            )?;
+            let set = uniform_pool.allocate_descriptor_sets(&uniform_layout)?.remove(0);
+            let (mut write, info) = set.write_buffer(0, buf.buffer, 0, vk::WHOLE_SIZE, vk::DescriptorType::UNIFORM_BUFFER);
+            write.p_buffer_info = &info;
+            uniform_pool.update_descriptor_sets(&[write], &[]);
+            uniform_buffers.push(buf);
+            uniform_sets.push(set);
+        }
+
+        Ok(Self {
+            depth_images: vec![],
+            depth_views: vec![],
+            raw: vec![],
+            blurred: vec![],
+            input_sets: vec![],
+            blur_sets: vec![],
+            ao_sets: vec![],
+            descriptor_pool: None,
+            uniform_buffers,
+            uniform_sets,
+            _uniform_pool: uniform_pool,
+            kernel,
+            _noise: noise,
+            noise_view,
+            noise_sampler,
+            sampler,
+            depth_sampler,
+            uniform_layout,
+            input_layout,
+            single_layout,
+            ssao_pipeline_layout,
+            blur_pipeline_layout,
+            ssao_pipeline,
+            blur_pipeline,
+        })
+    }
+
+    /// Layout сета с AO для шейдеров освещения, один sampler2D в binding 0
+    pub fn ao_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.single_layout[0].layout
+    }
+
+    /// Сет с готовым AO для картинки index, валиден до следующего resize
+    pub fn ao_set(&self, index: usize) -> Option<vk::DescriptorSet> {
+        self.ao_sets.get(index).map(|s| s.set)
+    }
+
+    /// Готовый (заблюренный) AO картинки index (image, view), для render graph
+    pub fn ao_image(&self, index: usize) -> Option<(vk::Image, vk::ImageView)> {
+        self.blurred.get(index).map(|t| (t.image.image, t.view.view))
+    }
+
+    /// Пересоздает таргеты под новый размер свапчейна. Вызывается из init_framebuffer
+    pub fn resize(&mut self, app: &VulkanApp) -> Result<(), &'static str> {
+        self.input_sets = vec![];
+        self.blur_sets = vec![];
+        self.ao_sets = vec![];
+        self.descriptor_pool = None;
+        self.raw = vec![];
+        self.blurred = vec![];
+        self.depth_views = vec![];
+        self.depth_images = vec![];
+
+        let n = app.swapchain.images.len() as u32;
+        let pool = VulaknDescriptorPool::try_new(
+            &app.core._logical_device,
+            &vec![vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: n * 4,
+            }],
+            n * 3,
+            None
+        )?;
+
+        let extent = app.swapchain.extent;
+        for _ in 0..n {
+            let depth_image = VulkanImageBuilder::new(&app.core)
+                .format(SSAO_DEPTH_FORMAT)
+                .extent(extent.width, extent.height, 1)
+                .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+                .build()?;
+            let depth_view = VulkanImageViewBuilder::new(&app.core._logical_device, depth_image.image)
+                .format(SSAO_DEPTH_FORMAT)
+                .aspect(vk::ImageAspectFlags::DEPTH)
+                .build()?;
+            let raw = PostTarget::try_new(&app.core, extent, AO_FORMAT, vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)?;
+            let blurred = PostTarget::try_new(&app.core, extent, AO_FORMAT, vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)?;
+
+            let input_set = pool.allocate_descriptor_sets(&self.input_layout)?.remove(0);
+            write_sampler(&pool, &input_set, 0, self.depth_sampler.sampler, depth_view.view, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+            write_sampler(&pool, &input_set, 1, self.noise_sampler.sampler, self.noise_view.view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            let blur_set = pool.allocate_descriptor_sets(&self.single_layout)?.remove(0);
+            write_sampler(&pool, &blur_set, 0, self.sampler.sampler, raw.view.view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            let ao_set = pool.allocate_descriptor_sets(&self.single_layout)?.remove(0);
+            write_sampler(&pool, &ao_set, 0, self.sampler.sampler, blurred.view.view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+            self.depth_images.push(depth_image);
+            self.depth_views.push(depth_view);
+            self.raw.push(raw);
+            self.blurred.push(blurred);
+            self.input_sets.push(input_set);
+            self.blur_sets.push(blur_set);
+            self.ao_sets.push(ao_set);
+        }
+        self.descriptor_pool = Some(pool);
+        Ok(())
+    }
+
+    /// Открывает depth-only рендеринг префасса, дальше объекты пишут туда свою геометрию
+    /// Закрывать через cmd.end_dynamic_rendering()
+    pub fn begin_prepass(&self, cmd: &VulkanCommandBuffer, index: usize) -> Result<(), &'static str> {
+        let depth_image = self.depth_images.get(index).ok_or("SSAO is not initialized")?;
+        let extent = vk::Extent2D { width: depth_image.extent.width, height: depth_image.extent.height };
+        // прошлый кадр с этой картинкой читал depth в SSAO, картинка это помнит
+        depth_image.discard_to(
+            cmd, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        );
+        let depth_attachment = vk::RenderingAttachmentInfo {
+            image_view: self.depth_views[index].view,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            clear_value: vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+            ..Default::default()
+        };
+        let rendering_info = vk::RenderingInfo {
+            render_area: vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent },
+            layer_count: 1,
+            color_attachment_count: 0,
+            p_depth_attachment: &depth_attachment,
+            ..Default::default()
+        };
+        cmd.begin_dynamic_rendering(&rendering_info)?;
+        unsafe {
+            cmd.set_viewport(0, &[vk::Viewport {
+                x: 0.0, y: 0.0,
+                width: extent.width as f32, height: extent.height as f32,
+                min_depth: 0.0, max_depth: 1.0,
+            }]);
+            cmd.set_scissor(0, &[vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent }]);
+        }
+        Ok(())
+    }
+
+    /// Считает AO и блюрит его. После этого ao_set(index) можно читать во фрагментном шейдере
+    /// # Args
+    /// * `index` - индекс картинки свапчейна
+    /// * `frame` - кадр в полете, выбирает UBO
+    /// * `proj` - та же проекция что рисовала префасс (с jitter если есть)
+    /// Если settings.enabled, префасс для index уже должен быть записан
+    pub fn record(
+        &self,
+        cmd: &VulkanCommandBuffer,
+        index: usize,
+        frame: usize,
+        settings: &SsaoSettings,
+        proj: &Matrix<4, 4>,
+    ) -> Result<(), &'static str> {
+        let raw = self.raw.get(index).ok_or("SSAO is not initialized")?;
+        let blurred = &self.blurred[index];
+        let extent = raw.extent();
+
+        unsafe {
+            if settings.enabled {
+                let inv_proj = proj.inverse()?;
+                let u = SsaoUniform {
+                    proj: proj.transpose().data,
+                    inv_proj: inv_proj.transpose().data,
+                    params: [
+                        settings.radius,
+                        settings.bias,
+                        settings.intensity,
+                        settings.sample_count.clamp(1, SSAO_MAX_KERNEL as u32) as f32,
+                    ],
+                    noise_scale: [
+                        extent.width as f32 / SSAO_NOISE_SIZE as f32,
+                        extent.height as f32 / SSAO_NOISE_SIZE as f32,
+                        0.0, 0.0,
+                    ],
+                    kernel: self.kernel,
+                };
+                self.uniform_buffers.get(frame).ok_or("SSAO frame index out of range")?.mem_copy(&[u], None, None, None)?;
+
+                self.depth_images[index].transition_to(
+                    cmd, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                    vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ,
+                );
+                raw.image.discard_to(cmd, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+                begin_fullscreen(cmd, raw.view.view, extent, vk::AttachmentLoadOp::DONT_CARE)?;
+                cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.ssao_pipeline.pipeline);
+                cmd.bind_descriptor_sets(
+                    vk::PipelineBindPoint::GRAPHICS, self.ssao_pipeline_layout.layout, 0,
+                    &[self.uniform_sets[frame].set, self.input_sets[index].set], &[]
+                );
+                cmd.draw(3, 1, 0, 0);
+                cmd.end_dynamic_rendering()?;
+
+                raw.image.transition_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+            } else {
+                // блюр его не читает, но layout в сете должен совпадать
+                raw.image.discard_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+            }
+
+            // ---- блюр, при выключенном AO просто заливает 1.0
+            let push = BlurPush {
+                texel: [1.0 / extent.width as f32, 1.0 / extent.height as f32],
+                enabled: settings.enabled as u32,
+                _pad: 0,
+            };
+            blurred.image.discard_to(cmd, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+            begin_fullscreen(cmd, blurred.view.view, extent, vk::AttachmentLoadOp::DONT_CARE)?;
+            cmd.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, self.blur_pipeline.pipeline);
+            cmd.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, self.blur_pipeline_layout.layout, 0, &[self.blur_sets[index].set], &[]);
+            cmd.push_constants(self.blur_pipeline_layout.layout, vk::ShaderStageFlags::FRAGMENT, 0, as_bytes(&push));
+            cmd.draw(3, 1, 0, 0);
+            cmd.end_dynamic_rendering()?;
+
+            blurred.image.transition_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+        }
+        Ok(())
+    }
+}
+
+/// Depth-only пайплайн для префасса SSAO
+/// view_proj первым полем в set 0, model - в DrawPush (vert_depth_prepass_push.spv)
+/// # Args
+/// * `layout` - layout основного пайплайна объекта, сеты и push constants с него и берутся
+pub fn build_depth_prepass_pipeline(
+    core: &VulkanCore,
+    layout: vk::PipelineLayout,
+) -> Result<VulkanPipeline, &'static str> {
+    let vert = VulkanShader::try_new(&core._logical_device, &shader_path("vert_depth_prepass_push.spv")?)?;
+    let entry_point = std::ffi::CString::new("main").unwrap();
+    let shader_stages = vec![
+        vk::PipelineShaderStageCreateInfo {
+            module: vert._shader,
+            p_name: entry_point.as_ptr(),
+            stage: vk::ShaderStageFlags::VERTEX,
+            ..Default::default()
+        },
+    ];
+
+    let binding_description = VulkanVertex::get_binding_description(None);
+    let attribute_descriptions = VulkanVertex::get_attribute_descriptions();
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
+        vertex_binding_description_count: 1,
+        p_vertex_binding_descriptions: &binding_description,
+        vertex_attribute_description_count: attribute_descriptions.len() as u32,
+        p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
+        ..Default::default()
+    };
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
+        dynamic_state_count: dynamic_states.len() as u32,
+        p_dynamic_states: dynamic_states.as_ptr(),
+        ..Default::default()
+    };
+
+    // color attachments нет
+    let blend_attachment = vk::PipelineColorBlendAttachmentState::default();
+    let color_blend = vk::PipelineColorBlendStateCreateInfo {
+        attachment_count: 0,
+        p_attachments: &blend_attachment,
+        ..Default::default()
+    };
+
+    VulkanPipelineBuilder::new_dynamic(core, layout)
+        .with_depth_attachment_format(SSAO_DEPTH_FORMAT)
+        .with_shader_stages(shader_stages)
+        .with_vertex_input(vertex_input_info)
+        .with_dynamic_states(dynamic_state_info)
+        .with_color_blend(color_blend)
+        .with_depth_stencil(vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: vk::TRUE,
+            depth_write_enable: vk::TRUE,
+            depth_compare_op: vk::CompareOp::LESS,
+            ..Default::default()
+        })
+        .build()
+}
+
+/// Детерминированный шум в [0, 1), rand крейта нет, а больше и не нужно
+fn random01(i: u32) -> f32 {
+    let mut x = i.wrapping_mul(0x9E37_79B9) ^ 0x85EB_CA6B;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
@@ -0,0 +1,145 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: present mode / VSync из UI + ограничитель FPS
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::time::{Duration, Instant};
+
+use ash::vk;
+
+/// Варианты, которые предлагаются в UI. FIFO по спеке есть всегда
+pub const PRESENT_MODE_CHOICES: [vk::PresentModeKHR; 4] = [
+    vk::PresentModeKHR::FIFO,
+    vk::PresentModeKHR::FIFO_RELAXED,
+    vk::PresentModeKHR::MAILBOX,
+    vk::PresentModeKHR::IMMEDIATE,
+];
+
+/// Последний кусок ожидания крутимся, sleep на нем промахивается
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+pub fn present_mode_name(mode: vk::PresentModeKHR) -> &'static str {
+    match mode {
+        vk::PresentModeKHR::FIFO => "FIFO",
+        vk::PresentModeKHR::FIFO_RELAXED => "FIFO relaxed",
+        vk::PresentModeKHR::MAILBOX => "Mailbox",
+        vk::PresentModeKHR::IMMEDIATE => "Immediate",
+        _ => "Unknown",
+    }
+}
+
+/// Маска по PRESENT_MODE_CHOICES, чтобы настройки оставались Copy
+pub fn present_mode_mask(modes: &[vk::PresentModeKHR]) -> u32 {
+    PRESENT_MODE_CHOICES.iter().enumerate()
+        .filter(|(_, m)| modes.contains(m))
+        .fold(0, |mask, (i, _)| mask | (1 << i))
+}
+
+/// Ждет синхронизацию с экраном: картинки не рвутся, FPS упирается в частоту монитора
+pub fn is_vsync(mode: vk::PresentModeKHR) -> bool {
+    mode == vk::PresentModeKHR::FIFO || mode == vk::PresentModeKHR::FIFO_RELAXED
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PresentSettings {
+    pub mode: vk::PresentModeKHR,  // запрошенный, реальный режется по supported
+    pub fps_limit: u32,  // 0 - без ограничения
+    pub supported: u32,  // маска present_mode_mask, заполняется из свапчейна
+}
+
+impl Default for PresentSettings {
+    fn default() -> Self {
+        Self { mode: vk::PresentModeKHR::FIFO, fps_limit: 0, supported: 1 }
+    }
+}
+
+impl PresentSettings {
+    fn is_supported(&self, mode: vk::PresentModeKHR) -> bool {
+        PRESENT_MODE_CHOICES.iter().position(|m| *m == mode)
+            .is_some_and(|i| self.supported & (1 << i) != 0)
+    }
+
+    /// Что реально отдать свапчейну
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        if self.is_supported(self.mode) { self.mode } else { vk::PresentModeKHR::FIFO }
+    }
+
+    pub fn render_ui(&mut self, ui: &imgui::Ui) {
+        ui.separator();
+        ui.text("Present:");
+        let mut vsync = is_vsync(self.present_mode());
+        if ui.checkbox("VSync", &mut vsync) {
+            // без vsync лучше mailbox: не рвет картинку, но и не ждет монитор
+            self.mode = if vsync {
+                vk::PresentModeKHR::FIFO
+            } else {
+                [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE].into_iter()
+                    .find(|m| self.is_supported(*m))
+                    .unwrap_or(vk::PresentModeKHR::FIFO)
+            };
+        }
+        for mode in PRESENT_MODE_CHOICES {
+            if !self.is_supported(mode) {
+                continue;
+            }
+            if ui.radio_button_bool(present_mode_name(mode), self.present_mode() == mode) {
+                self.mode = mode;
+            }
+            ui.same_line();
+        }
+        ui.new_line();
+        ui.slider("FPS limit (0 - off)", 0, 360, &mut self.fps_limit);
+    }
+}
+
+/// Держит темп кадров: грубо спит, остаток докручивает в цикле.
+/// Дедлайны идут от прошлого дедлайна, а не от конца ожидания, поэтому не уплывают
+pub struct FrameLimiter {
+    period: Option<Duration>,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self { period: None, next_frame: None }
+    }
+
+    /// # Args
+    /// * `fps` - целевой FPS, 0 выключает ограничение
+    pub fn set_target_fps(&mut self, fps: u32) {
+        let period = (fps > 0).then(|| Duration::from_secs_f64(1.0 / fps as f64));
+        if period != self.period {
+            self.period = period;
+            self.next_frame = None;
+        }
+    }
+
+    /// Ждет начала следующего кадра, вызывать раз за кадр
+    pub fn wait(&mut self) {
+        let Some(period) = self.period else {
+            return;
+        };
+        let now = Instant::now();
+        let deadline = self.next_frame.unwrap_or(now);
+        if deadline > now {
+            let left = deadline - now;
+            if left > SPIN_MARGIN {
+                std::thread::sleep(left - SPIN_MARGIN);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+        // отстали больше чем на кадр - не догоняем пачкой, отсчет заново
+        let now = Instant::now();
+        self.next_frame = Some(if now > deadline + period { now + period } else { deadline + period });
+    }
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
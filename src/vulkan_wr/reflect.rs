@@ -0,0 +1,954 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: разбор SPIR-V: дескрипторы, push constants, входы вершин, workgroup,
+// specialization constants и layout'ы, собранные по шейдерам
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::collections::HashMap;
+
+use ash::{vk, Device};
+
+use super::{
+    error::describe,
+    shader::VulkanShader,
+    types::gpu_layout::{GpuField, GpuStruct},
+    descriptor::descriptor_set_layout::VulkanDescriptorSetLayout,
+    pipeline::{pipeline_layout::VulkanPipelineLayout, specialization::{SpecConstants, SpecValue}},
+};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Опкоды и енумы из спеки, только то, что нужно для reflection
+mod spv {
+    pub const OP_NAME: u32 = 5;
+    pub const OP_MEMBER_NAME: u32 = 6;
+    pub const OP_ENTRY_POINT: u32 = 15;
+    pub const OP_EXECUTION_MODE: u32 = 16;
+    pub const OP_TYPE_BOOL: u32 = 20;
+    pub const OP_TYPE_INT: u32 = 21;
+    pub const OP_TYPE_FLOAT: u32 = 22;
+    pub const OP_TYPE_VECTOR: u32 = 23;
+    pub const OP_TYPE_MATRIX: u32 = 24;
+    pub const OP_TYPE_IMAGE: u32 = 25;
+    pub const OP_TYPE_SAMPLER: u32 = 26;
+    pub const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const OP_TYPE_ARRAY: u32 = 28;
+    pub const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const OP_TYPE_STRUCT: u32 = 30;
+    pub const OP_TYPE_POINTER: u32 = 32;
+    pub const OP_CONSTANT: u32 = 43;
+    pub const OP_SPEC_CONSTANT_TRUE: u32 = 48;
+    pub const OP_SPEC_CONSTANT_FALSE: u32 = 49;
+    pub const OP_SPEC_CONSTANT: u32 = 50;
+    pub const OP_VARIABLE: u32 = 59;
+    pub const OP_DECORATE: u32 = 71;
+    pub const OP_MEMBER_DECORATE: u32 = 72;
+    pub const OP_EXECUTION_MODE_ID: u32 = 331;
+    pub const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+    pub const DEC_SPEC_ID: u32 = 1;
+    pub const DEC_BLOCK: u32 = 2;
+    pub const DEC_BUFFER_BLOCK: u32 = 3;
+    pub const DEC_ARRAY_STRIDE: u32 = 6;
+    pub const DEC_MATRIX_STRIDE: u32 = 7;
+    pub const DEC_BUILTIN: u32 = 11;
+    pub const DEC_LOCATION: u32 = 30;
+    pub const DEC_BINDING: u32 = 33;
+    pub const DEC_DESCRIPTOR_SET: u32 = 34;
+    pub const DEC_OFFSET: u32 = 35;
+
+    pub const SC_UNIFORM_CONSTANT: u32 = 0;
+    pub const SC_INPUT: u32 = 1;
+    pub const SC_UNIFORM: u32 = 2;
+    pub const SC_PUSH_CONSTANT: u32 = 9;
+    pub const SC_STORAGE_BUFFER: u32 = 12;
+
+    pub const DIM_BUFFER: u32 = 5;
+    pub const DIM_SUBPASS_DATA: u32 = 6;
+
+    pub const MODE_LOCAL_SIZE: u32 = 17;
+    pub const MODE_LOCAL_SIZE_ID: u32 = 38;
+}
+
+/// Один binding, как его видит шейдер
+#[derive(Clone, Debug)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,  // 0 - runtime массив
+    pub stages: vk::ShaderStageFlags,
+    pub name: String,
+    pub members: Vec<ReflectedMember>,  // для uniform/storage буферов
+}
+
+/// Член блока с offset'ами, которые выставил компилятор шейдера
+#[derive(Clone, Debug)]
+pub struct ReflectedMember {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,  // 0 - runtime массив
+    pub members: Vec<ReflectedMember>,  // если элемент - структура
+}
+
+/// Вход вершинного шейдера
+#[derive(Clone, Copy, Debug)]
+pub struct ReflectedInput {
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+/// layout(constant_id = N) в шейдере, default - значение из GLSL
+#[derive(Clone, Debug)]
+pub struct ReflectedSpecConstant {
+    pub id: u32,
+    pub name: String,
+    pub default: SpecValue,
+}
+
+/// Что шейдер (или несколько стадий после merge) ждет от пайплайна
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub stages: vk::ShaderStageFlags,
+    pub entry_point: String,
+    pub bindings: Vec<ReflectedBinding>,  // отсортированы по (set, binding)
+    pub push_constants: Option<vk::PushConstantRange>,
+    pub vertex_inputs: Vec<ReflectedInput>,  // по location
+    pub workgroup_size: Option<[u32; 3]>,
+    pub spec_constants: Vec<ReflectedSpecConstant>,  // по id
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarKind {
+    Bool,
+    Int,
+    UInt,
+    Float,
+}
+
+#[derive(Clone, Debug)]
+enum SpvType {
+    Scalar { kind: ScalarKind, width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length_id: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    array_stride: Option<u32>,
+    spec_id: Option<u32>,
+    builtin: bool,
+    block: bool,
+    buffer_block: bool,
+}
+
+/// Сырые таблицы модуля, из них потом собирается ShaderReflection
+#[derive(Default)]
+struct Module {
+    execution_model: Option<u32>,
+    entry_point: String,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    types: HashMap<u32, SpvType>,
+    constants: HashMap<u32, u32>,
+    spec_constants: Vec<(u32, u32, u32)>,  // (type, id, значение по умолчанию)
+    decorations: HashMap<u32, Decorations>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    member_matrix_strides: HashMap<(u32, u32), u32>,
+    member_builtin: HashMap<u32, bool>,
+    variables: Vec<(u32, u32, u32)>,  // (type, id, storage)
+    local_size: Option<[u32; 3]>,
+    local_size_ids: Option<[u32; 3]>,
+}
+
+/// Строка в SPIR-V: байты little-endian, с нулем в конце, добита до слова
+fn read_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = vec![];
+    for (i, word) in words.iter().enumerate() {
+        for b in word.to_le_bytes() {
+            if b == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(b);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+fn stage_from_model(model: u32) -> Result<vk::ShaderStageFlags, &'static str> {
+    Ok(match model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        _ => return Err("SPIR-V: unsupported execution model"),
+    })
+}
+
+impl Module {
+    fn parse(code: &[u32]) -> Result<Self, &'static str> {
+        if code.len() < 5 || code[0] != SPIRV_MAGIC {
+            return Err("SPIR-V: bad magic, not a shader module");
+        }
+        let mut m = Module::default();
+        let mut i = 5;
+        while i < code.len() {
+            let count = (code[i] >> 16) as usize;
+            let opcode = code[i] & 0xffff;
+            if count == 0 || i + count > code.len() {
+                return Err("SPIR-V: broken instruction stream");
+            }
+            let a = &code[i + 1..i + count];
+            m.instruction(opcode, a)?;
+            i += count;
+        }
+        if let Some(ids) = m.local_size_ids {
+            let mut size = [1; 3];
+            for (k, id) in ids.iter().enumerate() {
+                size[k] = *m.constants.get(id).ok_or("SPIR-V: LocalSizeId is not a constant")?;
+            }
+            m.local_size = Some(size);
+        }
+        Ok(m)
+    }
+
+    fn instruction(&mut self, opcode: u32, a: &[u32]) -> Result<(), &'static str> {
+        // короче, чем проверять длину в каждой ветке
+        let arg = |n: usize| a.get(n).copied().ok_or("SPIR-V: instruction is too short");
+        match opcode {
+            spv::OP_NAME => {
+                self.names.insert(arg(0)?, read_string(a.get(1..).unwrap_or(&[])).0);
+            }
+            spv::OP_MEMBER_NAME => {
+                self.member_names.insert((arg(0)?, arg(1)?), read_string(a.get(2..).unwrap_or(&[])).0);
+            }
+            spv::OP_ENTRY_POINT => {
+                // в модуле с несколькими точками входа берем первую
+                if self.execution_model.is_none() {
+                    self.execution_model = Some(arg(0)?);
+                    self.entry_point = read_string(a.get(2..).unwrap_or(&[])).0;
+                }
+            }
+            spv::OP_EXECUTION_MODE => {
+                if arg(1)? == spv::MODE_LOCAL_SIZE {
+                    self.local_size = Some([arg(2)?, arg(3)?, arg(4)?]);
+                }
+            }
+            spv::OP_EXECUTION_MODE_ID => {
+                if arg(1)? == spv::MODE_LOCAL_SIZE_ID {
+                    self.local_size_ids = Some([arg(2)?, arg(3)?, arg(4)?]);
+                }
+            }
+            spv::OP_TYPE_BOOL => {
+                self.types.insert(arg(0)?, SpvType::Scalar { kind: ScalarKind::Bool, width: 32 });
+            }
+            spv::OP_TYPE_INT => {
+                let kind = if arg(2)? != 0 { ScalarKind::Int } else { ScalarKind::UInt };
+                self.types.insert(arg(0)?, SpvType::Scalar { kind, width: arg(1)? });
+            }
+            spv::OP_TYPE_FLOAT => {
+                self.types.insert(arg(0)?, SpvType::Scalar { kind: ScalarKind::Float, width: arg(1)? });
+            }
+            spv::OP_TYPE_VECTOR => {
+                self.types.insert(arg(0)?, SpvType::Vector { component: arg(1)?, count: arg(2)? });
+            }
+            spv::OP_TYPE_MATRIX => {
+                self.types.insert(arg(0)?, SpvType::Matrix { column: arg(1)?, count: arg(2)? });
+            }
+            spv::OP_TYPE_IMAGE => {
+                self.types.insert(arg(0)?, SpvType::Image { dim: arg(2)?, sampled: arg(6)? });
+            }
+            spv::OP_TYPE_SAMPLER => {
+                self.types.insert(arg(0)?, SpvType::Sampler);
+            }
+            spv::OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(arg(0)?, SpvType::SampledImage);
+            }
+            spv::OP_TYPE_ARRAY => {
+                self.types.insert(arg(0)?, SpvType::Array { element: arg(1)?, length_id: arg(2)? });
+            }
+            spv::OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(arg(0)?, SpvType::RuntimeArray { element: arg(1)? });
+            }
+            spv::OP_TYPE_STRUCT => {
+                self.types.insert(arg(0)?, SpvType::Struct { members: a.get(1..).unwrap_or(&[]).to_vec() });
+            }
+            spv::OP_TYPE_POINTER => {
+                self.types.insert(arg(0)?, SpvType::Pointer { pointee: arg(2)? });
+            }
+            spv::OP_TYPE_ACCELERATION_STRUCTURE => {
+                self.types.insert(arg(0)?, SpvType::AccelerationStructure);
+            }
+            spv::OP_CONSTANT => {
+                self.constants.insert(arg(1)?, arg(2)?);
+            }
+            spv::OP_SPEC_CONSTANT => {
+                // в constants значение по умолчанию, им может быть размер массива
+                self.constants.insert(arg(1)?, arg(2)?);
+                self.spec_constants.push((arg(0)?, arg(1)?, arg(2)?));
+            }
+            spv::OP_SPEC_CONSTANT_TRUE | spv::OP_SPEC_CONSTANT_FALSE => {
+                self.spec_constants.push((arg(0)?, arg(1)?, (opcode == spv::OP_SPEC_CONSTANT_TRUE) as u32));
+            }
+            spv::OP_VARIABLE => {
+                self.variables.push((arg(0)?, arg(1)?, arg(2)?));
+            }
+            spv::OP_DECORATE => {
+                let dec = self.decorations.entry(arg(0)?).or_default();
+                match arg(1)? {
+                    spv::DEC_DESCRIPTOR_SET => dec.set = Some(arg(2)?),
+                    spv::DEC_BINDING => dec.binding = Some(arg(2)?),
+                    spv::DEC_LOCATION => dec.location = Some(arg(2)?),
+                    spv::DEC_ARRAY_STRIDE => dec.array_stride = Some(arg(2)?),
+                    spv::DEC_SPEC_ID => dec.spec_id = Some(arg(2)?),
+                    spv::DEC_BUILTIN => dec.builtin = true,
+                    spv::DEC_BLOCK => dec.block = true,
+                    spv::DEC_BUFFER_BLOCK => dec.buffer_block = true,
+                    _ => {}
+                }
+            }
+            spv::OP_MEMBER_DECORATE => {
+                let key = (arg(0)?, arg(1)?);
+                match arg(2)? {
+                    spv::DEC_OFFSET => { self.member_offsets.insert(key, arg(3)?); }
+                    spv::DEC_MATRIX_STRIDE => { self.member_matrix_strides.insert(key, arg(3)?); }
+                    spv::DEC_BUILTIN => { self.member_builtin.insert(key.0, true); }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn ty(&self, id: u32) -> Result<&SpvType, &'static str> {
+        self.types.get(&id).ok_or("SPIR-V: reference to unknown type")
+    }
+
+    fn decor(&self, id: u32) -> Decorations {
+        self.decorations.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Размер типа в байтах по явным offset/stride. Runtime массив - 0
+    fn size_of(&self, id: u32, matrix_stride: Option<u32>) -> Result<u32, &'static str> {
+        Ok(match self.ty(id)? {
+            SpvType::Scalar { width, .. } => width / 8,
+            SpvType::Vector { component, count } => self.size_of(*component, None)? * count,
+            SpvType::Matrix { column, count } => match matrix_stride {
+                Some(stride) => stride * count,
+                None => self.size_of(*column, None)? * count,
+            },
+            SpvType::Array { element, length_id } => {
+                let length = *self.constants.get(length_id).ok_or("SPIR-V: array length is not a constant")?;
+                match self.decor(id).array_stride {
+                    Some(stride) => stride * length,
+                    None => self.size_of(*element, matrix_stride)? * length,
+                }
+            }
+            SpvType::RuntimeArray { .. } => 0,
+            SpvType::Struct { .. } => self.struct_extent(id)?.1,
+            _ => return Err("SPIR-V: size of an opaque type"),
+        })
+    }
+
+    /// (первый offset, конец последнего члена) структуры
+    fn struct_extent(&self, id: u32) -> Result<(u32, u32), &'static str> {
+        let SpvType::Struct { members } = self.ty(id)? else {
+            return Err("SPIR-V: expected a struct");
+        };
+        let mut start = u32::MAX;
+        let mut end = 0;
+        for (mi, member) in members.iter().enumerate() {
+            let key = (id, mi as u32);
+            let offset = *self.member_offsets.get(&key).ok_or("SPIR-V: struct member without Offset")?;
+            start = start.min(offset);
+            end = end.max(offset + self.size_of(*member, self.member_matrix_strides.get(&key).copied())?);
+        }
+        Ok((if start == u32::MAX { 0 } else { start }, end))
+    }
+
+    /// Члены структуры, вложенные структуры (и массивы из них) раскрываются
+    fn members(&self, id: u32) -> Result<Vec<ReflectedMember>, &'static str> {
+        let SpvType::Struct { members } = self.ty(id)? else {
+            return Ok(vec![]);
+        };
+        let mut out = vec![];
+        for (mi, member) in members.iter().enumerate() {
+            let key = (id, mi as u32);
+            let (element, _) = self.unwrap_array(*member)?;
+            out.push(ReflectedMember {
+                name: self.member_names.get(&key).cloned().unwrap_or_default(),
+                offset: *self.member_offsets.get(&key).ok_or("SPIR-V: struct member without Offset")?,
+                size: self.size_of(*member, self.member_matrix_strides.get(&key).copied())?,
+                members: self.members(element)?,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Снимает массивы: (тип элемента, количество). 0 - runtime массив
+    fn unwrap_array(&self, mut id: u32) -> Result<(u32, u32), &'static str> {
+        let mut count = 1;
+        loop {
+            match self.ty(id)? {
+                SpvType::Array { element, length_id } => {
+                    count *= *self.constants.get(length_id).ok_or("SPIR-V: array length is not a constant")?;
+                    id = *element;
+                }
+                SpvType::RuntimeArray { element } => {
+                    count = 0;
+                    id = *element;
+                }
+                _ => return Ok((id, count)),
+            }
+        }
+    }
+
+    fn descriptor_type(&self, storage: u32, id: u32) -> Result<Option<vk::DescriptorType>, &'static str> {
+        Ok(Some(match self.ty(id)? {
+            SpvType::SampledImage => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            SpvType::Sampler => vk::DescriptorType::SAMPLER,
+            SpvType::AccelerationStructure => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            // sampled: 1 - через sampler, 2 - storage
+            SpvType::Image { dim, sampled } => match (*dim, *sampled) {
+                (spv::DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                (spv::DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (spv::DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                _ => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            // старый glsl помечает SSBO как Uniform + BufferBlock
+            SpvType::Struct { .. } if storage == spv::SC_STORAGE_BUFFER || self.decor(id).buffer_block =>
+                vk::DescriptorType::STORAGE_BUFFER,
+            SpvType::Struct { .. } if storage == spv::SC_UNIFORM => vk::DescriptorType::UNIFORM_BUFFER,
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Значение по умолчанию в типе константы. 64 бит в SpecValue нет
+    fn spec_default(&self, ty: u32, raw: u32) -> Result<SpecValue, &'static str> {
+        Ok(match self.ty(ty)? {
+            SpvType::Scalar { kind: ScalarKind::Bool, .. } => SpecValue::Bool(raw != 0),
+            SpvType::Scalar { kind: ScalarKind::Int, width: 32 } => SpecValue::Int(raw as i32),
+            SpvType::Scalar { kind: ScalarKind::UInt, width: 32 } => SpecValue::UInt(raw),
+            SpvType::Scalar { kind: ScalarKind::Float, width: 32 } => SpecValue::Float(f32::from_bits(raw)),
+            _ => return Err("SPIR-V: specialization constant is not a 32-bit scalar"),
+        })
+    }
+
+    fn vertex_format(&self, id: u32) -> Result<vk::Format, &'static str> {
+        let (kind, width, count) = match self.ty(id)? {
+            SpvType::Scalar { kind, width } => (*kind, *width, 1),
+            SpvType::Vector { component, count } => match self.ty(*component)? {
+                SpvType::Scalar { kind, width } => (*kind, *width, *count),
+                _ => return Ok(vk::Format::UNDEFINED),
+            },
+            // матрицы занимают несколько location, для проверки хватит первого
+            _ => return Ok(vk::Format::UNDEFINED),
+        };
+        use vk::Format as F;
+        Ok(match (kind, width, count) {
+            (ScalarKind::Float, 32, 1) => F::R32_SFLOAT,
+            (ScalarKind::Float, 32, 2) => F::R32G32_SFLOAT,
+            (ScalarKind::Float, 32, 3) => F::R32G32B32_SFLOAT,
+            (ScalarKind::Float, 32, 4) => F::R32G32B32A32_SFLOAT,
+            (ScalarKind::Int, 32, 1) => F::R32_SINT,
+            (ScalarKind::Int, 32, 2) => F::R32G32_SINT,
+            (ScalarKind::Int, 32, 3) => F::R32G32B32_SINT,
+            (ScalarKind::Int, 32, 4) => F::R32G32B32A32_SINT,
+            (ScalarKind::UInt, 32, 1) => F::R32_UINT,
+            (ScalarKind::UInt, 32, 2) => F::R32G32_UINT,
+            (ScalarKind::UInt, 32, 3) => F::R32G32B32_UINT,
+            (ScalarKind::UInt, 32, 4) => F::R32G32B32A32_UINT,
+            (ScalarKind::Float, 64, 1) => F::R64_SFLOAT,
+            (ScalarKind::Float, 64, 2) => F::R64G64_SFLOAT,
+            (ScalarKind::Float, 64, 3) => F::R64G64B64_SFLOAT,
+            (ScalarKind::Float, 64, 4) => F::R64G64B64A64_SFLOAT,
+            _ => F::UNDEFINED,
+        })
+    }
+}
+
+/// Совместимы, если шейдер и Rust расходятся только в dynamic
+fn same_kind(shader: vk::DescriptorType, rust: vk::DescriptorType) -> bool {
+    shader == rust
+        || (shader == vk::DescriptorType::UNIFORM_BUFFER && rust == vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        || (shader == vk::DescriptorType::STORAGE_BUFFER && rust == vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+}
+
+impl ShaderReflection {
+    /// Разбирает SPIR-V, code - уже в словах
+    pub fn parse(code: &[u32]) -> Result<Self, &'static str> {
+        let m = Module::parse(code)?;
+        let stage = stage_from_model(m.execution_model.ok_or("SPIR-V: module has no entry point")?)?;
+
+        let mut bindings = vec![];
+        let mut push_constants = None;
+        let mut vertex_inputs = vec![];
+        for &(ty, id, storage) in m.variables.iter() {
+            let SpvType::Pointer { pointee, .. } = m.ty(ty)? else {
+                return Err("SPIR-V: variable is not a pointer");
+            };
+            let dec = m.decor(id);
+            match storage {
+                spv::SC_UNIFORM_CONSTANT | spv::SC_UNIFORM | spv::SC_STORAGE_BUFFER => {
+                    let (Some(set), Some(binding)) = (dec.set, dec.binding) else {
+                        continue;
+                    };
+                    let (element, count) = m.unwrap_array(*pointee)?;
+                    let Some(descriptor_type) = m.descriptor_type(storage, element)? else {
+                        continue;
+                    };
+                    // у блоков имя полезнее у типа (Uniforms), у картинок - у переменной
+                    let name = m.names.get(&id).filter(|n| !n.is_empty())
+                        .or_else(|| m.names.get(&element))
+                        .cloned()
+                        .unwrap_or_default();
+                    let members = m.members(element)?;
+                    bindings.push(ReflectedBinding { set, binding, descriptor_type, count, stages: stage, name, members });
+                }
+                spv::SC_PUSH_CONSTANT => {
+                    let (start, end) = m.struct_extent(*pointee)?;
+                    push_constants = Some(vk::PushConstantRange { stage_flags: stage, offset: start, size: end - start });
+                }
+                spv::SC_INPUT if stage == vk::ShaderStageFlags::VERTEX => {
+                    if dec.builtin || m.member_builtin.contains_key(pointee) {
+                        continue;
+                    }
+                    let location = dec.location.ok_or("SPIR-V: vertex input without location")?;
+                    vertex_inputs.push(ReflectedInput { location, format: m.vertex_format(*pointee)? });
+                }
+                _ => {}
+            }
+        }
+        bindings.sort_by_key(|b| (b.set, b.binding));
+        vertex_inputs.sort_by_key(|i| i.location);
+
+        // без SpecId это OpSpecConstantOp и тп, снаружи их не задать
+        let mut spec_constants = vec![];
+        for &(ty, id, raw) in m.spec_constants.iter() {
+            let Some(spec_id) = m.decor(id).spec_id else {
+                continue;
+            };
+            let name = m.names.get(&id).cloned().unwrap_or_default();
+            spec_constants.push(ReflectedSpecConstant { id: spec_id, name, default: m.spec_default(ty, raw)? });
+        }
+        spec_constants.sort_by_key(|c| c.id);
+
+        Ok(Self {
+            stages: stage,
+            entry_point: m.entry_point,
+            bindings,
+            push_constants,
+            vertex_inputs,
+            workgroup_size: if stage == vk::ShaderStageFlags::COMPUTE { m.local_size } else { None },
+            spec_constants,
+        })
+    }
+
+    /// Сводит стадии одного пайплайна. Один и тот же binding в разных стадиях должен совпадать
+    pub fn merge(stages: &[&ShaderReflection]) -> Result<Self, &'static str> {
+        let mut out = ShaderReflection::default();
+        for stage in stages {
+            if out.entry_point.is_empty() {
+                out.entry_point = stage.entry_point.clone();
+            }
+            out.stages |= stage.stages;
+            for b in stage.bindings.iter() {
+                match out.bindings.iter_mut().find(|o| o.set == b.set && o.binding == b.binding) {
+                    Some(o) => {
+                        if o.descriptor_type != b.descriptor_type || o.count != b.count {
+                            return Err(describe(format!(
+                                "Shader stages disagree on set {} binding {}: {:?} x{} ({}) vs {:?} x{} ({})",
+                                b.set, b.binding, o.descriptor_type, o.count, o.name, b.descriptor_type, b.count, b.name
+                            )));
+                        }
+                        o.stages |= b.stages;
+                    }
+                    None => out.bindings.push(b.clone()),
+                }
+            }
+            // один общий диапазон на все стадии, cmd.push_constants тогда с теми же stage_flags
+            if let Some(pc) = stage.push_constants {
+                out.push_constants = Some(match out.push_constants {
+                    None => pc,
+                    Some(prev) => {
+                        let start = prev.offset.min(pc.offset);
+                        let end = (prev.offset + prev.size).max(pc.offset + pc.size);
+                        vk::PushConstantRange { stage_flags: prev.stage_flags | pc.stage_flags, offset: start, size: end - start }
+                    }
+                });
+            }
+            if stage.stages.contains(vk::ShaderStageFlags::VERTEX) {
+                out.vertex_inputs = stage.vertex_inputs.clone();
+            }
+            if stage.workgroup_size.is_some() {
+                out.workgroup_size = stage.workgroup_size;
+            }
+            // один SpecializationInfo на все стадии, значит и тип у id должен быть один
+            for c in stage.spec_constants.iter() {
+                match out.spec_constants.iter().find(|o| o.id == c.id) {
+                    Some(o) if !o.default.same_kind(c.default) => {
+                        return Err(describe(format!(
+                            "Shader stages disagree on the type of constant_id {}: {} {:?} vs {} {:?}",
+                            c.id, o.name, o.default, c.name, c.default
+                        )));
+                    }
+                    Some(_) => {}
+                    None => out.spec_constants.push(c.clone()),
+                }
+            }
+        }
+        out.bindings.sort_by_key(|b| (b.set, b.binding));
+        out.spec_constants.sort_by_key(|c| c.id);
+        Ok(out)
+    }
+
+    /// Сколько сетов нужно pipeline layout'у (с дырками)
+    pub fn set_count(&self) -> u32 {
+        self.bindings.iter().map(|b| b.set + 1).max().unwrap_or(0)
+    }
+
+    /// Сверяет с руками собранным layout сета. Лишние binding'и в Rust - не ошибка,
+    /// шейдер может их просто не трогать
+    pub fn check_set(&self, set: u32, bindings: &[vk::DescriptorSetLayoutBinding]) -> Result<(), &'static str> {
+        for b in self.bindings.iter().filter(|b| b.set == set) {
+            let Some(rust) = bindings.iter().find(|r| r.binding == b.binding) else {
+                return Err(describe(format!(
+                    "Descriptor set layout is missing set {} binding {} ({}) the shader uses", set, b.binding, b.name
+                )));
+            };
+            if !same_kind(b.descriptor_type, rust.descriptor_type) {
+                return Err(describe(format!(
+                    "Descriptor type differs at set {} binding {} ({}): shader wants {:?}, layout has {:?}",
+                    set, b.binding, b.name, b.descriptor_type, rust.descriptor_type
+                )));
+            }
+            if b.count != 0 && rust.descriptor_count < b.count {
+                return Err(describe(format!(
+                    "Descriptor count differs at set {} binding {} ({}): shader wants {}, layout has {}",
+                    set, b.binding, b.name, b.count, rust.descriptor_count
+                )));
+            }
+            if !rust.stage_flags.contains(b.stages) {
+                return Err(describe(format!(
+                    "Set {} binding {} ({}) is used in {:?} but visible only in {:?}",
+                    set, b.binding, b.name, b.stages, rust.stage_flags
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Сверяет блок шейдера с Rust структурой из gpu_struct! по offset'ам.
+    /// Шейдер может объявить только начало структуры, как gbuffer.frag свой LightsSSBO
+    pub fn check_block<T: GpuStruct>(&self, set: u32, binding: u32) -> Result<(), &'static str> {
+        let Some(b) = self.bindings.iter().find(|b| b.set == set && b.binding == binding) else {
+            return Ok(());  // шейдер его не трогает
+        };
+        if let Err(path) = compare_members(&b.members, &T::fields(), &b.name) {
+            return Err(block_mismatch::<T>(set, binding, &path));
+        }
+        Ok(())
+    }
+
+    /// Storage блок вида { T items[]; }: сверяет элемент runtime массива с T
+    pub fn check_table<T: GpuStruct>(&self, set: u32, binding: u32) -> Result<(), &'static str> {
+        let Some(b) = self.bindings.iter().find(|b| b.set == set && b.binding == binding) else {
+            return Ok(());
+        };
+        let Some(items) = b.members.first().filter(|m| m.size == 0) else {
+            return Err(describe(format!(
+                "Set {} binding {} ({}) is not a runtime array of {}", set, binding, b.name, T::GLSL
+            )));
+        };
+        if let Err(path) = compare_members(&items.members, &T::fields(), &format!("{}.{}[]", b.name, items.name)) {
+            return Err(block_mismatch::<T>(set, binding, &path));
+        }
+        Ok(())
+    }
+
+    /// Push constants шейдера должны влезать в Rust структуру
+    pub fn check_push_constants(&self, size: u32) -> Result<(), &'static str> {
+        if let Some(pc) = self.push_constants {
+            if pc.offset + pc.size > size {
+                return Err(describe(format!(
+                    "Shader reads push constants up to byte {}, the Rust struct has {}", pc.offset + pc.size, size
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Тип значения из Rust должен совпадать с constant_id в шейдере.
+    /// Id, которого шейдер не объявил, не ошибка: флаги фич идут во все стадии
+    pub fn check_specialization(&self, spec: &SpecConstants) -> Result<(), &'static str> {
+        for (id, value) in spec.iter() {
+            if let Some(c) = self.spec_constants.iter().find(|c| c.id == id) {
+                if !c.default.same_kind(value) {
+                    return Err(describe(format!(
+                        "Type of constant_id {} ({}) differs: shader has {:?}, Rust gives {:?}", id, c.name, c.default, value
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Каждый вход вершинного шейдера должен быть в атрибутах
+    pub fn check_vertex_input(&self, attributes: &[vk::VertexInputAttributeDescription]) -> Result<(), &'static str> {
+        for input in self.vertex_inputs.iter() {
+            if !attributes.iter().any(|a| a.location == input.location) {
+                return Err(describe(format!(
+                    "Vertex shader reads location {} ({:?}), the pipeline has no such attribute", input.location, input.format
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Текст ошибки с тем, как блок должен выглядеть в GLSL
+fn block_mismatch<T: GpuStruct>(set: u32, binding: u32, path: &str) -> &'static str {
+    describe(format!(
+        "Set {} binding {}: {} differs from Rust {}, expected:\n{}", set, binding, path, T::GLSL, T::glsl()
+    ))
+}
+
+/// Ok или путь до первого расходящегося члена
+fn compare_members(shader: &[ReflectedMember], rust: &[GpuField], path: &str) -> Result<(), String> {
+    for (i, m) in shader.iter().enumerate() {
+        let here = format!("{}.{}", path, m.name);
+        let Some(r) = rust.get(i) else {
+            return Err(format!("{} (no such field in Rust)", here));
+        };
+        if m.offset as usize != r.offset || (m.size != 0 && m.size as usize != r.size) {
+            return Err(format!("{} (shader offset {} size {}, Rust {} offset {} size {})",
+                here, m.offset, m.size, r.name, r.offset, r.size));
+        }
+        // у вложенных offset'ы от начала элемента
+        compare_members(&m.members, &r.members, &here)?;
+    }
+    Ok(())
+}
+
+/// Layout'ы, собранные по шейдерам. pipeline_layout объявлен первым, дропается раньше сетов
+pub struct ReflectedLayouts {
+    pub pipeline_layout: VulkanPipelineLayout,
+    pub set_layouts: Vec<VulkanDescriptorSetLayout>,  // индекс - номер сета
+    pub reflection: ShaderReflection,
+}
+
+/// Собирает descriptor set и pipeline layout'ы из стадий.
+/// SPIR-V не различает dynamic и обычные буферы, такие binding'и отмечаются руками
+pub struct ReflectedLayoutBuilder<'a> {
+    device: &'a Device,
+    reflection: ShaderReflection,
+    dynamic: Vec<(u32, u32)>,
+    push_size: Option<u32>,
+    push_stages: vk::ShaderStageFlags,
+    external: Vec<(u32, vk::DescriptorSetLayout)>,
+}
+
+impl<'a> ReflectedLayoutBuilder<'a> {
+    pub fn new(device: &'a Device, shaders: &[&VulkanShader]) -> Result<Self, &'static str> {
+        let stages: Vec<&ShaderReflection> = shaders.iter().map(|s| &s.reflection).collect();
+        Ok(Self {
+            device,
+            reflection: ShaderReflection::merge(&stages)?,
+            dynamic: vec![],
+            push_size: None,
+            push_stages: vk::ShaderStageFlags::empty(),
+            external: vec![],
+        })
+    }
+
+    /// UNIFORM_BUFFER -> UNIFORM_BUFFER_DYNAMIC (и так же storage)
+    pub fn dynamic(mut self, set: u32, binding: u32) -> Self {
+        self.dynamic.push((set, binding));
+        self
+    }
+
+    /// Размер Rust структуры push constants: проверяется и становится размером диапазона,
+    /// чтобы cmd.push_constants(as_bytes(&push)) не вылезал за layout
+    pub fn push_constant_size(mut self, size: u32) -> Self {
+        self.push_size = Some(size);
+        self
+    }
+
+    /// Стадии диапазона push constants сверх тех, что их читают. Нужно, когда
+    /// cmd.push_constants зовется с одними флагами на все пайплайны layout'а
+    pub fn push_constant_stages(mut self, stages: vk::ShaderStageFlags) -> Self {
+        self.push_stages |= stages;
+        self
+    }
+
+    /// Сет собран не тут: bindless с runtime массивом, AO и G-buffer у своих проходов,
+    /// общий с другим layout'ом. В set_layouts на его месте пустой layout, сеты аллоцирует владелец
+    pub fn external(mut self, set: u32, layout: vk::DescriptorSetLayout) -> Self {
+        self.external.push((set, layout));
+        self
+    }
+
+    pub fn build(self) -> Result<ReflectedLayouts, &'static str> {
+        let mut reflection = self.reflection;
+        for &(set, binding) in self.dynamic.iter() {
+            let b = reflection.bindings.iter_mut()
+                .find(|b| b.set == set && b.binding == binding)
+                .ok_or("Dynamic binding is not used by any shader stage")?;
+            b.descriptor_type = match b.descriptor_type {
+                vk::DescriptorType::UNIFORM_BUFFER => vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                vk::DescriptorType::STORAGE_BUFFER => vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                _ => return Err("Only uniform and storage buffers can be dynamic"),
+            };
+        }
+        if let Some(size) = self.push_size {
+            reflection.check_push_constants(size)?;
+            if let Some(pc) = reflection.push_constants.as_mut() {
+                pc.size = size - pc.offset;
+            }
+        }
+        if let Some(pc) = reflection.push_constants.as_mut() {
+            pc.stage_flags |= self.push_stages;
+        }
+
+        let external_count = self.external.iter().map(|&(set, _)| set + 1).max().unwrap_or(0);
+        let mut set_layouts = vec![];
+        let mut raw = vec![];
+        for set in 0..reflection.set_count().max(external_count) {
+            if let Some(&(_, layout)) = self.external.iter().find(|&&(s, _)| s == set) {
+                set_layouts.push(VulkanDescriptorSetLayout::try_new(self.device, &vec![], None)?);
+                raw.push(layout);
+                continue;
+            }
+            let mut bindings = vec![];
+            for b in reflection.bindings.iter().filter(|b| b.set == set) {
+                if b.count == 0 {
+                    return Err(describe(format!(
+                        "Set {} binding {} ({}) is a runtime array, pass its layout with external()", set, b.binding, b.name
+                    )));
+                }
+                bindings.push(vk::DescriptorSetLayoutBinding {
+                    binding: b.binding,
+                    descriptor_type: b.descriptor_type,
+                    descriptor_count: b.count,
+                    stage_flags: b.stages,
+                    ..Default::default()
+                });
+            }
+            // пустой сет на месте дырки, layout должен покрывать все номера
+            let layout = VulkanDescriptorSetLayout::try_new(self.device, &bindings, None)?;
+            raw.push(layout.layout);
+            set_layouts.push(layout);
+        }
+
+        let ranges: Vec<vk::PushConstantRange> = reflection.push_constants.into_iter().collect();
+        let pipeline_layout = VulkanPipelineLayout::try_new(self.device, &raw, &ranges)?;
+
+        Ok(ReflectedLayouts { pipeline_layout, set_layouts, reflection })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::lighting::uniform::LightsSSBO;
+    use crate::vulkan_wr::types::model::DrawPush;
+
+    // собирает build.rs, как и все шейдеры
+    const LIGHT_FRAG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shaders/frag_light.spv"));
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    fn struct_id(m: &Module, name: &str) -> u32 {
+        // у глобальной копии Material в bindless.glsl нет Offset, берем только блоки
+        *m.names.iter()
+            .find(|(id, n)| n.as_str() == name && m.member_offsets.contains_key(&(**id, 0)))
+            .unwrap_or_else(|| panic!("no struct {}", name)).0
+    }
+
+    #[test]
+    fn light_frag_bindings() {
+        let r = ShaderReflection::parse(&words(LIGHT_FRAG)).unwrap();
+        assert_eq!(r.stages, vk::ShaderStageFlags::FRAGMENT);
+        assert_eq!(r.entry_point, "main");
+        let bindings: Vec<_> = r.bindings.iter().map(|b| (b.set, b.binding, b.descriptor_type, b.count)).collect();
+        assert_eq!(bindings, vec![
+            (0, 1, vk::DescriptorType::STORAGE_BUFFER, 1),
+            (1, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 0),  // runtime массив bindless
+            (1, 1, vk::DescriptorType::STORAGE_BUFFER, 1),
+            (3, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+        ]);
+        assert!(r.check_block::<LightsSSBO>(0, 1).is_ok());
+    }
+
+    #[test]
+    fn light_frag_push_constants() {
+        let r = ShaderReflection::parse(&words(LIGHT_FRAG)).unwrap();
+        // mat4 + 3 uint, хвостовой _pad есть только в Rust
+        let pc = r.push_constants.unwrap();
+        assert_eq!((pc.offset, pc.size, pc.stage_flags), (0, 76, vk::ShaderStageFlags::FRAGMENT));
+        assert!(r.check_push_constants(size_of::<DrawPush>() as u32).is_ok());
+        assert!(r.check_push_constants(64).is_err());
+    }
+
+    #[test]
+    fn light_frag_spec_constants() {
+        let r = ShaderReflection::parse(&words(LIGHT_FRAG)).unwrap();
+        let spec: Vec<_> = r.spec_constants.iter().map(|c| (c.id, c.name.as_str(), c.default)).collect();
+        assert_eq!(spec, vec![(0, "MAX_LIGHTS", SpecValue::UInt(5))]);
+    }
+
+    #[test]
+    fn struct_extent_and_size_of_follow_offsets() {
+        let m = Module::parse(&words(LIGHT_FRAG)).unwrap();
+        assert_eq!(m.struct_extent(struct_id(&m, "DrawPush")), Ok((0, 76)));
+        assert_eq!(m.size_of(struct_id(&m, "DirectionalLight"), None), Ok(32));
+        assert_eq!(m.size_of(struct_id(&m, "PointLight"), None), Ok(48));
+        // 16 байт счетчиков + по MAX_LIGHTS (значение по умолчанию) каждого вида
+        assert_eq!(m.struct_extent(struct_id(&m, "LightsSSBO")), Ok((0, 16 + 5 * (32 + 48 + 64))));
+        // runtime массив не имеет размера
+        assert_eq!(m.struct_extent(struct_id(&m, "MaterialTable")), Ok((0, 0)));
+    }
+
+    #[test]
+    fn truncated_stream_is_an_error() {
+        let code = words(LIGHT_FRAG);
+        // обрезка посреди первой длинной инструкции
+        let mut i = 5;
+        while code[i] >> 16 < 2 {
+            i += (code[i] >> 16) as usize;
+        }
+        assert_eq!(ShaderReflection::parse(&code[..i + 1]).err(), Some("SPIR-V: broken instruction stream"));
+        assert!(ShaderReflection::parse(&code[..3]).is_err());
+
+        // нулевая длина инструкции тоже не зацикливает разбор
+        let mut zero = code[..5].to_vec();
+        zero.push(0);
+        assert!(ShaderReflection::parse(&zero).is_err());
+
+        // любой обрезанный поток - Ok или Err, но не паника
+        for n in 0..code.len() {
+            let _ = ShaderReflection::parse(&code[..n]);
+        }
+    }
+}
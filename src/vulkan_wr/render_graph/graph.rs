@@ -0,0 +1,539 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: render graph: пассы объявляют что читают и пишут, граф их упорядочивает,
+//       выкидывает ненужные, ставит барьеры synchronization2 и раздает transient картинки
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ash::vk;
+
+use super::super::{
+    core::VulkanCore,
+    command_pb::command_buffer::VulkanCommandBuffer,
+    image::image::VulkanImage,
+    sync::barrier::{SyncScope, image_barrier2, buffer_barrier2},
+};
+use super::resource::{ImageHandle, BufferHandle, ImageUsage, BufferUsage, ResourceState, Transition};
+use super::transient::{TransientImageDesc, TransientRequest, TransientPool};
+
+/// Запись пасса. Вызывается один раз, барьеры перед ним граф уже поставил
+pub type PassRecord<'a> = Box<dyn FnOnce(&VulkanCommandBuffer, &PassContext) -> Result<(), &'static str> + 'a>;
+
+/// Что пасс видит при записи: настоящие картинки за хендлами (transient создаются только при execute)
+pub struct PassContext {
+    images: Vec<(vk::Image, vk::ImageView)>,
+}
+
+impl PassContext {
+    pub fn image(&self, handle: ImageHandle) -> vk::Image {
+        self.images[handle.0].0
+    }
+
+    pub fn view(&self, handle: ImageHandle) -> vk::ImageView {
+        self.images[handle.0].1
+    }
+}
+
+/// Что пасс делает с ресурсами. Порядок объявления пассов задает, какую версию ресурса пасс видит
+pub struct PassDesc {
+    name: &'static str,
+    images: Vec<(ImageHandle, ImageUsage)>,
+    managed: Vec<(ImageHandle, ImageUsage)>,
+    buffers: Vec<(BufferHandle, BufferUsage)>,
+    side_effect: bool,
+}
+
+impl PassDesc {
+    pub fn new(name: &'static str) -> Self {
+        Self { name, images: vec![], managed: vec![], buffers: vec![], side_effect: false }
+    }
+
+    /// Чтение или запись, смотря по usage
+    pub fn image(mut self, handle: ImageHandle, usage: ImageUsage) -> Self {
+        self.images.push((handle, usage));
+        self
+    }
+
+    /// Картинку пасс пишет и сам ставит на нее барьеры (bloom, SSAO и тп со своими под-проходами).
+    /// Граф перед пассом ее не трогает, а после считает что она в end_usage
+    pub fn managed(mut self, handle: ImageHandle, end_usage: ImageUsage) -> Self {
+        self.managed.push((handle, end_usage));
+        self
+    }
+
+    pub fn buffer(mut self, handle: BufferHandle, usage: BufferUsage) -> Self {
+        self.buffers.push((handle, usage));
+        self
+    }
+
+    /// Не выкидывать, даже если никто не читает то что он пишет
+    pub fn side_effect(mut self) -> Self {
+        self.side_effect = true;
+        self
+    }
+}
+
+enum ImageSource<'a> {
+    // tracked - после графа состояние записывается обратно в VulkanImage
+    Imported { image: vk::Image, view: vk::ImageView, tracked: Option<&'a VulkanImage> },
+    Transient(TransientImageDesc),
+}
+
+struct GraphImage<'a> {
+    source: ImageSource<'a>,
+    range: vk::ImageSubresourceRange,
+    state: ResourceState,
+    final_usage: Option<ImageUsage>,
+}
+
+struct GraphBuffer {
+    buffer: vk::Buffer,
+    state: ResourceState,
+}
+
+struct PassNode<'a> {
+    desc: PassDesc,
+    record: Option<PassRecord<'a>>,
+}
+
+/// Граф одного кадра. Собирается заново каждый кадр, живет до execute
+pub struct RenderGraph<'a> {
+    images: Vec<GraphImage<'a>>,
+    buffers: Vec<GraphBuffer>,
+    passes: Vec<PassNode<'a>>,
+}
+
+impl Default for RenderGraph<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self { images: vec![], buffers: vec![], passes: vec![] }
+    }
+
+    /// Картинка снаружи графа (свапчейн, depth, таргеты пост-проходов)
+    /// # Args
+    /// * `range` - какую часть барьеры покрывают (все слои shadow map и тп)
+    /// * `initial` - в каком она состоянии к началу кадра, см. ResourceState::undefined_after
+    pub fn import_image(&mut self, image: vk::Image, view: vk::ImageView, range: vk::ImageSubresourceRange, initial: ResourceState) -> ImageHandle {
+        self.images.push(GraphImage {
+            source: ImageSource::Imported { image, view, tracked: None },
+            range,
+            state: initial,
+            final_usage: None,
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    /// Картинка, которая сама помнит свой layout: начальное состояние берется из нее, финальное туда же и пишется
+    /// Вся картинка идет одним куском, состояние берется с первого subresource
+    pub fn import_tracked(&mut self, image: &'a VulkanImage, view: vk::ImageView) -> ImageHandle {
+        self.images.push(GraphImage {
+            source: ImageSource::Imported { image: image.image, view, tracked: Some(image) },
+            range: image.full_range(),
+            state: ResourceState::from_tracked(image.state(0, 0)),
+            final_usage: None,
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    /// Картинка только внутри кадра. Память под нее делится с другими transient, которые с ней не пересекаются
+    pub fn create_image(&mut self, desc: TransientImageDesc) -> ImageHandle {
+        self.images.push(GraphImage {
+            source: ImageSource::Transient(desc),
+            range: vk::ImageSubresourceRange {
+                aspect_mask: desc.aspect(),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            state: ResourceState::undefined(),
+            final_usage: None,
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, buffer: vk::Buffer, initial: ResourceState) -> BufferHandle {
+        self.buffers.push(GraphBuffer { buffer, state: initial });
+        BufferHandle(self.buffers.len() - 1)
+    }
+
+    /// В каком состоянии оставить картинку после графа (свапчейн -> Present)
+    pub fn export_image(&mut self, handle: ImageHandle, usage: ImageUsage) {
+        self.images[handle.0].final_usage = Some(usage);
+    }
+
+    pub fn add_pass<F>(&mut self, desc: PassDesc, record: F)
+    where
+        F: FnOnce(&VulkanCommandBuffer, &PassContext) -> Result<(), &'static str> + 'a,
+    {
+        self.passes.push(PassNode { desc, record: Some(Box::new(record)) });
+    }
+
+    /// Зависимости по ресурсам: RAW, WAW и WAR. deps[p] - кто должен отработать до p
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        let mut deps = vec![vec![]; self.passes.len()];
+        let mut image_writer: Vec<Option<usize>> = vec![None; self.images.len()];
+        let mut image_readers: Vec<Vec<usize>> = vec![vec![]; self.images.len()];
+        let mut buffer_writer: Vec<Option<usize>> = vec![None; self.buffers.len()];
+        let mut buffer_readers: Vec<Vec<usize>> = vec![vec![]; self.buffers.len()];
+
+        fn touch(p: usize, write: bool, writer: &mut Option<usize>, readers: &mut Vec<usize>, deps: &mut Vec<usize>) {
+            deps.extend(writer.iter().copied());
+            if write {
+                deps.extend(readers.drain(..));
+                *writer = Some(p);
+            } else {
+                readers.push(p);
+            }
+        }
+
+        for (p, pass) in self.passes.iter().enumerate() {
+            let images = pass.desc.images.iter().map(|&(h, u)| (h, u.is_write()))
+                .chain(pass.desc.managed.iter().map(|&(h, _)| (h, true)));
+            for (h, write) in images {
+                touch(p, write, &mut image_writer[h.0], &mut image_readers[h.0], &mut deps[p]);
+            }
+            for &(h, u) in pass.desc.buffers.iter() {
+                touch(p, u.is_write(), &mut buffer_writer[h.0], &mut buffer_readers[h.0], &mut deps[p]);
+            }
+            deps[p].retain(|&d| d != p);
+            deps[p].sort_unstable();
+            deps[p].dedup();
+        }
+        deps
+    }
+
+    /// Пассы, которые реально нужны: пишут что-то снаружи графа или side_effect, плюс все от чего они зависят
+    fn alive(&self, deps: &[Vec<usize>]) -> Vec<bool> {
+        let mut alive: Vec<bool> = self.passes.iter().map(|pass| {
+            pass.desc.side_effect
+                || pass.desc.buffers.iter().any(|&(_, u)| u.is_write())
+                || pass.desc.managed.iter().any(|&(h, _)| matches!(self.images[h.0].source, ImageSource::Imported { .. }))
+                || pass.desc.images.iter().any(|&(h, u)| u.is_write() && matches!(self.images[h.0].source, ImageSource::Imported { .. }))
+        }).collect();
+        // зависимости всегда на более ранние пассы, одного прохода с конца хватает
+        for p in (0..self.passes.len()).rev() {
+            if alive[p] {
+                for &d in deps[p].iter() {
+                    alive[d] = true;
+                }
+            }
+        }
+        alive
+    }
+
+    /// Топологический порядок живых пассов, при равенстве - порядок объявления
+    fn order(&self, deps: &[Vec<usize>], alive: &[bool]) -> Result<Vec<usize>, &'static str> {
+        let mut pending: Vec<usize> = deps.iter().map(|d| d.iter().filter(|&&x| alive[x]).count()).collect();
+        let mut users: Vec<Vec<usize>> = vec![vec![]; self.passes.len()];
+        for (p, d) in deps.iter().enumerate() {
+            for &x in d {
+                users[x].push(p);
+            }
+        }
+        let mut ready: BinaryHeap<Reverse<usize>> = (0..self.passes.len())
+            .filter(|&p| alive[p] && pending[p] == 0)
+            .map(Reverse)
+            .collect();
+        let mut order = vec![];
+        while let Some(Reverse(p)) = ready.pop() {
+            order.push(p);
+            for &u in users[p].iter() {
+                if alive[u] {
+                    pending[u] -= 1;
+                    if pending[u] == 0 {
+                        ready.push(Reverse(u));
+                    }
+                }
+            }
+        }
+        if order.len() != alive.iter().filter(|&&a| a).count() {
+            return Err("Render graph has a dependency cycle");
+        }
+        Ok(order)
+    }
+
+    /// Живые пассы в порядке исполнения
+    fn schedule(&self) -> Result<Vec<usize>, &'static str> {
+        let deps = self.dependencies();
+        let alive = self.alive(&deps);
+        self.order(&deps, &alive)
+    }
+
+    /// Запросы transient под порядок исполнения: время жизни в номерах шагов.
+    /// Плюс индекс запроса у каждой картинки и обратно, хендл у каждого запроса
+    fn transient_requests(&self, order: &[usize]) -> (Vec<TransientRequest>, Vec<Option<usize>>, Vec<usize>) {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.images.len()];
+        for (step, &p) in order.iter().enumerate() {
+            let desc = &self.passes[p].desc;
+            for h in desc.images.iter().map(|&(h, _)| h).chain(desc.managed.iter().map(|&(h, _)| h)) {
+                if let ImageSource::Transient(_) = self.images[h.0].source {
+                    lifetimes[h.0] = Some(match lifetimes[h.0] {
+                        Some((first, _)) => (first, step),
+                        None => (step, step),
+                    });
+                }
+            }
+        }
+        let mut requests = vec![];
+        let mut request_of = vec![None; self.images.len()];
+        let mut handle_of = vec![];
+        for (h, image) in self.images.iter().enumerate() {
+            if let (ImageSource::Transient(desc), Some((first, last))) = (&image.source, lifetimes[h]) {
+                request_of[h] = Some(requests.len());
+                handle_of.push(h);
+                requests.push(TransientRequest { desc: *desc, first, last });
+            }
+        }
+        (requests, request_of, handle_of)
+    }
+
+    /// Переходы картинок перед пассом p, состояния двигаются дальше
+    /// # Args
+    /// * `aliased_from` - прошлый жилец памяти transient, снимается при первом использовании
+    fn image_transitions(&mut self, p: usize, aliased_from: &mut [Option<usize>]) -> Vec<(ImageHandle, Transition)> {
+        let mut transitions = vec![];
+        let usages = self.passes[p].desc.images.clone();
+        for (h, usage) in usages {
+            // первое использование алиаса ждет, пока прошлый жилец памяти отработает
+            if let Some(prev) = aliased_from[h.0].take() {
+                let prev_state = self.images[prev].state;
+                self.images[h.0].state.read_stages |= prev_state.write_stage | prev_state.read_stages;
+                self.images[h.0].state.write_access |= prev_state.write_access;
+            }
+            let transition = self.images[h.0].state.transition(usage.layout(), usage.stage(), usage.access(), usage.is_write());
+            if let Some(t) = transition {
+                transitions.push((h, t));
+            }
+        }
+        transitions
+    }
+
+    fn image_barrier(&self, h: usize, t: Transition) -> vk::ImageMemoryBarrier2<'static> {
+        let image = match self.images[h].source {
+            ImageSource::Imported { image, .. } => image,
+            ImageSource::Transient(_) => vk::Image::null(),  // подставляется в execute
+        };
+        image_barrier2(
+            image, self.images[h].range,
+            SyncScope::new(t.src_stage, t.src_access), SyncScope::new(t.dst_stage, t.dst_access),
+            t.old_layout, t.new_layout,
+        )
+    }
+
+    /// Упорядочивает, выделяет transient и записывает все пассы в cmd
+    /// # Args
+    /// * `pool` - transient картинки этого кадра в полете
+    /// Возвращает имена отработавших пассов в порядке исполнения
+    pub fn execute(mut self, core: &VulkanCore, cmd: &VulkanCommandBuffer, pool: &mut TransientPool) -> Result<Vec<&'static str>, &'static str> {
+        let order = self.schedule()?;
+        let (requests, request_of, handle_of) = self.transient_requests(&order);
+        let transients = pool.acquire(core, &requests)?;
+
+        let mut context = PassContext { images: vec![] };
+        let mut aliased_from = vec![None; self.images.len()];
+        for (h, image) in self.images.iter().enumerate() {
+            context.images.push(match (&image.source, request_of[h]) {
+                (ImageSource::Imported { image, view, .. }, _) => (*image, *view),
+                (ImageSource::Transient(_), Some(r)) => {
+                    aliased_from[h] = transients[r].alias_of.map(|prev| handle_of[prev]);
+                    (transients[r].image, transients[r].view.view)
+                },
+                (ImageSource::Transient(_), None) => (vk::Image::null(), vk::ImageView::null()),
+            });
+        }
+
+        let mut executed = vec![];
+        for p in order {
+            let mut image_barriers = vec![];
+            let mut buffer_barriers = vec![];
+            for (h, t) in self.image_transitions(p, &mut aliased_from) {
+                let mut barrier = self.image_barrier(h.0, t);
+                barrier.image = context.image(h);
+                image_barriers.push(barrier);
+            }
+            let usages = self.passes[p].desc.buffers.clone();
+            for (h, usage) in usages {
+                let graph_buffer = &mut self.buffers[h.0];
+                if let Some(t) = graph_buffer.state.transition(vk::ImageLayout::UNDEFINED, usage.stage(), usage.access(), usage.is_write()) {
+                    buffer_barriers.push(buffer_barrier2(
+                        graph_buffer.buffer,
+                        SyncScope::new(t.src_stage, t.src_access), SyncScope::new(t.dst_stage, t.dst_access),
+                    ));
+                }
+            }
+            if !image_barriers.is_empty() || !buffer_barriers.is_empty() {
+                let dependency_info = vk::DependencyInfo::default()
+                    .image_memory_barriers(&image_barriers)
+                    .buffer_memory_barriers(&buffer_barriers);
+                unsafe { cmd.pipeline_barrier2(&dependency_info); }
+            }
+
+            let record = self.passes[p].record.take().ok_or("Render graph pass recorded twice")?;
+            record(cmd, &context)?;
+            for (h, end_usage) in self.passes[p].desc.managed.iter() {
+                self.images[h.0].state = ResourceState::from_usage(*end_usage);
+            }
+            executed.push(self.passes[p].desc.name);
+        }
+
+        // финальные состояния (свапчейн в PRESENT_SRC_KHR)
+        let mut image_barriers = vec![];
+        for h in 0..self.images.len() {
+            if let Some(usage) = self.images[h].final_usage {
+                let transition = self.images[h].state.transition(usage.layout(), usage.stage(), usage.access(), usage.is_write());
+                if let Some(t) = transition {
+                    let mut barrier = self.image_barrier(h, t);
+                    barrier.image = context.image(ImageHandle(h));
+                    image_barriers.push(barrier);
+                }
+            }
+        }
+        unsafe { cmd.image_barriers2(&image_barriers); }
+
+        for image in self.images.iter() {
+            if let ImageSource::Imported { tracked: Some(tracked), .. } = &image.source {
+                tracked.assume(image.range, image.state.to_tracked());
+            }
+        }
+        Ok(executed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color_range() -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+
+    fn transient() -> TransientImageDesc {
+        TransientImageDesc {
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            extent: vk::Extent2D { width: 64, height: 64 },
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+
+    fn imported(graph: &mut RenderGraph) -> ImageHandle {
+        graph.import_image(vk::Image::null(), vk::ImageView::null(), color_range(), ResourceState::undefined())
+    }
+
+    fn pass(graph: &mut RenderGraph, desc: PassDesc) {
+        graph.add_pass(desc, |_, _| Ok(()));
+    }
+
+    #[test]
+    fn passes_run_after_what_they_depend_on() {
+        let mut graph = RenderGraph::new();
+        let hdr = graph.create_image(transient());
+        let out = imported(&mut graph);
+        pass(&mut graph, PassDesc::new("scene").image(hdr, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("tonemap").image(hdr, ImageUsage::SampledFragment).image(out, ImageUsage::ColorAttachment));
+        // пишет hdr после чтения (WAR), результат никому не нужен кроме side_effect
+        pass(&mut graph, PassDesc::new("overwrite").image(hdr, ImageUsage::ColorAttachment).side_effect());
+
+        let deps = graph.dependencies();
+        assert_eq!(deps, vec![vec![], vec![0], vec![0, 1]]);
+        assert_eq!(graph.schedule().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn independent_passes_keep_declaration_order() {
+        let mut graph = RenderGraph::new();
+        let a = imported(&mut graph);
+        let b = imported(&mut graph);
+        pass(&mut graph, PassDesc::new("b").image(b, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("a").image(a, ImageUsage::ColorAttachment));
+        assert_eq!(graph.schedule().unwrap(), vec![0, 1]);
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let mut graph = RenderGraph::new();
+        let unused = graph.create_image(transient());
+        let out = imported(&mut graph);
+        pass(&mut graph, PassDesc::new("debug view").image(unused, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("main").image(out, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("readback").side_effect());
+
+        let order = graph.schedule().unwrap();
+        assert_eq!(order, vec![1, 2]);
+        // выкинутый пасс не просит память под свою картинку
+        let (requests, request_of, _) = graph.transient_requests(&order);
+        assert!(requests.is_empty());
+        assert_eq!(request_of[unused.0], None);
+    }
+
+    #[test]
+    fn read_after_write_gets_one_barrier() {
+        let mut graph = RenderGraph::new();
+        let hdr = graph.create_image(transient());
+        let out = imported(&mut graph);
+        pass(&mut graph, PassDesc::new("scene").image(hdr, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("tonemap").image(hdr, ImageUsage::SampledFragment).image(out, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("overlay").image(hdr, ImageUsage::SampledFragment).image(out, ImageUsage::ColorAttachment));
+
+        let order = graph.schedule().unwrap();
+        let mut aliased_from = vec![None; 2];
+        let mut barriers: Vec<Vec<(ImageHandle, Transition)>> = vec![];
+        for p in order {
+            barriers.push(graph.image_transitions(p, &mut aliased_from));
+        }
+
+        let hdr_barriers: Vec<Vec<Transition>> = barriers.iter()
+            .map(|b| b.iter().filter(|(h, _)| *h == hdr).map(|&(_, t)| t).collect())
+            .collect();
+        // scene: переход из UNDEFINED, tonemap: один RAW, overlay: чтение после чтения без барьера
+        assert_eq!(hdr_barriers[0].len(), 1);
+        assert_eq!(hdr_barriers[1].len(), 1);
+        assert!(hdr_barriers[2].is_empty());
+
+        let raw = hdr_barriers[1][0];
+        assert_eq!(raw.src_stage, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
+        assert!(raw.src_access.contains(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE));
+        assert_eq!(raw.dst_stage, vk::PipelineStageFlags2::FRAGMENT_SHADER);
+        assert_eq!(raw.dst_access, vk::AccessFlags2::SHADER_SAMPLED_READ);
+        assert_eq!(raw.old_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        assert_eq!(raw.new_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    #[test]
+    fn transient_lifetimes_follow_execution_order() {
+        let mut graph = RenderGraph::new();
+        let ao = graph.create_image(transient());
+        let bloom = graph.create_image(transient());
+        let out = imported(&mut graph);
+        pass(&mut graph, PassDesc::new("ssao").image(ao, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("lighting").image(ao, ImageUsage::SampledFragment).image(out, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("bloom").image(out, ImageUsage::SampledFragment).image(bloom, ImageUsage::ColorAttachment));
+        pass(&mut graph, PassDesc::new("composite").image(bloom, ImageUsage::SampledFragment).image(out, ImageUsage::ColorAttachment));
+
+        let order = graph.schedule().unwrap();
+        let (requests, request_of, handle_of) = graph.transient_requests(&order);
+        assert_eq!(handle_of, vec![ao.0, bloom.0]);
+        let ao_req = requests[request_of[ao.0].unwrap()];
+        let bloom_req = requests[request_of[bloom.0].unwrap()];
+        assert_eq!((ao_req.first, ao_req.last), (0, 1));
+        assert_eq!((bloom_req.first, bloom_req.last), (2, 3));
+        // не пересекаются, значит пул положит их в один блок памяти
+        assert!(ao_req.last < bloom_req.first);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod resource;
+pub mod transient;
+pub mod graph;
@@ -0,0 +1,244 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: ресурсы графа: хендлы, как пасс их использует и в каком они состоянии
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+use super::super::{image::image::SubresourceState, sync::barrier::is_write_access};
+
+/// Картинка внутри одного графа, индекс в RenderGraph::images
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageHandle(pub(crate) usize);
+
+/// Буфер внутри одного графа, индекс в RenderGraph::buffers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle(pub(crate) usize);
+
+/// Как пасс трогает картинку. Из этого граф выводит layout, стадию и доступ для барьера
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageUsage {
+    ColorAttachment,  // запись (и blend/LOAD чтение) color attachment, резолв MSAA тоже сюда
+    DepthAttachment,  // depth test + запись
+    DepthReadOnly,  // depth test без записи, можно одновременно сэмплить
+    SampledFragment,  // texture() во фрагментном
+    DepthSampledFragment,  // depth как текстура во фрагментном, DEPTH_STENCIL_READ_ONLY_OPTIMAL
+    SampledCompute,  // texture()/texelFetch в compute
+    StorageCompute,  // imageLoad/imageStore в compute, GENERAL
+    TransferSrc,
+    TransferDst,
+    Present,  // только как финальное состояние свапчейна
+}
+
+impl ImageUsage {
+    pub fn layout(self) -> vk::ImageLayout {
+        match self {
+            ImageUsage::ColorAttachment => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUsage::DepthAttachment => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ImageUsage::DepthReadOnly | ImageUsage::DepthSampledFragment => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageUsage::SampledFragment | ImageUsage::SampledCompute => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageUsage::StorageCompute => vk::ImageLayout::GENERAL,
+            ImageUsage::TransferSrc => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageUsage::TransferDst => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageUsage::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+
+    pub fn stage(self) -> vk::PipelineStageFlags2 {
+        match self {
+            ImageUsage::ColorAttachment => vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            ImageUsage::DepthAttachment | ImageUsage::DepthReadOnly =>
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            ImageUsage::SampledFragment | ImageUsage::DepthSampledFragment => vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            ImageUsage::SampledCompute | ImageUsage::StorageCompute => vk::PipelineStageFlags2::COMPUTE_SHADER,
+            ImageUsage::TransferSrc | ImageUsage::TransferDst => vk::PipelineStageFlags2::ALL_TRANSFER,
+            // present ждет семафор, внутри буфера после него ничего нет
+            ImageUsage::Present => vk::PipelineStageFlags2::NONE,
+        }
+    }
+
+    pub fn access(self) -> vk::AccessFlags2 {
+        match self {
+            ImageUsage::ColorAttachment => vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            ImageUsage::DepthAttachment => vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ImageUsage::DepthReadOnly => vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            ImageUsage::SampledFragment | ImageUsage::DepthSampledFragment | ImageUsage::SampledCompute => vk::AccessFlags2::SHADER_SAMPLED_READ,
+            ImageUsage::StorageCompute => vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            ImageUsage::TransferSrc => vk::AccessFlags2::TRANSFER_READ,
+            ImageUsage::TransferDst => vk::AccessFlags2::TRANSFER_WRITE,
+            ImageUsage::Present => vk::AccessFlags2::NONE,
+        }
+    }
+
+    pub fn is_write(self) -> bool {
+        matches!(self, ImageUsage::ColorAttachment | ImageUsage::DepthAttachment | ImageUsage::StorageCompute | ImageUsage::TransferDst)
+    }
+}
+
+/// Как пасс трогает буфер
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+    UniformRead(vk::PipelineStageFlags2),
+    StorageRead(vk::PipelineStageFlags2),
+    StorageWrite(vk::PipelineStageFlags2),
+    VertexIndexRead,
+    TransferDst,
+}
+
+impl BufferUsage {
+    pub fn stage(self) -> vk::PipelineStageFlags2 {
+        match self {
+            BufferUsage::UniformRead(stage) | BufferUsage::StorageRead(stage) | BufferUsage::StorageWrite(stage) => stage,
+            BufferUsage::VertexIndexRead => vk::PipelineStageFlags2::VERTEX_INPUT,
+            BufferUsage::TransferDst => vk::PipelineStageFlags2::ALL_TRANSFER,
+        }
+    }
+
+    pub fn access(self) -> vk::AccessFlags2 {
+        match self {
+            BufferUsage::UniformRead(_) => vk::AccessFlags2::UNIFORM_READ,
+            BufferUsage::StorageRead(_) => vk::AccessFlags2::SHADER_STORAGE_READ,
+            BufferUsage::StorageWrite(_) => vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            BufferUsage::VertexIndexRead => vk::AccessFlags2::VERTEX_ATTRIBUTE_READ | vk::AccessFlags2::INDEX_READ,
+            BufferUsage::TransferDst => vk::AccessFlags2::TRANSFER_WRITE,
+        }
+    }
+
+    pub fn is_write(self) -> bool {
+        matches!(self, BufferUsage::StorageWrite(_) | BufferUsage::TransferDst)
+    }
+}
+
+/// Что граф знает о ресурсе между пассами
+/// last_write - стадия и доступ последней записи, visible_to - стадии, которым она уже видна,
+/// read_stages - кто читал после записи (их ждет следующая запись, WAR)
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceState {
+    pub layout: vk::ImageLayout,
+    pub write_stage: vk::PipelineStageFlags2,
+    pub write_access: vk::AccessFlags2,
+    pub visible_to: vk::PipelineStageFlags2,
+    pub read_stages: vk::PipelineStageFlags2,
+}
+
+impl ResourceState {
+    /// Содержимое не нужно, первый же пасс переводит из UNDEFINED
+    pub fn undefined() -> Self {
+        Self {
+            layout: vk::ImageLayout::UNDEFINED,
+            write_stage: vk::PipelineStageFlags2::NONE,
+            write_access: vk::AccessFlags2::NONE,
+            visible_to: vk::PipelineStageFlags2::NONE,
+            read_stages: vk::PipelineStageFlags2::NONE,
+        }
+    }
+
+    /// Содержимое не нужно, но переход должен дождаться stage: wait стадия семафора свапчейна
+    /// или прошлый кадр, который мог еще читать эту картинку
+    pub fn undefined_after(stage: vk::PipelineStageFlags2) -> Self {
+        Self { read_stages: stage, ..Self::undefined() }
+    }
+
+    /// Ресурс пришел уже в этом состоянии (предыдущий кадр, загрузка и тп)
+    /// # Args
+    /// * `usage` - как его трогали последним
+    pub fn from_usage(usage: ImageUsage) -> Self {
+        Self {
+            layout: usage.layout(),
+            write_stage: if usage.is_write() { usage.stage() } else { vk::PipelineStageFlags2::NONE },
+            write_access: if usage.is_write() { usage.access() } else { vk::AccessFlags2::NONE },
+            visible_to: if usage.is_write() { vk::PipelineStageFlags2::NONE } else { usage.stage() },
+            read_stages: if usage.is_write() { vk::PipelineStageFlags2::NONE } else { usage.stage() },
+        }
+    }
+}
+
+impl ResourceState {
+    /// Из того, что помнит сама картинка (VulkanImage::state)
+    pub fn from_tracked(state: SubresourceState) -> Self {
+        if is_write_access(state.access) {
+            Self {
+                layout: state.layout,
+                write_stage: state.stage,
+                write_access: state.access,
+                visible_to: vk::PipelineStageFlags2::NONE,
+                read_stages: vk::PipelineStageFlags2::NONE,
+            }
+        } else {
+            Self { layout: state.layout, visible_to: state.stage, read_stages: state.stage, ..Self::undefined() }
+        }
+    }
+
+    /// Обратно в VulkanImage после графа: src следующего барьера это и запись, и все читатели после нее
+    pub fn to_tracked(&self) -> SubresourceState {
+        SubresourceState {
+            layout: self.layout,
+            stage: self.write_stage | self.read_stages,
+            access: self.write_access,
+        }
+    }
+}
+
+/// Что нужно вставить перед пассом, чтобы перейти из state в новое использование
+#[derive(Clone, Copy, Debug)]
+pub struct Transition {
+    pub src_stage: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stage: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+}
+
+impl ResourceState {
+    /// Переход к новому использованию. None если барьер не нужен (чтение после чтения в том же layout)
+    /// state обновляется в любом случае
+    pub fn transition(&mut self, layout: vk::ImageLayout, stage: vk::PipelineStageFlags2, access: vk::AccessFlags2, write: bool) -> Option<Transition> {
+        let layout_change = layout != self.layout && layout != vk::ImageLayout::UNDEFINED;
+        let transition = if write || layout_change {
+            // RAW/WAW ждут запись, WAR ждет всех читателей. Смена layout это тоже запись
+            Some(Transition {
+                src_stage: self.write_stage | self.read_stages,
+                src_access: self.write_access,
+                dst_stage: stage,
+                dst_access: access,
+                old_layout: self.layout,
+                new_layout: layout,
+            })
+        } else if !self.visible_to.contains(stage) && self.write_stage != vk::PipelineStageFlags2::NONE {
+            // запись уже была, но этой стадии еще не показана
+            Some(Transition {
+                src_stage: self.write_stage,
+                src_access: self.write_access,
+                dst_stage: stage,
+                dst_access: access,
+                old_layout: self.layout,
+                new_layout: self.layout,
+            })
+        } else {
+            None
+        };
+
+        if write {
+            self.write_stage = stage;
+            self.write_access = access;
+            self.visible_to = vk::PipelineStageFlags2::NONE;
+            self.read_stages = vk::PipelineStageFlags2::NONE;
+        } else if layout_change {
+            // сам переход уже виден dst стадии, остальным нужен еще барьер от нее
+            self.write_stage = stage;
+            self.write_access = vk::AccessFlags2::NONE;
+            self.visible_to = stage;
+            self.read_stages = stage;
+        } else {
+            self.visible_to |= stage;
+            self.read_stages |= stage;
+        }
+        if layout != vk::ImageLayout::UNDEFINED {
+            self.layout = layout;
+        }
+        transition
+    }
+}
@@ -0,0 +1,217 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: transient картинки графа. Живут внутри кадра, картинки с непересекающимся временем жизни делят память
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::{vk, Device};
+
+use super::super::{
+    core::VulkanCore,
+    image::{image::format_aspect, image_view::{VulkanImageView, VulkanImageViewBuilder}},
+};
+
+/// Описание transient картинки. Одинаковые описания с теми же временами жизни переиспользуются между кадрами
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransientImageDesc {
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub usage: vk::ImageUsageFlags,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl TransientImageDesc {
+    pub fn aspect(&self) -> vk::ImageAspectFlags {
+        format_aspect(self.format)
+    }
+}
+
+/// Запрос графа: описание + первый и последний пасс (в порядке исполнения), где картинка нужна
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransientRequest {
+    pub desc: TransientImageDesc,
+    pub first: usize,
+    pub last: usize,
+}
+
+pub struct TransientImage {
+    pub image: vk::Image,
+    pub view: VulkanImageView,
+    pub alias_of: Option<usize>,  // кто жил в этой памяти до нее, по индексу запроса
+}
+
+/// Раскладка запросов по блокам памяти
+struct AliasPlan {
+    slots: Vec<(u64, u32)>,  // размер и memory_type_bits блока
+    slot_of: Vec<usize>,
+    alias_of: Vec<Option<usize>>,
+}
+
+/// Жадно по времени жизни: слот свободен, если его последний жилец умер раньше
+fn plan_aliasing(requests: &[TransientRequest], memory_reqs: &[vk::MemoryRequirements]) -> AliasPlan {
+    let mut order: Vec<usize> = (0..requests.len()).collect();
+    order.sort_by_key(|&i| requests[i].first);
+    // (последний пасс, размер, memory_type_bits, последний жилец)
+    let mut slots: Vec<(usize, u64, u32, usize)> = vec![];
+    let mut slot_of = vec![0; requests.len()];
+    let mut alias_of = vec![None; requests.len()];
+    for i in order {
+        let reqs = memory_reqs[i];
+        let free = slots.iter().position(|&(last, _, bits, _)| last < requests[i].first && bits & reqs.memory_type_bits != 0);
+        match free {
+            Some(s) => {
+                alias_of[i] = Some(slots[s].3);
+                slots[s] = (requests[i].last, slots[s].1.max(reqs.size), slots[s].2 & reqs.memory_type_bits, i);
+                slot_of[i] = s;
+            },
+            None => {
+                slots.push((requests[i].last, reqs.size, reqs.memory_type_bits, i));
+                slot_of[i] = slots.len() - 1;
+            },
+        }
+    }
+    AliasPlan {
+        slots: slots.into_iter().map(|(_, size, bits, _)| (size, bits)).collect(),
+        slot_of,
+        alias_of,
+    }
+}
+
+/// Держит transient картинки одного кадра в полете. Пересоздает их, только если граф попросил другое
+pub struct TransientPool {
+    images: Vec<TransientImage>,
+    memory: Vec<vk::DeviceMemory>,  // по одному блоку на слот алиасинга
+    requests: Vec<TransientRequest>,
+    device: Device,
+}
+
+impl TransientPool {
+    pub fn new(device: &Device) -> Self {
+        Self { images: vec![], memory: vec![], requests: vec![], device: device.clone() }
+    }
+
+    /// Картинки под запросы, в том же порядке. Пул должен быть свободен (fence кадра уже дождались)
+    pub fn acquire(&mut self, core: &VulkanCore, requests: &[TransientRequest]) -> Result<&[TransientImage], &'static str> {
+        if self.requests.as_slice() == requests {
+            return Ok(&self.images);
+        }
+        self.release();
+
+        let device = &core._logical_device;
+        let mut raw = vec![];
+        for request in requests {
+            let create_info = vk::ImageCreateInfo {
+                image_type: vk::ImageType::TYPE_2D,
+                format: request.desc.format,
+                extent: vk::Extent3D { width: request.desc.extent.width.max(1), height: request.desc.extent.height.max(1), depth: 1 },
+                mip_levels: 1,
+                array_layers: 1,
+                samples: request.desc.samples,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage: request.desc.usage,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            };
+            let image = unsafe { device.create_image(&create_info, None) }
+                .map_err(|_| "Failed to create transient image")?;
+            raw.push((image, unsafe { device.get_image_memory_requirements(image) }));
+        }
+
+        let memory_reqs: Vec<vk::MemoryRequirements> = raw.iter().map(|&(_, reqs)| reqs).collect();
+        let AliasPlan { slots, slot_of, alias_of } = plan_aliasing(requests, &memory_reqs);
+        for &(size, bits) in slots.iter() {
+            self.memory.push(core.allocate_memory(size, bits, vk::MemoryPropertyFlags::DEVICE_LOCAL)?);
+        }
+
+        for (i, (image, _)) in raw.into_iter().enumerate() {
+            unsafe {
+                device.bind_image_memory(image, self.memory[slot_of[i]], 0)
+                    .map_err(|_| "Failed to bind transient image memory")?;
+            }
+            let view = VulkanImageViewBuilder::new(device, image)
+                .format(requests[i].desc.format)
+                .aspect(requests[i].desc.aspect())
+                .build()?;
+            self.images.push(TransientImage { image, view, alias_of: alias_of[i] });
+        }
+        self.requests = requests.to_vec();
+        Ok(&self.images)
+    }
+
+    /// Сколько памяти сэкономил алиасинг: картинок больше, чем блоков памяти
+    pub fn aliased_count(&self) -> usize {
+        self.images.len() - self.memory.len()
+    }
+
+    fn release(&mut self) {
+        for image in self.images.drain(..) {
+            let TransientImage { image, view, .. } = image;
+            drop(view);
+            unsafe { self.device.destroy_image(image, None); }
+        }
+        for memory in self.memory.drain(..) {
+            unsafe { self.device.free_memory(memory, None); }
+        }
+        self.requests = vec![];
+    }
+}
+
+impl Drop for TransientPool {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(first: usize, last: usize) -> TransientRequest {
+        TransientRequest {
+            desc: TransientImageDesc {
+                format: vk::Format::R16G16B16A16_SFLOAT,
+                extent: vk::Extent2D { width: 64, height: 64 },
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                samples: vk::SampleCountFlags::TYPE_1,
+            },
+            first,
+            last,
+        }
+    }
+
+    fn reqs(size: u64, bits: u32) -> vk::MemoryRequirements {
+        vk::MemoryRequirements { size, alignment: 256, memory_type_bits: bits }
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_memory() {
+        let plan = plan_aliasing(&[request(0, 1), request(2, 3)], &[reqs(1024, 0b11), reqs(4096, 0b10)]);
+        assert_eq!(plan.slots, vec![(4096, 0b10)]);
+        assert_eq!(plan.slot_of, vec![0, 0]);
+        assert_eq!(plan.alias_of, vec![None, Some(0)]);
+    }
+
+    #[test]
+    fn overlapping_lifetimes_get_own_memory() {
+        // второй начинается на том же шаге, где умирает первый
+        let plan = plan_aliasing(&[request(0, 2), request(2, 3)], &[reqs(1024, 1), reqs(1024, 1)]);
+        assert_eq!(plan.slots.len(), 2);
+        assert_eq!(plan.alias_of, vec![None, None]);
+    }
+
+    #[test]
+    fn incompatible_memory_types_do_not_alias() {
+        let plan = plan_aliasing(&[request(0, 0), request(1, 1)], &[reqs(1024, 0b01), reqs(1024, 0b10)]);
+        assert_eq!(plan.slots.len(), 2);
+        assert_eq!(plan.alias_of, vec![None, None]);
+    }
+
+    #[test]
+    fn alias_chain_follows_last_tenant() {
+        // порядок запросов не по времени: алиасинг идет по first
+        let plan = plan_aliasing(&[request(4, 5), request(0, 1), request(2, 3)], &[reqs(512, 1); 3]);
+        assert_eq!(plan.slots.len(), 1);
+        assert_eq!(plan.alias_of, vec![Some(2), None, Some(1)]);
+    }
+}
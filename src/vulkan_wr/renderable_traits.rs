@@ -3,10 +3,9 @@ use std::ptr::null;
 
 use super::{
     app::VulkanApp,
-    command_pb::command_buffer::VulkanCommandBuffer,
     render_pass::pass::VulkanRenderPass,
-    sync::fence::VulkanFence,
     framebuffer::VulkanFramebuffer,
+    pipeline::permutation::FeatureFlags,
 };
 use crate::vulkan_wr::image::image_view::VulkanImageView;
 use ash::vk;
@@ -42,15 +41,16 @@ pub trait ShutdownObject<T: ShutdownObjectResources> {
 
 pub struct InitFrameResources<'a> {
     pub render_pass: Option<&'a VulkanRenderPass>,
-    pub upload_cmd: Option<&'a VulkanCommandBuffer>,
-    pub fence: Option<&'a VulkanFence>,
+    pub samples: vk::SampleCountFlags,  // MSAA основного прохода
+    pub ao_layout: Option<vk::DescriptorSetLayout>,  // сет с AO от SsaoPass, None если сцена его не делает
+    pub gbuffer_layout: Option<vk::DescriptorSetLayout>,  // сет G-buffer для deferred света, None если сцена без deferred
 }
 
 impl<'a> InitObjectResources for InitFrameResources<'a> {}
 
 impl<'a> Default for InitFrameResources<'a> {
     fn default() -> Self {
-        Self { render_pass: None, upload_cmd: None, fence: None }
+        Self { render_pass: None, samples: vk::SampleCountFlags::TYPE_1, ao_layout: None, gbuffer_layout: None }
     }
 }
 
@@ -61,13 +61,17 @@ pub struct RenderFrameResources<'a> {
     pub depth_attachment: Option<&'a VulkanImageView>,
     pub render_area: Option<vk::Rect2D>,
     pub clear_values: Vec<vk::ClearValue>,
+    pub ao_set: Option<vk::DescriptorSet>,  // AO для текущей картинки свапчейна
+    pub deferred: bool,  // геометрия пишется в G-buffer вместо HDR
+    pub oit: bool,  // прозрачные рисуются отдельным OIT проходом, в основном только непрозрачные
+    pub features: FeatureFlags,  // фичи кадра (тени), вариант пайплайна = они | фичи материала
 }
 
 impl<'a> RenderObjectResources for RenderFrameResources<'a>{}
 
 impl<'a> Default for RenderFrameResources<'a> {
     fn default() -> Self {
-        Self { render_pass: None, framebuffer: None, color_attachment: None, depth_attachment: None, render_area: None, clear_values: vec![] }
+        Self { render_pass: None, framebuffer: None, color_attachment: None, depth_attachment: None, render_area: None, clear_values: vec![], ao_set: None, deferred: false, oit: false, features: FeatureFlags::empty() }
     }
 }
 
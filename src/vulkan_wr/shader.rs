@@ -6,21 +6,55 @@
 
 
 use ash::{vk, Device};
-use std::io::Read;
+use std::path::{Path, PathBuf};
+use super::reflect::ShaderReflection;
+
+// EMBEDDED_SHADERS: (имя .spv, байты), пустой без фичи embed_shaders
+include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
 
 pub struct VulkanShader {
     pub _shader: vk::ShaderModule,
+    pub reflection: ShaderReflection,  // что шейдер ждет от layout'ов, см. reflect
     _device:Device,
 }
 
 pub type SResult<T> = Result<T, &'static str>;
 
+/// Где лежат .spv: OUT_DIR сборки, пока он на месте (оттуда их берет hot reload), иначе shaders/ рядом
+/// с бинарником. Сборка туда ничего не кладет, без файлов try_new берет встроенные (embed_shaders, по умолчанию)
+pub fn shader_dir() -> SResult<PathBuf> {
+    let built = Path::new(env!("OUT_DIR")).join("shaders");
+    if built.is_dir() {
+        return Ok(built);
+    }
+    let exe_path = std::env::current_exe()
+        .map_err(|_| "Failed to get current executable path")?;
+    let exe_dir = exe_path
+        .parent()
+        .ok_or("Executable is in the root directory?")?;
+    Ok(exe_dir.join("shaders"))
+}
+
+/// Путь до скомпилированного шейдера
+pub fn shader_path(name: &str) -> SResult<String> {
+    Ok(shader_dir()?.join(name).to_str().ok_or("Failed found shaders")?.to_string())
+}
+
+/// Встроенный в бинарник .spv по имени файла
+fn embedded(path: &str) -> Option<&'static [u8]> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    EMBEDDED_SHADERS.iter().find(|(n, _)| *n == name).map(|(_, bytes)| *bytes)
+}
+
 
 impl VulkanShader {
     pub fn try_new(device: &Device, path: &str) -> SResult<Self> {
-        let mut file = std::fs::File::open(path).map_err(|_| "Unable to load shader!")?;
-        let mut raw = vec![];
-        let file_size = file.read_to_end(&mut raw).map_err(|_| "")?;
+        // с диска, чтобы hot reload работал, и только потом из бинарника
+        let raw = match std::fs::read(path) {
+            Ok(raw) => raw,
+            Err(_) => embedded(path).ok_or("Unable to load shader!")?.to_vec(),
+        };
+        let file_size = raw.len();
 
         // вулкан хочет u32, а не u8 круто
         let mut dwords = vec![];
@@ -35,10 +69,13 @@ impl VulkanShader {
             ..Default::default()
         };
 
+        let reflection = ShaderReflection::parse(&dwords)?;
+
         let shader = unsafe{ device.create_shader_module(&shader_info, None).map_err(|_| "Err create_shader_module")?};
 
         Ok(Self{
             _shader: shader,
+            reflection,
             _device: device.clone(),
         })
     }
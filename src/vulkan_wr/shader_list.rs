@@ -0,0 +1,39 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: какие шейдеры собираются и во что. Общий для build.rs (через include!) и hot reload,
+// поэтому только std и полные пути
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+/// Расширения исходников, они же стадии
+pub const SHADER_STAGES: &[&str] = &["vert", "frag", "comp", "geom", "tesc", "tese"];
+
+/// Подпапка shaders/ с общими кусками для #include, сама не собирается
+pub const SHADER_INCLUDE_DIR: &str = "include";
+
+/// light.frag -> frag_light.spv. None - не шейдер
+pub fn spv_name(source: &str) -> Option<String> {
+    let (stem, ext) = source.rsplit_once('.')?;
+    if !SHADER_STAGES.contains(&ext) {
+        return None;
+    }
+    Some(format!("{}_{}.spv", ext, stem))
+}
+
+/// Все исходники прямо в dir (include/ не трогается), по имени
+pub fn discover_shaders(dir: &std::path::Path) -> std::io::Result<Vec<String>> {
+    let mut sources = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if spv_name(&name).is_some() {
+            sources.push(name);
+        }
+    }
+    sources.sort();
+    Ok(sources)
+}
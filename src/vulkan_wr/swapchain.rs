@@ -8,6 +8,14 @@
 use ash::{Device, khr, vk::{self, SwapchainKHR}};
 use super::core::VulkanCore;
 
+/// Что acquire/present сказали про свапчейн
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapchainState {
+    Optimal,
+    Suboptimal,  // картинку можно дорисовать и показать, пересоздать после present
+    OutOfDate,  // картинки нет, пересоздать до рисования
+}
+
 pub struct VulkanSwapchain {
     pub swapchain: vk::SwapchainKHR,
     pub ext_device: ash::khr::swapchain::Device,
@@ -17,11 +25,14 @@ pub struct VulkanSwapchain {
     pub depth_format: vk::Format,
     pub color_space: vk::ColorSpaceKHR,
     pub extent: vk::Extent2D,
+    pub present_mode: vk::PresentModeKHR,  // с каким реально создан
+    pub supported_present_modes: Vec<vk::PresentModeKHR>,  // что умеет поверхность
     _device: Device,
 }
 
 impl VulkanSwapchain {
-    pub fn acquire_next_image(&self, sem: Option<vk::Semaphore>, fence: Option<vk::Fence>) -> Result<(u32, bool), &'static str> {
+    /// Картинка + что свапчейн о себе думает. При OutOfDate индекс ничего не значит и семафор не сигналится
+    pub fn acquire_next_image(&self, sem: Option<vk::Semaphore>, fence: Option<vk::Fence>) -> Result<(u32, SwapchainState), &'static str> {
         unsafe {
             match self.ext_device.acquire_next_image(
                 self.swapchain,
@@ -29,31 +40,21 @@ impl VulkanSwapchain {
                 sem.unwrap_or(vk::Semaphore::null()),
                 fence.unwrap_or(vk::Fence::null())
             ) {
-                    Ok((index, flag)) => {
-                        return Ok((index, flag));
-                    },
-                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                        return Ok((0, true));  // 0 ничего не значит, с true свапчейн всегда считается протухших и удаляется
-                    },
-                    Err(e) => {
-                        return Err("Failed to acquire_next_image");
-                    }
+                    Ok((index, false)) => Ok((index, SwapchainState::Optimal)),
+                    Ok((index, true)) => Ok((index, SwapchainState::Suboptimal)),
+                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok((0, SwapchainState::OutOfDate)),
+                    Err(_) => Err("Failed to acquire_next_image"),
             }
         }
     }
-    // почему то эти функции не возращают suboptimal а дают сразу ERROR_OUT_OF_DATE_KHR
-    pub fn queue_present(&self, queue: vk::Queue, present_info: &vk::PresentInfoKHR) -> Result<bool, &'static str> {
+
+    pub fn queue_present(&self, queue: vk::Queue, present_info: &vk::PresentInfoKHR) -> Result<SwapchainState, &'static str> {
         unsafe {
             match self.ext_device.queue_present(queue, present_info) {
-                Ok(flag) => {
-                    return Ok(flag);
-                },
-                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                    return Ok(false);
-                },
-                Err(e) => {
-                    return Err("Failed to present swapchain image");
-                }
+                Ok(false) => Ok(SwapchainState::Optimal),
+                Ok(true) => Ok(SwapchainState::Suboptimal),
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(SwapchainState::OutOfDate),
+                Err(_) => Err("Failed to present swapchain image"),
             }
         }
     }
@@ -189,13 +190,11 @@ impl<'a> VulkanSwapchainBuilder<'a> {
                 .map_err(|_| "Failed to get present modes")?
         };
 
-        let present_mode = self.present_mode.unwrap_or_else(|| {
-            if present_modes.contains(&vk::PresentModeKHR::FIFO) {
-                vk::PresentModeKHR::FIFO
-            } else {
-                present_modes[0]
-            }
-        });
+        // неподдерживаемый запрос не ошибка: FIFO по спеке есть всегда
+        let present_mode = match self.present_mode {
+            Some(mode) if present_modes.contains(&mode) => mode,
+            _ => vk::PresentModeKHR::FIFO,
+        };
 
         // --- EXTENT ---
         let extent = self.extent.unwrap_or(caps.current_extent);  // current_extent
@@ -251,6 +250,8 @@ impl<'a> VulkanSwapchainBuilder<'a> {
             color_space: color_space,
             depth_format: depth_format,
             extent: extent,
+            present_mode: present_mode,
+            supported_present_modes: present_modes,
             _device: device.clone(),
         })
     }
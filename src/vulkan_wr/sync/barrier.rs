@@ -0,0 +1,87 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: барьеры synchronization2, стадия и доступ лежат в каждом барьере
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::vk;
+
+/// Все доступы на запись. Чтение после чтения барьера не требует, все остальное требует
+const WRITE_ACCESS: vk::AccessFlags2 = vk::AccessFlags2::from_raw(
+    vk::AccessFlags2::SHADER_WRITE.as_raw()
+    | vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+    | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+    | vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+    | vk::AccessFlags2::HOST_WRITE.as_raw()
+    | vk::AccessFlags2::MEMORY_WRITE.as_raw()
+);
+
+pub fn is_write_access(access: vk::AccessFlags2) -> bool {
+    access.intersects(WRITE_ACCESS)
+}
+
+/// Стадия и доступ по одну сторону барьера
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncScope {
+    pub stage: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+impl SyncScope {
+    pub const NONE: SyncScope = SyncScope { stage: vk::PipelineStageFlags2::NONE, access: vk::AccessFlags2::NONE };
+
+    pub fn new(stage: vk::PipelineStageFlags2, access: vk::AccessFlags2) -> Self {
+        Self { stage, access }
+    }
+}
+
+/// Барьер картинки: ждем src, переводим layout, показываем dst
+pub fn image_barrier2(
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    src: SyncScope, dst: SyncScope,
+    old_layout: vk::ImageLayout, new_layout: vk::ImageLayout,
+) -> vk::ImageMemoryBarrier2<'static> {
+    vk::ImageMemoryBarrier2 {
+        src_stage_mask: src.stage,
+        src_access_mask: src.access,
+        dst_stage_mask: dst.stage,
+        dst_access_mask: dst.access,
+        old_layout,
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: range,
+        ..Default::default()
+    }
+}
+
+/// Барьер на весь буфер
+pub fn buffer_barrier2(buffer: vk::Buffer, src: SyncScope, dst: SyncScope) -> vk::BufferMemoryBarrier2<'static> {
+    vk::BufferMemoryBarrier2 {
+        src_stage_mask: src.stage,
+        src_access_mask: src.access,
+        dst_stage_mask: dst.stage,
+        dst_access_mask: dst.access,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        buffer,
+        offset: 0,
+        size: vk::WHOLE_SIZE,
+        ..Default::default()
+    }
+}
+
+/// Глобальный барьер по памяти, без привязки к ресурсу
+pub fn memory_barrier2(src: SyncScope, dst: SyncScope) -> vk::MemoryBarrier2<'static> {
+    vk::MemoryBarrier2 {
+        src_stage_mask: src.stage,
+        src_access_mask: src.access,
+        dst_stage_mask: dst.stage,
+        dst_access_mask: dst.access,
+        ..Default::default()
+    }
+}
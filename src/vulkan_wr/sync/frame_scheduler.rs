@@ -0,0 +1,104 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: кадры в полете на одном timeline семафоре + отложенное удаление ресурсов
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::any::Any;
+use std::collections::VecDeque;
+
+use ash::{vk, Device};
+
+use super::timeline::VulkanTimelineSemaphore;
+
+/// Кадр N сигналит значение N на timeline, кадры считаются только отправленные.
+/// Пропущенный кадр (свернутое окно, пересоздание свапчейна) значения не тратит
+pub struct FrameScheduler {
+    pub timeline: VulkanTimelineSemaphore,
+    frames_in_flight: u32,
+    submitted: u64,  // сколько кадров ушло в очередь, последний сигналит это значение
+    retired: VecDeque<(u64, Box<dyn Any>)>,  // (после какого значения можно дропнуть, ресурс)
+}
+
+impl FrameScheduler {
+    /// # Args
+    /// * `frames_in_flight` - сколько кадров одновременно может быть на GPU, минимум 1
+    pub fn try_new(device: &Device, frames_in_flight: u32) -> Result<Self, &'static str> {
+        Ok(Self {
+            timeline: VulkanTimelineSemaphore::try_new(device, 0)?,
+            frames_in_flight: frames_in_flight.max(1),
+            submitted: 0,
+            retired: VecDeque::new(),
+        })
+    }
+
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight
+    }
+
+    /// Слот per-frame ресурсов текущего кадра, от 0 до frames_in_flight
+    pub fn frame_slot(&self) -> usize {
+        (self.submitted % self.frames_in_flight as u64) as usize
+    }
+
+    /// Значение, которое просигналит текущий кадр
+    pub fn frame_value(&self) -> u64 {
+        self.submitted + 1
+    }
+
+    /// Ждет, пока GPU отпустит слот текущего кадра, и удаляет то что уже можно
+    /// Возвращает слот, его per-frame ресурсы теперь можно переписывать
+    pub fn begin_frame(&mut self) -> Result<usize, &'static str> {
+        let wait_value = self.frame_value().saturating_sub(self.frames_in_flight as u64);
+        self.timeline.wait_forever(wait_value, "frame")?;
+        self.collect()?;
+        Ok(self.frame_slot())
+    }
+
+    /// Сигнал timeline для SubmitInfo2 этого кадра
+    pub fn signal_info(&self) -> vk::SemaphoreSubmitInfo<'static> {
+        self.timeline.submit_info(self.frame_value(), vk::PipelineStageFlags2::ALL_COMMANDS)
+    }
+
+    /// Кадр ушел в очередь с signal_info, следующий будет сигналить значение больше
+    pub fn end_frame(&mut self) {
+        self.submitted += 1;
+    }
+
+    /// До какого кадра GPU уже все закончил
+    pub fn completed(&self) -> Result<u64, &'static str> {
+        self.timeline.value()
+    }
+
+    /// Ресурс больше не нужен, но текущий или прошлые кадры могут его еще читать.
+    /// Дропнется, когда GPU пройдет текущий кадр
+    pub fn defer_drop<T: 'static>(&mut self, item: T) {
+        self.retired.push_back((self.frame_value(), Box::new(item)));
+    }
+
+    /// Дропает все, что GPU уже прошел
+    pub fn collect(&mut self) -> Result<(), &'static str> {
+        let completed = self.completed()?;
+        while self.retired.front().is_some_and(|(value, _)| *value <= completed) {
+            self.retired.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Ждет все отправленные кадры и дропает все отложенное
+    pub fn wait_idle(&mut self) -> Result<(), &'static str> {
+        self.timeline.wait_forever(self.submitted, "frame")?;
+        // то что отложили после последнего сабмита GPU уже не увидит
+        self.retired.clear();
+        Ok(())
+    }
+}
+
+impl Drop for FrameScheduler {
+    fn drop(&mut self) {
+        if self.wait_idle().is_err() {
+            println!("Something went wrong with the frame scheduler wait");
+        }
+    }
+}
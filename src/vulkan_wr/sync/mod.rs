@@ -1,3 +1,6 @@
 
 pub mod fence;
 pub mod semaphore;
+pub mod barrier;
+pub mod timeline;
+pub mod frame_scheduler;
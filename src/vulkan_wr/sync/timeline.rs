@@ -0,0 +1,100 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: Vulkan timeline semaphore wrapper
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use ash::{vk, Device};
+
+/// Как часто wait_forever напоминает о себе
+const SLOW_WAIT_LOG_NS: u64 = 1_000_000_000;
+
+/// Семафор со счетчиком: GPU сигналит значения по возрастанию, CPU может ждать любое из них
+pub struct VulkanTimelineSemaphore {
+    pub semaphore: vk::Semaphore,
+    _device: Device,
+}
+
+impl VulkanTimelineSemaphore {
+    pub fn try_new(device: &Device, initial_value: u64) -> Result<Self, &'static str> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let semaphore_info = vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+        let semaphore = unsafe {
+            device.create_semaphore(&semaphore_info, None)
+                .map_err(|_| "Failed to create timeline semaphore")?
+        };
+        Ok(Self {
+            semaphore,
+            _device: device.clone(),
+        })
+    }
+
+    /// До какого значения GPU уже дошел
+    pub fn value(&self) -> Result<u64, &'static str> {
+        unsafe {
+            self._device.get_semaphore_counter_value(self.semaphore)
+                .map_err(|_| "Failed to read timeline semaphore")
+        }
+    }
+
+    /// Ждет значение на CPU
+    /// # Args
+    /// * `timeout` - в наносекундах
+    /// Ok(false) если не дождались за timeout
+    pub fn wait(&self, value: u64, timeout: u64) -> Result<bool, &'static str> {
+        let semaphores = [self.semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(&semaphores)
+            .values(&values);
+        match unsafe { self._device.wait_semaphores(&wait_info, timeout) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(vk::Result::ERROR_DEVICE_LOST) => Err("Device lost while waiting timeline semaphore"),
+            Err(_) => Err("Failed to wait timeline semaphore"),
+        }
+    }
+
+    /// Ждет сколько нужно: долгий кадр (тяжелая сцена, отладчик, свернутое окно) не ошибка.
+    /// Раз в секунду пишет, что все еще ждет. Ошибка только если девайс потерян
+    /// # Args
+    /// * `what` - чье значение ждем, для лога
+    pub fn wait_forever(&self, value: u64, what: &str) -> Result<(), &'static str> {
+        let mut seconds = 0;
+        while !self.wait(value, SLOW_WAIT_LOG_NS)? {
+            seconds += 1;
+            println!("{}: value {} is still pending after {} s", what, value, seconds);
+        }
+        Ok(())
+    }
+
+    /// Сигнал с CPU, для тестовых прогонов без GPU работы
+    pub fn signal(&self, value: u64) -> Result<(), &'static str> {
+        let signal_info = vk::SemaphoreSignalInfo::default()
+            .semaphore(self.semaphore)
+            .value(value);
+        unsafe {
+            self._device.signal_semaphore(&signal_info)
+                .map_err(|_| "Failed to signal timeline semaphore")
+        }
+    }
+
+    /// Для SubmitInfo2: просигналить value после stage
+    pub fn submit_info(&self, value: u64, stage: vk::PipelineStageFlags2) -> vk::SemaphoreSubmitInfo<'static> {
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(self.semaphore)
+            .value(value)
+            .stage_mask(stage)
+    }
+}
+
+impl Drop for VulkanTimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            self._device.destroy_semaphore(self.semaphore, None)
+        };
+    }
+}
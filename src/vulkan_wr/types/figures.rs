@@ -22,6 +22,7 @@ pub fn make_plane(color: [f32;3]) -> Mesh {
         index_count: indices.len(),
         material: Some(Material{..Default::default()}),
         texture_id: 0,
+        center: [0.0; 3],
     };
 
     Mesh {
@@ -75,6 +76,7 @@ pub fn make_cube(face_colors: Option<[[f32;3];6]>) -> Mesh {
         index_count: indices.len(),
         material: Some(Material{..Default::default()}),
         texture_id: 0,
+        center: [0.0; 3],
     };
 
     Mesh {
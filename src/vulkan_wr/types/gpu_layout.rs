@@ -0,0 +1,356 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: std140/std430 раскладка для Rust структур, которые уходят в шейдер.
+// gpu_struct! проверяет offset'ы еще при сборке, reflect потом сверяет с SPIR-V
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+/// Правила раскладки блока. std140 - uniform, std430 - storage (и push constants)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayoutRule {
+    Std140,
+    Std430,
+}
+
+/// Размер и выравнивание по правилу
+#[derive(Clone, Copy, Debug)]
+pub struct GpuLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+/// Поле, как его разложит GLSL
+#[derive(Clone, Debug)]
+pub struct GpuField {
+    pub name: &'static str,
+    pub glsl: &'static str,
+    pub array_len: usize,  // 0 - не массив
+    pub offset: usize,
+    pub size: usize,
+    pub members: Vec<GpuField>,  // если элемент - структура
+}
+
+/// Тип, который можно положить в блок
+pub trait GpuType {
+    const GLSL: &'static str;  // тип элемента, для массивов без [N]
+    const ARRAY_LEN: usize = 0;
+    const STD140: GpuLayout;
+    const STD430: GpuLayout;
+
+    fn members(_rule: LayoutRule) -> Vec<GpuField> {
+        vec![]
+    }
+}
+
+/// Структура из gpu_struct!. RULE - под какой блок собрана Rust сторона
+pub trait GpuStruct: GpuType {
+    const RULE: LayoutRule;
+
+    fn fields() -> Vec<GpuField> {
+        Self::members(Self::RULE)
+    }
+
+    /// Объявление для GLSL, _pad поля не попадают
+    fn glsl() -> String {
+        let mut out = format!("struct {} {{\n", Self::GLSL);
+        for f in Self::fields() {
+            match f.array_len {
+                0 => out.push_str(&format!("    {} {};  // offset {}\n", f.glsl, f.name, f.offset)),
+                n => out.push_str(&format!("    {} {}[{}];  // offset {}\n", f.glsl, f.name, n, f.offset)),
+            }
+        }
+        out.push_str("};\n");
+        out
+    }
+}
+
+pub const fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+pub const fn layout_of<T: GpuType>(rule: LayoutRule) -> GpuLayout {
+    match rule {
+        LayoutRule::Std140 => T::STD140,
+        LayoutRule::Std430 => T::STD430,
+    }
+}
+
+/// _pad* - добивка только для Rust, в GLSL ее нет
+pub const fn is_pad(name: &str) -> bool {
+    let name = name.as_bytes();
+    let pad = b"_pad";
+    if name.len() < pad.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < pad.len() {
+        if name[i] != pad[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Конец последнего поля -> размер структуры. В std140 структура выравнивается до vec4
+pub const fn struct_layout(rule: LayoutRule, end: usize, align: usize) -> GpuLayout {
+    let align = match rule {
+        LayoutRule::Std140 => round_up(align, 16),
+        LayoutRule::Std430 => align,
+    };
+    GpuLayout { size: round_up(end, align), align }
+}
+
+macro_rules! gpu_scalar {
+    ($($ty:ty => $glsl:literal, $size:literal, $align:literal;)*) => {
+        $(
+            impl GpuType for $ty {
+                const GLSL: &'static str = $glsl;
+                const STD140: GpuLayout = GpuLayout { size: $size, align: $align };
+                const STD430: GpuLayout = GpuLayout { size: $size, align: $align };
+            }
+        )*
+    };
+}
+
+// массивы скаляров в блоках тут не нужны, поэтому [f32; 4] - это vec4
+gpu_scalar! {
+    f32 => "float", 4, 4;
+    u32 => "uint", 4, 4;
+    i32 => "int", 4, 4;
+    [f32; 2] => "vec2", 8, 8;
+    [f32; 3] => "vec3", 12, 16;
+    [f32; 4] => "vec4", 16, 16;
+    [u32; 4] => "uvec4", 16, 16;
+    [i32; 4] => "ivec4", 16, 16;
+    [[f32; 4]; 4] => "mat4", 64, 16;
+}
+
+/// Массив структур: в std140 шаг добивается до 16
+impl<T: GpuStruct, const N: usize> GpuType for [T; N] {
+    const GLSL: &'static str = T::GLSL;
+    const ARRAY_LEN: usize = N;
+    const STD140: GpuLayout = {
+        let align = round_up(T::STD140.align, 16);
+        GpuLayout { size: round_up(T::STD140.size, align) * N, align }
+    };
+    const STD430: GpuLayout = GpuLayout {
+        size: round_up(T::STD430.size, T::STD430.align) * N,
+        align: T::STD430.align,
+    };
+
+    fn members(rule: LayoutRule) -> Vec<GpuField> {
+        T::members(rule)
+    }
+}
+
+/// Объявляет #[repr(C)] структуру для шейдера. Offset'ы каждого поля (кроме _pad*)
+/// сверяются с std140/std430 при компиляции, не совпало - сборка падает.
+///
+/// gpu_struct! {
+///     #[derive(Clone, Copy, Debug)]
+///     pub struct Uniforms: Std140 {
+///         pub mvp: [[f32; 4]; 4],
+///         pub time: f32,
+///         pub _pad: [f32; 3],
+///     }
+/// }
+macro_rules! gpu_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident : $rule:ident {
+            $( $(#[$fmeta:meta])* $fvis:vis $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        $vis struct $name {
+            $( $(#[$fmeta])* $fvis $field: $ty, )*
+        }
+
+        impl $name {
+            const fn gpu_layout(rule: $crate::vulkan_wr::types::gpu_layout::LayoutRule) -> $crate::vulkan_wr::types::gpu_layout::GpuLayout {
+                use $crate::vulkan_wr::types::gpu_layout::{is_pad, layout_of, round_up, struct_layout};
+                let mut end = 0;
+                let mut align = 1;
+                $(
+                    if !is_pad(stringify!($field)) {
+                        let l = layout_of::<$ty>(rule);
+                        end = round_up(end, l.align) + l.size;
+                        if l.align > align {
+                            align = l.align;
+                        }
+                    }
+                )*
+                struct_layout(rule, end, align)
+            }
+        }
+
+        impl $crate::vulkan_wr::types::gpu_layout::GpuType for $name {
+            const GLSL: &'static str = stringify!($name);
+            const STD140: $crate::vulkan_wr::types::gpu_layout::GpuLayout =
+                Self::gpu_layout($crate::vulkan_wr::types::gpu_layout::LayoutRule::Std140);
+            const STD430: $crate::vulkan_wr::types::gpu_layout::GpuLayout =
+                Self::gpu_layout($crate::vulkan_wr::types::gpu_layout::LayoutRule::Std430);
+
+            #[allow(unused_assignments)]  // offset после последнего поля
+            fn members(rule: $crate::vulkan_wr::types::gpu_layout::LayoutRule) -> Vec<$crate::vulkan_wr::types::gpu_layout::GpuField> {
+                use $crate::vulkan_wr::types::gpu_layout::{is_pad, layout_of, round_up, GpuField, GpuType};
+                let mut fields = vec![];
+                let mut offset = 0;
+                $(
+                    if !is_pad(stringify!($field)) {
+                        let l = layout_of::<$ty>(rule);
+                        offset = round_up(offset, l.align);
+                        fields.push(GpuField {
+                            name: stringify!($field),
+                            glsl: <$ty as GpuType>::GLSL,
+                            array_len: <$ty as GpuType>::ARRAY_LEN,
+                            offset,
+                            size: l.size,
+                            members: <$ty as GpuType>::members(rule),
+                        });
+                        offset += l.size;
+                    }
+                )*
+                fields
+            }
+        }
+
+        impl $crate::vulkan_wr::types::gpu_layout::GpuStruct for $name {
+            const RULE: $crate::vulkan_wr::types::gpu_layout::LayoutRule = $crate::vulkan_wr::types::gpu_layout::LayoutRule::$rule;
+        }
+
+        // та самая проверка при сборке
+        #[allow(unused_assignments)]
+        const _: () = {
+            use $crate::vulkan_wr::types::gpu_layout::{is_pad, layout_of, round_up, LayoutRule};
+            let rule = LayoutRule::$rule;
+            let mut offset = 0;
+            $(
+                if !is_pad(stringify!($field)) {
+                    let l = layout_of::<$ty>(rule);
+                    offset = round_up(offset, l.align);
+                    assert!(
+                        offset == std::mem::offset_of!($name, $field),
+                        concat!(stringify!($name), ".", stringify!($field), ": Rust offset differs from ", stringify!($rule), ", add or fix _pad"),
+                    );
+                    offset += l.size;
+                }
+            )*
+            assert!(
+                std::mem::size_of::<$name>() == layout_of::<$name>(rule).size,
+                concat!(stringify!($name), ": Rust size differs from ", stringify!($rule), ", add or fix trailing _pad"),
+            );
+        };
+    };
+}
+
+pub(crate) use gpu_struct;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets<T: GpuStruct>() -> Vec<(&'static str, usize)> {
+        T::fields().iter().map(|f| (f.name, f.offset)).collect()
+    }
+
+    gpu_struct! {
+        struct Vec3Block: Std430 {
+            a: f32,
+            _pad0: [f32; 3],
+            b: [f32; 3],
+            c: f32,  // влезает в хвост vec3
+        }
+    }
+
+    gpu_struct! {
+        struct Inner140: Std140 {
+            v: [f32; 2],
+            _pad0: [f32; 2],
+        }
+    }
+
+    gpu_struct! {
+        struct Nested140: Std140 {
+            a: f32,
+            _pad0: [f32; 3],
+            inner: Inner140,
+            b: f32,
+            _pad1: [f32; 3],
+        }
+    }
+
+    gpu_struct! {
+        struct Inner430: Std430 {
+            v: [f32; 2],
+        }
+    }
+
+    gpu_struct! {
+        struct Nested430: Std430 {
+            a: f32,
+            _pad0: f32,
+            inner: Inner430,
+            b: f32,
+            _pad1: f32,
+        }
+    }
+
+    gpu_struct! {
+        struct Scalar: Std430 {
+            x: f32,
+        }
+    }
+
+    gpu_struct! {
+        struct MatrixBlock: Std140 {
+            t: f32,
+            _pad0: [f32; 3],
+            m: [[f32; 4]; 4],
+            u: u32,
+            _pad1: [f32; 3],
+        }
+    }
+
+    #[test]
+    fn vec3_aligns_to_16_and_float_fills_its_tail() {
+        assert_eq!(offsets::<Vec3Block>(), vec![("a", 0), ("b", 16), ("c", 28)]);
+        assert_eq!(Vec3Block::STD430.size, 32);
+        assert_eq!(Vec3Block::STD430.align, 16);
+    }
+
+    #[test]
+    fn std140_array_stride_rounds_up_to_16() {
+        let std140 = layout_of::<[Scalar; 3]>(LayoutRule::Std140);
+        assert_eq!((std140.size, std140.align), (48, 16));
+        let std430 = layout_of::<[Scalar; 3]>(LayoutRule::Std430);
+        assert_eq!((std430.size, std430.align), (12, 4));
+    }
+
+    #[test]
+    fn nested_struct_aligns_to_16_only_in_std140() {
+        assert_eq!(offsets::<Nested140>(), vec![("a", 0), ("inner", 16), ("b", 32)]);
+        assert_eq!(Nested140::STD140.size, 48);
+        assert_eq!(offsets::<Nested430>(), vec![("a", 0), ("inner", 8), ("b", 16)]);
+        assert_eq!((Nested430::STD430.size, Nested430::STD430.align), (24, 8));
+    }
+
+    #[test]
+    fn mat4_is_four_vec4_columns() {
+        let mat = layout_of::<[[f32; 4]; 4]>(LayoutRule::Std140);
+        assert_eq!((mat.size, mat.align), (4 * 16, 16));
+        assert_eq!(offsets::<MatrixBlock>(), vec![("t", 0), ("m", 16), ("u", 80)]);
+        assert_eq!(MatrixBlock::STD140.size, 96);
+    }
+
+    #[test]
+    fn pad_fields_stay_out_of_glsl() {
+        let glsl = Nested140::glsl();
+        assert!(glsl.contains("Inner140 inner;  // offset 16"));
+        assert!(!glsl.contains("_pad"));
+    }
+}
@@ -77,6 +77,17 @@ impl Matrix<4 , 4> {
         ])
     }
 
+    /// Сдвиг проекции на субпиксель для TAA, смещение в NDC (2 * px / размер экрана)
+    /// Работает для любой проекции: x_clip += dx * w_clip
+    pub fn jitter(&self, ndc_dx: f32, ndc_dy: f32) -> Self {
+        let mut m = *self;
+        for c in 0..4 {
+            m.data[0][c] += ndc_dx * self.data[3][c];
+            m.data[1][c] += ndc_dy * self.data[3][c];
+        }
+        m
+    }
+
     pub fn scale(sx: f32, sy: f32, sz: f32) -> Self {
         let mut data = [[0.0; 4]; 4];
         data[0][0] = sx;
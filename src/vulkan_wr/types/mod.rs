@@ -4,3 +4,4 @@ pub mod vertex;
 pub mod vector;
 pub mod model;
 pub mod figures;
+pub mod gpu_layout;
@@ -1,13 +1,16 @@
 
 use crate::vulkan_wr::app::VulkanApp;
 use crate::vulkan_wr::buffer::buffer::VulkanBuffer;
-use crate::vulkan_wr::descriptor::descriptor_set_layout::VulkanDescriptorSetLayout;
+use crate::vulkan_wr::pipeline::permutation::FeatureFlags;
 use crate::vulkan_wr::renderable_traits::InitFrameResources;
-use crate::vulkan_wr::texture::TextureGPU;
+use crate::vulkan_wr::texture::{TextureGPU, DecodedImage};
+use crate::vulkan_wr::upload::UploadTicket;
 
 use super::vertex::VulkanVertex;
 use super::matrix::Matrix;
+use super::figures::make_stub_rgba;
 use super::vector::VulkanVector;
+use super::gpu_layout::gpu_struct;
 use std::collections::HashMap;
 use std::path::Path;
 use tobj::Material;
@@ -19,8 +22,8 @@ pub struct MeshGPU {
     pub index_count: u32,
     pub submeshes: Vec<SubMesh>,
     pub texture: Vec<TextureGPU>,
-    pub material_ubo: VulkanBuffer,
-    pub transform_ubo: VulkanBuffer,
+    pub materials: Vec<MaterialUBO>,  // по сабмешам, на GPU их кладет BindlessTable::add_mesh
+    pub model: [[f32; 4]; 4],  // уже транспонирована, как ждет GLSL, для DrawPush
 }
 
 #[derive(Clone)]
@@ -36,6 +39,33 @@ pub struct SubMesh {
     pub index_count: usize,
     pub material: Option<Material>,
     pub texture_id: usize,
+    pub center: [f32; 3],  // центр в мире, считается в to_gpu_meshes, по нему сортируются прозрачные
+}
+
+impl SubMesh {
+    /// d из mtl, если его нет то 1 - Tr
+    pub fn opacity(&self) -> f32 {
+        let Some(mat) = self.material.as_ref() else {
+            return 1.0;
+        };
+        mat.dissolve
+            .or_else(|| mat.unknown_param.get("Tr").and_then(|tr| tr.trim().parse::<f32>().ok()).map(|tr| 1.0 - tr))
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0)
+    }
+
+    /// Идет в прозрачную очередь, а не в основную
+    pub fn is_transparent(&self) -> bool {
+        self.opacity() < 1.0
+    }
+
+    /// Фичи материала для варианта пайплайна. map_d в mtl - маска вырезания, ее держит альфа
+    /// diffuse текстуры
+    pub fn features(&self) -> FeatureFlags {
+        let mut flags = FeatureFlags::empty();
+        flags.set(FeatureFlags::ALPHA_TEST, self.material.as_ref().is_some_and(|m| m.dissolve_texture.is_some()));
+        flags
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -53,11 +83,23 @@ impl Transform {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct TransformUBO {
-    model: [[f32; 4]; 4],
-    normal: [[f32; 4]; 4],
+// GLSL сторона - shaders/include/draw.glsl. 80 байт, влезает в гарантированные 128
+gpu_struct! {
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct DrawPush: Std430 {
+        pub model: [[f32; 4]; 4],
+        pub material_index: u32,  // id в BindlessTable
+        pub object_id: u32,  // номер меша в сцене
+        pub oit: u32,  // light.frag: 0 - обычный цвет с альфой, 1 - accum/revealage
+        pub _pad: f32,
+    }
+}
+
+impl DrawPush {
+    /// Диапазон для pipeline layout: весь блок с нуля
+    pub fn range(stages: vk::ShaderStageFlags) -> vk::PushConstantRange {
+        vk::PushConstantRange { stage_flags: stages, offset: 0, size: std::mem::size_of::<Self>() as u32 }
+    }
 }
 
 
@@ -67,13 +109,15 @@ pub struct Model {
     // pub albedo_color: VulkanVector<3>,
 }
 
-#[repr(C)] // без компилятор может поменять порядок
-#[derive(Clone, Copy, Debug)]
-pub struct MaterialUBO {  // Выровнять надо каждый до vec4
-    pub ambient: [f32; 4],  // .w свободен
-    pub diffuse: [f32; 4],
-    pub specular: [f32; 4],
-    pub extra: [f32; 4],  // extra[0] = shininess, остальные — padding
+// на GPU в виде BindlessMaterial, GLSL сторона - Material в shaders/include/bindless.glsl
+gpu_struct! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct MaterialUBO: Std140 {
+        pub ambient: [f32; 4],  // .w свободен
+        pub diffuse: [f32; 4],  // .w - непрозрачность (d)
+        pub specular: [f32; 4],
+        pub extra: [f32; 4],  // extra[0] = shininess, остальные — padding
+    }
 }
 
 impl Default for MaterialUBO {
@@ -163,6 +207,7 @@ impl Model {
                 index_count: indices.len(),
                 material: mat,
                 texture_id: 0,
+                center: [0.0; 3],
             });
 
             meshes.push(Mesh {
@@ -179,112 +224,215 @@ impl Model {
         })
     }
 
-    pub fn to_gpu_meshes(
-        &mut self,
-        app: &mut VulkanApp,
-        resources: &mut InitFrameResources,
-        sampler_set_layout: &[VulkanDescriptorSetLayout],
-        alignment: u64
-    ) -> Result<Vec<MeshGPU>, &'static str> {
-        let mut gpu_meshes = Vec::new();
-        let mat_size = std::mem::size_of::<MaterialUBO>() as u64;
-        let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-
-        for mesh in self.meshes.iter_mut() {
-            let vb = VulkanBuffer::try_new(
-                &app.core,
-                (std::mem::size_of::<VulkanVertex>() * mesh.vertices.len()) as u64 * app.image_count as u64,
-                vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-                None, None, None, None
-            )?;
-
-            let ib = VulkanBuffer::try_new(
-                &app.core,
-                (std::mem::size_of::<u32>() * mesh.indices.len()) as u64,
-                vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                None, None, None, None
-            )?;
-            unsafe {
-                vb.mem_copy(mesh.vertices.as_slice(), None, None, None)?;
-                ib.mem_copy(mesh.indices.as_slice(), None, None, None)?;
-            }
+    /// Грузит по расширению: .gltf/.glb (с фичей gltf), остальное как OBJ
+    pub fn load(path: &str) -> Result<Self, &'static str> {
+        let ext = Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("gltf") | Some("glb") => Self::try_new_gltf(path),
+            _ => Self::try_new(path),
+        }
+    }
 
-            let mut textures_for_mesh = Vec::new();
-            let mat_buf = VulkanBuffer::try_new(
-                &app.core,
-                aligned_size * mesh.submeshes.len() as u64 * app.image_count as u64,
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-                None, None, None, None
-            )?;
-
-            let mut offset: u64 = 0;
-            for (i, sm) in mesh.submeshes.iter_mut().enumerate() {
-                sm.texture_id = i;
-                let texture = match &sm.material {
-                    None => TextureGPU::make_white(app, resources, sampler_set_layout, &[255,255,255,0])?,
-                    Some(mat) => match &mat.diffuse_texture {
-                        Some(path) => TextureGPU::load_texture(app, resources, path.clone(), sampler_set_layout)?,
-                        None => TextureGPU::make_white(app, resources, sampler_set_layout, &[255,255,255,0])?,
-                    }
-                };
+    /// Каждый mesh glTF - свой Mesh, примитивы - его сабмеши.
+    /// Трансформы нод не применяются, как и в OBJ все в координатах меша.
+    /// Текстуры только по uri, встроенные в буфер пока заменяются белой
+    #[cfg(feature = "gltf")]
+    pub fn try_new_gltf(path: &str) -> Result<Self, &'static str> {
+        let (document, buffers, _) = gltf::import(path).map_err(|_| "Failed to load glTF")?;
+        let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
 
-                let mat = sm.material.as_ref();
-                let ambient = mat.and_then(|m| m.ambient).unwrap_or([0.1; 3]);
-                let diffuse = mat.and_then(|m| m.diffuse).unwrap_or([1.0; 3]);
-                let specular = mat.and_then(|m| m.specular).unwrap_or([1.0; 3]);
-                let shininess = mat.and_then(|m| m.shininess).unwrap_or(32.0);
-
-                let material_data = MaterialUBO {
-                    ambient: [ambient[0], ambient[1], ambient[2], 0.0],
-                    diffuse: [diffuse[0], diffuse[1], diffuse[2], 0.0],
-                    specular: [specular[0], specular[1], specular[2], 0.0],
-                    extra: [shininess, 0.0, 0.0, 0.0],
-                    ..Default::default()
+        let mut meshes = Vec::new();
+        for gltf_mesh in document.meshes() {
+            let mut mesh = Mesh::default();
+            for primitive in gltf_mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
                 };
+                let base = mesh.vertices.len() as u32;
+                let normals: Vec<[f32; 3]> = reader.read_normals().map(|n| n.collect()).unwrap_or_default();
+                let uvs: Vec<[f32; 2]> = reader.read_tex_coords(0).map(|t| t.into_f32().collect()).unwrap_or_default();
+                for (i, pos) in positions.enumerate() {
+                    mesh.vertices.push(VulkanVertex {
+                        pos,
+                        norm: normals.get(i).copied().unwrap_or([0.0; 3]),
+                        uv: uvs.get(i).copied().unwrap_or([0.0; 2]),
+                        color: [0.0, 0.0, 0.0],
+                    });
+                }
 
-                unsafe { mat_buf.mem_copy(&[material_data], Some(offset), None, None)?; }
-                offset += aligned_size;
-                textures_for_mesh.push(texture);
+                let index_offset = mesh.indices.len();
+                match reader.read_indices() {
+                    Some(read) => mesh.indices.extend(read.into_u32().map(|i| base + i)),
+                    None => mesh.indices.extend(base..mesh.vertices.len() as u32),
+                }
+                mesh.submeshes.push(SubMesh {
+                    index_offset,
+                    index_count: mesh.indices.len() - index_offset,
+                    material: Some(gltf_material(&primitive.material(), base_dir)),
+                    texture_id: 0,
+                    center: [0.0; 3],
+                });
             }
+            if !mesh.submeshes.is_empty() {
+                meshes.push(mesh);
+            }
+        }
 
-            let alignment = app.get_min_ubo_alignment();  // aligned_size GPU
-            let mat_size = std::mem::size_of::<TransformUBO>() as u64;
-            let aligned_size = ((mat_size + alignment - 1) / alignment) * alignment;
-
-            let transf_ubo = VulkanBuffer::try_new(
-                &app.core,
-                // size_of::<TransformUBO>()
-                aligned_size * app.image_count as u64 ,
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-                None, None, None, None
-            )?;
-            let tmp = self.transform.to_matrix();
-            let transf_data = TransformUBO{
-                model: tmp.transpose().data,
-                normal: (tmp.inverse())?.data  // transpose().transpose().
-            };
-            for i in 0..app.image_count as u64 {
-                unsafe { transf_ubo.mem_copy(&[transf_data], Some(aligned_size * i), None, None)?; }
+        Ok(Model {
+            meshes,
+            transform: Transform::default(),
+        })
+    }
 
-            }
+    #[cfg(not(feature = "gltf"))]
+    pub fn try_new_gltf(_path: &str) -> Result<Self, &'static str> {
+        Err("glTF support is disabled, build with feature gltf")
+    }
 
-            gpu_meshes.push(MeshGPU {
-                vertex_buf: vb,
-                index_buf: ib,
-                index_count: mesh.indices.len() as u32,
-                submeshes: mesh.submeshes.clone(),
-                texture: textures_for_mesh,
-                material_ubo: mat_buf,
-                transform_ubo: transf_ubo,
-            });
+    /// Разжимает все diffuse текстуры модели, ключ - путь из материала.
+    /// Не загрузившиеся пропускаются, на их месте будет белая
+    pub fn decode_textures(&self) -> HashMap<String, DecodedImage> {
+        let mut images = HashMap::new();
+        let paths = self.meshes.iter()
+            .flat_map(|m| m.submeshes.iter())
+            .filter_map(|sm| sm.material.as_ref().and_then(|mat| mat.diffuse_texture.clone()));
+        for path in paths {
+            if images.contains_key(&path) {
+                continue;
+            }
+            match DecodedImage::decode(&path) {
+                Ok(image) => { images.insert(path, image); }
+                Err(err) => println!("texture {}: {}, white is used", path, err),
+            }
         }
+        images
+    }
 
+    pub fn to_gpu_meshes(
+        &mut self,
+        app: &mut VulkanApp,
+        _resources: &mut InitFrameResources,
+    ) -> Result<Vec<MeshGPU>, &'static str> {
+        let images = self.decode_textures();
+        let mut gpu_meshes = Vec::new();
+        for i in 0..self.meshes.len() {
+            gpu_meshes.push(self.mesh_to_gpu(app, i, &images)?.0);
+        }
+        // все загрузки одной пачкой, ждем один раз
+        app.uploader.flush()?;
         Ok(gpu_meshes)
     }
+
+    /// Один меш на GPU без ожидания: рисовать можно после app.uploader.wait(ticket)
+    /// # Args
+    /// * `images` - уже разжатые текстуры, см. decode_textures. Чего нет - будет белым
+    pub fn mesh_to_gpu(
+        &mut self,
+        app: &mut VulkanApp,
+        index: usize,
+        images: &HashMap<String, DecodedImage>,
+    ) -> Result<(MeshGPU, UploadTicket), &'static str> {
+        let model_matrix = self.transform.to_matrix();
+        let mesh = &mut self.meshes[index];
+
+        // меши статичные, кладем в DEVICE_LOCAL через transfer очередь
+        let (vb, _) = app.uploader.upload_buffer(&app.core, mesh.vertices.as_slice(), vk::BufferUsageFlags::VERTEX_BUFFER)?;
+        let (ib, mut ticket) = app.uploader.upload_buffer(&app.core, mesh.indices.as_slice(), vk::BufferUsageFlags::INDEX_BUFFER)?;
+
+        let mut textures_for_mesh = Vec::new();
+        let mut materials = Vec::with_capacity(mesh.submeshes.len());
+
+        for (i, sm) in mesh.submeshes.iter_mut().enumerate() {
+            sm.texture_id = i;
+            sm.center = submesh_center(&mesh.vertices, &mesh.indices[sm.index_offset..sm.index_offset + sm.index_count], &model_matrix);
+            let image = sm.material.as_ref()
+                .and_then(|mat| mat.diffuse_texture.as_ref())
+                .and_then(|path| images.get(path));
+            let (texture, texture_ticket) = match image {
+                Some(image) => TextureGPU::upload_rgba(app, image.rgba.as_slice(), image.width, image.height)?,
+                None => {
+                    let (data, w, h) = make_stub_rgba(255, 255, 255, 0);
+                    TextureGPU::upload_rgba(app, data.as_slice(), w, h)?
+                }
+            };
+            // загрузки завершаются по порядку, последний тикет покрывает весь меш
+            ticket = ticket.max(texture_ticket);
+
+            let mat = sm.material.as_ref();
+            let ambient = mat.and_then(|m| m.ambient).unwrap_or([0.1; 3]);
+            let diffuse = mat.and_then(|m| m.diffuse).unwrap_or([1.0; 3]);
+            let specular = mat.and_then(|m| m.specular).unwrap_or([1.0; 3]);
+            let shininess = mat.and_then(|m| m.shininess).unwrap_or(32.0);
+            let opacity = sm.opacity();
+
+            let material_data = MaterialUBO {
+                ambient: [ambient[0], ambient[1], ambient[2], 0.0],
+                diffuse: [diffuse[0], diffuse[1], diffuse[2], opacity],
+                specular: [specular[0], specular[1], specular[2], 0.0],
+                extra: [shininess, 0.0, 0.0, 0.0],
+                ..Default::default()
+            };
+
+            materials.push(material_data);
+            textures_for_mesh.push(texture);
+        }
+
+        Ok((MeshGPU {
+            vertex_buf: vb,
+            index_buf: ib,
+            index_count: mesh.indices.len() as u32,
+            submeshes: mesh.submeshes.clone(),
+            texture: textures_for_mesh,
+            materials,
+            model: model_matrix.transpose().data,
+        }, ticket))
+    }
+}
+
+/// Материал glTF в терминах mtl: base color -> diffuse, альфа -> d
+#[cfg(feature = "gltf")]
+fn gltf_material(material: &gltf::Material, base_dir: &Path) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    let diffuse_texture = pbr.base_color_texture().and_then(|info| match info.texture().source().source() {
+        gltf::image::Source::Uri { uri, .. } => base_dir.join(uri).to_str().map(|p| p.to_string()),
+        gltf::image::Source::View { .. } => None,
+    });
+    Material {
+        name: material.name().unwrap_or("").to_string(),
+        diffuse: Some([r, g, b]),
+        dissolve: Some(a),
+        diffuse_texture,
+        ..Default::default()
+    }
+}
+
+/// Центр AABB сабмеша в мировых координатах
+/// # Args
+/// * `indices` - только индексы этого сабмеша
+fn submesh_center(vertices: &[VulkanVertex], indices: &[u32], model: &Matrix<4, 4>) -> [f32; 3] {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for &idx in indices {
+        let pos = vertices[idx as usize].pos;
+        for k in 0..3 {
+            min[k] = min[k].min(pos[k]);
+            max[k] = max[k].max(pos[k]);
+        }
+    }
+    if indices.is_empty() {
+        return [model.data[0][3], model.data[1][3], model.data[2][3]];
+    }
+    let c = [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5, (min[2] + max[2]) * 0.5];
+    let mut out = [0.0; 3];
+    for row in 0..3 {
+        out[row] = model.data[row][0] * c[0] + model.data[row][1] * c[1] + model.data[row][2] * c[2] + model.data[row][3];
+    }
+    out
 }
 
 impl Default for Transform {
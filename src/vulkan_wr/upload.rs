@@ -0,0 +1,320 @@
+// #=#=#=#=#=#=#=#=#-DeZtrOidDeV-#=#=#=#=#=#=#=#=#
+// Author: DeZtrOid
+// Date: 2025
+// Desc: загрузка текстур и мешей через очередь копирования
+// staging -> DEVICE_LOCAL на transfer очереди, потом передача владения графике
+// #=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#=#
+
+
+use std::sync::Mutex;
+
+use ash::vk;
+
+use super::{
+    core::VulkanCore,
+    buffer::buffer::VulkanBuffer,
+    image::image::{VulkanImage, SubresourceState},
+    command_pb::{command_pool::VulkanCommandPool, command_buffer::VulkanCommandBuffer},
+    sync::{timeline::VulkanTimelineSemaphore, barrier::{SyncScope, image_barrier2, buffer_barrier2}},
+};
+
+/// Значение timeline, после которого загрузка видна графике
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadTicket(pub u64);
+
+/// Загрузка в полете: staging и буферы команд живут, пока GPU не дошел до value
+struct PendingUpload {
+    value: u64,
+    _staging: VulkanBuffer,
+    transfer_cmd: VulkanCommandBuffer,
+    graphics_cmd: Option<VulkanCommandBuffer>,
+}
+
+struct UploaderState {
+    last_value: u64,
+    pending: Vec<PendingUpload>,
+    free_transfer: Vec<VulkanCommandBuffer>,
+    free_graphics: Vec<VulkanCommandBuffer>,
+}
+
+/// Если transfer семейство отдельное, каждая загрузка - два сабмита:
+/// копия + release на transfer очереди, acquire + барьер на графической, между ними timeline.
+/// Если нет - одна копия на графическом семействе, владение не передается.
+/// Timeline два: значение должно расти, а две очереди сигналят вперемешку, поэтому у каждой свой
+pub struct VulkanUploader {
+    state: Mutex<UploaderState>,
+    copied: VulkanTimelineSemaphore,  // сигналит transfer очередь, копия готова
+    timeline: VulkanTimelineSemaphore,  // загрузка видна графике, номер загрузки = UploadTicket
+    transfer_pool: VulkanCommandPool,
+    graphics_pool: Option<VulkanCommandPool>,  // только для acquire при отдельном семействе
+}
+
+impl VulkanUploader {
+    pub fn try_new(core: &VulkanCore) -> Result<Self, &'static str> {
+        let transfer_pool = VulkanCommandPool::try_new(
+            &core._logical_device,
+            core._transfer_queue_index,
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            None
+        )?;
+        let graphics_pool = if core.has_dedicated_transfer() {
+            Some(VulkanCommandPool::try_new(
+                &core._logical_device,
+                core._graphics_queue_index,
+                vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+                None
+            )?)
+        } else {
+            None
+        };
+        Ok(Self {
+            state: Mutex::new(UploaderState { last_value: 0, pending: vec![], free_transfer: vec![], free_graphics: vec![] }),
+            copied: VulkanTimelineSemaphore::try_new(&core._logical_device, 0)?,
+            timeline: VulkanTimelineSemaphore::try_new(&core._logical_device, 0)?,
+            transfer_pool,
+            graphics_pool,
+        })
+    }
+
+    /// Копирует data в mip 0 слоя 0 и оставляет картинку в SHADER_READ_ONLY_OPTIMAL для фрагментного шейдера.
+    /// Не ждет, картинку можно сэмплить в сабмитах после wait(ticket)
+    pub fn upload_image(&self, core: &VulkanCore, image: &VulkanImage, data: &[u8]) -> Result<UploadTicket, &'static str> {
+        let staging = Self::staging(core, data)?;
+        let copy_region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: image.extent,
+        };
+        let dst = SyncScope::new(vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_SAMPLED_READ);
+        let copied = SyncScope::new(vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_WRITE);
+        let (src_family, dst_family) = (core._transfer_queue_index, core._graphics_queue_index);
+        let dedicated = core.has_dedicated_transfer();
+
+        self.submit(core, staging, dst.stage,
+            |cmd, staging| {
+                // старое содержимое не нужно, из UNDEFINED владение передавать не надо
+                image.discard_to(cmd, vk::ImageLayout::TRANSFER_DST_OPTIMAL, copied.stage, copied.access);
+                unsafe {
+                    cmd.copy_buffer_to_image(staging.buffer, image.image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[copy_region]);
+                }
+                if dedicated {
+                    // release: смена layout тут, dst сторона пустая - ее скажет acquire
+                    let release = vk::ImageMemoryBarrier2 {
+                        src_queue_family_index: src_family,
+                        dst_queue_family_index: dst_family,
+                        ..image_barrier2(image.image, image.full_range(), copied, SyncScope::NONE,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    };
+                    unsafe { cmd.image_barriers2(&[release]); }
+                } else {
+                    image.transition_to(cmd, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, dst.stage, dst.access);
+                }
+            },
+            |cmd| {
+                // acquire: тот же барьер с теми же layout и семействами, src сторона пустая
+                let acquire = vk::ImageMemoryBarrier2 {
+                    src_queue_family_index: src_family,
+                    dst_queue_family_index: dst_family,
+                    ..image_barrier2(image.image, image.full_range(), SyncScope::NONE, dst,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                };
+                unsafe { cmd.image_barriers2(&[acquire]); }
+                image.assume(image.full_range(), SubresourceState { layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, stage: dst.stage, access: dst.access });
+            },
+        )
+    }
+
+    /// Создает DEVICE_LOCAL буфер под data и заливает его. Не ждет, см. upload_image
+    /// # Args
+    /// * `usage` - VERTEX_BUFFER, INDEX_BUFFER... TRANSFER_DST добавится сам
+    pub fn upload_buffer<T>(&self, core: &VulkanCore, data: &[T], usage: vk::BufferUsageFlags) -> Result<(VulkanBuffer, UploadTicket), &'static str> {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        let staging = Self::staging(core, data)?;
+        let buffer = VulkanBuffer::try_new(
+            core,
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            None, None, None, None
+        )?;
+        let dst = buffer_dst_scope(usage);
+        let copied = SyncScope::new(vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_WRITE);
+        let (src_family, dst_family) = (core._transfer_queue_index, core._graphics_queue_index);
+        let dedicated = core.has_dedicated_transfer();
+        let dst_buffer = buffer.buffer;
+
+        let ticket = self.submit(core, staging, dst.stage,
+            |cmd, staging| {
+                unsafe {
+                    cmd.copy_buffer(staging.buffer, dst_buffer, &[vk::BufferCopy { src_offset: 0, dst_offset: 0, size }]);
+                    let barrier = if dedicated {
+                        vk::BufferMemoryBarrier2 {
+                            src_queue_family_index: src_family,
+                            dst_queue_family_index: dst_family,
+                            ..buffer_barrier2(dst_buffer, copied, SyncScope::NONE)
+                        }
+                    } else {
+                        buffer_barrier2(dst_buffer, copied, dst)
+                    };
+                    cmd.buffer_barriers2(&[barrier]);
+                }
+            },
+            |cmd| {
+                let acquire = vk::BufferMemoryBarrier2 {
+                    src_queue_family_index: src_family,
+                    dst_queue_family_index: dst_family,
+                    ..buffer_barrier2(dst_buffer, SyncScope::NONE, dst)
+                };
+                unsafe { cmd.buffer_barriers2(&[acquire]); }
+            },
+        )?;
+        Ok((buffer, ticket))
+    }
+
+    /// Ждет загрузку на CPU и отпускает все, что уже доехало
+    pub fn wait(&self, ticket: UploadTicket) -> Result<(), &'static str> {
+        self.timeline.wait(ticket.0, u64::MAX)?;
+        self.collect()
+    }
+
+    /// Ждет все отправленные загрузки
+    pub fn flush(&self) -> Result<(), &'static str> {
+        let last = self.state.lock().unwrap().last_value;
+        self.wait(UploadTicket(last))
+    }
+
+    /// Дошла ли загрузка, не блокирует
+    pub fn is_done(&self, ticket: UploadTicket) -> Result<bool, &'static str> {
+        Ok(self.timeline.value()? >= ticket.0)
+    }
+
+    /// Освобождает staging и возвращает буферы команд завершенных загрузок
+    pub fn collect(&self) -> Result<(), &'static str> {
+        let done = self.timeline.value()?;
+        let mut state = self.state.lock().unwrap();
+        let (finished, pending): (Vec<_>, Vec<_>) = state.pending.drain(..).partition(|p| p.value <= done);
+        state.pending = pending;
+        for upload in finished {
+            state.free_transfer.push(upload.transfer_cmd);
+            state.free_graphics.extend(upload.graphics_cmd);
+        }
+        Ok(())
+    }
+
+    /// Для SubmitInfo2 графики: дождаться загрузки на стадии stage, без ожидания на CPU
+    pub fn wait_info(&self, ticket: UploadTicket, stage: vk::PipelineStageFlags2) -> vk::SemaphoreSubmitInfo<'static> {
+        self.timeline.submit_info(ticket.0, stage)
+    }
+
+    fn staging<T>(core: &VulkanCore, data: &[T]) -> Result<VulkanBuffer, &'static str> {
+        let staging = VulkanBuffer::try_new(
+            core,
+            std::mem::size_of_val(data) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            None, None, None, None
+        )?;
+        unsafe {
+            staging.mem_copy(data, None, None, None)?;
+        }
+        Ok(staging)
+    }
+
+    fn next_cmd(pool: &VulkanCommandPool, free: &mut Vec<VulkanCommandBuffer>) -> Result<VulkanCommandBuffer, &'static str> {
+        match free.pop() {
+            Some(cmd) => {
+                cmd.reset(None)?;
+                Ok(cmd)
+            }
+            None => Ok(pool.allocate_command_buffers(1, vk::CommandBufferLevel::PRIMARY)?.remove(0)),
+        }
+    }
+
+    /// Пишет и отправляет одну загрузку
+    /// # Args
+    /// * `record_transfer` - копия и release (или обычный барьер без отдельного семейства)
+    /// * `record_acquire` - acquire на графике, зовется только при отдельном семействе
+    /// * `acquire_stage` - где графика ждет копию
+    fn submit<T, A>(&self, core: &VulkanCore, staging: VulkanBuffer, acquire_stage: vk::PipelineStageFlags2,
+        record_transfer: T, record_acquire: A,
+    ) -> Result<UploadTicket, &'static str>
+        where T: FnOnce(&VulkanCommandBuffer, &VulkanBuffer), A: FnOnce(&VulkanCommandBuffer)
+    {
+        let mut state = self.state.lock().unwrap();
+        let value = state.last_value + 1;
+
+        let transfer_cmd = Self::next_cmd(&self.transfer_pool, &mut state.free_transfer)?;
+        transfer_cmd.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, None)?;
+        record_transfer(&transfer_cmd, &staging);
+        transfer_cmd.end()?;
+
+        let graphics_cmd = match &self.graphics_pool {
+            Some(pool) => {
+                let cmd = Self::next_cmd(pool, &mut state.free_graphics)?;
+                cmd.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, None)?;
+                record_acquire(&cmd);
+                cmd.end()?;
+                Some(cmd)
+            }
+            None => None,
+        };
+
+        let transfer_cmds = [vk::CommandBufferSubmitInfo::default().command_buffer(transfer_cmd._buffer)];
+        let transfer_signal = [if graphics_cmd.is_some() {
+            self.copied.submit_info(value, vk::PipelineStageFlags2::ALL_TRANSFER)
+        } else {
+            self.timeline.submit_info(value, vk::PipelineStageFlags2::ALL_TRANSFER)
+        }];
+        let submit = vk::SubmitInfo2::default()
+            .command_buffer_infos(&transfer_cmds)
+            .signal_semaphore_infos(&transfer_signal);
+        core.queue_submit2_transfer(&[submit], vk::Fence::null())?;
+
+        if let Some(cmd) = &graphics_cmd {
+            let graphics_cmds = [vk::CommandBufferSubmitInfo::default().command_buffer(cmd._buffer)];
+            let wait = [self.copied.submit_info(value, acquire_stage)];
+            let signal = [self.timeline.submit_info(value, vk::PipelineStageFlags2::ALL_COMMANDS)];
+            let submit = vk::SubmitInfo2::default()
+                .wait_semaphore_infos(&wait)
+                .command_buffer_infos(&graphics_cmds)
+                .signal_semaphore_infos(&signal);
+            core.queue_submit2(&[submit], vk::Fence::null())?;
+        }
+
+        state.last_value = value;
+        state.pending.push(PendingUpload { value, _staging: staging, transfer_cmd, graphics_cmd });
+        Ok(UploadTicket(value))
+    }
+}
+
+impl Drop for VulkanUploader {
+    fn drop(&mut self) {
+        // staging и буферы команд нельзя отпускать, пока GPU их читает
+        if self.flush().is_err() {
+            println!("Something went wrong with the upload queue wait");
+        }
+    }
+}
+
+/// Кто будет читать буфер после загрузки
+fn buffer_dst_scope(usage: vk::BufferUsageFlags) -> SyncScope {
+    if usage.contains(vk::BufferUsageFlags::VERTEX_BUFFER) {
+        SyncScope::new(vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT, vk::AccessFlags2::VERTEX_ATTRIBUTE_READ)
+    } else if usage.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+        SyncScope::new(vk::PipelineStageFlags2::INDEX_INPUT, vk::AccessFlags2::INDEX_READ)
+    } else if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+        SyncScope::new(vk::PipelineStageFlags2::ALL_GRAPHICS, vk::AccessFlags2::UNIFORM_READ)
+    } else if usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+        SyncScope::new(vk::PipelineStageFlags2::ALL_COMMANDS, vk::AccessFlags2::SHADER_STORAGE_READ)
+    } else {
+        SyncScope::new(vk::PipelineStageFlags2::ALL_COMMANDS, vk::AccessFlags2::MEMORY_READ)
+    }
+}
@@ -167,6 +167,7 @@ pub struct Window {
     pub _width: u32,
     pub _height: u32,
     pub mouse_captured: bool,
+    resized: bool,  // был FramebufferSize с прошлого take_resized
 }
 
 impl Window {
@@ -193,6 +194,7 @@ impl Window {
             _width: width,
             _height: height,
             mouse_captured: true,
+            resized: false,
         })
     }
 
@@ -211,6 +213,7 @@ impl Window {
                 glfw::WindowEvent::FramebufferSize(width, height) => {
                     self._width = width as u32;
                     self._height = height as u32;
+                    self.resized = true;
                 },
                 _ => {},
             }
@@ -237,6 +240,11 @@ impl Window {
         (self._width, self._height)
     }
 
+    /// Менялся ли размер фреймбуфера с прошлого вызова, флаг сбрасывается
+    pub fn take_resized(&mut self) -> bool {
+        std::mem::take(&mut self.resized)
+    }
+
     pub unsafe fn get_required_extensions(&self) -> (*const *const core::ffi::c_char, core::ffi::c_uint){
         let mut len: core::ffi::c_uint = 0;
         unsafe {